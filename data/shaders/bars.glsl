@@ -4,6 +4,7 @@ precision highp int;
 uniform vec3 iResolution;
 uniform float iTime;
 uniform float uAngle;
+uniform int uRepeat;
 
 // common.glsl inserted here
 
@@ -15,12 +16,16 @@ void main() {
     vec2 dir = vec2(cos(uAngle), sin(uAngle));
     float t = dot(uv - 0.5, dir) + 0.5;
     t = clamp(t, 0.0, 1.0);
+    // Repeat the gradient; the last repeat still ends on the final color
+    float s = t * float(uRepeat);
+    t = s - min(floor(s), float(uRepeat) - 1.0);
     vec3 color = paletteColor(t);
     color = applyLighting(color, t, uv);
     // Apply noise grain
     float n = hash(gl_FragCoord.xy);
     color += n * uNoise * 0.3;
     color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
     color = applyDither(color, gl_FragCoord.xy);
-    fragColor = vec4(color, 1.0);
+    fragColor = vec4(color, paletteAlpha);
 }
@@ -29,6 +29,7 @@ void main() {
     float n = hash(gl_FragCoord.xy);
     color += n * uNoise * 0.3;
     color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
     color = applyDither(color, gl_FragCoord.xy);
-    fragColor = vec4(color, 1.0);
+    fragColor = vec4(color, paletteAlpha);
 }
@@ -0,0 +1,67 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uScale;
+uniform float uSpeed;
+uniform int uOctaves;
+uniform float uWarp;
+
+// common.glsl inserted here
+
+// Value noise: random values at lattice points, smoothly interpolated
+float vnoise(vec2 p) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+
+    float a = hash(i + vec2(0.0, 0.0));
+    float b = hash(i + vec2(1.0, 0.0));
+    float c = hash(i + vec2(0.0, 1.0));
+    float d = hash(i + vec2(1.0, 1.0));
+
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// Fractal Brownian motion: uOctaves octaves of noise at doubling frequency
+// and halving amplitude, rotated between octaves to hide the lattice
+float fbm(vec2 p) {
+    const mat2 rot = mat2(0.8, 0.6, -0.6, 0.8);
+    float v = 0.0;
+    float amp = 0.5;
+    float total = 0.0;
+    for (int i = 0; i < uOctaves; i++) {
+        v += amp * vnoise(p);
+        total += amp;
+        p = rot * p * 2.0 + 17.0;
+        amp *= 0.5;
+    }
+    return v / max(total, 0.0001); // normalize to 0–1
+}
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+
+    // Aspect-corrected so clouds keep their shape at any export size
+    vec2 p = (uv - 0.5) * vec2(iResolution.x / iResolution.y, 1.0) * uScale * 3.0;
+    p += vec2(uSpeed * 0.7, uSpeed * 0.3);
+
+    // Warp the domain by a second fbm for billowy shapes
+    float t = fbm(p + fbm(p + uSpeed * 0.1) * uWarp);
+    // fbm clusters around 0.5; stretch so all four palette stops appear
+    t = clamp((t - 0.5) * 2.2 + 0.5, 0.0, 1.0);
+
+    vec3 color = paletteColor(t);
+    color = applyLighting(color, t, uv);
+
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
@@ -3,6 +3,8 @@ uniform vec3 uColor1;
 uniform vec3 uColor2;
 uniform vec3 uColor3;
 uniform vec3 uColor4;
+// Opacity of colors 1–4, from RGBA palettes
+uniform vec4 uColorAlpha;
 uniform float uBlend;
 uniform int uDistortType;
 uniform float uDistortStrength;
@@ -11,8 +13,23 @@ uniform int uLightingType;
 uniform float uLightStrength;
 uniform float uBevelWidth;
 uniform float uLightAngle;
+uniform int uShadeType;
+uniform float uShadeStrength;
+uniform vec2 uShadeCenter;
 uniform float uNoise;
 uniform float uDither;
+// 1 while rendering a seamless tile (presets flagged tileable)
+uniform int uTile;
+// Render seed, hashed by seedSalt(); 0 gives the original patterns
+uniform int uSeed;
+// Space palette colors are blended in: 0 sRGB, 1 linear light, 2 OkLab
+uniform int uInterpSpace;
+// How many of the four colors are used, 2–4; the rest are ignored
+uniform int uColorCount;
+// Kaleidoscope folds around the center, below 2 for none, and the output's
+// width / height so the wedges keep their angle on any shape
+uniform int uSymmetry;
+uniform float uSymmetryAspect;
 
 vec2 swirlUV(vec2 uv) {
     vec2 c = uv - 0.5;
@@ -30,23 +47,135 @@ vec2 rippleUV(vec2 uv) {
     return uv;
 }
 
+// Reflect uv into the first of 2 * uSymmetry wedges around the center, so
+// the preset is drawn once and mirrored around. Mirror lines lie at
+// multiples of pi / uSymmetry, so with an even count they include both axes
+// and the image matches itself across opposite edges.
+vec2 symmetryUV(vec2 uv) {
+    if (uSymmetry < 2) return uv;
+    vec2 aspect = vec2(uSymmetryAspect, 1.0);
+    vec2 c = (uv - 0.5) * aspect;
+    float wedge = 3.14159265 / float(uSymmetry);
+    float a = mod(atan(c.y, c.x), 2.0 * wedge);
+    a = wedge - abs(a - wedge);
+    return vec2(cos(a), sin(a)) * length(c) / aspect + 0.5;
+}
+
 vec2 distortUV(vec2 uv) {
+    uv = symmetryUV(uv);
     if (uDistortType == 1) return swirlUV(uv);
     if (uDistortType == 2) return rippleUV(uv);
     return uv;
 }
 
+vec3 srgbToLinear(vec3 c) {
+    return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+vec3 linearToSrgb(vec3 c) {
+    c = clamp(c, 0.0, 1.0);
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+// sRGB to OkLab and back, matching ColorSpace::Oklab in palette.rs
+vec3 srgbToOklab(vec3 c) {
+    vec3 l = srgbToLinear(clamp(c, 0.0, 1.0));
+    vec3 lms = vec3(
+        dot(l, vec3(0.4122214708, 0.5363325363, 0.0514459929)),
+        dot(l, vec3(0.2119034982, 0.6806995451, 0.1073969566)),
+        dot(l, vec3(0.0883024619, 0.2817188376, 0.6299787005)));
+    lms = pow(lms, vec3(1.0 / 3.0));
+    return vec3(
+        dot(lms, vec3(0.2104542553, 0.7936177850, -0.0040720468)),
+        dot(lms, vec3(1.9779984951, -2.4285922050, 0.4505937099)),
+        dot(lms, vec3(0.0259040371, 0.7827717662, -0.8086757660)));
+}
+
+vec3 oklabToSrgb(vec3 lab) {
+    vec3 lms = vec3(
+        dot(lab, vec3(1.0, 0.3963377774, 0.2158037573)),
+        dot(lab, vec3(1.0, -0.1055613458, -0.0638541728)),
+        dot(lab, vec3(1.0, -0.0894841775, -1.2914855480)));
+    lms = lms * lms * lms;
+    return linearToSrgb(vec3(
+        dot(lms, vec3(4.0767416621, -3.3077115913, 0.2309699292)),
+        dot(lms, vec3(-1.2684380046, 2.6097574011, -0.3413193965)),
+        dot(lms, vec3(-0.0041960863, -0.7034186147, 1.7076147010))));
+}
+
+// A palette color in the space colors are blended in
+vec3 blendSpace(vec3 c) {
+    if (uInterpSpace == 1) return srgbToLinear(c);
+    if (uInterpSpace == 2) return srgbToOklab(c);
+    return c;
+}
+
+// A blended color back in sRGB, for lighting and output
+vec3 fromBlendSpace(vec3 c) {
+    if (uInterpSpace == 1) return linearToSrgb(c);
+    if (uInterpSpace == 2) return oklabToSrgb(c);
+    return c;
+}
+
+// Number of colors in use, for presets that split t into color bands
+int colorCount() {
+    return clamp(uColorCount, 2, 4);
+}
+
+// Opacity of the color last returned by paletteColor, gradientColor,
+// cyclicColor or paletteStop, for fragColor's alpha
+float paletteAlpha = 1.0;
+
+// Opacity of palette color `i` (0–3)
+float stopAlpha(int i) {
+    return uColorAlpha[clamp(i, 0, 3)];
+}
+
+// Palette color `i` (0–3)
+vec3 paletteStop(int i) {
+    vec3 stops[4] = vec3[4](uColor1, uColor2, uColor3, uColor4);
+    paletteAlpha = stopAlpha(i);
+    return stops[clamp(i, 0, 3)];
+}
+
 vec3 paletteColor(float t) {
     t = clamp(t, 0.0, 1.0);
-    float fw = uBlend * 0.25;
-    float f1 = (fw > 0.0001) ? smoothstep(0.25 - fw, 0.25 + fw, t) : step(0.25, t);
-    float f2 = (fw > 0.0001) ? smoothstep(0.50 - fw, 0.50 + fw, t) : step(0.50, t);
-    float f3 = (fw > 0.0001) ? smoothstep(0.75 - fw, 0.75 + fw, t) : step(0.75, t);
-    vec3 color = uColor1;
-    color = mix(color, uColor2, f1);
-    color = mix(color, uColor3, f2);
-    color = mix(color, uColor4, f3);
-    return color;
+    int n = colorCount();
+    float band = 1.0 / float(n);
+    float fw = uBlend * band;
+    vec3 color = blendSpace(uColor1);
+    float alpha = stopAlpha(0);
+    for (int i = 1; i < n; i++) {
+        float edge = band * float(i);
+        float f = (fw > 0.0001) ? smoothstep(edge - fw, edge + fw, t) : step(edge, t);
+        color = mix(color, blendSpace(paletteStop(i)), f);
+        alpha = mix(alpha, stopAlpha(i), f);
+    }
+    paletteAlpha = alpha;
+    return fromBlendSpace(color);
+}
+
+// Linear interpolation through the colors in use as evenly spaced stops
+vec3 gradientColor(float t) {
+    int n = colorCount();
+    float s = clamp(t, 0.0, 1.0) * float(n - 1);
+    int i = min(int(floor(s)), n - 2);
+    float f = s - float(i);
+    vec3 color = mix(blendSpace(paletteStop(i)), blendSpace(paletteStop(i + 1)), f);
+    paletteAlpha = mix(stopAlpha(i), stopAlpha(i + 1), f);
+    return fromBlendSpace(color);
+}
+
+// Like gradientColor, but wrapping from the last color in use back to the
+// first, so repeating rings and full turns have no seam
+vec3 cyclicColor(float t) {
+    int n = colorCount();
+    float s = fract(t) * float(n);
+    int i = int(floor(s));
+    float f = s - float(i);
+    vec3 color = mix(blendSpace(paletteStop(i % n)), blendSpace(paletteStop((i + 1) % n)), f);
+    paletteAlpha = mix(stopAlpha(i % n), stopAlpha((i + 1) % n), f);
+    return fromBlendSpace(color);
 }
 
 vec3 applyLighting(vec3 color, float t, vec2 uv) {
@@ -55,13 +184,13 @@ vec3 applyLighting(vec3 color, float t, vec2 uv) {
     if (uLightingType == 1) {
         // Bevel: shadow/highlight at color band boundaries
         float w = uBevelWidth;
-        float b1 = smoothstep(-w, w, t - 0.25) * 2.0 - 1.0;
-        float b2 = smoothstep(-w, w, t - 0.50) * 2.0 - 1.0;
-        float b3 = smoothstep(-w, w, t - 0.75) * 2.0 - 1.0;
-        float m1 = 1.0 - smoothstep(0.0, w * 2.0, abs(t - 0.25));
-        float m2 = 1.0 - smoothstep(0.0, w * 2.0, abs(t - 0.50));
-        float m3 = 1.0 - smoothstep(0.0, w * 2.0, abs(t - 0.75));
-        shade = (b1 * m1 + b2 * m2 + b3 * m3) * 0.25;
+        int n = colorCount();
+        for (int i = 1; i < n; i++) {
+            float d = t - float(i) / float(n);
+            float b = smoothstep(-w, w, d) * 2.0 - 1.0;
+            float m = 1.0 - smoothstep(0.0, w * 2.0, abs(d));
+            shade += b * m * 0.25;
+        }
     } else if (uLightingType == 2) {
         // Gradient: directional light across image (0 = from top)
         vec2 lightDir = vec2(cos(uLightAngle), sin(uLightAngle));
@@ -74,12 +203,51 @@ vec3 applyLighting(vec3 color, float t, vec2 uv) {
     return clamp(color + shade * uLightStrength, 0.0, 1.0);
 }
 
+// Darkening that keeps desktop icons legible on bright wallpapers, from the
+// screen position (undistorted uv). Radial (1) darkens away from
+// uShadeCenter, corner (2) darkens toward it. Applied after lighting and
+// grain, right before dithering.
+vec3 applyShade(vec3 color, vec2 screenUV) {
+    if (uShadeType == 0) return color;
+    float d = length(screenUV - uShadeCenter);
+    float amount = uShadeType == 1
+        ? smoothstep(0.25, 0.75, d)
+        : 1.0 - smoothstep(0.0, 0.6, d);
+    return color * (1.0 - amount * uShadeStrength);
+}
+
+// Offset of 0–16 derived from uSeed that presets mix into their hashes, so
+// each seed gives a different (but repeatable) random pattern. The seed is
+// hashed (lowbias32) first: used directly, neighboring seeds would shift
+// the hashes by so little that their patterns looked alike.
+float seedSalt() {
+    if (uSeed == 0) return 0.0;
+    uint h = uint(uSeed);
+    h ^= h >> 16;
+    h *= 0x7feb352du;
+    h ^= h >> 15;
+    h *= 0x846ca68bu;
+    h ^= h >> 16;
+    return float(h >> 8) / 16777216.0 * 16.0;
+}
+
 float hash(vec2 p) {
     vec3 p3 = fract(vec3(p.xyx) * 0.1031);
-    p3 += dot(p3, p3.yzx + 33.33);
+    p3 += dot(p3, p3.yzx + 33.33 + seedSalt());
     return fract((p3.x + p3.y) * p3.z);
 }
 
+// Lattice cells across the image for tiling: whole numbers, so the
+// pattern wraps exactly at the edges
+vec2 tileCells(vec2 cells) {
+    return max(floor(cells + 0.5), vec2(1.0));
+}
+
+// hash() of a lattice point, wrapped every `period` cells when tiling
+float tileHash(vec2 lattice, vec2 period) {
+    return hash(uTile == 1 ? mod(lattice, period) : lattice);
+}
+
 float bayer4x4(vec2 p) {
     ivec2 i = ivec2(p) & 3;
     int idx = i.x + i.y * 4;
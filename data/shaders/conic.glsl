@@ -0,0 +1,36 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uAngle;
+uniform float uCenter;
+uniform float uCenterY;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+const float TAU = 6.28318530718;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+    vec2 center = vec2(0.5 + uCenter * 0.4, 0.5 + uCenterY * 0.4);
+
+    // One sweep through the palette per turn around the center, starting
+    // at uAngle; cyclicColor wraps back to the first color so there's no seam
+    vec2 p = (uv - center) * vec2(iResolution.x / iResolution.y, 1.0);
+    // atan is undefined at the center itself
+    float a = dot(p, p) > 0.0 ? atan(p.y, p.x) : 0.0;
+    float t = fract((a - uAngle) / TAU);
+    vec3 color = cyclicColor(t);
+    color = applyLighting(color, t, uv);
+
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
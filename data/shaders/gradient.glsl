@@ -0,0 +1,30 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uAngle;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+    // Project onto the gradient axis in pixel-proportional space, scaled so
+    // the corners furthest along it land exactly on 0 and 1 at any angle
+    float aspect = iResolution.x / iResolution.y;
+    vec2 p = (uv - 0.5) * vec2(aspect, 1.0);
+    vec2 dir = vec2(cos(uAngle), sin(uAngle));
+    float halfExtent = 0.5 * (abs(dir.x) * aspect + abs(dir.y));
+    float t = dot(p, dir) / (2.0 * halfExtent) + 0.5;
+    vec3 color = gradientColor(t);
+    color = applyLighting(color, t, uv);
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
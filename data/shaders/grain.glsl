@@ -0,0 +1,39 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float uAngle;
+uniform float uGrain;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+// Zero-mean grain in -1..1 with a triangular distribution (the sum of two
+// hashes), which hides bands better than uniform noise of the same strength
+float grain(vec2 p) {
+    return hash(p) + hash(p + vec2(17.31, 43.7)) - 1.0;
+}
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+    // Same gradient axis as the Gradient preset: the corners furthest
+    // along it land exactly on 0 and 1 at any angle
+    float aspect = iResolution.x / iResolution.y;
+    vec2 p = (uv - 0.5) * vec2(aspect, 1.0);
+    vec2 dir = vec2(cos(uAngle), sin(uAngle));
+    float halfExtent = 0.5 * (abs(dir.x) * aspect + abs(dir.y));
+    float t = dot(p, dir) / (2.0 * halfExtent) + 0.5;
+    vec3 color = gradientColor(t);
+    color = applyLighting(color, t, uv);
+    // Grain at full strength moves each channel by up to 0.08, a few 8-bit
+    // steps at the default; the same offset on every channel keeps hue
+    color += grain(gl_FragCoord.xy) * uGrain * 0.08;
+    // The shared noise control still adds its own (directional) grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
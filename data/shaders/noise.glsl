@@ -0,0 +1,54 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uScale;
+uniform float uSpeed;
+uniform float uContrast;
+
+// common.glsl inserted here
+
+// Value noise: random values at lattice points, smoothly interpolated.
+// Lattice points wrap every `period` cells when tiling.
+float vnoise(vec2 p, vec2 period) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+
+    float a = tileHash(i + vec2(0.0, 0.0), period);
+    float b = tileHash(i + vec2(1.0, 0.0), period);
+    float c = tileHash(i + vec2(0.0, 1.0), period);
+    float d = tileHash(i + vec2(1.0, 1.0), period);
+
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+
+    // Aspect-corrected so cells stay square at any export size
+    vec2 cells = vec2(iResolution.x / iResolution.y, 1.0) * uScale * 8.0;
+    vec2 p = (uv - 0.5) * cells;
+    if (uTile == 1) {
+        // A whole number of cells per edge, so opposite edges match
+        cells = tileCells(cells);
+        p = uv * cells;
+    }
+    p += vec2(uSpeed * 1.7, uSpeed * 1.3);
+
+    float t = clamp((vnoise(p, cells) - 0.5) * uContrast + 0.5, 0.0, 1.0);
+
+    vec3 color = paletteColor(t);
+    color = applyLighting(color, t, uv);
+
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
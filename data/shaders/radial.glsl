@@ -0,0 +1,36 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uScale;
+uniform float uCenter;
+uniform float uCenterY;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+    vec2 center = vec2(0.5 + uCenter * 0.4, 0.5 + uCenterY * 0.4);
+
+    // Distance in pixel-proportional space so rings stay round, normalized
+    // so the furthest corner is 1
+    vec2 aspect = vec2(iResolution.x / iResolution.y, 1.0);
+    float d = length((uv - center) * aspect);
+    float furthest = length(max(center, 1.0 - center) * aspect);
+
+    // uScale full cycles through the palette out to the furthest corner
+    float t = fract(d / furthest * uScale);
+    vec3 color = cyclicColor(t);
+    color = applyLighting(color, t, uv);
+
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
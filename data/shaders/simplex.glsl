@@ -0,0 +1,59 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uScale;
+uniform float uSpeed;
+uniform float uContrast;
+
+// common.glsl inserted here
+
+// Hash that returns a 2D gradient direction
+vec2 hash2(vec2 p) {
+    p = vec2(dot(p, vec2(127.1, 311.7)),
+             dot(p, vec2(269.5, 183.3)));
+    return -1.0 + 2.0 * fract(sin(p + seedSalt()) * 43758.5453123);
+}
+
+// 2D simplex noise: gradient noise on a triangular grid, fewer
+// axis-aligned artifacts than square-lattice noise. Returns 0–1.
+float snoise(vec2 p) {
+    const float K1 = 0.366025404; // (sqrt(3) - 1) / 2
+    const float K2 = 0.211324865; // (3 - sqrt(3)) / 6
+
+    vec2 i = floor(p + (p.x + p.y) * K1);
+    vec2 a = p - i + (i.x + i.y) * K2;
+    vec2 o = (a.x > a.y) ? vec2(1.0, 0.0) : vec2(0.0, 1.0);
+    vec2 b = a - o + K2;
+    vec2 c = a - 1.0 + 2.0 * K2;
+
+    vec3 h = max(0.5 - vec3(dot(a, a), dot(b, b), dot(c, c)), 0.0);
+    vec3 n = h * h * h * h * vec3(dot(a, hash2(i)),
+                                  dot(b, hash2(i + o)),
+                                  dot(c, hash2(i + 1.0)));
+    return dot(n, vec3(70.0)) * 0.5 + 0.5;
+}
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+
+    // Aspect-corrected so features stay round at any export size
+    vec2 p = (uv - 0.5) * vec2(iResolution.x / iResolution.y, 1.0) * uScale * 4.0;
+    p += vec2(uSpeed * 1.7, uSpeed * 1.3);
+
+    float t = clamp((snoise(p) - 0.5) * uContrast + 0.5, 0.0, 1.0);
+
+    vec3 color = paletteColor(t);
+    color = applyLighting(color, t, uv);
+
+    // Apply noise grain
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
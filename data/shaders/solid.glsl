@@ -0,0 +1,26 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform int uColorIndex;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+void main() {
+    // uColorIndex counts from 1, like the Color slider; only colors in use
+    // can be picked
+    int i = clamp(uColorIndex, 1, colorCount()) - 1;
+    vec3 color = paletteStop(i);
+    // Lighting and grain still apply when enabled; by default the output is
+    // one exact color, so there's nothing to band
+    vec2 uv = gl_FragCoord.xy / iResolution.xy;
+    color = applyLighting(color, 0.0, uv);
+    float n = hash(gl_FragCoord.xy);
+    color += n * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, paletteAlpha);
+}
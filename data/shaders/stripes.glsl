@@ -0,0 +1,55 @@
+#version 300 es
+precision highp float;
+precision highp int;
+uniform vec3 iResolution;
+uniform float iTime;
+uniform float uAngle;
+uniform int uStripeCount;
+uniform int uAntialias;
+
+// common.glsl inserted here
+
+out vec4 fragColor;
+
+void main() {
+    vec2 uv = distortUV(gl_FragCoord.xy / iResolution.xy);
+    // Measure across the stripes in pixels so they stay square to the
+    // angle, scaled so exactly uStripeCount of them span the screen
+    vec2 dir = vec2(cos(uAngle), sin(uAngle));
+    vec2 p = (uv - 0.5) * iResolution.xy;
+    float extent = abs(dir.x) * iResolution.x + abs(dir.y) * iResolution.y;
+    float s = (dot(p, dir) / extent + 0.5) * float(max(uStripeCount, 1));
+
+    // Each stripe is one flat palette color, cycling through the colors in use
+    float n = float(colorCount());
+    float k = floor(s);
+    vec3 color = paletteStop(int(mod(k, n)));
+    float alpha = paletteAlpha;
+    if (uAntialias == 1) {
+        // Cover one pixel across each edge with the neighboring stripe, so
+        // edges at an angle don't shimmer
+        float w = max(fwidth(s), 1e-4);
+        float f = fract(s);
+        vec3 c = blendSpace(color);
+        vec3 prev = blendSpace(paletteStop(int(mod(k - 1.0, n))));
+        vec3 next = blendSpace(paletteStop(int(mod(k + 1.0, n))));
+        float toPrev = clamp(0.5 - f / w, 0.0, 0.5);
+        float toNext = clamp(0.5 - (1.0 - f) / w, 0.0, 0.5);
+        c = mix(c, prev, toPrev);
+        c = mix(c, next, toNext);
+        color = fromBlendSpace(c);
+        alpha = mix(alpha, stopAlpha(int(mod(k - 1.0, n))), toPrev);
+        alpha = mix(alpha, stopAlpha(int(mod(k + 1.0, n))), toNext);
+    }
+
+    // Position within the color cycle, so bevel lighting lands on the edges
+    float t = fract(s / n);
+    color = applyLighting(color, t, uv);
+    // Apply noise grain
+    float g = hash(gl_FragCoord.xy);
+    color += g * uNoise * 0.3;
+    color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
+    color = applyDither(color, gl_FragCoord.xy);
+    fragColor = vec4(color, alpha);
+}
@@ -12,7 +12,7 @@ uniform float uSpeed;
 vec2 hash2(vec2 p) {
     p = vec2(dot(p, vec2(127.1, 311.7)),
              dot(p, vec2(269.5, 183.3)));
-    return -1.0 + 2.0 * fract(sin(p) * 43758.5453123);
+    return -1.0 + 2.0 * fract(sin(p + seedSalt()) * 43758.5453123);
 }
 
 // 2D gradient noise — produces smooth rounded shapes
@@ -60,6 +60,7 @@ void main() {
     float n = hash(gl_FragCoord.xy);
     color += n * uNoise * 0.3;
     color = clamp(color, 0.0, 1.0);
+    color = applyShade(color, gl_FragCoord.xy / iResolution.xy);
     color = applyDither(color, gl_FragCoord.xy);
-    fragColor = vec4(color, 1.0);
+    fragColor = vec4(color, paletteAlpha);
 }
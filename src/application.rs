@@ -1,9 +1,29 @@
-use gtk4::gio;
+use std::path::{Path, PathBuf};
+
 use gtk4::prelude::*;
+use gtk4::{gio, glib};
 use libadwaita as adw;
 
+use crate::shader_presets;
+use crate::wallpaper;
 use crate::window::WallrusWindow;
 
+/// D-Bus interface other apps can use to drive Wallrus, exported on the
+/// application's object path under its well-known bus name (the app ID).
+const DBUS_INTERFACE_XML: &str = r#"
+<node>
+  <interface name="io.github.megakode.Wallrus">
+    <method name="ApplyPalette">
+      <arg type="s" name="path" direction="in"/>
+      <arg type="s" name="preset" direction="in"/>
+    </method>
+    <method name="SetWallpaper">
+      <arg type="s" name="path" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
 pub struct WallrusApplication {
     app: adw::Application,
 }
@@ -23,6 +43,13 @@ impl WallrusApplication {
             style.set_color_scheme(adw::ColorScheme::Default);
         });
 
+        // The bus is optional: without it the GUI still works
+        app.connect_startup(|app| {
+            if let Err(e) = Self::register_dbus_service(app) {
+                eprintln!("D-Bus service unavailable: {}", e);
+            }
+        });
+
         app.connect_activate(Self::on_activate);
 
         Self { app }
@@ -33,7 +60,143 @@ impl WallrusApplication {
         window.present();
     }
 
+    /// Export the control interface on the application's bus connection.
+    ///
+    /// `ApplyPalette(path, preset)` loads a palette image and selects a preset
+    /// (empty to keep the current one) through the window's `apply-palette`
+    /// action, the same path the palette browser uses. `SetWallpaper(path)`
+    /// sets an existing image as the wallpaper.
+    fn register_dbus_service(app: &adw::Application) -> Result<(), String> {
+        let connection = app
+            .dbus_connection()
+            .ok_or("Application is not registered on the session bus")?;
+        let object_path = app
+            .dbus_object_path()
+            .ok_or("Application has no D-Bus object path")?;
+
+        let node = gio::DBusNodeInfo::for_xml(DBUS_INTERFACE_XML)
+            .map_err(|e| format!("Invalid D-Bus interface: {}", e))?;
+        let interface = node
+            .lookup_interface("io.github.megakode.Wallrus")
+            .ok_or("D-Bus interface missing from XML")?;
+
+        let app = app.clone();
+        connection
+            .register_object(&object_path, &interface)
+            .method_call(move |_, _, _, _, method, params, invocation| match method {
+                "ApplyPalette" => {
+                    let Some((path, preset)) = params.get::<(String, String)>() else {
+                        invocation.return_gerror(invalid_args());
+                        return;
+                    };
+                    invocation.return_result(Self::apply_palette(&app, &path, &preset));
+                }
+                "SetWallpaper" => {
+                    let Some((path,)) = params.get::<(String,)>() else {
+                        invocation.return_gerror(invalid_args());
+                        return;
+                    };
+                    let app = app.clone();
+                    invocation.return_future_local(async move {
+                        let path = PathBuf::from(path);
+                        let result =
+                            wallpaper::set_wallpaper(&path, wallpaper::WallpaperMode::default())
+                                .await;
+                        notify_wallpaper_result(Some(app.upcast_ref()), &path, &result);
+                        result
+                            .map(|_| None)
+                            .map_err(|e| glib::Error::new(gio::IOErrorEnum::Failed, &e))
+                    });
+                }
+                _ => invocation.return_gerror(glib::Error::new(
+                    gio::DBusError::UnknownMethod,
+                    &format!("Unknown method {}", method),
+                )),
+            })
+            .build()
+            .map_err(|e| format!("Failed to register D-Bus object: {}", e))?;
+        Ok(())
+    }
+
+    /// Validate the arguments, then forward to the window's `apply-palette`
+    /// action, opening the window first if needed.
+    fn apply_palette(
+        app: &adw::Application,
+        path: &str,
+        preset: &str,
+    ) -> Result<Option<glib::Variant>, glib::Error> {
+        if !PathBuf::from(path).is_file() {
+            return Err(glib::Error::new(
+                gio::IOErrorEnum::NotFound,
+                &format!("Palette image not found: {}", path),
+            ));
+        }
+        let known_preset = preset.is_empty()
+            || shader_presets::list_presets()
+                .iter()
+                .any(|e| e.name().eq_ignore_ascii_case(preset));
+        if !known_preset {
+            return Err(glib::Error::new(
+                gio::IOErrorEnum::InvalidArgument,
+                &format!("Unknown preset: {}", preset),
+            ));
+        }
+
+        if app.active_window().is_none() {
+            app.activate();
+        }
+        let window = app
+            .active_window()
+            .ok_or_else(|| glib::Error::new(gio::IOErrorEnum::Failed, "No window to apply to"))?;
+        WidgetExt::activate_action(
+            &window,
+            "win.apply-palette",
+            Some(&(path, preset).to_variant()),
+        )
+        .map_err(|e| glib::Error::new(gio::IOErrorEnum::Failed, &e.to_string()))?;
+        Ok(None)
+    }
+
     pub fn run(&self) -> i32 {
         self.app.run().into()
     }
 }
+
+/// Notification IDs; a newer notification replaces an older one with the
+/// same ID instead of piling up
+pub const WALLPAPER_NOTIFICATION: &str = "wallpaper";
+pub const EXPORT_NOTIFICATION: &str = "export";
+
+/// Send a desktop notification. Without a registered application (such as
+/// when rendering from the command line) the message goes to stdout instead.
+pub fn notify(app: Option<&gio::Application>, id: &str, title: &str, body: &str) {
+    match app.filter(|app| app.is_registered()) {
+        Some(app) => {
+            let notification = gio::Notification::new(title);
+            notification.set_body(Some(body));
+            app.send_notification(Some(id), &notification);
+        }
+        None => println!("{}: {}", title, body),
+    }
+}
+
+/// Notify that setting `path` as the wallpaper succeeded or failed.
+pub fn notify_wallpaper_result(
+    app: Option<&gio::Application>,
+    path: &Path,
+    result: &Result<(), String>,
+) {
+    match result {
+        Ok(()) => notify(
+            app,
+            WALLPAPER_NOTIFICATION,
+            "Wallpaper set",
+            &path.display().to_string(),
+        ),
+        Err(e) => notify(app, WALLPAPER_NOTIFICATION, "Failed to set wallpaper", e),
+    }
+}
+
+fn invalid_args() -> glib::Error {
+    glib::Error::new(gio::DBusError::InvalidArgs, "Invalid arguments")
+}
@@ -0,0 +1,391 @@
+/// Headless command-line mode: render a wallpaper without opening a window.
+///
+/// `wallrus --output <file> [--palette <image>] [--preset <name>]
+/// [--resolution <WxH>] [--seed <n>] [--blend <space>] [--colors <n>]
+/// [--order <order>] [--tile]
+/// [--set] [--fit <mode>] [--force] [--verbose]`
+/// renders offscreen, exports via the `export` module, optionally sets it
+/// as the wallpaper, and exits. An existing output file is only replaced
+/// with `--force`. Errors are printed to stderr with a nonzero
+/// exit code so scripts can detect them.
+///
+/// `wallrus list palettes|presets [--json]` prints what's installed, and
+/// `wallrus --diagnostics` the GL driver and environment for bug reports.
+use std::path::PathBuf;
+
+use gtk4::glib;
+
+use crate::application::{self, EXPORT_NOTIFICATION, WALLPAPER_NOTIFICATION};
+use crate::diagnostics;
+use crate::export::{self, Dither, ExportFormat, SampleQuality};
+use crate::gl_renderer::{self, RenderSeed, MAX_COLOR_COUNT, MIN_COLOR_COUNT};
+use crate::json;
+use crate::palette::{self, ColorSpace, ExtractOrder};
+use crate::settings;
+use crate::shader_presets;
+use crate::wallpaper::{self, WallpaperMode};
+
+const USAGE: &str = "\
+Usage: wallrus [--output <file> [options]]
+       wallrus list palettes|presets [--json]
+       wallrus --diagnostics
+
+Without arguments, Wallrus opens its window. With --output it renders a
+wallpaper headlessly and exits. `list palettes` prints each palette's
+category and path separated by a tab, `list presets` the preset and user
+shader names; --json prints a JSON array instead. --diagnostics prints the
+GL driver, wallpaper backend and directories in use, for bug reports.
+
+Options:
+  --output <file>       Image to write; format from the extension (png, jpg, webp)
+  --palette <image>     Palette image to take the four colors from
+  --preset <name>       Shader preset or user shader name (default: Bars)
+  --resolution <WxH>    Output size in pixels (default: 1920x1080)
+  --seed <n>            Seed for presets with randomness, 0–65535 (default: 0)
+  --blend <space>       Blend colors in srgb, linear light or oklab (default: srgb)
+  --colors <n>          Use only the first 2–4 palette colors (default: 4)
+  --order <order>       Reorder the palette colors: as-is, dark-to-light or
+                        light-to-dark (default: as-is, top to bottom)
+  --tile                Render a seamless tile that repeats without visible edges
+  --set                 Also set the rendered image as the desktop wallpaper
+  --fit <mode>          How --set fits the image to the screen: zoom, scaled,
+                        centered, spanned or wallpaper (default: the fit
+                        last picked in the window, else zoom)
+  --force               Overwrite the output file if it already exists
+  --verbose             Print render, readback and encode timings to stderr
+  --help                Show this help";
+
+/// Options for a headless render
+#[derive(Debug)]
+pub struct CliOptions {
+    pub output: PathBuf,
+    pub palette: Option<PathBuf>,
+    pub preset: String,
+    pub resolution: (u32, u32),
+    pub seed: RenderSeed,
+    pub color_space: ColorSpace,
+    pub color_count: u32,
+    /// Order the palette's colors are put in after extraction
+    pub order: ExtractOrder,
+    pub tile: bool,
+    pub set_wallpaper: bool,
+    /// Fit for `--set`; `None` uses the one saved by the window
+    pub fit: Option<WallpaperMode>,
+    /// Replace an existing output file
+    pub force: bool,
+    pub verbose: bool,
+}
+
+/// What the command line asks for
+pub enum CliCommand {
+    /// No arguments: start the GUI
+    Gui,
+    Help,
+    Render(CliOptions),
+    List {
+        what: ListTarget,
+        json: bool,
+    },
+    Diagnostics,
+}
+
+/// What `wallrus list` prints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListTarget {
+    Palettes,
+    Presets,
+}
+
+/// Parse command-line arguments (excluding the program name).
+pub fn parse_args(args: &[String]) -> Result<CliCommand, String> {
+    if args.is_empty() {
+        return Ok(CliCommand::Gui);
+    }
+    if args[0] == "list" {
+        return parse_list_args(&args[1..]);
+    }
+
+    let mut output = None;
+    let mut palette = None;
+    let mut preset = String::from("Bars");
+    let mut resolution = (1920, 1080);
+    let mut seed = RenderSeed::default();
+    let mut color_space = ColorSpace::default();
+    let mut color_count = MAX_COLOR_COUNT;
+    let mut order = ExtractOrder::default();
+    let mut tile = false;
+    let mut set_wallpaper = false;
+    let mut fit = None;
+    let mut force = false;
+    let mut verbose = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "--output" => output = Some(PathBuf::from(value("--output")?)),
+            "--palette" => palette = Some(PathBuf::from(value("--palette")?)),
+            "--preset" => preset = value("--preset")?,
+            "--resolution" => resolution = parse_resolution(&value("--resolution")?)?,
+            "--seed" => seed = RenderSeed::parse(&value("--seed")?)?,
+            "--blend" => color_space = ColorSpace::parse(&value("--blend")?)?,
+            "--colors" => color_count = parse_color_count(&value("--colors")?)?,
+            "--order" => order = ExtractOrder::parse(&value("--order")?)?,
+            "--tile" => tile = true,
+            "--set" => set_wallpaper = true,
+            "--fit" => fit = Some(WallpaperMode::parse(&value("--fit")?)?),
+            "--force" | "-f" => force = true,
+            "--verbose" | "-v" => verbose = true,
+            "--help" | "-h" => return Ok(CliCommand::Help),
+            "--diagnostics" => return Ok(CliCommand::Diagnostics),
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+
+    let output = output.ok_or("--output is required for headless rendering")?;
+    Ok(CliCommand::Render(CliOptions {
+        output,
+        palette,
+        preset,
+        resolution,
+        seed,
+        color_space,
+        color_count,
+        order,
+        tile,
+        set_wallpaper,
+        fit,
+        force,
+        verbose,
+    }))
+}
+
+/// Parse the arguments after `list`.
+fn parse_list_args(args: &[String]) -> Result<CliCommand, String> {
+    let mut what = None;
+    let mut json = false;
+    for arg in args {
+        match arg.as_str() {
+            "palettes" if what.is_none() => what = Some(ListTarget::Palettes),
+            "presets" if what.is_none() => what = Some(ListTarget::Presets),
+            "--json" => json = true,
+            "--help" | "-h" => return Ok(CliCommand::Help),
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
+    let what = what.ok_or("list needs palettes or presets")?;
+    Ok(CliCommand::List { what, json })
+}
+
+/// Parse a `WIDTHxHEIGHT` resolution string.
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid resolution '{}', expected WIDTHxHEIGHT", s);
+    let (w, h) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let w: u32 = w.trim().parse().map_err(|_| invalid())?;
+    let h: u32 = h.trim().parse().map_err(|_| invalid())?;
+    if w == 0 || h == 0 {
+        return Err(invalid());
+    }
+    Ok((w, h))
+}
+
+/// Parse the number of palette colors to use.
+fn parse_color_count(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(n) if (MIN_COLOR_COUNT..=MAX_COLOR_COUNT).contains(&n) => Ok(n),
+        _ => Err(format!(
+            "Invalid color count '{}', expected {} to {}",
+            s, MIN_COLOR_COUNT, MAX_COLOR_COUNT
+        )),
+    }
+}
+
+/// Run the command line. Returns `None` when the GUI should start instead,
+/// otherwise the process exit code.
+pub fn run(args: &[String]) -> Option<i32> {
+    let options = match parse_args(args) {
+        Ok(CliCommand::Gui) => return None,
+        Ok(CliCommand::Help) => {
+            println!("{}", USAGE);
+            return Some(0);
+        }
+        Ok(CliCommand::Render(options)) => options,
+        Ok(CliCommand::List { what, json }) => {
+            let output = list(what, json);
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+            return Some(0);
+        }
+        Ok(CliCommand::Diagnostics) => {
+            // Still report the rest when no GL context can be made
+            let renderer = gl_renderer::create_headless_renderer()
+                .map_err(|e| eprintln!("Warning: {}", e))
+                .ok();
+            let renderer = renderer.as_ref().map(|(renderer, _context)| renderer);
+            print!("{}", diagnostics::collect_diagnostics(renderer));
+            return Some(0);
+        }
+        Err(e) => {
+            eprintln!("wallrus: {}\n\n{}", e, USAGE);
+            return Some(2);
+        }
+    };
+
+    match render(&options) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("wallrus: {}", e);
+            Some(1)
+        }
+    }
+}
+
+/// The installed palettes or presets, one per line or as a JSON array.
+fn list(what: ListTarget, json: bool) -> String {
+    let lines: Vec<String> = match what {
+        ListTarget::Palettes => palette::list_palette_categories()
+            .into_iter()
+            .flat_map(|(category, paths)| {
+                paths.into_iter().map(move |path| {
+                    let path = path.to_string_lossy().into_owned();
+                    if json {
+                        format!(
+                            "{{\"category\": {}, \"path\": {}}}",
+                            json::quote(&category),
+                            json::quote(&path)
+                        )
+                    } else {
+                        format!("{}\t{}", category, path)
+                    }
+                })
+            })
+            .collect(),
+        ListTarget::Presets => shader_presets::list_presets()
+            .iter()
+            .map(|entry| {
+                if json {
+                    json::quote(entry.name())
+                } else {
+                    entry.name().to_string()
+                }
+            })
+            .collect(),
+    };
+
+    if !json {
+        return lines.join("\n");
+    }
+    if lines.is_empty() {
+        return "[]".to_string();
+    }
+    let entries: Vec<String> = lines.iter().map(|line| format!("  {}", line)).collect();
+    format!("[\n{}\n]", entries.join(",\n"))
+}
+
+/// Render, export and optionally set the wallpaper.
+fn render(options: &CliOptions) -> Result<(), String> {
+    // Check the output before spending time on the render
+    if options.output.exists() && !options.force {
+        return Err(format!(
+            "{} already exists; pass --force to overwrite it",
+            options.output.display()
+        ));
+    }
+    let format = ExportFormat::from_extension(
+        options
+            .output
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("png"),
+    );
+    export::prepare_output_path(&options.output, format)?;
+
+    let (mut renderer, _context) = gl_renderer::create_headless_preset_renderer(&options.preset)?;
+    renderer.seed = options.seed;
+    renderer.color_space = options.color_space;
+    renderer.color_count = options.color_count;
+
+    if let Some(path) = &options.palette {
+        let colors =
+            palette::extract_colors_ordered(path, palette::DEFAULT_COLOR_COUNT, options.order)?;
+        let [c1, c2, c3, c4] = palette::to_four_colors(&colors);
+        if let Some(warning) = palette::classify_palette_image(path)?.warning() {
+            eprintln!("Warning: {}", warning);
+        }
+        renderer.color1 = c1;
+        renderer.color2 = c2;
+        renderer.color3 = c3;
+        renderer.color4 = c4;
+    }
+
+    let (w, h) = options.resolution;
+    // Progress goes to stderr, since stdout is for the saved path
+    let mut control = export::ExportControl::new(export::CancelToken::default(), |progress| {
+        eprintln!("{}", progress)
+    });
+    control.next(export::ExportProgress {
+        done: 0,
+        total: 1,
+        current: &options.output,
+        pass: None,
+    })?;
+    let pixels = if options.tile {
+        let quality = SampleQuality::default();
+        export::render_tile(&mut renderer, w, h, quality, Dither::Off, &mut control)?
+    } else {
+        renderer.render_to_pixels(w as i32, h as i32)?
+    };
+
+    if format == ExportFormat::Jpeg && export::has_transparency(&pixels) {
+        let background = export::DEFAULT_JPEG_BACKGROUND.map(|c| c as f32 / 255.0);
+        eprintln!(
+            "Warning: JPEG has no transparency; transparent areas are filled with {}",
+            palette::rgb_to_hex(background)
+        );
+    }
+
+    let started = std::time::Instant::now();
+    export::save_pixels(
+        &pixels,
+        w,
+        h,
+        &options.output,
+        format,
+        export::DEFAULT_EXPORT_QUALITY,
+        Some(&renderer.wallpaper_meta()),
+    )?;
+    renderer.record_encode(started.elapsed());
+    if options.verbose {
+        eprintln!("Using GPU: {}", renderer.gpu_description());
+        // The CLI never draws a preview frame, so that line is left out
+        for line in renderer.timings().lines().iter().skip(1) {
+            eprintln!("{}", line);
+        }
+    }
+
+    application::notify(
+        None,
+        EXPORT_NOTIFICATION,
+        "Saved",
+        &options.output.display().to_string(),
+    );
+
+    if options.set_wallpaper {
+        let fit = options
+            .fit
+            .unwrap_or_else(|| settings::load().wallpaper_mode);
+        glib::MainContext::default().block_on(wallpaper::set_wallpaper(&options.output, fit))?;
+        application::notify(
+            None,
+            WALLPAPER_NOTIFICATION,
+            "Wallpaper set",
+            &options.output.display().to_string(),
+        );
+    }
+    Ok(())
+}
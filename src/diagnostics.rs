@@ -0,0 +1,85 @@
+/// Environment details for bug reports: the GL driver strings, wallpaper
+/// backend, palette and shader directories and app version. They're shown
+/// on the Troubleshooting page of the About window and printed by
+/// `wallrus --diagnostics`.
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::gl_renderer::{GlInfo, RendererState};
+use crate::palette;
+use crate::shader_presets;
+use crate::wallpaper::{self, WallpaperBackend};
+
+/// Environment variables that change which GPU or wallpaper backend is used
+const ENVIRONMENT: [&str; 5] = [
+    "XDG_CURRENT_DESKTOP",
+    "XDG_SESSION_TYPE",
+    "WAYLAND_DISPLAY",
+    "DRI_PRIME",
+    "FLATPAK_ID",
+];
+
+/// What [`collect_diagnostics`] found
+#[derive(Debug, Clone)]
+pub struct Diagnostics {
+    pub app_version: &'static str,
+    /// `None` without a GL context
+    pub gl: Option<GlInfo>,
+    /// Backend wallpapers are set with, after the saved preference
+    pub backend: WallpaperBackend,
+    /// Backend the session would get without a preference
+    pub detected_backend: WallpaperBackend,
+    pub bundled_palettes: Option<PathBuf>,
+    pub user_palettes: PathBuf,
+    pub user_shaders: PathBuf,
+    /// Each of [`ENVIRONMENT`] and its value, if set
+    pub environment: Vec<(&'static str, Option<String>)>,
+}
+
+/// Gather the diagnostics, with the GL strings of `renderer` if there is one.
+pub fn collect_diagnostics(renderer: Option<&RendererState>) -> Diagnostics {
+    Diagnostics {
+        app_version: env!("CARGO_PKG_VERSION"),
+        gl: renderer.map(RendererState::gl_info),
+        backend: wallpaper::backend(),
+        detected_backend: wallpaper::detect_backend(),
+        bundled_palettes: palette::bundled_palettes_dir(),
+        user_palettes: palette::user_palettes_dir(),
+        user_shaders: shader_presets::user_shaders_dir(),
+        environment: ENVIRONMENT
+            .iter()
+            .map(|&name| (name, std::env::var(name).ok()))
+            .collect(),
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    /// One `Key: value` line each, ending in a newline
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Wallrus version: {}", self.app_version)?;
+        match &self.gl {
+            Some(gl) => {
+                writeln!(f, "GL vendor: {}", gl.vendor)?;
+                writeln!(f, "GL renderer: {}", gl.renderer)?;
+                writeln!(f, "GL version: {}", gl.version)?;
+            }
+            None => writeln!(f, "GL: no context")?,
+        }
+        writeln!(
+            f,
+            "Wallpaper backend: {} (detected: {})",
+            self.backend.label(),
+            self.detected_backend.label()
+        )?;
+        match &self.bundled_palettes {
+            Some(dir) => writeln!(f, "Bundled palettes: {}", dir.display())?,
+            None => writeln!(f, "Bundled palettes: not found")?,
+        }
+        writeln!(f, "User palettes: {}", self.user_palettes.display())?;
+        writeln!(f, "User shaders: {}", self.user_shaders.display())?;
+        for (name, value) in &self.environment {
+            writeln!(f, "{}: {}", name, value.as_deref().unwrap_or("unset"))?;
+        }
+        Ok(())
+    }
+}
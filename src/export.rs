@@ -1,4 +1,15 @@
-use image::{ImageBuffer, Rgba};
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk4::{gdk, glib};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ExtendedColorType, ImageBuffer, ImageFormat, Rgba};
+
+use crate::gl_renderer::{RenderSeed, RendererState, MAX_COLOR_COUNT, MIN_COLOR_COUNT};
+use crate::palette::{self, ColorSpace};
+use crate::shader_presets::PresetEntry;
 
 /// Export resolution presets
 #[derive(Debug, Clone, Copy)]
@@ -7,8 +18,14 @@ pub enum ExportResolution {
     Hd,                // 1920x1080
     Qhd,               // 2560x1440
     Uhd4k,             // 3840x2160
+    Ultrawide,         // 3440x1440
+    Custom(u32, u32),  // User-entered size
 }
 
+/// Bounds for custom export dimensions
+pub const MIN_CUSTOM_DIMENSION: u32 = 16;
+pub const MAX_CUSTOM_DIMENSION: u32 = 16384;
+
 impl ExportResolution {
     pub fn dimensions(self) -> (u32, u32) {
         match self {
@@ -16,63 +33,1385 @@ impl ExportResolution {
             ExportResolution::Hd => (1920, 1080),
             ExportResolution::Qhd => (2560, 1440),
             ExportResolution::Uhd4k => (3840, 2160),
+            ExportResolution::Ultrawide => (3440, 1440),
+            ExportResolution::Custom(w, h) => (w, h),
         }
     }
 
     /// Build from ComboRow index. Index 0 = Display (requires dimensions),
-    /// 1 = HD, 2 = QHD, 3 = 4K.
-    pub fn from_index(index: u32, display_dims: (u32, u32)) -> Self {
+    /// 1 = HD, 2 = QHD, 3 = 4K, 4 = ultrawide, 5 = Custom (requires dimensions).
+    pub fn from_index(index: u32, display_dims: (u32, u32), custom_dims: (u32, u32)) -> Self {
         match index {
             0 => ExportResolution::Display(display_dims.0, display_dims.1),
             1 => ExportResolution::Hd,
             2 => ExportResolution::Qhd,
             3 => ExportResolution::Uhd4k,
+            4 => ExportResolution::Ultrawide,
+            5 => ExportResolution::Custom(
+                custom_dims
+                    .0
+                    .clamp(MIN_CUSTOM_DIMENSION, MAX_CUSTOM_DIMENSION),
+                custom_dims
+                    .1
+                    .clamp(MIN_CUSTOM_DIMENSION, MAX_CUSTOM_DIMENSION),
+            ),
             _ => ExportResolution::Hd,
         }
     }
+
+    /// ComboRow index of the Custom entry
+    pub const CUSTOM_INDEX: u32 = 5;
+}
+
+/// Supersampling for exports: render at a multiple of the target size and
+/// average each block of pixels down, smoothing edges and gradients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleQuality {
+    #[default]
+    X1,
+    X2,
+    X4,
+}
+
+impl SampleQuality {
+    /// Render scale per axis
+    pub fn factor(self) -> u32 {
+        match self {
+            SampleQuality::X1 => 1,
+            SampleQuality::X2 => 2,
+            SampleQuality::X4 => 4,
+        }
+    }
+
+    /// Map a ComboRow index (1x, 2x, 4x) to a quality
+    pub fn from_index(index: u32) -> Self {
+        match index {
+            1 => SampleQuality::X2,
+            2 => SampleQuality::X4,
+            _ => SampleQuality::X1,
+        }
+    }
 }
 
+/// Dithering applied when quantizing a render to 8 bits per channel.
+///
+/// Only the rendered image is dithered; palette colors embedded as metadata
+/// and saved palette images keep their exact values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dither {
+    #[default]
+    Off,
+    /// 8x8 Bayer matrix; deterministic, so re-exports are identical
+    Ordered,
+}
+
+/// 8x8 Bayer threshold matrix (values 0–63)
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Render the current preset at `width`x`height` with supersampling and
+/// optional dithering, returning 8-bit RGBA pixels.
+///
+/// The factor is lowered when the supersampled size would exceed the GPU's
+/// render limit, so a large export still succeeds, just less smoothed. It
+/// stays a power of two, since the GPU path halves the render per blit.
+/// Supersampled renders are averaged down on the GPU, or on the CPU where
+/// the context can't blit. Dithering needs a float framebuffer; without one
+/// the render falls back to plain 8-bit output.
+pub fn render_pixels(
+    renderer: &RendererState,
+    width: u32,
+    height: u32,
+    quality: SampleQuality,
+    dither: Dither,
+    control: &mut ExportControl,
+) -> Result<Vec<u8>, String> {
+    let max_size = renderer.max_render_size().max(1) as u32;
+    let factor = [4, 2, 1]
+        .into_iter()
+        .filter(|&f| f <= quality.factor())
+        .find(|f| width * f <= max_size && height * f <= max_size)
+        .unwrap_or(1);
+    let (w, h) = ((width * factor) as i32, (height * factor) as i32);
+    let gpu_downscale = factor > 1 && renderer.supports_blit();
+    let mut before_pass = |pass, passes| control.pass(pass, passes);
+
+    if dither != Dither::Off {
+        let pixels = if gpu_downscale {
+            renderer.render_supersampled_f32(width as i32, height as i32, factor, &mut before_pass)
+        } else {
+            renderer.render_to_pixels_f32(w, h).map(|pixels| {
+                if factor > 1 {
+                    downsample_box(&pixels, width, height, factor, |v| v)
+                } else {
+                    pixels
+                }
+            })
+        };
+        match pixels {
+            Ok(pixels) => return Ok(quantize(&pixels, width, dither)),
+            Err(e) => eprintln!("Float render unavailable, exporting without dither: {}", e),
+        }
+    }
+
+    if gpu_downscale {
+        return renderer.render_supersampled(width as i32, height as i32, factor, &mut before_pass);
+    }
+    let pixels = renderer.render_to_pixels(w, h)?;
+    if factor == 1 {
+        return Ok(pixels);
+    }
+    before_pass(1, 1)?;
+    let averaged = downsample_box(&pixels, width, height, factor, |v| v as f32 / 255.0);
+    Ok(quantize(&averaged, width, Dither::Off))
+}
+
+/// Render a seamless tile: tileable presets wrap their pattern (`uTile`),
+/// others have their edges blended by `make_tileable`, unless an even
+/// kaleidoscope fold count already mirrors them. A render whose opposite
+/// borders still don't match (e.g. with distortion or lighting centered on
+/// the image) is blended too.
+pub fn render_tile(
+    renderer: &mut RendererState,
+    width: u32,
+    height: u32,
+    quality: SampleQuality,
+    dither: Dither,
+    control: &mut ExportControl,
+) -> Result<Vec<u8>, String> {
+    if !renderer.is_tileable() {
+        let mut pixels = render_pixels(renderer, width, height, quality, dither, control)?;
+        if !(renderer.symmetry_tiles() && tiles_seamlessly(&pixels, width, height)) {
+            make_tileable(&mut pixels, width, height);
+        }
+        return Ok(pixels);
+    }
+
+    renderer.tile = true;
+    let result = render_pixels(renderer, width, height, quality, dither, control);
+    renderer.tile = false;
+
+    let mut pixels = result?;
+    if !tiles_seamlessly(&pixels, width, height) {
+        make_tileable(&mut pixels, width, height);
+    }
+    Ok(pixels)
+}
+
+/// Largest mean per-channel step across a tile seam, relative to the steps
+/// between neighboring pixels along the same edges, that still looks seamless
+const SEAM_TOLERANCE: f32 = 2.0;
+
+/// Steps below this many 8-bit levels are never visible
+const SEAM_MIN_STEP: f32 = 2.0;
+
+/// Compare opposite borders of an RGBA image: whether the step from the
+/// last column to the first (and the last row to the first) is no bigger
+/// than the image's own steps next to those edges.
+pub fn tiles_seamlessly(pixels: &[u8], width: u32, height: u32) -> bool {
+    let (w, h) = (width as usize, height as usize);
+    if w < 3 || h < 3 {
+        return true;
+    }
+    let px = |x: usize, y: usize| &pixels[(y * w + x) * 4..][..3];
+    let step = |a: &[u8], b: &[u8]| {
+        a.iter()
+            .zip(b)
+            .map(|(&a, &b)| (a as f32 - b as f32).abs())
+            .sum::<f32>()
+            / 3.0
+    };
+
+    // Mean step across the seam and next to it, for the left/right edges
+    // or (transposed) the top/bottom ones
+    let edge = |transposed: bool| {
+        let (len, n) = if transposed { (h, w) } else { (w, h) };
+        let at = |along: usize, i: usize| {
+            if transposed {
+                px(i, along)
+            } else {
+                px(along, i)
+            }
+        };
+        let (mut seam, mut inner) = (0.0, 0.0);
+        for i in 0..n {
+            seam += step(at(len - 1, i), at(0, i));
+            inner += (step(at(0, i), at(1, i)) + step(at(len - 2, i), at(len - 1, i))) / 2.0;
+        }
+        (seam / n as f32, inner / n as f32)
+    };
+    let (columns, rows) = (edge(false), edge(true));
+
+    [columns, rows]
+        .iter()
+        .all(|&(seam, inner)| seam <= (inner * SEAM_TOLERANCE).max(SEAM_MIN_STEP))
+}
+
+/// Fraction of the width/height over which `make_tileable` blends edges
+const TILE_BLEND_FRACTION: usize = 8;
+
+/// Make an RGBA image tile by mirror-blending its edges: pixels near each
+/// edge are mixed with their mirror images across the seam, fully (50/50)
+/// at the seam and fading out over an eighth of the image. Opposite edges
+/// then meet with the same colors.
+pub fn make_tileable(pixels: &mut [u8], width: u32, height: u32) {
+    let (w, h) = (width as usize, height as usize);
+    let blend = |pixels: &mut [u8], a: usize, b: usize, weight: f32| {
+        for c in 0..4 {
+            let (va, vb) = (pixels[a + c] as f32, pixels[b + c] as f32);
+            pixels[a + c] = (va + (vb - va) * weight).round() as u8;
+            pixels[b + c] = (vb + (va - vb) * weight).round() as u8;
+        }
+    };
+
+    // i counts from the seam inward; the seam pair gets weight 0.5
+    let band = (w / TILE_BLEND_FRACTION).max(1).min(w / 2);
+    for y in 0..h {
+        for i in 0..band {
+            let weight = 0.5 * (1.0 - i as f32 / band as f32);
+            blend(pixels, (y * w + i) * 4, (y * w + w - 1 - i) * 4, weight);
+        }
+    }
+    let band = (h / TILE_BLEND_FRACTION).max(1).min(h / 2);
+    for x in 0..w {
+        for i in 0..band {
+            let weight = 0.5 * (1.0 - i as f32 / band as f32);
+            blend(pixels, (i * w + x) * 4, ((h - 1 - i) * w + x) * 4, weight);
+        }
+    }
+}
+
+/// Average each `factor`x`factor` block of an RGBA image into one pixel,
+/// converting channels to 0–1 with `to_unit`. `width` and `height` are
+/// the output size.
+fn downsample_box<T: Copy>(
+    pixels: &[T],
+    width: u32,
+    height: u32,
+    factor: u32,
+    to_unit: impl Fn(T) -> f32,
+) -> Vec<f32> {
+    let (w, f) = (width as usize, factor as usize);
+    let src_row = w * f * 4;
+    let count = (f * f) as f32;
+    let mut out = Vec::with_capacity(w * height as usize * 4);
+    for y in 0..height as usize {
+        for x in 0..w {
+            let mut sum = [0.0f32; 4];
+            for sy in 0..f {
+                let row = &pixels[(y * f + sy) * src_row..][..src_row];
+                for sx in 0..f {
+                    let px = &row[(x * f + sx) * 4..][..4];
+                    for (acc, &v) in sum.iter_mut().zip(px) {
+                        *acc += to_unit(v);
+                    }
+                }
+            }
+            out.extend(sum.map(|s| s / count));
+        }
+    }
+    out
+}
+
+/// Quantize 0–1 RGBA floats to 8 bits per channel. With `Dither::Ordered`
+/// a Bayer threshold offsets RGB by up to half a step before rounding,
+/// breaking gradients' flat bands into a fine, stable pattern. Alpha is
+/// never dithered.
+fn quantize(pixels: &[f32], width: u32, dither: Dither) -> Vec<u8> {
+    let width = width as usize;
+    pixels
+        .chunks_exact(4)
+        .enumerate()
+        .flat_map(|(i, px)| {
+            let offset = match dither {
+                Dither::Off => 0.0,
+                Dither::Ordered => {
+                    let (x, y) = (i % width, i / width);
+                    (BAYER_8X8[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5
+                }
+            };
+            let to_u8 = |v: f32, offset: f32| (v * 255.0 + offset).round().clamp(0.0, 255.0) as u8;
+            [
+                to_u8(px[0], offset),
+                to_u8(px[1], offset),
+                to_u8(px[2], offset),
+                to_u8(px[3], 0.0),
+            ]
+        })
+        .collect()
+}
+
+/// Default JPEG quality (0–100), the only lossy export format
+pub const DEFAULT_EXPORT_QUALITY: u8 = 90;
+
 /// Export format
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ExportFormat {
+    #[default]
     Png,
     Jpeg,
+    WebP,
 }
 
 impl ExportFormat {
     /// Infer format from a file extension string.
     pub fn from_extension(ext: &str) -> Self {
+        Self::parse_extension(ext).unwrap_or_default()
+    }
+
+    /// The format a file extension names, or `None` if it isn't one of ours.
+    pub fn parse_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
-            "jpg" | "jpeg" => ExportFormat::Jpeg,
-            _ => ExportFormat::Png,
+            "png" => Some(ExportFormat::Png),
+            "jpg" | "jpeg" => Some(ExportFormat::Jpeg),
+            "webp" => Some(ExportFormat::WebP),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name for messages
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Png => "PNG",
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::WebP => "WebP",
         }
     }
 }
 
-/// Save RGBA pixel data to an image file
-pub fn save_pixels(
+/// Get `path` ready to be written as `format`: its extension, if it has one,
+/// must name that format, and missing parent directories are created.
+pub fn prepare_output_path(path: &Path, format: ExportFormat) -> Result<(), String> {
+    if let Some(ext) = path.extension() {
+        let ext = ext.to_string_lossy();
+        match ExportFormat::parse_extension(&ext) {
+            Some(named) if named == format => {}
+            Some(named) => {
+                return Err(format!(
+                    "{} has a {} extension but is being saved as {}",
+                    path.display(),
+                    named.label(),
+                    format.label()
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "Unsupported image extension \".{}\" (expected png, jpg or webp)",
+                    ext
+                ))
+            }
+        }
+    }
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e)),
+        _ => Ok(()),
+    }
+}
+
+/// PNG text chunk keywords for embedded wallpaper metadata
+const META_KEY_PALETTE: &str = "Wallrus Palette";
+const META_KEY_PRESET: &str = "Wallrus Preset";
+const META_KEY_PARAMS: &str = "Wallrus Params";
+const META_KEY_CONTROLS: &str = "Wallrus Controls";
+const META_KEY_SEED: &str = "Wallrus Seed";
+const META_KEY_COLOR_SPACE: &str = "Wallrus Color Space";
+const META_KEY_COLOR_COUNT: &str = "Wallrus Color Count";
+const META_KEY_SOFTWARE: &str = "Software";
+
+/// How a wallpaper was made, embedded in exported PNGs as text chunks so the
+/// palette and preset can be recreated from the image later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WallpaperMeta {
+    pub colors: [[f32; 3]; 4],
+    pub preset: String,
+    /// Preset parameter values by uniform name
+    pub params: Vec<(String, f32)>,
+    /// Shared control and effect values by uniform name, the two halves of
+    /// `uShadeCenter` as `uShadeCenter.x` and `.y`; empty for files from
+    /// before they were recorded
+    pub controls: Vec<(String, f32)>,
+    /// Seed of the render; 0 for files from before seeds were recorded
+    pub seed: RenderSeed,
+    /// Space the colors were blended in; sRGB for files from before it was
+    /// recorded
+    pub color_space: ColorSpace,
+    /// Palette colors the render blended through; all four for files from
+    /// before it was recorded
+    pub color_count: u32,
+    pub app_version: String,
+}
+
+impl WallpaperMeta {
+    /// Metadata for the running app version
+    pub fn new(colors: [[f32; 3]; 4], preset: &str) -> Self {
+        Self {
+            colors,
+            preset: preset.to_string(),
+            params: Vec::new(),
+            controls: Vec::new(),
+            seed: RenderSeed::default(),
+            color_space: ColorSpace::default(),
+            color_count: MAX_COLOR_COUNT,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// The value recorded for the shared control or effect `uniform`
+    pub fn control(&self, uniform: &str) -> Option<f32> {
+        self.controls
+            .iter()
+            .find(|(name, _)| name == uniform)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Read the metadata Wallrus embedded in a PNG. Returns None for files that
+/// aren't PNGs or that carry no (or incomplete) Wallrus metadata.
+pub fn read_embedded_metadata(path: &Path) -> Option<WallpaperMeta> {
+    let file = std::fs::File::open(path).ok()?;
+    let reader = png::Decoder::new(std::io::BufReader::new(file))
+        .read_info()
+        .ok()?;
+    let info = reader.info();
+
+    // tEXt chunks hold the Latin-1 fields, iTXt the UTF-8 preset name
+    let text = |key: &str| {
+        info.uncompressed_latin1_text
+            .iter()
+            .find(|c| c.keyword == key)
+            .map(|c| c.text.clone())
+            .or_else(|| {
+                info.utf8_text
+                    .iter()
+                    .find(|c| c.keyword == key)
+                    .and_then(|c| c.get_text().ok())
+            })
+    };
+
+    let hex_colors: Vec<[f32; 3]> = text(META_KEY_PALETTE)?
+        .split(',')
+        .map(|hex| palette::hex_to_rgb(hex).ok())
+        .collect::<Option<_>>()?;
+    let colors: [[f32; 3]; 4] = hex_colors.try_into().ok()?;
+
+    // `name=value` pairs; malformed entries are skipped
+    let values = |key: &str| -> Vec<(String, f32)> {
+        text(key)
+            .map(|text| {
+                text.split(',')
+                    .filter_map(|pair| {
+                        let (name, value) = pair.split_once('=')?;
+                        Some((name.to_string(), value.parse().ok()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let seed = text(META_KEY_SEED)
+        .and_then(|text| RenderSeed::parse(&text).ok())
+        .unwrap_or_default();
+
+    let color_space = text(META_KEY_COLOR_SPACE)
+        .and_then(|text| ColorSpace::parse(&text).ok())
+        .unwrap_or_default();
+
+    let color_count = text(META_KEY_COLOR_COUNT)
+        .and_then(|text| text.parse().ok())
+        .filter(|n| (MIN_COLOR_COUNT..=MAX_COLOR_COUNT).contains(n))
+        .unwrap_or(MAX_COLOR_COUNT);
+
+    let software = text(META_KEY_SOFTWARE).unwrap_or_default();
+    Some(WallpaperMeta {
+        colors,
+        preset: text(META_KEY_PRESET)?,
+        params: values(META_KEY_PARAMS),
+        controls: values(META_KEY_CONTROLS),
+        seed,
+        color_space,
+        color_count,
+        app_version: software.trim_start_matches("Wallrus ").to_string(),
+    })
+}
+
+/// Encode an 8-bit RGB or RGBA PNG with the metadata as text chunks.
+fn encode_png_with_metadata(
+    img: &DynamicImage,
+    meta: &WallpaperMeta,
+    out: &mut Vec<u8>,
+) -> Result<(), String> {
+    let to_err = |e: png::EncodingError| format!("Failed to encode PNG: {}", e);
+
+    let mut encoder = png::Encoder::new(&mut *out, img.width(), img.height());
+    encoder.set_color(if img.color().has_alpha() {
+        png::ColorType::Rgba
+    } else {
+        png::ColorType::Rgb
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let hex: Vec<String> = meta
+        .colors
+        .iter()
+        .map(|c| palette::rgb_to_hex(*c))
+        .collect();
+    encoder
+        .add_text_chunk(META_KEY_PALETTE.to_string(), hex.join(","))
+        .map_err(to_err)?;
+    encoder
+        .add_itxt_chunk(META_KEY_PRESET.to_string(), meta.preset.clone())
+        .map_err(to_err)?;
+    for (key, values) in [
+        (META_KEY_PARAMS, &meta.params),
+        (META_KEY_CONTROLS, &meta.controls),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        let pairs: Vec<String> = values
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        encoder
+            .add_text_chunk(key.to_string(), pairs.join(","))
+            .map_err(to_err)?;
+    }
+    encoder
+        .add_text_chunk(META_KEY_SEED.to_string(), meta.seed.to_string())
+        .map_err(to_err)?;
+    encoder
+        .add_text_chunk(
+            META_KEY_COLOR_SPACE.to_string(),
+            meta.color_space.name().to_string(),
+        )
+        .map_err(to_err)?;
+    encoder
+        .add_text_chunk(
+            META_KEY_COLOR_COUNT.to_string(),
+            meta.color_count.to_string(),
+        )
+        .map_err(to_err)?;
+    encoder
+        .add_text_chunk(
+            META_KEY_SOFTWARE.to_string(),
+            format!("Wallrus {}", meta.app_version),
+        )
+        .map_err(to_err)?;
+
+    let mut writer = encoder.write_header().map_err(to_err)?;
+    writer.write_image_data(img.as_bytes()).map_err(to_err)?;
+    writer.finish().map_err(to_err)
+}
+
+/// Background transparent renders are composited over for JPEG, which has
+/// no alpha channel, unless the caller flattens them first
+pub const DEFAULT_JPEG_BACKGROUND: [u8; 3] = [0, 0, 0];
+
+/// Whether any pixel of RGBA data is less than fully opaque
+pub fn has_transparency(pixels: &[u8]) -> bool {
+    pixels.chunks_exact(4).any(|p| p[3] < 255)
+}
+
+/// Composite straight-alpha RGBA pixels over an opaque `background`,
+/// giving RGBA pixels with full alpha.
+pub fn composite_over(pixels: &[u8], background: [u8; 3]) -> Vec<u8> {
+    pixels
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let a = p[3] as u32;
+            let mix = |c: u8, bg: u8| ((c as u32 * a + bg as u32 * (255 - a) + 127) / 255) as u8;
+            [
+                mix(p[0], background[0]),
+                mix(p[1], background[1]),
+                mix(p[2], background[2]),
+                255,
+            ]
+        })
+        .collect()
+}
+
+/// Encode RGBA pixel data in the given format.
+///
+/// `quality` (0–100) only affects JPEG. PNG is always lossless, and WebP is
+/// written losslessly too since the `image` crate only ships a lossless
+/// WebP encoder; both ignore the quality parameter.
+///
+/// PNG and WebP keep the alpha channel when the pixels have transparency
+/// (say, from a shader reading `uColorAlpha`) and are written as RGB
+/// otherwise. JPEG composites transparency over [`DEFAULT_JPEG_BACKGROUND`];
+/// use [`composite_over`] first for another background.
+///
+/// `meta`, when given, is embedded in PNG output and ignored for other formats.
+pub fn export_image(
     pixels: &[u8],
     width: u32,
     height: u32,
-    path: &std::path::Path,
     format: ExportFormat,
-) -> Result<(), String> {
+    quality: u8,
+    meta: Option<&WallpaperMeta>,
+) -> Result<Vec<u8>, String> {
     let img: ImageBuffer<Rgba<u8>, _> = ImageBuffer::from_raw(width, height, pixels.to_vec())
         .ok_or("Failed to create image buffer from pixel data")?;
+    let img = if has_transparency(pixels) {
+        DynamicImage::ImageRgba8(img)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(img).to_rgb8())
+    };
 
+    let mut out = Vec::new();
     match format {
-        ExportFormat::Png => {
-            img.save(path)
-                .map_err(|e| format!("Failed to save PNG: {}", e))?;
-        }
+        ExportFormat::Png => match meta {
+            Some(meta) => encode_png_with_metadata(&img, meta, &mut out)?,
+            None => {
+                img.write_to(&mut std::io::Cursor::new(&mut out), ImageFormat::Png)
+                    .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+            }
+        },
         ExportFormat::Jpeg => {
-            // Convert RGBA to RGB for JPEG
-            let rgb_img = image::DynamicImage::ImageRgba8(img).to_rgb8();
-            rgb_img
-                .save(path)
-                .map_err(|e| format!("Failed to save JPEG: {}", e))?;
+            let rgb_img = if img.color().has_alpha() {
+                let flat = composite_over(img.as_bytes(), DEFAULT_JPEG_BACKGROUND);
+                DynamicImage::ImageRgba8(
+                    ImageBuffer::from_raw(width, height, flat)
+                        .ok_or("Failed to create image buffer from pixel data")?,
+                )
+                .to_rgb8()
+            } else {
+                img.to_rgb8()
+            };
+            // The encoder accepts quality 1–100
+            JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+                .encode_image(&rgb_img)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        }
+        ExportFormat::WebP => {
+            let color = if img.color().has_alpha() {
+                ExtendedColorType::Rgba8
+            } else {
+                ExtendedColorType::Rgb8
+            };
+            WebPEncoder::new_lossless(&mut out)
+                .encode(img.as_bytes(), width, height, color)
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Save RGBA pixel data to an image file, creating missing parent
+/// directories (see `prepare_output_path`)
+pub fn save_pixels(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    path: &Path,
+    format: ExportFormat,
+    quality: u8,
+    meta: Option<&WallpaperMeta>,
+) -> Result<(), String> {
+    prepare_output_path(path, format)?;
+    let data = export_image(pixels, width, height, format, quality, meta)?;
+    let partial = partial_path(path);
+    std::fs::write(&partial, data)
+        .and_then(|()| std::fs::rename(&partial, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&partial);
+            format!("Failed to write {}: {}", path.display(), e)
+        })
+}
+
+/// Hidden file beside `path` that exports are written to first and renamed
+/// from once complete, so a failed or cancelled export leaves no partial
+/// file at `path`
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.part", name))
+}
+
+/// How far a multi-file (or multi-frame) export has got, passed to its
+/// progress callback before each item is rendered and before each
+/// supersample pass
+#[derive(Debug, Clone, Copy)]
+pub struct ExportProgress<'a> {
+    /// Items finished so far
+    pub done: usize,
+    pub total: usize,
+    /// The file being written
+    pub current: &'a Path,
+    /// Supersample pass of `current` about to run, and how many there are;
+    /// `None` as the item starts
+    pub pass: Option<(u32, u32)>,
+}
+
+impl ExportProgress<'_> {
+    /// Share of the whole export finished, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        let passes = self
+            .pass
+            .map_or(0.0, |(pass, passes)| (pass - 1) as f64 / passes as f64);
+        ((self.done as f64 + passes) / self.total.max(1) as f64).min(1.0)
+    }
+}
+
+impl std::fmt::Display for ExportProgress<'_> {
+    /// `[3/12] wallrus_plasma.png`, with ` (pass 1/2)` during supersampling
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = self
+            .current
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        write!(
+            f,
+            "[{}/{}] {}",
+            (self.done + 1).min(self.total),
+            self.total,
+            name
+        )?;
+        if let Some((pass, passes)) = self.pass {
+            write!(f, " (pass {}/{})", pass, passes)?;
         }
+        Ok(())
+    }
+}
+
+/// Error of an export stopped through its [`CancelToken`]
+pub const EXPORT_CANCELLED: &str = "Export cancelled";
+
+/// Stops a running export between items and supersample passes; clones
+/// share one flag. Exports run on the GL thread, so the flag isn't atomic.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Rc<Cell<bool>>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.set(true);
     }
 
-    Ok(())
+    pub fn is_cancelled(&self) -> bool {
+        self.0.get()
+    }
+}
+
+/// Progress reporting and cancellation for a long export
+pub struct ExportControl<'a> {
+    on_progress: Box<dyn FnMut(&ExportProgress) + 'a>,
+    cancel: CancelToken,
+    /// `done`, `total` and `current` of the item being rendered
+    item: Option<(usize, usize, PathBuf)>,
+}
+
+impl<'a> ExportControl<'a> {
+    /// Call `on_progress` before each item and supersample pass, and stop
+    /// once `cancel` is cancelled
+    pub fn new(cancel: CancelToken, on_progress: impl FnMut(&ExportProgress) + 'a) -> Self {
+        Self {
+            on_progress: Box::new(on_progress),
+            cancel,
+            item: None,
+        }
+    }
+
+    /// Report that `progress.current` is next, then give
+    /// `Err(EXPORT_CANCELLED)` if the export was cancelled meanwhile
+    pub fn next(&mut self, progress: ExportProgress) -> Result<(), String> {
+        self.item = Some((
+            progress.done,
+            progress.total,
+            progress.current.to_path_buf(),
+        ));
+        (self.on_progress)(&progress);
+        self.check()
+    }
+
+    /// Like [`next`](Self::next), for supersample pass `pass` of `passes`
+    /// of the current item
+    fn pass(&mut self, pass: u32, passes: u32) -> Result<(), String> {
+        if let Some((done, total, current)) = &self.item {
+            (self.on_progress)(&ExportProgress {
+                done: *done,
+                total: *total,
+                current,
+                pass: Some((pass, passes)),
+            });
+        }
+        self.check()
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.cancel.is_cancelled() {
+            return Err(EXPORT_CANCELLED.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Wrap RGBA pixel data in a GDK texture, e.g. for the clipboard.
+/// The pixels are copied once; no file or encoding round-trip is involved.
+pub fn rgba_texture(pixels: &[u8], width: u32, height: u32) -> gdk::MemoryTexture {
+    gdk::MemoryTexture::new(
+        width as i32,
+        height as i32,
+        gdk::MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from(pixels),
+        width as usize * 4,
+    )
+}
+
+/// Outcome of a batch export
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub exported: Vec<PathBuf>,
+    /// Palette image and the error it failed with
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+/// Render a wallpaper for every palette in `category` and write each into
+/// `out_dir` as a PNG named by `template` (see `expand_filename_template`).
+/// Names that come out the same within the batch get a numeric suffix.
+///
+/// Uses the renderer's offscreen path, switching it to `preset` first; the
+/// renderer's palette colors are restored afterwards. A palette that fails
+/// is recorded in the summary and the batch moves on. Progress is reported
+/// to `control` before each palette; cancelling stops the batch with
+/// [`EXPORT_CANCELLED`], keeping the files already written.
+pub fn batch_export(
+    renderer: &mut RendererState,
+    category: &str,
+    preset: &PresetEntry,
+    resolution: (u32, u32),
+    out_dir: &Path,
+    template: &str,
+    control: &mut ExportControl,
+) -> Result<BatchSummary, String> {
+    let categories = palette::list_palette_categories();
+    let palettes = categories
+        .get(category)
+        .ok_or_else(|| format!("Unknown palette category: {}", category))?;
+
+    if renderer.current_preset != preset.name() || renderer.load_error.is_some() {
+        renderer.load_entry(preset)?;
+    }
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+
+    let saved_colors = [
+        renderer.color1,
+        renderer.color2,
+        renderer.color3,
+        renderer.color4,
+    ];
+    let (w, h) = resolution;
+    let mut summary = BatchSummary::default();
+    let timestamp = unix_timestamp();
+
+    for (i, palette_path) in palettes.iter().enumerate() {
+        let fields = FilenameFields {
+            palette: palette_path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("palette")
+                .to_string(),
+            preset: preset.name().to_string(),
+            width: w,
+            height: h,
+            timestamp,
+            index: i + 1,
+        };
+        let name = expand_filename_template(template, &fields);
+        let out_path = unique_path(out_dir, &name, "png", &summary.exported);
+        let next = control.next(ExportProgress {
+            done: i,
+            total: palettes.len(),
+            current: &out_path,
+            pass: None,
+        });
+        if let Err(e) = next {
+            [
+                renderer.color1,
+                renderer.color2,
+                renderer.color3,
+                renderer.color4,
+            ] = saved_colors;
+            return Err(e);
+        }
+
+        let result = palette::extract_four_colors(palette_path).and_then(|colors| {
+            [
+                renderer.color1,
+                renderer.color2,
+                renderer.color3,
+                renderer.color4,
+            ] = colors;
+            let pixels = renderer.render_to_pixels(w as i32, h as i32)?;
+            save_pixels(
+                &pixels,
+                w,
+                h,
+                &out_path,
+                ExportFormat::Png,
+                DEFAULT_EXPORT_QUALITY,
+                Some(&renderer.wallpaper_meta()),
+            )
+        });
+        match result {
+            Ok(()) => summary.exported.push(out_path),
+            Err(e) => summary.errors.push((palette_path.clone(), e)),
+        }
+    }
+
+    [
+        renderer.color1,
+        renderer.color2,
+        renderer.color3,
+        renderer.color4,
+    ] = saved_colors;
+    Ok(summary)
+}
+
+/// Screens a resolution pack is rendered for: label, width, height
+pub const PACK_SIZES: [(&str, u32, u32); 4] = [
+    ("phone", 1170, 2532),
+    ("laptop", 1920, 1080),
+    ("desktop", 2560, 1440),
+    ("4k", 3840, 2160),
+];
+
+/// Render the current scene once per `PACK_SIZES` entry into the folder
+/// `out_dir/<name>`, as PNGs named `<label>_<width>x<height>.png`.
+///
+/// Each size is a render of its own (not a crop or resize), so the
+/// composition follows the target's shape. Animated presets are frozen at
+/// the current frame so every file shows the same moment. Returns the
+/// written files in `PACK_SIZES` order. Progress and cancellation work as
+/// for [`batch_export`].
+pub fn export_pack(
+    renderer: &mut RendererState,
+    out_dir: &Path,
+    name: &str,
+    quality: SampleQuality,
+    dither: Dither,
+    control: &mut ExportControl,
+) -> Result<Vec<PathBuf>, String> {
+    let pack_dir = out_dir.join(name);
+    std::fs::create_dir_all(&pack_dir)
+        .map_err(|e| format!("Failed to create {}: {}", pack_dir.display(), e))?;
+
+    let saved_time = renderer.time_override;
+    renderer.time_override =
+        Some(saved_time.unwrap_or_else(|| renderer.start_time.elapsed().as_secs_f32()));
+
+    let meta = renderer.wallpaper_meta();
+    let result = PACK_SIZES
+        .iter()
+        .enumerate()
+        .map(|(i, &(label, w, h))| {
+            let path = pack_dir.join(format!("{}_{}x{}.png", label, w, h));
+            control.next(ExportProgress {
+                done: i,
+                total: PACK_SIZES.len(),
+                current: &path,
+                pass: None,
+            })?;
+            let pixels = render_pixels(renderer, w, h, quality, dither, control)?;
+            save_pixels(
+                &pixels,
+                w,
+                h,
+                &path,
+                ExportFormat::Png,
+                DEFAULT_EXPORT_QUALITY,
+                Some(&meta),
+            )?;
+            Ok(path)
+        })
+        .collect();
+
+    renderer.time_override = saved_time;
+    result
+}
+
+/// Seconds since the Unix epoch, for the `{timestamp}` token
+pub fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Default export filename template (without extension)
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "wallrus_{preset}_{timestamp}";
+
+/// Values for the tokens of a filename template
+#[derive(Debug, Clone)]
+pub struct FilenameFields {
+    /// Palette image file stem, or "custom" for edited colors
+    pub palette: String,
+    pub preset: String,
+    pub width: u32,
+    pub height: u32,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// 1-based position in a batch; 1 for single exports
+    pub index: usize,
+}
+
+/// Expand a filename template such as `{palette}_{preset}_{width}x{height}_{date}`.
+///
+/// Known tokens: `{palette}`, `{preset}` (lowercase), `{width}`, `{height}`,
+/// `{date}` (YYYY-MM-DD), `{time}` (HHMMSS), `{timestamp}` (Unix seconds)
+/// and `{index}`. Unknown tokens are kept literally. Characters that
+/// aren't allowed in filenames are dropped from the result, which falls
+/// back to "wallrus" if nothing is left.
+pub fn expand_filename_template(template: &str, fields: &FilenameFields) -> String {
+    let format_time = |format: &str| {
+        glib::DateTime::from_unix_local(fields.timestamp as i64)
+            .ok()
+            .and_then(|t| t.format(format).ok())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| fields.timestamp.to_string())
+    };
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let Some(end) = after.find('}') else {
+            rest = after;
+            break;
+        };
+        let token = &after[1..end];
+        if token.contains('{') {
+            // A stray brace before the real token, e.g. "{a{date}"
+            out.push('{');
+            rest = &after[1..];
+            continue;
+        }
+        match token {
+            "palette" => out.push_str(&fields.palette),
+            "preset" => out.push_str(&fields.preset.to_lowercase()),
+            "width" => out.push_str(&fields.width.to_string()),
+            "height" => out.push_str(&fields.height.to_string()),
+            "date" => out.push_str(&format_time("%Y-%m-%d")),
+            "time" => out.push_str(&format_time("%H%M%S")),
+            "timestamp" => out.push_str(&fields.timestamp.to_string()),
+            "index" => out.push_str(&fields.index.to_string()),
+            _ => out.push_str(&after[..=end]),
+        }
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+
+    let name: String = out
+        .chars()
+        .filter(|c| {
+            !c.is_control() && !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "wallrus".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// `name.ext` in `dir`, or `name-2.ext`, `name-3.ext`, … if it's in `taken`
+fn unique_path(dir: &Path, name: &str, ext: &str, taken: &[PathBuf]) -> PathBuf {
+    let mut path = dir.join(format!("{}.{}", name, ext));
+    let mut n = 2;
+    while taken.contains(&path) {
+        path = dir.join(format!("{}-{}.{}", name, n, ext));
+        n += 1;
+    }
+    path
+}
+
+/// Default clip length and frame rate for video export
+pub const DEFAULT_VIDEO_SECONDS: u32 = 5;
+pub const DEFAULT_VIDEO_FPS: u32 = 30;
+
+/// Frame count and length in seconds of a clip of about `seconds` at
+/// `fps` that loops: the length is snapped to a whole number (at least
+/// one) of `period`s, so the frame after the last would be the first.
+pub fn video_loop(fps: u32, seconds: u32, period: f32) -> (u32, f32) {
+    let cycles = (seconds as f32 / period).round().max(1.0);
+    let length = cycles * period;
+    let frames = (length * fps as f32).round().max(1.0) as u32;
+    (frames, length)
+}
+
+/// Video container for animated exports
+#[derive(Debug, Clone, Copy, Default)]
+pub enum VideoFormat {
+    #[default]
+    Mp4,
+    WebM,
+}
+
+impl VideoFormat {
+    /// Infer format from a file extension string.
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_lowercase().as_str() {
+            "webm" => VideoFormat::WebM,
+            _ => VideoFormat::default(),
+        }
+    }
+
+    /// ffmpeg's name for the container, given explicitly since the clip is
+    /// written to a temporary file first
+    fn muxer(self) -> &'static str {
+        match self {
+            VideoFormat::Mp4 => "mp4",
+            VideoFormat::WebM => "webm",
+        }
+    }
+
+    /// ffmpeg codec arguments for this container
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::Mp4 => &["-c:v", "libx264", "-pix_fmt", "yuv420p", "-crf", "18"],
+            VideoFormat::WebM => &[
+                "-c:v",
+                "libvpx-vp9",
+                "-pix_fmt",
+                "yuv420p",
+                "-b:v",
+                "0",
+                "-crf",
+                "30",
+            ],
+        }
+    }
+}
+
+/// Render about `seconds` of the current preset at `fps` and encode it
+/// into a video clip at `path` by piping raw RGBA frames to ffmpeg.
+///
+/// The length is snapped to the preset's loop period (see [`video_loop`])
+/// and frames are rendered with `iTime` stepping evenly from 0 across it,
+/// so the clip loops and is independent of how fast the GPU renders it.
+/// Static presets (no `iTime`) are rejected; export a still image instead.
+///
+/// Progress is reported to `control` frame by frame; a cancelled or failed
+/// clip is discarded rather than left truncated.
+pub fn export_video(
+    renderer: &mut RendererState,
+    (width, height): (u32, u32),
+    fps: u32,
+    seconds: u32,
+    path: &Path,
+    format: VideoFormat,
+    control: &mut ExportControl,
+) -> Result<(), String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    if !renderer.uses_time() {
+        return Err(format!(
+            "Preset '{}' is not animated; export a still image instead",
+            renderer.current_preset
+        ));
+    }
+    if fps == 0 || seconds == 0 {
+        return Err("Video length and frame rate must be at least 1".to_string());
+    }
+
+    let partial = partial_path(path);
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", width, height)])
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        // yuv420p needs even dimensions
+        .args(["-vf", "scale=trunc(iw/2)*2:trunc(ih/2)*2"])
+        .args(format.codec_args())
+        .args(["-f", format.muxer()])
+        .arg(&partial)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                "Video export requires ffmpeg, which was not found in PATH".to_string()
+            }
+            _ => format!("Failed to start ffmpeg: {}", e),
+        })?;
+
+    let mut stdin = child.stdin.take().expect("ffmpeg stdin is piped");
+    let (frames, length) = video_loop(fps, seconds, renderer.loop_period());
+    let mut result = Ok(());
+    for frame in 0..frames {
+        result = control.next(ExportProgress {
+            done: frame as usize,
+            total: frames as usize,
+            current: path,
+            pass: None,
+        });
+        if result.is_err() {
+            break;
+        }
+        renderer.time_override = Some(frame as f32 * length / frames as f32);
+        result = renderer
+            .render_to_pixels(width as i32, height as i32)
+            .and_then(|pixels| {
+                stdin
+                    .write_all(&pixels)
+                    .map_err(|e| format!("Failed to write frame to ffmpeg: {}", e))
+            });
+        if result.is_err() {
+            break;
+        }
+    }
+    renderer.time_override = None;
+    // Close stdin so ffmpeg sees end of input and finishes the file
+    drop(stdin);
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to wait for ffmpeg: {}", e));
+    // Don't leave a truncated clip behind
+    let result = output.and_then(|output| match output.status.success() {
+        true => result,
+        false => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(format!("ffmpeg failed: {}", stderr.trim()))
+        }
+    });
+    match result {
+        Ok(()) => std::fs::rename(&partial, path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An RGBA image with each pixel's color given by `color(x, y)`
+    fn image(width: u32, height: u32, color: impl Fn(u32, u32) -> u8) -> Vec<u8> {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let c = color(x, y);
+                [c, c / 2, 255 - c, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blended_edges_tile() {
+        let (w, h) = (64, 48);
+        let mut pixels = image(w, h, |x, _| (x * 255 / (w - 1)) as u8);
+        assert!(!tiles_seamlessly(&pixels, w, h));
+
+        make_tileable(&mut pixels, w, h);
+        assert!(tiles_seamlessly(&pixels, w, h));
+        for y in 0..h as usize {
+            let row = &pixels[y * w as usize * 4..][..w as usize * 4];
+            assert_eq!(row[..4], row[row.len() - 4..]);
+        }
+    }
+
+    #[test]
+    fn seamless_images_are_left_alone() {
+        let (w, h) = (32, 32);
+        let flat = image(w, h, |_, _| 90);
+        let mut blended = flat.clone();
+        make_tileable(&mut blended, w, h);
+        assert_eq!(blended, flat);
+
+        // One period of a wave across the image wraps around smoothly
+        let wave = image(w, h, |x, y| {
+            let t = (x + y) as f32 / w as f32 * std::f32::consts::TAU;
+            (127.5 + 100.0 * t.sin()) as u8
+        });
+        assert!(tiles_seamlessly(&wave, w, h));
+        assert!(tiles_seamlessly(&image(2, 2, |x, _| x as u8 * 200), 2, 2));
+    }
+
+    #[test]
+    fn filename_templates_expand() {
+        let fields = FilenameFields {
+            palette: "sunset".to_string(),
+            preset: "Plasma".to_string(),
+            width: 1920,
+            height: 1080,
+            timestamp: 1_700_000_000,
+            index: 3,
+        };
+        let expand = |template| expand_filename_template(template, &fields);
+        assert_eq!(
+            expand("{palette}_{preset}_{width}x{height}_{index}"),
+            "sunset_plasma_1920x1080_3"
+        );
+        assert_eq!(expand("wallrus_{timestamp}"), "wallrus_1700000000");
+
+        // Unknown tokens and stray braces are kept as written
+        assert_eq!(expand("{nope}-{a{index}-{"), "{nope}-{a3-{");
+        // Characters that aren't allowed in filenames are stripped
+        assert_eq!(expand("a/b\\c:d*e?f\"g<h>i|j\tk"), "abcdefghijk");
+        // Nothing usable left falls back to "wallrus"
+        assert_eq!(expand(""), "wallrus");
+        assert_eq!(expand(" /..: "), "wallrus");
+    }
+
+    #[test]
+    fn video_length_snaps_to_whole_loops() {
+        // 5 s of a 2π loop is about 0.8 loops, which rounds up to one
+        let (frames, length) = video_loop(30, 5, std::f32::consts::TAU);
+        assert!((length - std::f32::consts::TAU).abs() < 1e-5);
+        assert_eq!(frames, 188);
+        // 9 s of a 4 s loop rounds down to two, exactly 240 frames
+        assert_eq!(video_loop(30, 9, 4.0), (240, 8.0));
+        // A very long period still gives a single loop
+        assert_eq!(video_loop(24, 1, 100.0), (2400, 100.0));
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let colors = ["#1d2b53", "#7e2553", "#ff004d", "#ffa300"]
+            .map(|hex| palette::hex_to_rgb(hex).unwrap());
+        let mut meta = WallpaperMeta::new(colors, "Plasma");
+        meta.params = vec![("uContrast".to_string(), 1.2)];
+        meta.controls = vec![
+            ("uAngle".to_string(), 0.785),
+            ("uDistortType".to_string(), 2.0),
+            ("uShadeCenter.x".to_string(), -0.5),
+        ];
+        meta.seed = RenderSeed(1234);
+        meta.color_space = ColorSpace::Oklab;
+        meta.color_count = 3;
+
+        let path = std::env::temp_dir().join("wallrus_test_metadata.png");
+        let pixels = image(4, 4, |x, y| (x * 16 + y) as u8);
+        save_pixels(&pixels, 4, 4, &path, ExportFormat::Png, 90, Some(&meta)).unwrap();
+        let read = read_embedded_metadata(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read, Some(meta));
+    }
 }
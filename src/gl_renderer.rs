@@ -1,11 +1,16 @@
 use glow::HasContext;
 use gtk4::prelude::*;
-use gtk4::{glib, GLArea};
-use std::cell::RefCell;
+use gtk4::{gdk, glib, GLArea};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+use crate::export::WallpaperMeta;
+use crate::palette::{self, ColorSpace, CvdType};
 use crate::shader::ShaderProgram;
-use crate::shader_presets;
+use crate::shader_presets::{self, ParamKind, PresetEntry, PresetParam};
 
 /// Load GL function pointers via the platform's native GL proc address loader.
 /// On Wayland this uses eglGetProcAddress, on X11 glXGetProcAddress.
@@ -54,11 +59,360 @@ mod gl_loader {
     }
 }
 
+/// EGL contexts for rendering with no display server at all: on a GPU's DRM
+/// render node through GBM (`create_headless_context`), or on Mesa's
+/// surfaceless platform, which `create_offscreen_renderer` uses for software
+/// rendering when GTK can't provide a context (headless CI, some VMs). The
+/// contexts have no surface; drawing goes to the renderer's framebuffer.
+/// Only the few EGL and GBM entry points needed are loaded.
+mod egl_headless {
+    use std::ffi::{c_char, c_void, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    type EglDisplay = *mut c_void;
+    type EglContext = *mut c_void;
+    type EglConfig = *mut c_void;
+    type GbmDevice = *mut c_void;
+
+    const EGL_PLATFORM_GBM_KHR: u32 = 0x31D7;
+    const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+    const EGL_OPENGL_ES_API: u32 = 0x30A0;
+    const EGL_NONE: i32 = 0x3038;
+    const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+    const EGL_OPENGL_ES3_BIT: i32 = 0x0040;
+    const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+
+    /// Where the EGL display comes from
+    pub enum Platform<'a> {
+        /// A GBM device on this DRM render node
+        Gbm(&'a Path),
+        /// Mesa's surfaceless platform; set `LIBGL_ALWAYS_SOFTWARE` first to
+        /// get llvmpipe
+        Surfaceless,
+    }
+
+    /// The GBM device behind a GBM display, and the render node's open file
+    struct Gbm {
+        fd: i32,
+        device: GbmDevice,
+        destroy: unsafe extern "C" fn(GbmDevice),
+    }
+
+    /// Keeps the display and context alive; both are released on drop
+    pub struct Context {
+        display: EglDisplay,
+        context: EglContext,
+        make_current: unsafe extern "C" fn(EglDisplay, *mut c_void, *mut c_void, EglContext) -> u32,
+        destroy_context: unsafe extern "C" fn(EglDisplay, EglContext) -> u32,
+        terminate: unsafe extern "C" fn(EglDisplay) -> u32,
+        gbm: Option<Gbm>,
+    }
+
+    unsafe fn symbol<T>(lib: *mut c_void, library: &str, name: &[u8]) -> Result<T, String> {
+        let sym = libc::dlsym(lib, name.as_ptr() as *const c_char);
+        if sym.is_null() {
+            let name = String::from_utf8_lossy(&name[..name.len() - 1]);
+            return Err(format!("{} not found in {}", name, library));
+        }
+        Ok(std::mem::transmute_copy(&sym))
+    }
+
+    /// Open `node` and create a GBM device on it.
+    unsafe fn open_gbm(node: &Path) -> Result<Gbm, String> {
+        let lib = libc::dlopen(c"libgbm.so.1".as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+        if lib.is_null() {
+            return Err("libgbm.so.1 not available (install Mesa's libgbm)".to_string());
+        }
+        let create_device: unsafe extern "C" fn(i32) -> GbmDevice =
+            symbol(lib, "libgbm", b"gbm_create_device\0")?;
+        let destroy = symbol(lib, "libgbm", b"gbm_device_destroy\0")?;
+
+        let path = CString::new(node.as_os_str().as_bytes())
+            .map_err(|_| format!("Invalid device path {}", node.display()))?;
+        let fd = libc::open(path.as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+        if fd < 0 {
+            let error = std::io::Error::last_os_error();
+            let hint = if error.kind() == std::io::ErrorKind::PermissionDenied {
+                " (is your user in the render group?)"
+            } else {
+                ""
+            };
+            return Err(format!(
+                "Failed to open {}: {}{}",
+                node.display(),
+                error,
+                hint
+            ));
+        }
+        let device = create_device(fd);
+        if device.is_null() {
+            libc::close(fd);
+            return Err(format!(
+                "Failed to create a GBM device on {}",
+                node.display()
+            ));
+        }
+        Ok(Gbm {
+            fd,
+            device,
+            destroy,
+        })
+    }
+
+    /// Create a GLES 3 context on `platform` and make it current.
+    pub fn create(platform: Platform) -> Result<Context, String> {
+        unsafe {
+            let lib = libc::dlopen(c"libEGL.so.1".as_ptr(), libc::RTLD_NOW | libc::RTLD_GLOBAL);
+            if lib.is_null() {
+                return Err("libEGL.so.1 not available".to_string());
+            }
+
+            let get_platform_display: unsafe extern "C" fn(
+                u32,
+                *mut c_void,
+                *const i32,
+            ) -> EglDisplay = symbol(lib, "libEGL", b"eglGetPlatformDisplay\0")
+                .or_else(|_| symbol(lib, "libEGL", b"eglGetPlatformDisplayEXT\0"))?;
+            let initialize: unsafe extern "C" fn(EglDisplay, *mut i32, *mut i32) -> u32 =
+                symbol(lib, "libEGL", b"eglInitialize\0")?;
+            let bind_api: unsafe extern "C" fn(u32) -> u32 =
+                symbol(lib, "libEGL", b"eglBindAPI\0")?;
+            let choose_config: unsafe extern "C" fn(
+                EglDisplay,
+                *const i32,
+                *mut EglConfig,
+                i32,
+                *mut i32,
+            ) -> u32 = symbol(lib, "libEGL", b"eglChooseConfig\0")?;
+            let create_context: unsafe extern "C" fn(
+                EglDisplay,
+                EglConfig,
+                EglContext,
+                *const i32,
+            ) -> EglContext = symbol(lib, "libEGL", b"eglCreateContext\0")?;
+            let get_error: unsafe extern "C" fn() -> i32 = symbol(lib, "libEGL", b"eglGetError\0")?;
+            let mut context = Context {
+                display: std::ptr::null_mut(),
+                context: std::ptr::null_mut(),
+                make_current: symbol(lib, "libEGL", b"eglMakeCurrent\0")?,
+                destroy_context: symbol(lib, "libEGL", b"eglDestroyContext\0")?,
+                terminate: symbol(lib, "libEGL", b"eglTerminate\0")?,
+                gbm: None,
+            };
+            let fail = |what: &str| format!("{} failed (EGL error 0x{:X})", what, get_error());
+
+            let (platform, native_display) = match platform {
+                Platform::Gbm(node) => {
+                    let gbm = open_gbm(node)?;
+                    let device = gbm.device;
+                    // Stored first so the device is closed even if EGL fails
+                    context.gbm = Some(gbm);
+                    (EGL_PLATFORM_GBM_KHR, device)
+                }
+                Platform::Surfaceless => (EGL_PLATFORM_SURFACELESS_MESA, std::ptr::null_mut()),
+            };
+            let display = get_platform_display(platform, native_display, std::ptr::null());
+            if display.is_null() {
+                return Err(fail("eglGetPlatformDisplay"));
+            }
+            context.display = display;
+            let (mut major, mut minor) = (0, 0);
+            if initialize(display, &mut major, &mut minor) == 0 {
+                return Err(fail("eglInitialize"));
+            }
+            if bind_api(EGL_OPENGL_ES_API) == 0 {
+                return Err(fail("eglBindAPI"));
+            }
+
+            let config_attribs = [EGL_RENDERABLE_TYPE, EGL_OPENGL_ES3_BIT, EGL_NONE];
+            let mut config: EglConfig = std::ptr::null_mut();
+            let mut count = 0;
+            if choose_config(display, config_attribs.as_ptr(), &mut config, 1, &mut count) == 0
+                || count == 0
+            {
+                return Err(fail("eglChooseConfig"));
+            }
+
+            let context_attribs = [EGL_CONTEXT_MAJOR_VERSION, 3, EGL_NONE];
+            let egl_context = create_context(
+                display,
+                config,
+                std::ptr::null_mut(),
+                context_attribs.as_ptr(),
+            );
+            if egl_context.is_null() {
+                return Err(fail("eglCreateContext"));
+            }
+            context.context = egl_context;
+            let none = std::ptr::null_mut();
+            if (context.make_current)(display, none, none, egl_context) == 0 {
+                return Err(fail("eglMakeCurrent"));
+            }
+            Ok(context)
+        }
+    }
+
+    impl Drop for Context {
+        fn drop(&mut self) {
+            unsafe {
+                if !self.display.is_null() {
+                    let none = std::ptr::null_mut();
+                    (self.make_current)(self.display, none, none, none);
+                    if !self.context.is_null() {
+                        (self.destroy_context)(self.display, self.context);
+                    }
+                    (self.terminate)(self.display);
+                }
+                if let Some(gbm) = self.gbm.take() {
+                    (gbm.destroy)(gbm.device);
+                    libc::close(gbm.fd);
+                }
+            }
+        }
+    }
+}
+
 /// Vertex data for a fullscreen quad (two triangles covering NDC -1..1)
 const QUAD_VERTICES: [f32; 12] = [
     -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0,
 ];
 
+/// Fragment shader used when a preset fails to build: a plain vertical
+/// gradient through the four palette colors, using only core GLSL ES so it
+/// always compiles.
+const FALLBACK_FRAGMENT_SHADER: &str = r#"#version 300 es
+precision mediump float;
+uniform vec3 iResolution;
+uniform vec3 uColor1;
+uniform vec3 uColor2;
+uniform vec3 uColor3;
+uniform vec3 uColor4;
+out vec4 fragColor;
+
+void main() {
+    float t = gl_FragCoord.y / iResolution.y * 3.0;
+    vec3 color = mix(uColor1, uColor2, clamp(t, 0.0, 1.0));
+    color = mix(color, uColor3, clamp(t - 1.0, 0.0, 1.0));
+    color = mix(color, uColor4, clamp(t - 2.0, 0.0, 1.0));
+    fragColor = vec4(color, 1.0);
+}
+"#;
+
+/// Create the fullscreen-quad VAO and VBO on the current context
+fn create_quad(gl: &glow::Context) -> (glow::VertexArray, glow::Buffer) {
+    unsafe {
+        let vao = gl.create_vertex_array().expect("Failed to create VAO");
+        let vbo = gl.create_buffer().expect("Failed to create VBO");
+
+        gl.bind_vertex_array(Some(vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+
+        let vertex_bytes: &[u8] = std::slice::from_raw_parts(
+            QUAD_VERTICES.as_ptr() as *const u8,
+            QUAD_VERTICES.len() * std::mem::size_of::<f32>(),
+        );
+        gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertex_bytes, glow::STATIC_DRAW);
+
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 8, 0);
+
+        gl.bind_vertex_array(None);
+        gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+        (vao, vbo)
+    }
+}
+
+/// Seed for a preset's randomness, passed as the `uSeed` uniform. The same
+/// seed reproduces the same render; presets without randomness ignore it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderSeed(pub u32);
+
+impl RenderSeed {
+    /// Largest seed the seed field and `--seed` accept
+    pub const MAX: u32 = 0xFFFF;
+
+    pub fn random() -> Self {
+        RenderSeed(rand::random::<u32>() & Self::MAX)
+    }
+
+    /// Parse a seed typed by the user
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.trim().parse::<u32>() {
+            Ok(seed) if seed <= Self::MAX => Ok(RenderSeed(seed)),
+            _ => Err(format!(
+                "Seed must be a whole number from 0 to {}",
+                Self::MAX
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for RenderSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How long recent renders took, for diagnostics. Updated as frames and
+/// exports render; reading it doesn't change rendering.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RenderTimings {
+    /// Last measured preview frame. GPU time from a timer query where the
+    /// driver has them (a frame or two behind), otherwise the CPU time to
+    /// submit the frame
+    pub frame: Option<Duration>,
+    pub frame_on_gpu: bool,
+    /// Last offscreen render, split into drawing (waited for with glFinish)
+    /// and reading the pixels back. Supersampled renders count as one.
+    pub offscreen_draw: Option<Duration>,
+    pub readback: Option<Duration>,
+    /// Encoding of the last exported image, reported by the exporter
+    pub encode: Option<Duration>,
+}
+
+impl RenderTimings {
+    /// One "Label: 1.23 ms" line per timing, with "not measured yet" for
+    /// those that haven't happened
+    pub fn lines(&self) -> Vec<String> {
+        let preview = if self.frame_on_gpu {
+            "Preview frame (GPU)"
+        } else {
+            "Preview frame (CPU)"
+        };
+        [
+            (preview, self.frame),
+            ("Offscreen draw", self.offscreen_draw),
+            ("Readback", self.readback),
+            ("Encode", self.encode),
+        ]
+        .into_iter()
+        .map(|(label, duration)| match duration {
+            Some(d) => format!("{}: {:.2} ms", label, d.as_secs_f64() * 1000.0),
+            None => format!("{}: not measured yet", label),
+        })
+        .collect()
+    }
+}
+
+/// Fewest and most palette colors a render can blend through. Colors past
+/// the count are ignored, so one palette can give sparser wallpapers.
+pub const MIN_COLOR_COUNT: u32 = 2;
+pub const MAX_COLOR_COUNT: u32 = 4;
+
+/// Range of kaleidoscope folds (`uSymmetry`); 1 leaves the preset as is
+pub const MIN_SYMMETRY: u32 = 1;
+pub const MAX_SYMMETRY: u32 = 12;
+
+/// Identification strings of the GL driver
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+}
+
 /// Renderer state that persists across frames
 pub struct RendererState {
     pub gl: glow::Context,
@@ -66,11 +420,28 @@ pub struct RendererState {
     pub vao: glow::VertexArray,
     pub vbo: glow::Buffer,
     pub start_time: std::time::Instant,
+    // Fixed iTime for deterministic offscreen frames (video export)
+    pub time_override: Option<f32>,
+    // Pointer position over the preview, normalized 0–1 with origin bottom-left
+    pub mouse: [f32; 2],
     // Shader uniforms — palette colors
     pub color1: [f32; 3],
     pub color2: [f32; 3],
     pub color3: [f32; 3],
     pub color4: [f32; 3],
+    // How many of colors 1–4 the presets blend through (sets uColorCount)
+    pub color_count: u32,
+    // Alpha of colors 1–4 from RGBA palette images (1.0 for RGB palettes)
+    pub color_alpha: [f32; 4],
+    // Simulated color vision for the on-screen preview; exports ignore it
+    pub cvd_preview: Option<CvdType>,
+    // Let the preview's alpha show the widget behind it; exports ignore it
+    pub preview_alpha: bool,
+    // Render a seamless tile (sets uTile); only takes effect on tileable presets
+    pub tile: bool,
+    pub seed: RenderSeed,
+    // Space palette colors are blended in (sets uInterpSpace); output stays sRGB
+    pub color_space: ColorSpace,
     // Shader uniforms — parameters
     pub angle: f32,
     pub scale: f32,
@@ -79,6 +450,8 @@ pub struct RendererState {
     pub distort_type: i32,
     pub distort_strength: f32,
     pub ripple_freq: f32,
+    // Kaleidoscope folds around the center, applied before distortion
+    pub symmetry: u32,
     pub noise: f32,
     pub center: f32,
     pub dither: f32,
@@ -87,33 +460,36 @@ pub struct RendererState {
     pub light_strength: f32,
     pub bevel_width: f32,
     pub light_angle: f32,
+    // Shader uniforms — shade for icon contrast (0=none, 1=radial, 2=corner);
+    // the center is in uv, origin bottom-left
+    pub shade_type: i32,
+    pub shade_strength: f32,
+    pub shade_center: [f32; 2],
+    // Preset-specific parameters by uniform name; unset ones use the
+    // default from shader_presets::params_for
+    pub params: HashMap<&'static str, f32>,
     // Current preset name
     pub current_preset: String,
+    // Why the current preset isn't rendering (the fallback gradient is shown)
+    pub load_error: Option<String>,
+    // Compile error from the last load of the current preset. Unlike
+    // load_error it is also set when the previous program stayed bound.
+    pub compile_error: Option<String>,
+    // Sources of the bound program, to rebuild it on a new GL context
+    shader_sources: Option<(String, String)>,
+    // GL objects were freed by release_gl and must not be deleted again
+    gl_released: bool,
+    // TIME_ELAPSED query for preview frames, if the driver supports them,
+    // and whether it has a result still to be collected
+    timer_query: Option<glow::Query>,
+    timer_pending: Cell<bool>,
+    timings: Cell<RenderTimings>,
 }
 
 impl RendererState {
     pub fn new(gl: glow::Context) -> Self {
-        let (vao, vbo) = unsafe {
-            let vao = gl.create_vertex_array().expect("Failed to create VAO");
-            let vbo = gl.create_buffer().expect("Failed to create VBO");
-
-            gl.bind_vertex_array(Some(vao));
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-
-            let vertex_bytes: &[u8] = std::slice::from_raw_parts(
-                QUAD_VERTICES.as_ptr() as *const u8,
-                QUAD_VERTICES.len() * std::mem::size_of::<f32>(),
-            );
-            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertex_bytes, glow::STATIC_DRAW);
-
-            gl.enable_vertex_attrib_array(0);
-            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 8, 0);
-
-            gl.bind_vertex_array(None);
-            gl.bind_buffer(glow::ARRAY_BUFFER, None);
-
-            (vao, vbo)
-        };
+        let (vao, vbo) = create_quad(&gl);
+        let timer_query = create_timer_query(&gl);
 
         Self {
             gl,
@@ -121,10 +497,19 @@ impl RendererState {
             vao,
             vbo,
             start_time: std::time::Instant::now(),
+            time_override: None,
+            mouse: [0.5, 0.5],
             color1: [0.80, 0.33, 0.00],
             color2: [0.93, 0.53, 0.07],
             color3: [1.00, 0.75, 0.15],
             color4: [1.00, 0.92, 0.35],
+            color_count: MAX_COLOR_COUNT,
+            color_alpha: [1.0; 4],
+            cvd_preview: None,
+            preview_alpha: false,
+            tile: false,
+            seed: RenderSeed::default(),
+            color_space: ColorSpace::default(),
             angle: std::f32::consts::FRAC_PI_4,
             scale: 1.0,
             speed: 1.0,
@@ -132,6 +517,7 @@ impl RendererState {
             distort_type: 0,
             distort_strength: 0.0,
             ripple_freq: 15.0,
+            symmetry: MIN_SYMMETRY,
             noise: 0.0,
             center: 0.0,
             dither: 0.0,
@@ -139,8 +525,85 @@ impl RendererState {
             light_strength: 0.0,
             bevel_width: 0.05,
             light_angle: (45.0_f32 - 90.0).to_radians(),
+            shade_type: 0,
+            shade_strength: 0.4,
+            shade_center: [0.5, 0.5],
+            params: HashMap::new(),
             current_preset: String::from("Bars"),
+            load_error: None,
+            compile_error: None,
+            shader_sources: None,
+            gl_released: false,
+            timer_query,
+            timer_pending: Cell::new(false),
+            timings: Cell::new(RenderTimings::default()),
+        }
+    }
+
+    /// Free this renderer's GL objects while its context is still current,
+    /// e.g. before the GLArea is unrealized. Settings are kept; the next
+    /// `reset_context` rebuilds everything on the new context.
+    pub fn release_gl(&mut self) {
+        unsafe {
+            if let Some(program) = self.program.take() {
+                program.delete(&self.gl);
+            }
+            self.gl.delete_vertex_array(self.vao);
+            self.gl.delete_buffer(self.vbo);
+            if let Some(query) = self.timer_query.take() {
+                self.gl.delete_query(query);
+            }
         }
+        self.gl_released = true;
+    }
+
+    /// Move the renderer onto a new GL context (after the GLArea is
+    /// re-realized or the GPU was reset), keeping every setting. The
+    /// shader program is rebuilt lazily by `ensure_program`.
+    pub fn reset_context(&mut self, gl: glow::Context) {
+        // Any remaining handles belong to the old context and die with it
+        self.program = None;
+        (self.vao, self.vbo) = create_quad(&gl);
+        self.timer_query = create_timer_query(&gl);
+        self.timer_pending.set(false);
+        self.gl = gl;
+        self.gl_released = false;
+    }
+
+    /// Rebuild the shader program if it's missing, e.g. after
+    /// `reset_context`. Falls back to the gradient program on failure.
+    pub fn ensure_program(&mut self) {
+        if self.program.is_some() {
+            return;
+        }
+        let result = match self.shader_sources.take() {
+            Some((vertex_src, fragment_src)) => self.set_shader(&vertex_src, &fragment_src),
+            // Already on the fallback; keep the original reason
+            None => Err(self
+                .load_error
+                .take()
+                .unwrap_or_else(|| "No shader loaded".to_string())),
+        };
+        if let Err(e) = result {
+            self.use_fallback(e);
+        }
+    }
+
+    /// Bind the fallback gradient program and remember why.
+    fn use_fallback(&mut self, error: String) {
+        eprintln!("Using fallback shader: {}", error);
+        let vertex_src = shader_presets::vertex_shader_source();
+        match ShaderProgram::new(&self.gl, &vertex_src, FALLBACK_FRAGMENT_SHADER) {
+            Ok(program) => {
+                if let Some(old_program) = self.program.replace(program) {
+                    old_program.delete(&self.gl);
+                }
+                // No sources: ensure_program rebuilds the fallback directly
+                self.shader_sources = None;
+            }
+            Err(e) => eprintln!("Fallback shader failed to build: {}", e),
+        }
+        self.load_error = Some(error);
     }
 
     /// Load a shader preset by name
@@ -154,88 +617,216 @@ impl RendererState {
         Ok(())
     }
 
+    /// Load a bundled preset or user shader.
+    ///
+    /// If a reload of the current preset fails to compile, its previous
+    /// program stays active. If switching to a different preset fails, the
+    /// fallback gradient is shown instead so the preview never goes blank;
+    /// either way the error is returned for the UI to report.
+    pub fn load_entry(&mut self, entry: &PresetEntry) -> Result<(), String> {
+        let vertex_src = shader_presets::vertex_shader_source();
+        let result = entry
+            .fragment_source()
+            .and_then(|fragment_src| self.set_shader(&vertex_src, &fragment_src));
+
+        if self.current_preset != entry.name() {
+            // Parameters belong to the previous preset
+            self.params.clear();
+        }
+        match result {
+            Ok(()) => {
+                self.current_preset = entry.name().to_string();
+                self.load_error = None;
+                self.compile_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.compile_error = Some(e.clone());
+                if self.current_preset != entry.name() || self.program.is_none() {
+                    self.current_preset = entry.name().to_string();
+                    self.use_fallback(e.clone());
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Check that a preset compiles and links, without binding it.
+    pub fn compile_check(&self, entry: &PresetEntry) -> Result<(), String> {
+        let vertex_src = shader_presets::vertex_shader_source();
+        let fragment_src = entry.fragment_source()?;
+
+        ShaderProgram::new_cached(&self.gl, &vertex_src, &fragment_src)?.delete(&self.gl);
+        Ok(())
+    }
+
+    /// Compile and bind a new program. The old program is only replaced
+    /// once the new one has compiled and linked successfully.
     pub fn set_shader(&mut self, vertex_src: &str, fragment_src: &str) -> Result<(), String> {
-        if let Some(old_program) = self.program.take() {
+        let program = ShaderProgram::new_cached(&self.gl, vertex_src, fragment_src)?;
+
+        if let Some(old_program) = self.program.replace(program) {
             old_program.delete(&self.gl);
         }
-
-        let program = ShaderProgram::new(&self.gl, vertex_src, fragment_src)?;
-        self.program = Some(program);
+        self.shader_sources = Some((vertex_src.to_string(), fragment_src.to_string()));
         Ok(())
     }
 
+    /// Draw to the current framebuffer, simulating `cvd_preview` if set.
+    /// With `preview_alpha` the framebuffer starts transparent and the
+    /// shader's output is blended onto it, which premultiplies its alpha the
+    /// way GTK composites a GLArea over the widgets below.
     pub fn render(&self, width: i32, height: i32) {
+        let timed_on_gpu = self.begin_frame_query();
+        let started = Instant::now();
+        self.render_preview(width, height);
+        if timed_on_gpu {
+            unsafe { self.gl.end_query(glow::TIME_ELAPSED) };
+            self.timer_pending.set(true);
+        } else if self.timer_query.is_none() {
+            self.update_timings(|t| {
+                t.frame = Some(started.elapsed());
+                t.frame_on_gpu = false;
+            });
+        }
+    }
+
+    /// Timings of recent frames and exports
+    pub fn timings(&self) -> RenderTimings {
+        self.timings.get()
+    }
+
+    /// Record how long encoding an exported image took
+    pub fn record_encode(&self, duration: Duration) {
+        self.update_timings(|t| t.encode = Some(duration));
+    }
+
+    fn update_timings(&self, update: impl FnOnce(&mut RenderTimings)) {
+        let mut timings = self.timings.get();
+        update(&mut timings);
+        self.timings.set(timings);
+    }
+
+    /// Collect the last frame's GPU time if it's ready and begin timing the
+    /// next frame. While a result is outstanding frames go untimed, so
+    /// timing never waits on the GPU. Returns whether a query was begun.
+    fn begin_frame_query(&self) -> bool {
+        let Some(query) = self.timer_query else {
+            return false;
+        };
+        unsafe {
+            if self.timer_pending.get() {
+                if self
+                    .gl
+                    .get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE)
+                    == 0
+                {
+                    return false;
+                }
+                let nanos = self.gl.get_query_parameter_u32(query, glow::QUERY_RESULT);
+                self.update_timings(|t| {
+                    t.frame = Some(Duration::from_nanos(nanos as u64));
+                    t.frame_on_gpu = true;
+                });
+                self.timer_pending.set(false);
+            }
+            self.gl.begin_query(glow::TIME_ELAPSED, query);
+        }
+        true
+    }
+
+    fn render_preview(&self, width: i32, height: i32) {
+        // GTK 4 always composites a GLArea's alpha, so without
+        // `preview_alpha` the cleared alpha of 1 is kept over the shader's
+        unsafe {
+            self.gl.enable(glow::BLEND);
+            if self.preview_alpha {
+                self.gl.blend_func_separate(
+                    glow::SRC_ALPHA,
+                    glow::ONE_MINUS_SRC_ALPHA,
+                    glow::ONE,
+                    glow::ONE_MINUS_SRC_ALPHA,
+                );
+            } else {
+                self.gl
+                    .blend_func_separate(glow::ONE, glow::ZERO, glow::ZERO, glow::ONE);
+            }
+        }
+        let clear_alpha = if self.preview_alpha { 0.0 } else { 1.0 };
+        self.draw(width, height, self.cvd_preview, clear_alpha);
+        unsafe { self.gl.disable(glow::BLEND) };
+    }
+
+    /// Draw one frame over a black background with `clear_alpha`.
+    fn draw(&self, width: i32, height: i32, cvd: Option<CvdType>, clear_alpha: f32) {
         let gl = &self.gl;
+        let mut colors = vec![self.color1, self.color2, self.color3, self.color4];
+        if let Some(kind) = cvd {
+            colors = palette::simulate_cvd(&colors, kind);
+        }
 
         unsafe {
             gl.viewport(0, 0, width, height);
-            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear_color(0.0, 0.0, 0.0, clear_alpha);
             gl.clear(glow::COLOR_BUFFER_BIT);
 
             if let Some(ref program) = self.program {
                 gl.use_program(Some(program.id));
 
-                let elapsed = self.start_time.elapsed().as_secs_f32();
+                let elapsed = self
+                    .time_override
+                    .unwrap_or_else(|| self.start_time.elapsed().as_secs_f32());
+                let (w, h) = (width as f32, height as f32);
 
-                if let Some(loc) = gl.get_uniform_location(program.id, "iResolution") {
-                    gl.uniform_3_f32(Some(&loc), width as f32, height as f32, 1.0);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "iTime") {
-                    gl.uniform_1_f32(Some(&loc), elapsed);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uColor1") {
-                    gl.uniform_3_f32(Some(&loc), self.color1[0], self.color1[1], self.color1[2]);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uColor2") {
-                    gl.uniform_3_f32(Some(&loc), self.color2[0], self.color2[1], self.color2[2]);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uColor3") {
-                    gl.uniform_3_f32(Some(&loc), self.color3[0], self.color3[1], self.color3[2]);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uColor4") {
-                    gl.uniform_3_f32(Some(&loc), self.color4[0], self.color4[1], self.color4[2]);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uAngle") {
-                    gl.uniform_1_f32(Some(&loc), self.angle);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uScale") {
-                    gl.uniform_1_f32(Some(&loc), self.scale);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uSpeed") {
-                    gl.uniform_1_f32(Some(&loc), self.speed);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uBlend") {
-                    gl.uniform_1_f32(Some(&loc), self.blend);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uDistortType") {
-                    gl.uniform_1_i32(Some(&loc), self.distort_type);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uDistortStrength") {
-                    gl.uniform_1_f32(Some(&loc), self.distort_strength);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uRippleFreq") {
-                    gl.uniform_1_f32(Some(&loc), self.ripple_freq);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uNoise") {
-                    gl.uniform_1_f32(Some(&loc), self.noise);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uCenter") {
-                    gl.uniform_1_f32(Some(&loc), self.center);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uDither") {
-                    gl.uniform_1_f32(Some(&loc), self.dither);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uLightingType") {
-                    gl.uniform_1_i32(Some(&loc), self.lighting_type);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uLightStrength") {
-                    gl.uniform_1_f32(Some(&loc), self.light_strength);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uBevelWidth") {
-                    gl.uniform_1_f32(Some(&loc), self.bevel_width);
-                }
-                if let Some(loc) = gl.get_uniform_location(program.id, "uLightAngle") {
-                    gl.uniform_1_f32(Some(&loc), self.light_angle);
+                // Standard animation uniforms
+                program.set_uniform_vec3(gl, "iResolution", [w, h, 1.0]);
+                program.set_uniform_f32(gl, "iTime", elapsed);
+                program.set_uniform_vec2(gl, "iMouse", [self.mouse[0] * w, self.mouse[1] * h]);
+
+                program.set_uniform_vec3(gl, "uColor1", colors[0]);
+                program.set_uniform_vec3(gl, "uColor2", colors[1]);
+                program.set_uniform_vec3(gl, "uColor3", colors[2]);
+                program.set_uniform_vec3(gl, "uColor4", colors[3]);
+                program.set_uniform_vec4(gl, "uColorAlpha", self.color_alpha);
+                program.set_uniform_f32(gl, "uAngle", self.angle);
+                program.set_uniform_f32(gl, "uScale", self.scale);
+                program.set_uniform_f32(gl, "uSpeed", self.speed);
+                program.set_uniform_f32(gl, "uBlend", self.blend);
+                program.set_uniform_i32(gl, "uDistortType", self.distort_type);
+                program.set_uniform_f32(gl, "uDistortStrength", self.distort_strength);
+                program.set_uniform_f32(gl, "uRippleFreq", self.ripple_freq);
+                let symmetry = self.symmetry.clamp(MIN_SYMMETRY, MAX_SYMMETRY);
+                program.set_uniform_i32(gl, "uSymmetry", symmetry as i32);
+                program.set_uniform_f32(gl, "uSymmetryAspect", w / h);
+                program.set_uniform_f32(gl, "uNoise", self.noise);
+                program.set_uniform_f32(gl, "uCenter", self.center);
+                program.set_uniform_f32(gl, "uDither", self.dither);
+                program.set_uniform_i32(gl, "uLightingType", self.lighting_type);
+                program.set_uniform_f32(gl, "uLightStrength", self.light_strength);
+                program.set_uniform_f32(gl, "uBevelWidth", self.bevel_width);
+                program.set_uniform_f32(gl, "uLightAngle", self.light_angle);
+                program.set_uniform_i32(gl, "uShadeType", self.shade_type);
+                program.set_uniform_f32(gl, "uShadeStrength", self.shade_strength);
+                program.set_uniform_vec2(gl, "uShadeCenter", self.shade_center);
+                program.set_uniform_i32(gl, "uTile", (self.tile && self.is_tileable()) as i32);
+                program.set_uniform_i32(gl, "uSeed", self.seed.0 as i32);
+                let interp_space = match self.color_space {
+                    ColorSpace::Srgb => 0,
+                    ColorSpace::Linear => 1,
+                    ColorSpace::Oklab => 2,
+                };
+                program.set_uniform_i32(gl, "uInterpSpace", interp_space);
+                let color_count = self.color_count.clamp(MIN_COLOR_COUNT, MAX_COLOR_COUNT);
+                program.set_uniform_i32(gl, "uColorCount", color_count as i32);
+
+                // Preset-specific parameters
+                for (param, value) in self.param_values() {
+                    match param.kind {
+                        ParamKind::Float => program.set_uniform_f32(gl, param.name, value),
+                        ParamKind::Int | ParamKind::Bool => {
+                            program.set_uniform_i32(gl, param.name, value.round() as i32)
+                        }
+                    }
                 }
 
                 gl.bind_vertex_array(Some(self.vao));
@@ -247,72 +838,320 @@ impl RendererState {
         }
     }
 
-    /// Render at a specific resolution and return RGBA pixel data
-    pub fn render_to_pixels(&self, width: i32, height: i32) -> Vec<u8> {
-        let gl = &self.gl;
+    /// The current preset's parameters with their effective values
+    pub fn param_values(&self) -> impl Iterator<Item = (&'static PresetParam, f32)> + '_ {
+        shader_presets::params_for(&self.current_preset)
+            .iter()
+            .map(|param| {
+                let value = self.params.get(param.name).copied();
+                (param, value.unwrap_or(param.default as f32))
+            })
+    }
+
+    /// Palette, preset, parameters, controls and effects currently rendered,
+    /// for embedding in exports and recipes
+    pub fn wallpaper_meta(&self) -> WallpaperMeta {
+        let mut meta = WallpaperMeta::new(
+            [self.color1, self.color2, self.color3, self.color4],
+            &self.current_preset,
+        );
+        meta.params = self
+            .param_values()
+            .map(|(param, value)| (param.name.to_string(), value))
+            .collect();
+        meta.controls = [
+            ("uAngle", self.angle),
+            ("uScale", self.scale),
+            ("uSpeed", self.speed),
+            ("uCenter", self.center),
+            ("uBlend", self.blend),
+            ("uNoise", self.noise),
+            ("uDistortType", self.distort_type as f32),
+            ("uDistortStrength", self.distort_strength),
+            ("uRippleFreq", self.ripple_freq),
+            ("uSymmetry", self.symmetry as f32),
+            ("uLightingType", self.lighting_type as f32),
+            ("uLightStrength", self.light_strength),
+            ("uBevelWidth", self.bevel_width),
+            ("uLightAngle", self.light_angle),
+            ("uDither", self.dither),
+            ("uShadeType", self.shade_type as f32),
+            ("uShadeStrength", self.shade_strength),
+            ("uShadeCenter.x", self.shade_center[0]),
+            ("uShadeCenter.y", self.shade_center[1]),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        meta.seed = self.seed;
+        meta.color_space = self.color_space;
+        meta.color_count = self.color_count;
+        meta
+    }
+
+    /// Whether the loaded shader animates, i.e. declares `iTime`.
+    /// The GLSL compiler drops unused uniforms, so this also catches
+    /// presets that declare it without reading it.
+    pub fn uses_time(&self) -> bool {
+        self.program.as_ref().is_some_and(|program| unsafe {
+            self.gl.get_uniform_location(program.id, "iTime").is_some()
+        })
+    }
+
+    /// Seconds of `iTime` after which the loaded shader repeats: its
+    /// `LOOP_PERIOD` if it declares one, otherwise
+    /// [`shader_presets::DEFAULT_LOOP_PERIOD`]
+    pub fn loop_period(&self) -> f32 {
+        self.shader_sources
+            .as_ref()
+            .and_then(|(_, fragment_src)| shader_presets::loop_period(fragment_src))
+            .unwrap_or(shader_presets::DEFAULT_LOOP_PERIOD)
+    }
+
+    /// Whether the loaded shader reads `uColorAlpha`, i.e. can output
+    /// transparency from an RGBA palette
+    pub fn uses_alpha(&self) -> bool {
+        self.program.as_ref().is_some_and(|program| unsafe {
+            self.gl
+                .get_uniform_location(program.id, "uColorAlpha")
+                .is_some()
+        })
+    }
+
+    /// Whether the loaded shader reads `uSeed`, i.e. has randomness to seed
+    pub fn uses_seed(&self) -> bool {
+        self.program.as_ref().is_some_and(|program| unsafe {
+            self.gl.get_uniform_location(program.id, "uSeed").is_some()
+        })
+    }
 
+    /// Whether the kaleidoscope mirrors the image onto its opposite edges,
+    /// which even fold counts do, so any preset repeats without blending
+    pub fn symmetry_tiles(&self) -> bool {
+        self.symmetry >= 2 && self.symmetry.is_multiple_of(2)
+    }
+
+    /// Whether the current preset can render a seamless tile
+    pub fn is_tileable(&self) -> bool {
+        shader_presets::controls_for(&self.current_preset).tileable
+    }
+
+    /// The GL_RENDERER string, e.g. "llvmpipe (LLVM 17.0.6, 256 bits)"
+    pub fn renderer_name(&self) -> String {
+        unsafe { self.gl.get_parameter_string(glow::RENDERER) }
+    }
+
+    /// The GL_VENDOR, GL_RENDERER and GL_VERSION strings
+    pub fn gl_info(&self) -> GlInfo {
         unsafe {
-            let fbo = gl.create_framebuffer().expect("Failed to create FBO");
-            let texture = gl.create_texture().expect("Failed to create texture");
+            GlInfo {
+                vendor: self.gl.get_parameter_string(glow::VENDOR),
+                renderer: self.gl.get_parameter_string(glow::RENDERER),
+                version: self.gl.get_parameter_string(glow::VERSION),
+            }
+        }
+    }
 
-            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
-            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+    /// Renderer, vendor and GL version strings on one line, for logs
+    pub fn gpu_description(&self) -> String {
+        let info = self.gl_info();
+        format!("{} ({}, {})", info.renderer, info.vendor, info.version)
+    }
 
-            gl.tex_image_2d(
-                glow::TEXTURE_2D,
-                0,
-                glow::RGBA as i32,
-                width,
-                height,
-                0,
-                glow::RGBA,
-                glow::UNSIGNED_BYTE,
-                None,
-            );
-            gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MIN_FILTER,
-                glow::LINEAR as i32,
-            );
-            gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MAG_FILTER,
-                glow::LINEAR as i32,
-            );
+    /// Whether the context is a CPU rasterizer rather than a GPU
+    pub fn is_software(&self) -> bool {
+        let name = self.renderer_name().to_lowercase();
+        ["llvmpipe", "softpipe", "swrast", "software"]
+            .iter()
+            .any(|s| name.contains(s))
+    }
 
-            gl.framebuffer_texture_2d(
-                glow::FRAMEBUFFER,
-                glow::COLOR_ATTACHMENT0,
-                glow::TEXTURE_2D,
-                Some(texture),
-                0,
-            );
+    /// Largest width or height `render_to_pixels` accepts (GL_MAX_TEXTURE_SIZE)
+    pub fn max_render_size(&self) -> i32 {
+        unsafe { self.gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) }
+    }
+
+    /// Render at a specific resolution and return RGBA pixel data.
+    /// Renders into an offscreen framebuffer, so the size is independent of
+    /// the preview widget; only the GPU's maximum texture size limits it.
+    pub fn render_to_pixels(&self, width: i32, height: i32) -> Result<Vec<u8>, String> {
+        self.render_offscreen(width, height, 1, false, &mut |_, _| Ok(()))
+    }
+
+    /// Like `render_to_pixels`, but renders into a half-float framebuffer
+    /// and returns RGBA as 0–1 floats, keeping the precision the shader
+    /// computed before 8-bit quantization (used for dithered export).
+    /// Fails on GPUs that can't render to float textures.
+    pub fn render_to_pixels_f32(&self, width: i32, height: i32) -> Result<Vec<f32>, String> {
+        self.render_supersampled_f32(width, height, 1, &mut |_, _| Ok(()))
+    }
+
+    /// Whether the context can downscale on the GPU with `glBlitFramebuffer`:
+    /// core since desktop GL 3.0 and GLES 3.0
+    pub fn supports_blit(&self) -> bool {
+        let version = self.gl.version();
+        version.major >= 3
+    }
+
+    /// Render at `factor` times `width`x`height` and average it down to
+    /// `width`x`height` on the GPU, returning RGBA pixels at the target size.
+    /// Needs [`supports_blit`](Self::supports_blit).
+    ///
+    /// `before_pass` is called with the pass number and count before each
+    /// halving; an error from it stops the render and is returned.
+    pub fn render_supersampled(
+        &self,
+        width: i32,
+        height: i32,
+        factor: u32,
+        before_pass: &mut dyn FnMut(u32, u32) -> Result<(), String>,
+    ) -> Result<Vec<u8>, String> {
+        self.render_offscreen(width, height, factor, false, before_pass)
+    }
 
-            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
-            if status != glow::FRAMEBUFFER_COMPLETE {
-                eprintln!("Framebuffer not complete: 0x{:X}", status);
+    /// `render_supersampled` through a half-float framebuffer, returning RGBA
+    /// as 0–1 floats like `render_to_pixels_f32`
+    pub fn render_supersampled_f32(
+        &self,
+        width: i32,
+        height: i32,
+        factor: u32,
+        before_pass: &mut dyn FnMut(u32, u32) -> Result<(), String>,
+    ) -> Result<Vec<f32>, String> {
+        let bytes = self.render_offscreen(width, height, factor, true, before_pass)?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]).clamp(0.0, 1.0))
+            .collect())
+    }
+
+    /// Render into a temporary framebuffer `factor` times the target size,
+    /// downscale it to `width`x`height` and read it back top-down.
+    /// With `float`, the targets are RGBA16F and the data is read as f32s.
+    ///
+    /// Downscaling halves the size with linear-filtered blits. A linear
+    /// sample midway between four pixels is their average, so each halving
+    /// is an exact 2x2 box filter and 4x goes through 2x on the way.
+    fn render_offscreen(
+        &self,
+        width: i32,
+        height: i32,
+        factor: u32,
+        float: bool,
+        before_pass: &mut dyn FnMut(u32, u32) -> Result<(), String>,
+    ) -> Result<Vec<u8>, String> {
+        if let Some(ref e) = self.load_error {
+            return Err(format!(
+                "Preset '{}' failed to build: {}",
+                self.current_preset, e
+            ));
+        }
+
+        let gl = &self.gl;
+        let (internal_format, pixel_type, bytes_per_pixel) = if float {
+            (glow::RGBA16F, glow::FLOAT, 16)
+        } else {
+            (glow::RGBA8, glow::UNSIGNED_BYTE, 4)
+        };
+        let factor = factor.max(1) as i32;
+        let (render_w, render_h) = (width * factor, height * factor);
+
+        unsafe {
+            let max_size = self.max_render_size();
+            if render_w > max_size || render_h > max_size {
+                return Err(format!(
+                    "{}x{} exceeds the GPU's maximum render size of {}x{}",
+                    render_w, render_h, max_size, max_size
+                ));
             }
 
-            self.render(width, height);
+            let mut target =
+                create_render_target(gl, render_w, render_h, internal_format, pixel_type)?;
+            let started = Instant::now();
+            self.draw(render_w, render_h, None, 1.0);
+            gl.finish();
+            let drawn = Instant::now();
+
+            // Drain stale errors so the checks below only see the blits and
+            // read_pixels (bounded: a lost context can keep reporting errors)
+            for _ in 0..8 {
+                if gl.get_error() == glow::NO_ERROR {
+                    break;
+                }
+            }
 
-            let mut pixels = vec![0u8; (width * height * 4) as usize];
+            let (mut cur_w, mut cur_h) = (render_w, render_h);
+            let passes = (factor as u32).ilog2();
+            let mut pass = 0;
+            while (cur_w, cur_h) != (width, height) {
+                pass += 1;
+                if let Err(e) = before_pass(pass, passes) {
+                    delete_render_target(gl, target);
+                    return Err(e);
+                }
+                let (next_w, next_h) = ((cur_w / 2).max(width), (cur_h / 2).max(height));
+                let next =
+                    match create_render_target(gl, next_w, next_h, internal_format, pixel_type) {
+                        Ok(next) => next,
+                        Err(e) => {
+                            delete_render_target(gl, target);
+                            return Err(e);
+                        }
+                    };
+                gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(target.0));
+                gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(next.0));
+                gl.blit_framebuffer(
+                    0,
+                    0,
+                    cur_w,
+                    cur_h,
+                    0,
+                    0,
+                    next_w,
+                    next_h,
+                    glow::COLOR_BUFFER_BIT,
+                    glow::LINEAR,
+                );
+                delete_render_target(gl, target);
+                target = next;
+                let blit_error = gl.get_error();
+                if blit_error != glow::NO_ERROR {
+                    delete_render_target(gl, target);
+                    return Err(format!("Failed to downscale: GL error 0x{:X}", blit_error));
+                }
+                (cur_w, cur_h) = (next_w, next_h);
+            }
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(target.0));
+            let mut pixels = vec![0u8; (width * height) as usize * bytes_per_pixel];
             gl.read_pixels(
                 0,
                 0,
                 width,
                 height,
                 glow::RGBA,
-                glow::UNSIGNED_BYTE,
+                pixel_type,
                 glow::PixelPackData::Slice(&mut pixels),
             );
+            let read_error = gl.get_error();
+            // Downscaling counts as part of the readback
+            self.update_timings(|t| {
+                t.offscreen_draw = Some(drawn - started);
+                t.readback = Some(drawn.elapsed());
+            });
+
+            // Restores the default framebuffer
+            delete_render_target(gl, target);
 
-            // Restore default framebuffer
-            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-            gl.delete_framebuffer(fbo);
-            gl.delete_texture(texture);
+            if read_error != glow::NO_ERROR {
+                return Err(format!(
+                    "Failed to read pixels: GL error 0x{:X}",
+                    read_error
+                ));
+            }
 
             // Flip vertically (OpenGL origin is bottom-left)
-            let row_size = (width * 4) as usize;
+            let row_size = width as usize * bytes_per_pixel;
             let mut flipped = vec![0u8; pixels.len()];
             for y in 0..height as usize {
                 let src = &pixels[y * row_size..(y + 1) * row_size];
@@ -320,13 +1159,79 @@ impl RendererState {
                 dst.copy_from_slice(src);
             }
 
-            flipped
+            Ok(flipped)
         }
     }
 }
 
+/// Create a framebuffer with a `width`x`height` color texture of
+/// `internal_format` attached, left bound as the current framebuffer.
+unsafe fn create_render_target(
+    gl: &glow::Context,
+    width: i32,
+    height: i32,
+    internal_format: u32,
+    pixel_type: u32,
+) -> Result<(glow::Framebuffer, glow::Texture), String> {
+    let fbo = gl.create_framebuffer().expect("Failed to create FBO");
+    let texture = gl.create_texture().expect("Failed to create texture");
+
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+    gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        internal_format as i32,
+        width,
+        height,
+        0,
+        glow::RGBA,
+        pixel_type,
+        None,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MIN_FILTER,
+        glow::LINEAR as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MAG_FILTER,
+        glow::LINEAR as i32,
+    );
+
+    gl.framebuffer_texture_2d(
+        glow::FRAMEBUFFER,
+        glow::COLOR_ATTACHMENT0,
+        glow::TEXTURE_2D,
+        Some(texture),
+        0,
+    );
+
+    let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+    if status != glow::FRAMEBUFFER_COMPLETE {
+        delete_render_target(gl, (fbo, texture));
+        return Err(format!("Framebuffer not complete: 0x{:X}", status));
+    }
+    Ok((fbo, texture))
+}
+
+/// Delete a framebuffer from `create_render_target` and bind the default one.
+unsafe fn delete_render_target(
+    gl: &glow::Context,
+    (fbo, texture): (glow::Framebuffer, glow::Texture),
+) {
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+    gl.delete_framebuffer(fbo);
+    gl.delete_texture(texture);
+}
+
 impl Drop for RendererState {
     fn drop(&mut self) {
+        if self.gl_released {
+            return;
+        }
         unsafe {
             // Program is cleaned up via ShaderProgram::delete in set_shader,
             // but handle any remaining program
@@ -335,10 +1240,29 @@ impl Drop for RendererState {
             }
             self.gl.delete_vertex_array(self.vao);
             self.gl.delete_buffer(self.vbo);
+            if let Some(query) = self.timer_query.take() {
+                self.gl.delete_query(query);
+            }
         }
     }
 }
 
+/// A TIME_ELAPSED query, if the context supports timer queries: core in
+/// desktop GL 3.3, an extension on GLES (and where a driver lacks them)
+fn create_timer_query(gl: &glow::Context) -> Option<glow::Query> {
+    let version = gl.version();
+    let extensions = gl.supported_extensions();
+    let supported = if version.is_embedded {
+        extensions.contains("GL_EXT_disjoint_timer_query")
+    } else {
+        (version.major, version.minor) >= (3, 3) || extensions.contains("GL_ARB_timer_query")
+    };
+    if !supported {
+        return None;
+    }
+    unsafe { gl.create_query().ok() }
+}
+
 /// Shared renderer state type used across the application
 pub type SharedRendererState = Rc<RefCell<Option<RendererState>>>;
 
@@ -347,6 +1271,159 @@ pub fn new_shared_state() -> SharedRendererState {
     Rc::new(RefCell::new(None))
 }
 
+/// Keeps the GL context behind an offscreen renderer alive: GTK's, or one of
+/// the headless EGL ones.
+pub struct OffscreenContext {
+    _gdk: Option<gdk::GLContext>,
+    _egl: Option<egl_headless::Context>,
+}
+
+/// Environment variable naming the DRM render node for headless rendering,
+/// e.g. `/dev/dri/renderD129`; the first render node is used otherwise
+pub const DRM_DEVICE_ENV: &str = "WALLRUS_DRM_DEVICE";
+
+/// Create a GL context on a GPU without any display server or window, for
+/// rendering on servers and in scripts.
+///
+/// The context comes from EGL on a GBM device opened on a DRM render node
+/// (`/dev/dri/renderD*`, or the one in [`DRM_DEVICE_ENV`]), so it needs read
+/// and write access to that node, normally through the `render` group, and
+/// Mesa's `libgbm` and `libEGL`. It has no surface: `render_to_pixels` draws
+/// into its own framebuffer. The returned context must be kept alive for
+/// as long as the `glow::Context` is used.
+pub fn create_headless_context() -> Result<(glow::Context, OffscreenContext), String> {
+    let node = match std::env::var_os(DRM_DEVICE_ENV) {
+        Some(node) => std::path::PathBuf::from(node),
+        None => render_nodes().into_iter().next().ok_or_else(|| {
+            format!(
+                "No DRM render node in /dev/dri; load the GPU's kernel driver, \
+                 set {} to a render node, or set LIBGL_ALWAYS_SOFTWARE=1 for \
+                 software rendering",
+                DRM_DEVICE_ENV
+            )
+        })?,
+    };
+    let context = egl_headless::create(egl_headless::Platform::Gbm(&node))
+        .map_err(|e| format!("Headless GL on {} failed: {}", node.display(), e))?;
+    let gl = unsafe { glow::Context::from_loader_function_cstr(gl_loader::get_proc_address) };
+    Ok((
+        gl,
+        OffscreenContext {
+            _gdk: None,
+            _egl: Some(context),
+        },
+    ))
+}
+
+/// Mesa's llvmpipe on a surfaceless EGL context, for when `hardware_error`
+/// kept a GPU context from being created.
+fn create_software_context(
+    hardware_error: &str,
+) -> Result<(glow::Context, OffscreenContext), String> {
+    eprintln!("{}; trying software rendering", hardware_error);
+    std::env::set_var("LIBGL_ALWAYS_SOFTWARE", "1");
+    let context = egl_headless::create(egl_headless::Platform::Surfaceless)
+        .map_err(|e| format!("{}; software fallback failed: {}", hardware_error, e))?;
+    let gl = unsafe { glow::Context::from_loader_function_cstr(gl_loader::get_proc_address) };
+    Ok((
+        gl,
+        OffscreenContext {
+            _gdk: None,
+            _egl: Some(context),
+        },
+    ))
+}
+
+/// Create a renderer on an offscreen GL context, for rendering without a window.
+///
+/// The context is surfaceless: `render_to_pixels` draws into its own
+/// framebuffer. GTK's context (no window is shown) is tried first; if there
+/// is no display or it can't create one, Mesa's llvmpipe software renderer
+/// is used through a surfaceless EGL context. The selected renderer is
+/// logged. The returned context must be kept alive for as long as the
+/// renderer is used.
+pub fn create_offscreen_renderer() -> Result<(RendererState, OffscreenContext), String> {
+    let (gl, context) = match create_gdk_context() {
+        Ok(context) => {
+            let gl =
+                unsafe { glow::Context::from_loader_function_cstr(gl_loader::get_proc_address) };
+            (
+                gl,
+                OffscreenContext {
+                    _gdk: Some(context),
+                    _egl: None,
+                },
+            )
+        }
+        Err(hardware_error) => create_software_context(&hardware_error)?,
+    };
+
+    let renderer = RendererState::new(gl);
+    eprintln!("Using GL renderer: {}", renderer.gpu_description());
+    Ok((renderer, context))
+}
+
+/// Like [`create_offscreen_renderer`], but without GTK: the GPU is used
+/// through [`create_headless_context`], so no display server is needed.
+/// Falls back to software rendering like `create_offscreen_renderer`.
+pub fn create_headless_renderer() -> Result<(RendererState, OffscreenContext), String> {
+    let (gl, context) = match create_headless_context() {
+        Ok(pair) => pair,
+        Err(hardware_error) => create_software_context(&hardware_error)?,
+    };
+
+    let renderer = RendererState::new(gl);
+    eprintln!("Using GL renderer: {}", renderer.gpu_description());
+    Ok((renderer, context))
+}
+
+/// Create an offscreen renderer with the named preset or user shader loaded
+/// and its scale and speed at their defaults, as the window applies them
+/// when a preset is selected.
+pub fn create_preset_renderer(preset: &str) -> Result<(RendererState, OffscreenContext), String> {
+    with_preset(preset, create_offscreen_renderer)
+}
+
+/// [`create_preset_renderer`] on a headless context, for the command line
+pub fn create_headless_preset_renderer(
+    preset: &str,
+) -> Result<(RendererState, OffscreenContext), String> {
+    with_preset(preset, create_headless_renderer)
+}
+
+fn with_preset(
+    preset: &str,
+    create: fn() -> Result<(RendererState, OffscreenContext), String>,
+) -> Result<(RendererState, OffscreenContext), String> {
+    let entry = shader_presets::list_presets()
+        .into_iter()
+        .find(|e| e.name().eq_ignore_ascii_case(preset))
+        .ok_or_else(|| format!("Unknown preset: {}", preset))?;
+
+    let (mut renderer, context) = create()?;
+    renderer.load_entry(&entry)?;
+    let controls = shader_presets::controls_for(entry.name());
+    renderer.scale = controls.scale_range.3 as f32;
+    renderer.speed = controls.speed_range.3 as f32;
+    Ok((renderer, context))
+}
+
+fn create_gdk_context() -> Result<gdk::GLContext, String> {
+    gtk4::init().map_err(|e| format!("Failed to initialize GTK: {}", e))?;
+    let display = gdk::Display::default().ok_or("No display available")?;
+
+    let context = display
+        .create_gl_context()
+        .map_err(|e| format!("Failed to create GL context: {}", e))?;
+    // Shaders target GLSL ES 3.0, matching the GLArea used by the window
+    context.set_allowed_apis(gdk::GLAPI::GLES);
+    context
+        .realize()
+        .map_err(|e| format!("Failed to realize GL context: {}", e))?;
+    context.make_current();
+    Ok(context)
+}
+
 /// Create a GLArea widget connected to the shared renderer state.
 /// Also loads the initial shader preset once the GL context is ready.
 pub fn create_gl_area(state: SharedRendererState) -> GLArea {
@@ -371,18 +1448,30 @@ pub fn create_gl_area(state: SharedRendererState) -> GLArea {
             glow::Context::from_loader_function_cstr(|name| gl_loader::get_proc_address(name))
         };
 
+        // Re-realized (new GL context): keep the existing settings and
+        // rebuild GL objects; the program is rebuilt on the next render
+        let mut state_ref = state_realize.borrow_mut();
+        if let Some(ref mut renderer) = *state_ref {
+            renderer.reset_context(gl);
+            return;
+        }
+
         let mut renderer = RendererState::new(gl);
+        eprintln!("Using GL renderer: {}", renderer.gpu_description());
         if let Err(e) = renderer.load_preset("Bars") {
             eprintln!("Failed to load initial shader: {}", e);
         }
 
-        *state_realize.borrow_mut() = Some(renderer);
+        *state_ref = Some(renderer);
     });
 
-    // Unrealize: cleanup
+    // Unrealize: free GL objects while the context is still alive
     let state_unrealize = state.clone();
-    gl_area.connect_unrealize(move |_| {
-        *state_unrealize.borrow_mut() = None;
+    gl_area.connect_unrealize(move |area| {
+        area.make_current();
+        if let Some(ref mut renderer) = *state_unrealize.borrow_mut() {
+            renderer.release_gl();
+        }
     });
 
     // Render callback
@@ -392,7 +1481,8 @@ pub fn create_gl_area(state: SharedRendererState) -> GLArea {
         let width = area.width() * scale;
         let height = area.height() * scale;
 
-        if let Some(ref renderer) = *state_render.borrow() {
+        if let Some(ref mut renderer) = *state_render.borrow_mut() {
+            renderer.ensure_program();
             renderer.render(width, height);
         }
 
@@ -401,3 +1491,243 @@ pub fn create_gl_area(state: SharedRendererState) -> GLArea {
 
     gl_area
 }
+
+/// Which GPU to render on when the system has more than one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GpuPreference {
+    /// Whatever the driver picks
+    #[default]
+    Default,
+    /// The GPU driving the display (usually the power-saving one)
+    Integrated,
+    /// The render-offload GPU (usually the faster one)
+    Discrete,
+}
+
+impl GpuPreference {
+    pub const ALL: [GpuPreference; 3] = [
+        GpuPreference::Default,
+        GpuPreference::Integrated,
+        GpuPreference::Discrete,
+    ];
+
+    /// Name stored in the saved settings and used as the action target
+    pub fn name(self) -> &'static str {
+        match self {
+            GpuPreference::Default => "default",
+            GpuPreference::Integrated => "integrated",
+            GpuPreference::Discrete => "discrete",
+        }
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            GpuPreference::Default => "Default",
+            GpuPreference::Integrated => "Integrated",
+            GpuPreference::Discrete => "Discrete",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pref| pref.name() == s)
+    }
+}
+
+/// Ask the GL drivers for the preferred GPU through their offload
+/// environment variables (`DRI_PRIME` for Mesa, PRIME render offload for
+/// NVIDIA). Drivers read these when the first context is created, so this
+/// must run before GTK initializes. Variables already set in the
+/// environment win, and asking for a discrete GPU on a single-GPU system
+/// warns and keeps the default.
+pub fn apply_gpu_preference(preference: GpuPreference) {
+    let vars: &[(&str, &str)] = match preference {
+        GpuPreference::Default => return,
+        GpuPreference::Integrated => &[("DRI_PRIME", "0")],
+        GpuPreference::Discrete => {
+            if render_nodes().len() < 2 {
+                eprintln!("Discrete GPU requested, but only one GPU was found; using the default");
+                return;
+            }
+            if Path::new("/proc/driver/nvidia").exists() {
+                &[
+                    ("DRI_PRIME", "1"),
+                    ("__NV_PRIME_RENDER_OFFLOAD", "1"),
+                    ("__GLX_VENDOR_LIBRARY_NAME", "nvidia"),
+                ]
+            } else {
+                &[("DRI_PRIME", "1")]
+            }
+        }
+    };
+    for (name, value) in vars {
+        match std::env::var(name) {
+            Ok(current) if current != *value => {
+                eprintln!(
+                    "{} is already set to {}; ignoring the {} GPU preference",
+                    name,
+                    current,
+                    preference.name()
+                );
+            }
+            Ok(_) => {}
+            Err(_) => std::env::set_var(name, value),
+        }
+    }
+}
+
+/// DRM render nodes (`/dev/dri/renderD*`), one per GPU, in device order
+fn render_nodes() -> Vec<std::path::PathBuf> {
+    let mut nodes: Vec<std::path::PathBuf> = std::fs::read_dir("/dev/dri")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| e.file_name().to_string_lossy().starts_with("renderD"))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    nodes.sort();
+    nodes
+}
+
+/// Preview frame rate caps offered in the menu
+pub const PREVIEW_FPS_CHOICES: [u32; 3] = [15, 30, 60];
+
+/// Default cap on preview redraws for animated presets
+pub const DEFAULT_PREVIEW_FPS: u32 = 30;
+
+/// Redraw rate while the window is unfocused
+const BACKGROUND_PREVIEW_FPS: u32 = 5;
+
+/// Paces preview redraws. Animated presets redraw from a timer at the capped
+/// rate (slower when unfocused, not at all when hidden); static presets draw
+/// only when `redraw` is called after something changes.
+pub struct FrameLimiter {
+    gl_area: GLArea,
+    state: SharedRendererState,
+    fps: Cell<u32>,
+    focused: Cell<bool>,
+    visible: Cell<bool>,
+    // The running timer and the interval it was started with
+    timer: RefCell<Option<(glib::SourceId, u32)>>,
+}
+
+impl FrameLimiter {
+    pub fn new(gl_area: &GLArea, state: SharedRendererState, fps: u32) -> Rc<Self> {
+        let limiter = Rc::new(Self {
+            gl_area: gl_area.clone(),
+            state,
+            fps: Cell::new(fps.max(1)),
+            focused: Cell::new(true),
+            visible: Cell::new(true),
+            timer: RefCell::new(None),
+        });
+
+        let weak = Rc::downgrade(&limiter);
+        gl_area.connect_map(move |_| {
+            if let Some(limiter) = weak.upgrade() {
+                limiter.update();
+            }
+        });
+        let weak = Rc::downgrade(&limiter);
+        gl_area.connect_unmap(move |_| {
+            if let Some(limiter) = weak.upgrade() {
+                limiter.stop();
+            }
+        });
+        limiter
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps.get()
+    }
+
+    pub fn set_fps(self: &Rc<Self>, fps: u32) {
+        self.fps.set(fps.max(1));
+        self.update();
+    }
+
+    pub fn set_focused(self: &Rc<Self>, focused: bool) {
+        self.focused.set(focused);
+        self.update();
+    }
+
+    /// Minimized or otherwise hidden windows pause animation entirely
+    pub fn set_visible(self: &Rc<Self>, visible: bool) {
+        self.visible.set(visible);
+        self.update();
+    }
+
+    /// Draw one frame, e.g. after a uniform changed
+    pub fn redraw(&self) {
+        self.gl_area.queue_render();
+    }
+
+    /// Start, retime or stop the timer to match the preset and window state.
+    /// Call after loading a preset; also draws one frame.
+    pub fn update(self: &Rc<Self>) {
+        self.redraw();
+
+        let animated = self.state.borrow().as_ref().is_some_and(|r| r.uses_time());
+        let fps = match (animated, self.visible.get() && self.gl_area.is_mapped()) {
+            (true, true) if self.focused.get() => self.fps.get(),
+            (true, true) => BACKGROUND_PREVIEW_FPS.min(self.fps.get()),
+            _ => {
+                self.stop();
+                return;
+            }
+        };
+        if self
+            .timer
+            .borrow()
+            .as_ref()
+            .is_some_and(|(_, running)| *running == fps)
+        {
+            return;
+        }
+
+        self.stop();
+        let weak = Rc::downgrade(self);
+        let interval = std::time::Duration::from_secs_f64(1.0 / fps as f64);
+        let id = glib::timeout_add_local(interval, move || match weak.upgrade() {
+            Some(limiter) => {
+                limiter.redraw();
+                glib::ControlFlow::Continue
+            }
+            None => glib::ControlFlow::Break,
+        });
+        *self.timer.borrow_mut() = Some((id, fps));
+    }
+
+    fn stop(&self) {
+        if let Some((id, _)) = self.timer.borrow_mut().take() {
+            id.remove();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transparent_colors_render_transparent() {
+        // Needs a GPU or Mesa's software renderer; skipped on machines with neither
+        let (mut renderer, _context) = match create_headless_preset_renderer("Gradient") {
+            Ok(pair) => pair,
+            Err(e) => {
+                eprintln!("skipping: {}", e);
+                return;
+            }
+        };
+        renderer.color_alpha = [0.25; 4];
+        assert!(renderer.uses_alpha());
+        let pixels = renderer.render_to_pixels(8, 8).unwrap();
+        assert!(pixels.chunks(4).all(|p| p[3] < 255));
+
+        renderer.color_alpha = [1.0; 4];
+        let pixels = renderer.render_to_pixels(8, 8).unwrap();
+        assert!(pixels.chunks(4).all(|p| p[3] == 255));
+    }
+}
@@ -0,0 +1,266 @@
+/// The little JSON Wallrus reads and writes: its own files (favorites and
+/// recent lists, looks, recipes) and the palette formats it imports (text
+/// definitions, pywal). Documents are parsed into a [`Json`] tree that keeps
+/// object fields in file order, so callers pick the fields they know and
+/// skip the rest. The TOML palette reader uses [`Scanner`] for its strings.
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A parsed JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parse a whole document. Returns `None` unless `text` is exactly one
+    /// JSON value, give or take whitespace.
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut scanner = Scanner::new(text);
+        let value = scanner.value()?;
+        scanner.skip_space();
+        scanner.at_end().then_some(value)
+    }
+
+    /// The field `key` of an object; the first one if it's repeated
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// An array of strings; `None` for anything else, including an array
+    /// holding other values
+    pub fn as_strings(&self) -> Option<Vec<String>> {
+        match self {
+            Json::Array(items) => items
+                .iter()
+                .map(|item| item.as_str().map(String::from))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    /// The numeric fields of an object; other fields are skipped
+    pub fn numbers(&self) -> Vec<(String, f32)> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .filter_map(|(name, value)| Some((name.clone(), value.as_number()? as f32)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Quote and escape a string as a JSON string literal.
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Character scanner over a JSON (or JSON-like) text
+pub struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self {
+            chars: text.chars().peekable(),
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    pub fn next_if(&mut self, f: impl FnOnce(&char) -> bool) -> Option<char> {
+        self.chars.next_if(f)
+    }
+
+    pub fn at_end(&mut self) -> bool {
+        self.chars.peek().is_none()
+    }
+
+    pub fn skip_space(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Consume `c` if it's next
+    pub fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    /// A `"quoted"` string with JSON escapes
+    pub fn string(&mut self) -> Option<String> {
+        if !self.eat('"') {
+            return None;
+        }
+        let mut s = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(s),
+                '\\' => match self.chars.next()? {
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        s.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    /// Any JSON value, after optional whitespace
+    pub fn value(&mut self) -> Option<Json> {
+        self.skip_space();
+        match self.peek()? {
+            '{' => self.object(),
+            '[' => self.array(),
+            '"' => self.string().map(Json::String),
+            c if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "null" => Some(Json::Null),
+                    "true" => Some(Json::Bool(true)),
+                    "false" => Some(Json::Bool(false)),
+                    _ => None,
+                }
+            }
+            _ => {
+                let mut digits = String::new();
+                while let Some(c) = self
+                    .chars
+                    .next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    digits.push(c);
+                }
+                digits.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn object(&mut self) -> Option<Json> {
+        self.eat('{');
+        let mut fields = Vec::new();
+        self.skip_space();
+        if self.eat('}') {
+            return Some(Json::Object(fields));
+        }
+        loop {
+            self.skip_space();
+            let key = self.string()?;
+            self.skip_space();
+            if !self.eat(':') {
+                return None;
+            }
+            fields.push((key, self.value()?));
+            self.skip_space();
+            if self.eat('}') {
+                return Some(Json::Object(fields));
+            }
+            if !self.eat(',') {
+                return None;
+            }
+        }
+    }
+
+    fn array(&mut self) -> Option<Json> {
+        self.eat('[');
+        let mut items = Vec::new();
+        self.skip_space();
+        if self.eat(']') {
+            return Some(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_space();
+            if self.eat(']') {
+                return Some(Json::Array(items));
+            }
+            if !self.eat(',') {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_parse_into_ordered_fields() {
+        let json = Json::parse(r#" { "b": [1, -2.5e1, true, null], "a": {"c": "é\n"} } "#);
+        let json = json.unwrap();
+        assert_eq!(
+            json.get("b"),
+            Some(&Json::Array(vec![
+                Json::Number(1.0),
+                Json::Number(-25.0),
+                Json::Bool(true),
+                Json::Null,
+            ]))
+        );
+        assert_eq!(
+            json.get("a").and_then(|a| a.get("c")),
+            Some(&Json::String("é\n".into()))
+        );
+        let Json::Object(fields) = &json else {
+            panic!("not an object")
+        };
+        assert_eq!(fields[0].0, "b");
+
+        assert_eq!(Json::parse("[1,]"), None);
+        assert_eq!(Json::parse("{} x"), None);
+        assert_eq!(Json::parse("nope"), None);
+    }
+
+    #[test]
+    fn quoted_strings_read_back() {
+        let s = "sun \"set\"\\\t\u{1}";
+        assert_eq!(Json::parse(&quote(s)), Some(Json::String(s.to_string())));
+    }
+}
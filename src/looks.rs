@@ -0,0 +1,105 @@
+/// Named parameter sets ("looks") for a preset, e.g. "Soft" and "Punchy" for
+/// Noise. Each preset's looks are stored as one small JSON object under the
+/// user config directory (`~/.config/wallrus/looks/<preset>.json`), mapping
+/// look names to their parameter values:
+///
+/// ```json
+/// {
+///   "Soft": { "uContrast": 0.8 },
+///   "Punchy": { "uContrast": 2.2 }
+/// }
+/// ```
+use std::path::PathBuf;
+
+use gtk4::glib;
+
+use crate::json::{self, Json};
+
+/// A saved parameter set. `params` pairs uniform names with values, like
+/// the parameters embedded in exported wallpapers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Look {
+    pub name: String,
+    pub params: Vec<(String, f32)>,
+}
+
+fn looks_dir() -> PathBuf {
+    glib::user_config_dir().join("wallrus").join("looks")
+}
+
+/// The looks file of a preset. Path separators in user shader names are
+/// replaced so the file stays inside the looks directory.
+fn looks_file(preset: &str) -> PathBuf {
+    let stem: String = preset
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\') { '_' } else { c })
+        .collect();
+    looks_dir().join(format!("{}.json", stem.trim_start_matches('.')))
+}
+
+/// The looks saved for `preset`, in the order they were first saved. A
+/// missing or unreadable file gives none.
+pub fn list_looks(preset: &str) -> Vec<Look> {
+    std::fs::read_to_string(looks_file(preset))
+        .ok()
+        .and_then(|text| parse_looks(&text))
+        .unwrap_or_default()
+}
+
+/// Save `params` as the look `name` for `preset`, replacing a look with the
+/// same name.
+pub fn save_look(preset: &str, name: &str, params: &[(String, f32)]) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Look name is empty".to_string());
+    }
+    let mut looks = list_looks(preset);
+    let look = Look {
+        name: name.to_string(),
+        params: params.to_vec(),
+    };
+    match looks.iter_mut().find(|l| l.name == name) {
+        Some(existing) => *existing = look,
+        None => looks.push(look),
+    }
+
+    std::fs::create_dir_all(looks_dir())
+        .map_err(|e| format!("Failed to create looks directory: {}", e))?;
+    std::fs::write(looks_file(preset), format_looks(&looks))
+        .map_err(|e| format!("Failed to save look: {}", e))
+}
+
+fn format_looks(looks: &[Look]) -> String {
+    let entries: Vec<String> = looks
+        .iter()
+        .map(|look| {
+            let params: Vec<String> = look
+                .params
+                .iter()
+                .map(|(uniform, value)| format!("{}: {}", json::quote(uniform), value))
+                .collect();
+            format!("  {}: {{ {} }}", json::quote(&look.name), params.join(", "))
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+/// Parse a looks file. Returns `None` unless it's an object of objects of
+/// numbers.
+fn parse_looks(text: &str) -> Option<Vec<Look>> {
+    let Json::Object(looks) = Json::parse(text)? else {
+        return None;
+    };
+    looks
+        .into_iter()
+        .map(|(name, params)| match params {
+            Json::Object(ref fields) if fields.iter().all(|(_, v)| v.as_number().is_some()) => {
+                Some(Look {
+                    name,
+                    params: params.numbers(),
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -1,10 +1,21 @@
 mod application;
+mod cli;
+mod diagnostics;
 mod export;
 mod gl_renderer;
+mod json;
+mod looks;
 mod palette;
+mod palette_card;
+mod preset_gallery;
+mod recipe;
+mod rotation;
+mod screenshot;
+mod settings;
 mod shader;
 mod shader_presets;
 mod wallpaper;
+mod wallpaper_history;
 mod window;
 
 use application::WallrusApplication;
@@ -12,6 +23,18 @@ use application::WallrusApplication;
 const APP_ID: &str = "io.github.megakode.Wallrus";
 
 fn main() {
+    let saved = settings::load();
+    // Before any GL context exists, including the headless renderer's
+    gl_renderer::apply_gpu_preference(saved.gpu_preference);
+    // Both the window and `--set` on the command line use the saved backend
+    wallpaper::set_preferred_backend(saved.wallpaper_backend);
+
+    // Headless rendering from the command line skips the GUI entirely
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let app = WallrusApplication::new(APP_ID);
     std::process::exit(app.run());
 }
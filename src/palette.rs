@@ -1,243 +1,4180 @@
-/// Palette image handling — extract colors from 1x4 palette images
+/// Palette image handling — extract colors from 1xN palette images
 /// and list available palette images organized by category (subfolder).
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use gtk4::glib;
-use image::{ImageBuffer, Rgb};
+use gtk4::{gio, glib};
+use image::metadata::Orientation;
+use image::{
+    DynamicImage, GenericImageView, ImageBuffer, ImageDecoder, ImageFormat, ImageReader, Pixel,
+    Rgb, RgbImage,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::json::{self, Json};
 
 /// The category name used for user-saved palettes.
 pub const CUSTOM_CATEGORY: &str = "Custom";
 
+/// Synthetic category listing starred palettes from any category.
+pub const FAVORITES_CATEGORY: &str = "Favorites";
+
+/// Synthetic category listing the palettes used most recently, newest first.
+pub const RECENT_CATEGORY: &str = "Recent";
+
+/// How many palettes the Recent category remembers
+pub const RECENT_LIMIT: usize = 10;
+
 /// A category name mapped to its palette image paths (sorted by filename).
 pub type PaletteCategories = BTreeMap<String, Vec<PathBuf>>;
 
-/// Extract 4 colors from a palette image.
+/// Photos are downsampled so their long edge is at most this many pixels
+/// before k-means clustering.
+const KMEANS_SAMPLE_SIZE: u32 = 128;
+
+/// Fixed RNG seed for k-means initialization, so the same photo always
+/// produces the same palette.
+const KMEANS_SEED: u64 = 0x57A1_1205;
+
+/// Maximum number of k-means refinement iterations.
+const KMEANS_MAX_ITERATIONS: usize = 32;
+
+/// Stop iterating once no cluster center moves further than this (in Lab units).
+const KMEANS_CONVERGENCE: f32 = 0.01;
+
+/// Number of colors in the standard 1x4 palette strip.
+pub const DEFAULT_COLOR_COUNT: usize = 4;
+
+/// Fewest colors the photo extraction dialog can pull
+pub const MIN_EXTRACT_COUNT: usize = 2;
+
+/// Most colors the photo extraction dialog can pull
+pub const MAX_EXTRACT_COUNT: usize = 8;
+
+/// Extract `n` colors from a palette image.
+///
+/// The image is expected to be 1xN px (one pixel per color, top to bottom).
+/// Larger images are also supported — the image is divided into `n` equal
+/// horizontal bands and the center pixel of each band is sampled.
 ///
-/// The image is expected to be 1x4px (one pixel per color, top to bottom).
-/// For backward compatibility, larger images are also supported — the image
-/// is divided into 4 equal horizontal bands and the center pixel of each
-/// band is sampled.
-pub fn extract_colors_from_image(path: &Path) -> Result<[[f32; 3]; 4], String> {
-    let img = image::open(path).map_err(|e| format!("Failed to load image: {}", e))?;
-    let rgb = img.to_rgb8();
-    let (width, height) = rgb.dimensions();
+/// If `n` exceeds the image height, it is clamped to the height so that
+/// each returned color comes from a distinct row.
+pub fn extract_colors_from_image(path: &Path, n: usize) -> Result<Vec<[f32; 3]>, String> {
+    extract_colors_in(path, n, ColorSpace::Srgb)
+}
 
-    if width == 0 || height == 0 {
-        return Err("Image has zero dimensions".to_string());
+/// [`extract_colors_from_image`], returning the colors in `space`. Pixels
+/// are sampled, not averaged, so only the encoding of the values differs.
+pub fn extract_colors_in(
+    path: &Path,
+    n: usize,
+    space: ColorSpace,
+) -> Result<Vec<[f32; 3]>, String> {
+    if n == 0 {
+        return Err("Color count must be at least 1".to_string());
+    }
+    if is_palette_definition(path) {
+        let colors = read_palette_definition(path)?;
+        return Ok(colors
+            .into_iter()
+            .take(n)
+            .map(|c| space.decode(c))
+            .collect());
     }
 
-    let cx = width / 2;
-    let band_height = height / 4;
+    let colors = match open_band_source(path, n)? {
+        BandSource::Sampled(pixels) => pixels.into_iter().map(pixel_to_rgb).collect(),
+        BandSource::Decoded(img) => match img.as_rgb8() {
+            Some(rgb) => extract_colors_from_rgb(rgb, n),
+            None => extract_colors_from_rgb(&img.to_rgb8(), n),
+        },
+    };
 
-    let mut colors = [[0.0f32; 3]; 4];
-    for i in 0..4 {
-        let cy = band_height * i as u32 + band_height / 2;
-        let cy = cy.min(height - 1);
-        let pixel = rgb.get_pixel(cx, cy);
-        colors[i] = [
-            pixel[0] as f32 / 255.0,
-            pixel[1] as f32 / 255.0,
-            pixel[2] as f32 / 255.0,
-        ];
-    }
+    Ok(colors.into_iter().map(|c| space.decode(c)).collect())
+}
 
-    Ok(colors)
+/// The sampling behind [`extract_colors_from_image`], on an image already in
+/// memory: the center pixel of each of `n` equal horizontal bands, as sRGB
+/// 0–1. `n` is clamped to the image height; an empty image or `n == 0`
+/// gives no colors.
+pub fn extract_colors_from_rgb(img: &RgbImage, n: usize) -> Vec<[f32; 3]> {
+    sample_image(img, n).into_iter().map(pixel_to_rgb).collect()
 }
 
-/// List all palette images organized by category.
-///
-/// Categories are subfolders inside the palette root directories.
-/// Images directly in the root (not in a subfolder) go into an "Uncategorized" category.
-/// Bundled and user-saved palettes are scanned and merged.
-/// Categories are sorted alphabetically; images within each are sorted by filename.
-pub fn list_palette_categories() -> PaletteCategories {
-    let mut categories: PaletteCategories = BTreeMap::new();
+fn pixel_to_rgb(pixel: [u8; 4]) -> [f32; 3] {
+    [
+        pixel[0] as f32 / 255.0,
+        pixel[1] as f32 / 255.0,
+        pixel[2] as f32 / 255.0,
+    ]
+}
 
-    if let Some(dir) = bundled_palettes_dir() {
-        collect_categorized_images(&dir, &mut categories);
+/// The 8-bit RGBA pixels at the [`band_sample_points`] of a decoded image
+fn sample_image<I>(img: &I, n: usize) -> Vec<[u8; 4]>
+where
+    I: GenericImageView,
+    I::Pixel: Pixel<Subpixel = u8>,
+{
+    let (width, height) = img.dimensions();
+    if width == 0 {
+        return Vec::new();
     }
+    band_sample_points(width, height, n)
+        .map(|(x, y)| img.get_pixel(x, y).to_rgba().0)
+        .collect()
+}
 
-    // Scan user-saved palettes from the sandbox data directory
-    let user_dir = user_palettes_dir();
-    collect_categorized_images(&user_dir, &mut categories);
+/// Extract the standard 4 colors from a palette image, including alpha.
+///
+/// Samples the same band centers as [`extract_four_colors`]; images without
+/// an alpha channel get an alpha of 1.0.
+pub fn extract_colors_rgba(path: &Path) -> Result<[[f32; 4]; 4], String> {
+    if is_palette_definition(path) {
+        let colors = to_four_colors(&read_palette_definition(path)?);
+        return Ok(colors.map(|[r, g, b]| [r, g, b, 1.0]));
+    }
+    let colors: Vec<[f32; 4]> = sample_band_pixels(path, DEFAULT_COLOR_COUNT)?
+        .into_iter()
+        .map(|pixel| pixel.map(|v| v as f32 / 255.0))
+        .collect();
 
-    // Sort images within each category by filename
-    for images in categories.values_mut() {
-        images.sort_by(|a, b| {
-            a.file_name()
-                .unwrap_or_default()
-                .cmp(b.file_name().unwrap_or_default())
-        });
+    // Short images repeat their last color, like to_four_colors
+    let mut four = [[0.0, 0.0, 0.0, 1.0]; 4];
+    for (i, slot) in four.iter_mut().enumerate() {
+        if let Some(c) = colors.get(i).or(colors.last()) {
+            *slot = *c;
+        }
     }
+    Ok(four)
+}
 
-    categories
+/// Images with more pixels than this aren't decoded whole just to sample a
+/// few pixels, when the format allows it (see [`open_band_source`])
+const LARGE_IMAGE_PIXELS: u64 = 1 << 22;
+
+/// A palette image opened for band sampling
+enum BandSource {
+    /// The pixels at the band sample points, already read
+    Sampled(Vec<[u8; 4]>),
+    /// The whole image, to sample in place
+    Decoded(DynamicImage),
+}
+
+/// The 8-bit RGBA pixels at the [`band_sample_points`] of an image.
+/// Grayscale, 16-bit and indexed images are converted like `to_rgba8`.
+fn sample_band_pixels(path: &Path, n: usize) -> Result<Vec<[u8; 4]>, String> {
+    Ok(match open_band_source(path, n)? {
+        BandSource::Sampled(pixels) => pixels,
+        BandSource::Decoded(img) => sample_image(&img, n),
+    })
 }
 
-/// Save 4 colors as a 1x4px palette PNG in the user data directory.
+/// Open an image for sampling `n` bands. Only the header is read first.
+/// Large non-interlaced PNGs are then streamed row by row up to the last
+/// sampled row, so a 4000x4000 "strip" never sits in memory; other images
+/// are decoded once.
 ///
-/// The image is saved under the "Custom" subfolder with a timestamp-based name.
-/// Returns the path of the saved file.
-pub fn save_palette_image(colors: &[[f32; 3]; 4]) -> Result<PathBuf, String> {
-    let custom_dir = user_palettes_dir().join(CUSTOM_CATEGORY.to_lowercase());
-    if !custom_dir.exists() {
-        std::fs::create_dir_all(&custom_dir)
-            .map_err(|e| format!("Failed to create custom palettes dir: {}", e))?;
+/// Indexed PNGs are expanded to their palette colors on both paths, so the
+/// samples are exact. The palette itself isn't used as the colors: its
+/// order needn't match the rows, and it can hold entries no pixel uses.
+fn open_band_source(path: &Path, n: usize) -> Result<BandSource, String> {
+    let load_err = |e: image::ImageError| format!("Failed to load image: {}", e);
+    let reader = open_image_reader(path)?;
+    let format = reader.format();
+    let (width, height) = reader.into_dimensions().map_err(load_err)?;
+    if width == 0 || height == 0 {
+        return Err("Image has zero dimensions".to_string());
     }
 
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-    let filename = format!("palette_{}.png", timestamp);
-    let path = custom_dir.join(&filename);
+    let large = width as u64 * height as u64 > LARGE_IMAGE_PIXELS;
+    if large && format == Some(ImageFormat::Png) {
+        let points: Vec<(u32, u32)> = band_sample_points(width, height, n).collect();
+        if let Some(pixels) = sample_png_rows(path, &points)? {
+            return Ok(BandSource::Sampled(pixels));
+        }
+    }
+    open_image_reader(path)?
+        .decode()
+        .map(BandSource::Decoded)
+        .map_err(load_err)
+}
 
-    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(1, 4);
-    for (i, color) in colors.iter().enumerate() {
-        let r = (color[0] * 255.0).round() as u8;
-        let g = (color[1] * 255.0).round() as u8;
-        let b = (color[2] * 255.0).round() as u8;
-        img.put_pixel(0, i as u32, Rgb([r, g, b]));
+/// Open an image with its format taken from its first bytes, falling back
+/// to the extension, so palettes saved without (or with the wrong)
+/// extension still decode. AVIF files fail with a message saying so when
+/// built without the `avif` feature.
+fn open_image_reader(
+    path: &Path,
+) -> Result<ImageReader<std::io::BufReader<std::fs::File>>, String> {
+    let reader = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to load image: {}", e))?;
+    if reader.format() == Some(ImageFormat::Avif) && !cfg!(feature = "avif") {
+        return Err(
+            "Failed to load image: AVIF needs Wallrus built with the avif feature".to_string(),
+        );
     }
+    Ok(reader)
+}
 
-    img.save(&path)
-        .map_err(|e| format!("Failed to save palette image: {}", e))?;
+/// Sample `points` from a PNG by decoding rows only until the last one
+/// needed. Returns `None` for interlaced PNGs, whose rows arrive in passes.
+fn sample_png_rows(path: &Path, points: &[(u32, u32)]) -> Result<Option<Vec<[u8; 4]>>, String> {
+    let to_err = |e: png::DecodingError| format!("Failed to load image: {}", e);
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to load image: {}", e))?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    // Palettes become RGB(A) and low bit depths 8-bit; 16-bit is kept so
+    // it can be rounded the way the image crate does
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(to_err)?;
+    if reader.info().interlaced {
+        return Ok(None);
+    }
+    let (color_type, depth) = reader.output_color_type();
+    let bytes = if depth == png::BitDepth::Sixteen {
+        2
+    } else {
+        1
+    };
+    let channels = color_type.samples();
 
-    Ok(path)
+    let mut pixels = vec![[0, 0, 0, 255]; points.len()];
+    let last_row = points.iter().map(|&(_, y)| y).max().unwrap_or(0);
+    for y in 0..=last_row {
+        let row = reader
+            .next_row()
+            .map_err(to_err)?
+            .ok_or_else(|| "Failed to load image: image data ended early".to_string())?;
+        for (pixel, &(x, _)) in pixels.iter_mut().zip(points).filter(|(_, p)| p.1 == y) {
+            let data = &row.data()[x as usize * channels * bytes..];
+            let sample = |i: usize| match bytes {
+                2 => {
+                    ((u16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as u32 + 128) / 257) as u8
+                }
+                _ => data[i],
+            };
+            *pixel = match color_type {
+                png::ColorType::Grayscale => [sample(0), sample(0), sample(0), 255],
+                png::ColorType::GrayscaleAlpha => [sample(0), sample(0), sample(0), sample(1)],
+                png::ColorType::Rgba => [sample(0), sample(1), sample(2), sample(3)],
+                _ => [sample(0), sample(1), sample(2), 255],
+            };
+        }
+    }
+    Ok(Some(pixels))
 }
 
-/// Delete a user-saved palette image.
-///
-/// Only allows deletion of files inside the user palettes directory (safety check).
-pub fn delete_palette_image(path: &Path) -> Result<(), String> {
-    let user_dir = user_palettes_dir();
-    if !path.starts_with(&user_dir) {
-        return Err("Cannot delete bundled palettes".to_string());
+/// Center pixel of each of `n` equal horizontal bands (`n` clamped to the
+/// image height so every band is at least one row). Bands are split
+/// proportionally, so heights that don't divide evenly still sample the
+/// whole image, and every point lies inside it. Empty images have no points.
+fn band_sample_points(width: u32, height: u32, n: usize) -> impl Iterator<Item = (u32, u32)> {
+    let n = n.min(height as usize) as u64;
+    let cx = width / 2;
+    (0..n).map(move |i| {
+        // Center of band i is at (i + 0.5) * height / n, always < height
+        let cy = (2 * i + 1) * height as u64 / (2 * n);
+        (cx, cy as u32)
+    })
+}
+
+/// Center pixel of each cell of a `cols`×`rows` grid, row by row. Like
+/// [`band_sample_points`], the counts are clamped to the image size and
+/// cells are split proportionally, so every point lies inside the image.
+fn grid_sample_points(
+    width: u32,
+    height: u32,
+    cols: usize,
+    rows: usize,
+) -> impl Iterator<Item = (u32, u32)> {
+    let centers = |len: u32, n: usize| {
+        let n = n.min(len as usize) as u64;
+        (0..n).map(move |i| ((2 * i + 1) * len as u64 / (2 * n)) as u32)
+    };
+    let xs: Vec<u32> = centers(width, cols).collect();
+    centers(height, rows).flat_map(move |y| xs.clone().into_iter().map(move |x| (x, y)))
+}
+
+/// Extract the colors of a palette laid out as a grid of `cols`×`rows`
+/// swatches, sampling the center of each cell. Colors are returned in
+/// row-major order, left to right and top to bottom. Dimensions that don't
+/// divide evenly are handled like strips (see [`band_sample_points`]).
+pub fn extract_colors_grid(path: &Path, cols: usize, rows: usize) -> Result<Vec<[f32; 3]>, String> {
+    if cols == 0 || rows == 0 {
+        return Err("Grid must have at least one column and one row".to_string());
     }
+    let img = open_oriented(path)?.to_rgb8();
+    let (width, height) = img.dimensions();
+    Ok(grid_sample_points(width, height, cols, rows)
+        .map(|(x, y)| pixel_to_rgb(img.get_pixel(x, y).to_rgba().0))
+        .collect())
+}
 
-    std::fs::remove_file(path).map_err(|e| format!("Failed to delete palette: {}", e))
+/// Order applied to colors after they're sampled from a palette, so strips
+/// authored dark-first and light-first can be used the same way
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtractOrder {
+    /// Band order, top to bottom
+    #[default]
+    AsIs,
+    DarkToLight,
+    LightToDark,
 }
 
-/// Whether the given category name is the user-saved custom category.
-pub fn is_custom_category(name: &str) -> bool {
-    name == CUSTOM_CATEGORY
+impl ExtractOrder {
+    pub const ALL: [ExtractOrder; 3] = [
+        ExtractOrder::AsIs,
+        ExtractOrder::DarkToLight,
+        ExtractOrder::LightToDark,
+    ];
+
+    /// Name used on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            ExtractOrder::AsIs => "as-is",
+            ExtractOrder::DarkToLight => "dark-to-light",
+            ExtractOrder::LightToDark => "light-to-dark",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|order| order.name() == s.to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown color order '{}', expected as-is, dark-to-light or light-to-dark",
+                    s
+                )
+            })
+    }
+
+    /// Reorder sRGB `colors` by relative luminance; colors that tie keep
+    /// their order.
+    pub fn apply(self, colors: &mut [[f32; 3]]) {
+        match self {
+            ExtractOrder::AsIs => {}
+            ExtractOrder::DarkToLight => {
+                colors.sort_by(|a, b| relative_luminance(*a).total_cmp(&relative_luminance(*b)))
+            }
+            ExtractOrder::LightToDark => {
+                colors.sort_by(|a, b| relative_luminance(*b).total_cmp(&relative_luminance(*a)))
+            }
+        }
+    }
 }
 
-/// Get the user palettes directory inside the sandbox data dir.
+/// [`extract_colors_from_image`], with the colors put in `order` afterwards
+pub fn extract_colors_ordered(
+    path: &Path,
+    n: usize,
+    order: ExtractOrder,
+) -> Result<Vec<[f32; 3]>, String> {
+    let mut colors = extract_colors_from_image(path, n)?;
+    order.apply(&mut colors);
+    Ok(colors)
+}
+
+/// Extract the standard 4 colors from a palette image.
 ///
-/// In Flatpak this is `~/.var/app/io.github.megakode.Wallrus/data/palettes/`.
-/// Outside Flatpak this is `~/.local/share/palettes/` (via `g_get_user_data_dir()`).
-/// Creates the directory if it doesn't exist.
-fn user_palettes_dir() -> PathBuf {
-    let dir = glib::user_data_dir().join("palettes");
-    if !dir.exists() {
-        let _ = std::fs::create_dir_all(&dir);
+/// Thin wrapper around [`extract_colors_from_image`] for callers that work
+/// with fixed 4-color palettes. Images shorter than 4px repeat their last color.
+pub fn extract_four_colors(path: &Path) -> Result<[[f32; 3]; 4], String> {
+    let extracted = extract_colors_from_image(path, DEFAULT_COLOR_COUNT)?;
+    Ok(to_four_colors(&extracted))
+}
+
+/// Fit a variable-length color list into the fixed 4-slot palette.
+///
+/// Extra colors are dropped; missing slots repeat the last color.
+/// An empty list yields black.
+pub fn to_four_colors(colors: &[[f32; 3]]) -> [[f32; 3]; 4] {
+    let mut out = [[0.0f32; 3]; 4];
+    if let Some(last) = colors.len().checked_sub(1) {
+        for (i, color) in out.iter_mut().enumerate() {
+            *color = colors[i.min(last)];
+        }
     }
-    dir
+    out
 }
 
-/// Get the bundled palettes directory.
+/// Cached 4-color extraction, keyed by path and modification time.
 ///
-/// Looks for palettes relative to the executable, then falls back to
-/// common installation paths. During development this is `data/palettes/`
-/// relative to the project root.
-pub fn bundled_palettes_dir() -> Option<PathBuf> {
-    // During development: look relative to the executable
-    if let Ok(exe) = std::env::current_exe() {
-        // target/debug/wallrus -> project_root/data/palettes
-        if let Some(project_root) = exe
-            .parent()
-            .and_then(|p| p.parent())
-            .and_then(|p| p.parent())
-        {
-            let dev_path = project_root.join("data").join("palettes");
-            if dev_path.is_dir() {
-                return Some(dev_path);
+/// Entries live in memory and in a small text file under the user cache
+/// directory (`~/.cache/wallrus/palette-colors`), so browsing large palette
+/// collections doesn't decode every image on each refresh. An entry is
+/// re-extracted with [`extract_four_colors`] when its file's mtime changes.
+pub fn cached_palette_colors(path: &Path) -> Result<[[f32; 3]; 4], String> {
+    COLOR_CACHE.with(|cache| {
+        cache
+            .borrow_mut()
+            .get_or_insert_with(|| ColorCache::load(color_cache_file()))
+            .colors(path)
+    })
+}
+
+thread_local! {
+    static COLOR_CACHE: RefCell<Option<ColorCache>> = const { RefCell::new(None) };
+}
+
+fn color_cache_file() -> PathBuf {
+    glib::user_cache_dir()
+        .join("wallrus")
+        .join("palette-colors")
+}
+
+/// One `mtime<TAB>#RRGGBB,#RRGGBB,#RRGGBB,#RRGGBB<TAB>path` line per entry.
+/// New entries are appended, so a path may appear more than once; the last
+/// line wins and the file is compacted when loaded.
+struct ColorCache {
+    file: PathBuf,
+    entries: HashMap<PathBuf, (u128, [[f32; 3]; 4])>,
+}
+
+impl ColorCache {
+    fn load(file: PathBuf) -> Self {
+        let contents = std::fs::read_to_string(&file).unwrap_or_default();
+        let mut entries = HashMap::new();
+        let mut lines = 0;
+        for line in contents.lines() {
+            lines += 1;
+            let mut fields = line.splitn(3, '\t');
+            let (Some(mtime), Some(colors), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Ok(mtime) = mtime.parse::<u128>() else {
+                continue;
+            };
+            let parsed: Option<Vec<[f32; 3]>> =
+                colors.split(',').map(|hex| hex_to_rgb(hex).ok()).collect();
+            if let Some(parsed) = parsed.filter(|c| c.len() == DEFAULT_COLOR_COUNT) {
+                entries.insert(PathBuf::from(path), (mtime, to_four_colors(&parsed)));
             }
         }
+
+        let cache = Self { file, entries };
+        if lines > cache.entries.len() {
+            cache.rewrite();
+        }
+        cache
     }
 
-    // Installed (prefix-relative): <prefix>/bin/wallrus -> <prefix>/share/wallrus/palettes
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(prefix) = exe.parent().and_then(|p| p.parent()) {
-            let prefix_path = prefix.join("share").join("wallrus").join("palettes");
-            if prefix_path.is_dir() {
-                return Some(prefix_path);
+    fn colors(&mut self, path: &Path) -> Result<[[f32; 3]; 4], String> {
+        let mtime = modified_nanos(path)?;
+        if let Some((cached_mtime, colors)) = self.entries.get(path) {
+            if *cached_mtime == mtime {
+                return Ok(*colors);
             }
         }
+
+        let colors = extract_four_colors(path)?;
+        self.entries.insert(path.to_path_buf(), (mtime, colors));
+        if let Err(e) = self.append(path, mtime, &colors) {
+            eprintln!("Failed to update palette color cache: {}", e);
+        }
+        Ok(colors)
     }
 
-    // Installed: /usr/share/wallrus/palettes
-    let system_path = PathBuf::from("/usr/share/wallrus/palettes");
-    if system_path.is_dir() {
-        return Some(system_path);
+    fn append(&self, path: &Path, mtime: u128, colors: &[[f32; 3]; 4]) -> std::io::Result<()> {
+        if let Some(dir) = self.file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)?;
+        file.write_all(cache_line(path, mtime, colors).as_bytes())
     }
 
-    // Flatpak or local: /app/share/wallrus/palettes
-    let flatpak_path = PathBuf::from("/app/share/wallrus/palettes");
-    if flatpak_path.is_dir() {
-        return Some(flatpak_path);
+    fn rewrite(&self) {
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|(path, (mtime, colors))| cache_line(path, *mtime, colors))
+            .collect();
+        if let Err(e) = std::fs::write(&self.file, contents) {
+            eprintln!("Failed to compact palette color cache: {}", e);
+        }
     }
+}
 
-    None
+fn cache_line(path: &Path, mtime: u128, colors: &[[f32; 3]; 4]) -> String {
+    let hex: Vec<String> = colors.iter().map(|c| rgb_to_hex(*c)).collect();
+    format!("{}\t{}\t{}\n", mtime, hex.join(","), path.display())
 }
 
-/// Scan a palette root directory for categorized images.
+fn modified_nanos(path: &Path) -> Result<u128, String> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(|t| {
+            t.duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        })
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Load a photo upright: the EXIF orientation phone cameras record (JPEG,
+/// WebP, TIFF) is applied, so the pixels match how other viewers show it.
+fn open_oriented(path: &Path) -> Result<DynamicImage, String> {
+    let to_err = |e: image::ImageError| format!("Failed to load image: {}", e);
+    let mut decoder = open_image_reader(path)?.into_decoder().map_err(to_err)?;
+    // Unreadable EXIF data leaves the image as stored rather than failing
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(to_err)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+/// Extract a representative palette of up to `k` colors from an arbitrary photo.
 ///
-/// - Subfolders become categories (folder name with first letter capitalized).
-/// - Image files directly in the root go into "Uncategorized".
-fn collect_categorized_images(root: &Path, categories: &mut PaletteCategories) {
-    let entries = match std::fs::read_dir(root) {
-        Ok(e) => e,
-        Err(_) => return,
+/// The image is turned upright, downsampled, then clustered with k-means in CIE Lab space
+/// (for perceptually even grouping) using a fixed seed. Cluster centers are
+/// returned sorted by population, most common first. Images with fewer than
+/// `k` unique colors return just the unique colors found.
+pub fn extract_palette_kmeans(path: &Path, k: usize) -> Result<Vec<[f32; 3]>, String> {
+    if k == 0 {
+        return Err("Color count must be at least 1".to_string());
+    }
+
+    let img = open_oriented(path)?;
+    let rgb = img
+        .thumbnail(KMEANS_SAMPLE_SIZE, KMEANS_SAMPLE_SIZE)
+        .to_rgb8();
+
+    if rgb.width() == 0 || rgb.height() == 0 {
+        return Err("Image has zero dimensions".to_string());
+    }
+
+    // Collapse identical pixels into weighted samples
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in rgb.pixels() {
+        *counts.entry(pixel.0).or_default() += 1;
+    }
+    let mut samples: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+    // Sort for deterministic iteration order (HashMap order is random)
+    samples.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    if samples.len() <= k {
+        return Ok(samples.iter().map(|(c, _)| u8_to_rgb(*c)).collect());
+    }
+
+    let points: Vec<[f32; 3]> = samples
+        .iter()
+        .map(|(c, _)| rgb_to_lab(u8_to_rgb(*c)))
+        .collect();
+    let weights: Vec<f32> = samples.iter().map(|(_, n)| *n as f32).collect();
+
+    let mut centers = kmeans_plus_plus_init(&points, &weights, k);
+    let mut assignments = vec![0usize; points.len()];
+
+    for _ in 0..KMEANS_MAX_ITERATIONS {
+        for (i, p) in points.iter().enumerate() {
+            assignments[i] = nearest_center(p, &centers);
+        }
+
+        let mut sums = vec![[0.0f32; 3]; k];
+        let mut totals = vec![0.0f32; k];
+        for (i, p) in points.iter().enumerate() {
+            let c = assignments[i];
+            for ch in 0..3 {
+                sums[c][ch] += p[ch] * weights[i];
+            }
+            totals[c] += weights[i];
+        }
+
+        let mut max_shift = 0.0f32;
+        for c in 0..k {
+            if totals[c] == 0.0 {
+                continue; // Empty cluster keeps its previous center
+            }
+            let new_center = [
+                sums[c][0] / totals[c],
+                sums[c][1] / totals[c],
+                sums[c][2] / totals[c],
+            ];
+            max_shift = max_shift.max(lab_distance_sq(&new_center, &centers[c]).sqrt());
+            centers[c] = new_center;
+        }
+
+        if max_shift < KMEANS_CONVERGENCE {
+            break;
+        }
+    }
+
+    let mut populations = vec![0.0f32; k];
+    for (i, p) in points.iter().enumerate() {
+        populations[nearest_center(p, &centers)] += weights[i];
+    }
+
+    let mut clusters: Vec<([f32; 3], f32)> = centers
+        .into_iter()
+        .zip(populations)
+        .filter(|(_, n)| *n > 0.0)
+        .collect();
+    clusters.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Ok(clusters
+        .into_iter()
+        .map(|(lab, _)| lab_to_rgb(lab))
+        .collect())
+}
+
+/// Extract 4 colors from any image file, choosing the method with
+/// [`classify_palette_image`]: strips are band-sampled with
+/// [`extract_colors_from_image`], photos are clustered with
+/// [`extract_palette_kmeans`]. The kind is returned so callers can warn
+/// about irregular strips.
+pub fn extract_palette_from_file(path: &Path) -> Result<([[f32; 3]; 4], PaletteImageKind), String> {
+    let kind = classify_palette_image(path)?;
+    let colors = match kind {
+        PaletteImageKind::Photo => extract_palette_kmeans(path, DEFAULT_COLOR_COUNT)?,
+        PaletteImageKind::Strip | PaletteImageKind::IrregularStrip => {
+            extract_colors_from_image(path, DEFAULT_COLOR_COUNT)?
+        }
     };
+    Ok((to_four_colors(&colors), kind))
+}
 
-    for entry in entries.flatten() {
-        let path = entry.path();
+/// What a palette image looks like, so callers can pick an extraction method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteImageKind {
+    /// A tall, narrow strip of flat color bands, like the bundled 1x4 palettes
+    Strip,
+    /// Strip-shaped, but the bands aren't flat (a gradient or a cropped
+    /// screenshot), so band sampling may pick unrepresentative colors
+    IrregularStrip,
+    /// Anything else; [`extract_palette_kmeans`] gives better colors
+    Photo,
+}
 
-        if path.is_dir() {
-            // Subfolder = category
-            let category_name = path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .map(capitalize_first)
-                .unwrap_or_else(|| "Uncategorized".to_string());
+impl PaletteImageKind {
+    /// A hint for the user when band sampling is the wrong tool, if any
+    pub fn warning(self) -> Option<&'static str> {
+        match self {
+            Self::Strip => None,
+            Self::IrregularStrip => Some("Palette bands aren't uniform, so the colors may be off"),
+            Self::Photo => Some("This looks like a photo; Import from Photo gives better colors"),
+        }
+    }
+}
 
-            let sub_entries = match std::fs::read_dir(&path) {
-                Ok(e) => e,
-                Err(_) => continue,
-            };
+/// Images at least this many times taller than wide count as strips.
+const STRIP_MIN_ASPECT: u32 = 2;
 
-            for sub_entry in sub_entries.flatten() {
-                let sub_path = sub_entry.path();
-                if sub_path.is_file() && is_image_file(&sub_path) {
-                    categories
-                        .entry(category_name.clone())
-                        .or_default()
-                        .push(sub_path);
-                }
+/// A band is flat if no pixel differs from the band's mean by more than
+/// this (per channel, 0–255), which leaves room for JPEG noise.
+const BAND_TOLERANCE: f32 = 24.0;
+
+/// Classify a palette image by its dimensions and, for strips, by whether
+/// each of the 4 sampled bands is a single flat color.
+pub fn classify_palette_image(path: &Path) -> Result<PaletteImageKind, String> {
+    // Definitions list their colors exactly, like a flat strip
+    if is_palette_definition(path) {
+        return read_palette_definition(path).map(|_| PaletteImageKind::Strip);
+    }
+    let img = open_image_reader(path)?
+        .decode()
+        .map_err(|e| format!("Failed to load image: {}", e))?;
+    Ok(classify_rgb(&img.to_rgb8()))
+}
+
+fn classify_rgb(img: &image::RgbImage) -> PaletteImageKind {
+    let (width, height) = img.dimensions();
+    if width == 0 || (width != 1 && height < width * STRIP_MIN_ASPECT) {
+        return PaletteImageKind::Photo;
+    }
+
+    let n = (DEFAULT_COLOR_COUNT as u32).min(height);
+    let band_height = height / n;
+    let uniform = (0..n).all(|i| {
+        let top = band_height * i;
+        let bottom = if i + 1 == n {
+            height
+        } else {
+            top + band_height
+        };
+        let pixels: Vec<[f32; 3]> = (top..bottom)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| img.get_pixel(x, y).0.map(|v| v as f32))
+            .collect();
+        let count = pixels.len() as f32;
+        let mean = pixels.iter().fold([0.0; 3], |acc, p| {
+            [
+                acc[0] + p[0] / count,
+                acc[1] + p[1] / count,
+                acc[2] + p[2] / count,
+            ]
+        });
+        pixels
+            .iter()
+            .all(|p| (0..3).all(|c| (p[c] - mean[c]).abs() <= BAND_TOLERANCE))
+    });
+
+    if uniform {
+        PaletteImageKind::Strip
+    } else {
+        PaletteImageKind::IrregularStrip
+    }
+}
+
+/// Pick initial k-means centers with the k-means++ strategy (seeded).
+fn kmeans_plus_plus_init(points: &[[f32; 3]], weights: &[f32], k: usize) -> Vec<[f32; 3]> {
+    let mut rng = ChaCha8Rng::seed_from_u64(KMEANS_SEED);
+    let mut centers = Vec::with_capacity(k);
+
+    // Start from the most populous color (samples are sorted by count)
+    centers.push(points[0]);
+
+    while centers.len() < k {
+        let dists: Vec<f32> = points
+            .iter()
+            .zip(weights)
+            .map(|(p, w)| {
+                let d = centers
+                    .iter()
+                    .map(|c| lab_distance_sq(p, c))
+                    .fold(f32::MAX, f32::min);
+                d * w
+            })
+            .collect();
+        let total: f32 = dists.iter().sum();
+        if total <= 0.0 {
+            break;
+        }
+
+        let mut target = rng.gen::<f32>() * total;
+        let mut chosen = points.len() - 1;
+        for (i, d) in dists.iter().enumerate() {
+            if target < *d {
+                chosen = i;
+                break;
             }
-        } else if path.is_file() && is_image_file(&path) {
-            // Files directly in root go to "Uncategorized"
-            categories
-                .entry("Uncategorized".to_string())
-                .or_default()
-                .push(path);
+            target -= d;
         }
+        centers.push(points[chosen]);
     }
+
+    centers
 }
 
-fn is_image_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|e| e.to_str())
-        .map(|ext| matches!(ext.to_lowercase().as_str(), "png" | "jpg" | "jpeg" | "webp"))
-        .unwrap_or(false)
+fn nearest_center(p: &[f32; 3], centers: &[[f32; 3]]) -> usize {
+    centers
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, lab_distance_sq(p, c)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
 }
 
-fn capitalize_first(s: &str) -> String {
-    let mut chars = s.chars();
-    match chars.next() {
-        None => String::new(),
-        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+fn lab_distance_sq(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+fn u8_to_rgb(c: [u8; 3]) -> [f32; 3] {
+    [
+        c[0] as f32 / 255.0,
+        c[1] as f32 / 255.0,
+        c[2] as f32 / 255.0,
+    ]
+}
+
+/// Convert an sRGB-encoded channel (0–1) to linear light.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How color values are encoded. Palettes, swatches and exported images are
+/// always sRGB; linear light is for arithmetic where mixing gamma-encoded
+/// values gives muddy, too-dark results (gradients between colors), and
+/// OkLab keeps the midpoints between very different hues vivid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Gamma-encoded, as stored in images; blending here is the original
+    /// behavior
+    #[default]
+    Srgb,
+    /// Linear light, via the sRGB transfer function
+    Linear,
+    /// Björn Ottosson's perceptual OkLab: L (0–1), a and b (about ±0.4)
+    Oklab,
+}
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 3] = [ColorSpace::Srgb, ColorSpace::Linear, ColorSpace::Oklab];
+
+    /// Name used on the command line and in embedded metadata
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::Linear => "linear",
+            ColorSpace::Oklab => "oklab",
+        }
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "sRGB",
+            ColorSpace::Linear => "Linear Light",
+            ColorSpace::Oklab => "OkLab",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|space| space.name() == s.to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown color space '{}', expected srgb, linear or oklab",
+                    s
+                )
+            })
+    }
+
+    /// Convert an sRGB color (0–1) into this space.
+    pub fn decode(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorSpace::Srgb => rgb,
+            ColorSpace::Linear => rgb.map(|c| srgb_to_linear(c.clamp(0.0, 1.0))),
+            ColorSpace::Oklab => rgb_to_oklab(rgb),
+        }
+    }
+
+    /// Convert a color in this space back to sRGB.
+    pub fn encode(self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ColorSpace::Srgb => rgb,
+            ColorSpace::Linear => rgb.map(|c| linear_to_srgb(c.clamp(0.0, 1.0))),
+            ColorSpace::Oklab => oklab_to_rgb(rgb),
+        }
+    }
+}
+
+/// The four working palette colors plus a per-slot lock.
+/// Locks only exist while the app is open; saved palettes store colors only.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteSlots {
+    pub colors: [[f32; 3]; 4],
+    pub locked: [bool; 4],
+}
+
+impl PaletteSlots {
+    pub fn new(colors: [[f32; 3]; 4], locked: [bool; 4]) -> Self {
+        Self { colors, locked }
+    }
+
+    /// Replace the unlocked colors with `colors`; locked slots keep theirs.
+    /// Every palette regeneration (picking, importing, transforming) goes
+    /// through here.
+    pub fn regenerate(&mut self, colors: &[[f32; 3]; 4]) {
+        for (i, color) in colors.iter().enumerate() {
+            if !self.locked[i] {
+                self.colors[i] = *color;
+            }
+        }
+    }
+}
+
+/// Color harmony schemes for generating a palette from one base color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HarmonyScheme {
+    Complementary,
+    Analogous,
+    Triadic,
+    Monochromatic,
+}
+
+impl HarmonyScheme {
+    /// Stable identifier, used as the menu action target
+    pub fn id(self) -> &'static str {
+        match self {
+            HarmonyScheme::Complementary => "complementary",
+            HarmonyScheme::Analogous => "analogous",
+            HarmonyScheme::Triadic => "triadic",
+            HarmonyScheme::Monochromatic => "monochromatic",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::all().into_iter().find(|s| s.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            HarmonyScheme::Complementary => "Complementary",
+            HarmonyScheme::Analogous => "Analogous",
+            HarmonyScheme::Triadic => "Triadic",
+            HarmonyScheme::Monochromatic => "Monochromatic",
+        }
+    }
+
+    pub fn all() -> [Self; 4] {
+        [
+            HarmonyScheme::Complementary,
+            HarmonyScheme::Analogous,
+            HarmonyScheme::Triadic,
+            HarmonyScheme::Monochromatic,
+        ]
+    }
+}
+
+/// Generate a 4-color palette from `base` by rotating its hue and varying
+/// lightness in HSL space. The base color is always one of the four, and the
+/// result can be passed straight to `save_palette_image`.
+pub fn generate_harmony(base: [f32; 3], scheme: HarmonyScheme) -> [[f32; 3]; 4] {
+    let [h, s, l] = rgb_to_hsl(base);
+    // (hue offset in degrees, lightness offset) for each slot
+    let offsets: [(f32, f32); 4] = match scheme {
+        HarmonyScheme::Complementary => [(0.0, -0.15), (0.0, 0.0), (180.0, 0.0), (180.0, 0.15)],
+        HarmonyScheme::Analogous => [(-30.0, 0.0), (0.0, 0.0), (30.0, 0.0), (60.0, 0.0)],
+        HarmonyScheme::Triadic => [(0.0, 0.0), (120.0, 0.0), (240.0, 0.0), (0.0, 0.2)],
+        HarmonyScheme::Monochromatic => [(0.0, -0.3), (0.0, -0.1), (0.0, 0.0), (0.0, 0.2)],
+    };
+    offsets.map(|(dh, dl)| {
+        if dh == 0.0 && dl == 0.0 {
+            base
+        } else {
+            hsl_to_rgb([h + dh, s, (l + dl).clamp(0.0, 1.0)])
+        }
+    })
+}
+
+/// Convert an RGB color (0–1) to HSL: hue in degrees [0, 360), saturation
+/// and lightness 0–1. Grays have hue and saturation 0.
+pub fn rgb_to_hsl(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let delta = max - min;
+    if delta <= f32::EPSILON {
+        return [0.0, 0.0, l];
+    }
+
+    let s = delta / (1.0 - (2.0 * l - 1.0).abs());
+    let h = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    [h.rem_euclid(360.0), s.clamp(0.0, 1.0), l]
+}
+
+/// Convert HSL (hue in degrees, any value wraps; saturation and lightness
+/// 0–1) to RGB (0–1).
+pub fn hsl_to_rgb(hsl: [f32; 3]) -> [f32; 3] {
+    let h = hsl[0].rem_euclid(360.0);
+    let s = hsl[1].clamp(0.0, 1.0);
+    let l = hsl[2].clamp(0.0, 1.0);
+
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r + m, g + m, b + m]
+}
+
+/// Shift hue, saturation and lightness of every color.
+/// `dh` is an additive hue rotation in degrees (wrapping at 360); `ds` and
+/// `dl` are added to saturation and lightness and clamped to 0–1.
+pub fn adjust_palette_hsl(colors: &[[f32; 3]], dh: f32, ds: f32, dl: f32) -> Vec<[f32; 3]> {
+    colors
+        .iter()
+        .map(|&c| {
+            let [h, s, l] = rgb_to_hsl(c);
+            let rgb = hsl_to_rgb([h + dh, (s + ds).clamp(0.0, 1.0), (l + dl).clamp(0.0, 1.0)]);
+            rgb.map(|v| v.clamp(0.0, 1.0))
+        })
+        .collect()
+}
+
+/// White point the temperature adjustment is measured from, in kelvin
+const NEUTRAL_TEMPERATURE: f32 = 6500.0;
+
+/// Approximate sRGB color of a blackbody at `kelvin` (Tanner Helland's fit,
+/// valid for roughly 1000–40000 K).
+fn blackbody_rgb(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (t - 60.0).powf(-0.13320476)
+    };
+    let g = if t <= 66.0 {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12217 * (t - 60.0).powf(-0.075514846)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (t - 10.0).ln() - 305.04478
+    };
+    [r, g, b].map(|v| (v / 255.0).clamp(0.0, 1.0))
+}
+
+/// Warm or cool every color like a white-balance slider: positive
+/// `kelvin_shift` tints toward the light of a cooler-burning (warmer,
+/// redder) blackbody, negative toward a hotter (bluer) one. Channels are
+/// scaled in linear light relative to 6500 K, keeping luminance roughly
+/// unchanged, then clamped to 0–1.
+pub fn adjust_temperature(colors: &[[f32; 3]], kelvin_shift: f32) -> Vec<[f32; 3]> {
+    if kelvin_shift == 0.0 {
+        return colors.to_vec();
+    }
+    let neutral = blackbody_rgb(NEUTRAL_TEMPERATURE).map(srgb_to_linear);
+    let tint = blackbody_rgb(NEUTRAL_TEMPERATURE - kelvin_shift).map(srgb_to_linear);
+    let mut gain = [0, 1, 2].map(|i| tint[i] / neutral[i].max(1e-6));
+    // Rec. 709 luminance of the gain, so the tint doesn't brighten or darken
+    let luma = 0.2126 * gain[0] + 0.7152 * gain[1] + 0.0722 * gain[2];
+    gain = gain.map(|g| g / luma.max(1e-6));
+    colors
+        .iter()
+        .map(|&c| {
+            let linear = ColorSpace::Linear.decode(c);
+            ColorSpace::Linear.encode([0, 1, 2].map(|i| linear[i] * gain[i]))
+        })
+        .collect()
+}
+
+/// Brightness and contrast of every channel: `contrast` scales the distance
+/// from mid-gray (0.5), 1.0 leaving it unchanged, and `brightness` (-1 to 1)
+/// is then added. Results are clamped to 0–1; brightness 0 with contrast 1
+/// returns the colors as they are.
+pub fn apply_brightness_contrast(
+    colors: &[[f32; 3]],
+    brightness: f32,
+    contrast: f32,
+) -> Vec<[f32; 3]> {
+    if brightness == 0.0 && contrast == 1.0 {
+        return colors.to_vec();
+    }
+    let contrast = contrast.max(0.0);
+    colors
+        .iter()
+        .map(|c| c.map(|v| ((v - 0.5) * contrast + 0.5 + brightness).clamp(0.0, 1.0)))
+        .collect()
+}
+
+/// Mix two palettes slot by slot in `space`, `t` = 0 giving `a` and 1
+/// giving `b`. `a` is sRGB, `b` is already in `space` (as returned by
+/// [`extract_colors_in`]) and the result is sRGB, as long as the shorter
+/// palette.
+pub fn blend_palettes(a: &[[f32; 3]], b: &[[f32; 3]], t: f32, space: ColorSpace) -> Vec<[f32; 3]> {
+    let t = t.clamp(0.0, 1.0);
+    a.iter()
+        .zip(b)
+        .map(|(&from, &to)| {
+            let from = space.decode(from);
+            space.encode([0, 1, 2].map(|i| from[i] + (to[i] - from[i]) * t))
+        })
+        .collect()
+}
+
+/// Shift the CIE Lab lightness of each color by `delta` (L ranges 0–100),
+/// keeping hue and chroma. Used to derive light and dark wallpaper variants.
+pub fn adjust_lightness(colors: &[[f32; 3]; 4], delta: f32) -> [[f32; 3]; 4] {
+    colors.map(|c| {
+        let mut lab = rgb_to_lab(c);
+        lab[0] = (lab[0] + delta).clamp(0.0, 100.0);
+        lab_to_rgb(lab)
+    })
+}
+
+/// Lab lightness added to the palette for the light-style wallpaper
+pub const LIGHT_VARIANT_DELTA: f32 = 15.0;
+
+/// Which desktop style a wallpaper of a light/dark pair is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Light,
+    Dark,
+}
+
+impl Variant {
+    /// Name used in the rendered file names
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Light => "light",
+            Variant::Dark => "dark",
+        }
+    }
+
+    /// The palette this variant is rendered with: the dark style uses it
+    /// as is, the light style lightened by [`LIGHT_VARIANT_DELTA`].
+    pub fn apply(self, colors: &[[f32; 3]; 4]) -> [[f32; 3]; 4] {
+        match self {
+            Variant::Light => adjust_lightness(colors, LIGHT_VARIANT_DELTA),
+            Variant::Dark => *colors,
+        }
+    }
+}
+
+/// Invert every channel (`1.0 - c`), e.g. to try a light palette as dark.
+pub fn invert_palette(colors: &[[f32; 3]; 4]) -> [[f32; 3]; 4] {
+    colors.map(|c| c.map(|v| 1.0 - v.clamp(0.0, 1.0)))
+}
+
+/// Desaturate every color to the gray of the same WCAG relative luminance,
+/// so the palette keeps its brightness ordering.
+pub fn grayscale_palette(colors: &[[f32; 3]; 4]) -> [[f32; 3]; 4] {
+    colors.map(|c| [linear_to_srgb(relative_luminance(c)); 3])
+}
+
+/// Overall lightness a random palette should aim for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeLightness {
+    Light,
+    Dark,
+}
+
+/// Constraints for [`random_palette`]. The default allows anything.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PaletteConstraint {
+    /// RNG seed; the same seed and constraints always give the same palette
+    pub seed: u64,
+    /// Minimum WCAG contrast ratio between neighbouring colors (1.0 or less
+    /// disables the check)
+    pub min_contrast: f32,
+    /// Keep every color light or dark, e.g. for a light or dark desktop theme
+    pub lightness: Option<ThemeLightness>,
+    /// Hue in degrees the palette is built around; random if `None`
+    pub base_hue: Option<f32>,
+}
+
+/// Candidates tried per color before settling for the best contrast found.
+const RANDOM_ATTEMPTS: usize = 64;
+
+/// Generate a palette satisfying `constraint` as far as possible.
+///
+/// Colors are picked in HSL: hues step away from the base hue (staying
+/// close to it when the hue is locked), lightness stays within the theme's
+/// range. Each color is re-rolled until it meets the contrast requirement
+/// with the previous one; if that's impossible (say, a high ratio within a
+/// dark-only range) the highest-contrast candidate is used.
+pub fn random_palette(constraint: PaletteConstraint) -> [[f32; 3]; 4] {
+    let mut rng = ChaCha8Rng::seed_from_u64(constraint.seed);
+    let base_hue = constraint
+        .base_hue
+        .unwrap_or_else(|| rng.gen_range(0.0..360.0));
+    let hue_step = if constraint.base_hue.is_some() {
+        rng.gen_range(0.0..30.0)
+    } else {
+        rng.gen_range(20.0..120.0)
+    };
+    let lightness_range = match constraint.lightness {
+        Some(ThemeLightness::Light) => 0.6..0.92,
+        Some(ThemeLightness::Dark) => 0.08..0.45,
+        None => 0.12..0.88,
+    };
+
+    let mut colors = [[0.0f32; 3]; 4];
+    for i in 0..colors.len() {
+        let hue = base_hue + hue_step * i as f32;
+        let mut best: Option<([f32; 3], f32)> = None;
+        for _ in 0..RANDOM_ATTEMPTS {
+            let jitter = if i == 0 {
+                0.0
+            } else {
+                rng.gen_range(-10.0..10.0)
+            };
+            let candidate = hsl_to_rgb([
+                hue + jitter,
+                rng.gen_range(0.35..0.9),
+                rng.gen_range(lightness_range.clone()),
+            ]);
+            let contrast = if i == 0 {
+                f32::INFINITY
+            } else {
+                contrast_ratio(candidate, colors[i - 1])
+            };
+            if best.is_none_or(|(_, c)| contrast > c) {
+                best = Some((candidate, contrast));
+            }
+            if contrast >= constraint.min_contrast {
+                break;
+            }
+        }
+        colors[i] = best.map(|(c, _)| c).unwrap_or_default();
+    }
+    colors
+}
+
+/// WCAG contrast ratio between two sRGB colors, from 1 (identical) to 21
+pub fn contrast_ratio(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// WCAG AA minimum contrast for normal-size text.
+pub const WCAG_AA_CONTRAST: f32 = 4.5;
+
+/// Contrast ratio of every pair of palette colors; `matrix[i][j]` compares
+/// colors `i` and `j`, so the matrix is symmetric with 1.0 on the diagonal.
+pub fn palette_contrast_matrix(colors: &[[f32; 3]; 4]) -> [[f32; 4]; 4] {
+    colors.map(|a| colors.map(|b| contrast_ratio(a, b)))
+}
+
+/// Largest CIE76 ΔE at which two colors count as the same; differences
+/// below 1 are generally invisible side by side.
+pub const IDENTICAL_DELTA_E: f32 = 1.0;
+
+/// How one palette slot differs between two palettes, from [`diff_palettes`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorDelta {
+    pub a: [f32; 3],
+    pub b: [f32; 3],
+    /// `b - a` per RGB channel
+    pub rgb: [f32; 3],
+    /// Perceptual difference (CIE76 ΔE: distance in Lab)
+    pub delta_e: f32,
+    /// Whether `delta_e` is within [`IDENTICAL_DELTA_E`]
+    pub identical: bool,
+}
+
+/// Compare two palettes slot by slot. Slots past the end of the shorter
+/// palette are left out.
+pub fn diff_palettes(a: &[[f32; 3]], b: &[[f32; 3]]) -> Vec<ColorDelta> {
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| {
+            let delta_e = lab_distance_sq(&rgb_to_lab(a), &rgb_to_lab(b)).sqrt();
+            ColorDelta {
+                a,
+                b,
+                rgb: [b[0] - a[0], b[1] - a[1], b[2] - a[2]],
+                delta_e,
+                identical: delta_e <= IDENTICAL_DELTA_E,
+            }
+        })
+        .collect()
+}
+
+/// Neighbouring colors closer than this (CIE76 ΔE) blur into one wide band
+pub const SIMILAR_ADJACENT_DELTA_E: f32 = 10.0;
+
+/// A palette whose most different pair is closer than this looks flat
+pub const LOW_VARIETY_DELTA_E: f32 = 20.0;
+
+/// Something about a palette that will make renders look dull
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaletteWarning {
+    /// Colors `index` and `index + 1` are only `delta_e` apart
+    SimilarAdjacent { index: usize, delta_e: f32 },
+    /// No two colors are more than `max_delta_e` apart
+    LowVariety { max_delta_e: f32 },
+}
+
+impl PaletteWarning {
+    /// A short explanation for the UI, with colors numbered from 1
+    pub fn message(self) -> String {
+        match self {
+            Self::SimilarAdjacent { index, delta_e } => format!(
+                "Colors {} and {} are nearly identical (ΔE {:.1}), so gradients will look flat",
+                index + 1,
+                index + 2,
+                delta_e
+            ),
+            Self::LowVariety { max_delta_e } => format!(
+                "All colors are very similar (ΔE {:.1} at most), so renders will look dull",
+                max_delta_e
+            ),
+        }
+    }
+}
+
+/// Flag neighbouring colors below [`SIMILAR_ADJACENT_DELTA_E`] and palettes
+/// whose colors all lie within [`LOW_VARIETY_DELTA_E`] of each other. A
+/// low-variety palette is reported on its own, since every neighbouring pair
+/// is then similar too.
+pub fn palette_warnings(colors: &[[f32; 3]]) -> Vec<PaletteWarning> {
+    let labs: Vec<[f32; 3]> = colors.iter().map(|&c| rgb_to_lab(c)).collect();
+    let delta_e = |i: usize, j: usize| lab_distance_sq(&labs[i], &labs[j]).sqrt();
+
+    let max_delta_e = (0..labs.len())
+        .flat_map(|i| (i + 1..labs.len()).map(move |j| (i, j)))
+        .map(|(i, j)| delta_e(i, j))
+        .fold(0.0, f32::max);
+    if labs.len() > 1 && max_delta_e < LOW_VARIETY_DELTA_E {
+        return vec![PaletteWarning::LowVariety { max_delta_e }];
+    }
+
+    (0..labs.len().saturating_sub(1))
+        .map(|i| (i, delta_e(i, i + 1)))
+        .filter(|&(_, d)| d < SIMILAR_ADJACENT_DELTA_E)
+        .map(|(index, delta_e)| PaletteWarning::SimilarAdjacent { index, delta_e })
+        .collect()
+}
+
+/// CSS named colors (duplicates like Aqua/Cyan and Gray/Grey listed once),
+/// with display names for swatch tooltips
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("Alice Blue", [0xF0, 0xF8, 0xFF]),
+    ("Antique White", [0xFA, 0xEB, 0xD7]),
+    ("Aquamarine", [0x7F, 0xFF, 0xD4]),
+    ("Azure", [0xF0, 0xFF, 0xFF]),
+    ("Beige", [0xF5, 0xF5, 0xDC]),
+    ("Bisque", [0xFF, 0xE4, 0xC4]),
+    ("Black", [0x00, 0x00, 0x00]),
+    ("Blanched Almond", [0xFF, 0xEB, 0xCD]),
+    ("Blue", [0x00, 0x00, 0xFF]),
+    ("Blue Violet", [0x8A, 0x2B, 0xE2]),
+    ("Brown", [0xA5, 0x2A, 0x2A]),
+    ("Burlywood", [0xDE, 0xB8, 0x87]),
+    ("Cadet Blue", [0x5F, 0x9E, 0xA0]),
+    ("Chartreuse", [0x7F, 0xFF, 0x00]),
+    ("Chocolate", [0xD2, 0x69, 0x1E]),
+    ("Coral", [0xFF, 0x7F, 0x50]),
+    ("Cornflower Blue", [0x64, 0x95, 0xED]),
+    ("Cornsilk", [0xFF, 0xF8, 0xDC]),
+    ("Crimson", [0xDC, 0x14, 0x3C]),
+    ("Cyan", [0x00, 0xFF, 0xFF]),
+    ("Dark Blue", [0x00, 0x00, 0x8B]),
+    ("Dark Cyan", [0x00, 0x8B, 0x8B]),
+    ("Dark Goldenrod", [0xB8, 0x86, 0x0B]),
+    ("Dark Gray", [0xA9, 0xA9, 0xA9]),
+    ("Dark Green", [0x00, 0x64, 0x00]),
+    ("Dark Khaki", [0xBD, 0xB7, 0x6B]),
+    ("Dark Magenta", [0x8B, 0x00, 0x8B]),
+    ("Dark Olive Green", [0x55, 0x6B, 0x2F]),
+    ("Dark Orange", [0xFF, 0x8C, 0x00]),
+    ("Dark Orchid", [0x99, 0x32, 0xCC]),
+    ("Dark Red", [0x8B, 0x00, 0x00]),
+    ("Dark Salmon", [0xE9, 0x96, 0x7A]),
+    ("Dark Sea Green", [0x8F, 0xBC, 0x8F]),
+    ("Dark Slate Blue", [0x48, 0x3D, 0x8B]),
+    ("Dark Slate Gray", [0x2F, 0x4F, 0x4F]),
+    ("Dark Turquoise", [0x00, 0xCE, 0xD1]),
+    ("Dark Violet", [0x94, 0x00, 0xD3]),
+    ("Deep Pink", [0xFF, 0x14, 0x93]),
+    ("Deep Sky Blue", [0x00, 0xBF, 0xFF]),
+    ("Dim Gray", [0x69, 0x69, 0x69]),
+    ("Dodger Blue", [0x1E, 0x90, 0xFF]),
+    ("Firebrick", [0xB2, 0x22, 0x22]),
+    ("Floral White", [0xFF, 0xFA, 0xF0]),
+    ("Forest Green", [0x22, 0x8B, 0x22]),
+    ("Gainsboro", [0xDC, 0xDC, 0xDC]),
+    ("Ghost White", [0xF8, 0xF8, 0xFF]),
+    ("Gold", [0xFF, 0xD7, 0x00]),
+    ("Goldenrod", [0xDA, 0xA5, 0x20]),
+    ("Gray", [0x80, 0x80, 0x80]),
+    ("Green", [0x00, 0x80, 0x00]),
+    ("Green Yellow", [0xAD, 0xFF, 0x2F]),
+    ("Honeydew", [0xF0, 0xFF, 0xF0]),
+    ("Hot Pink", [0xFF, 0x69, 0xB4]),
+    ("Indian Red", [0xCD, 0x5C, 0x5C]),
+    ("Indigo", [0x4B, 0x00, 0x82]),
+    ("Ivory", [0xFF, 0xFF, 0xF0]),
+    ("Khaki", [0xF0, 0xE6, 0x8C]),
+    ("Lavender", [0xE6, 0xE6, 0xFA]),
+    ("Lavender Blush", [0xFF, 0xF0, 0xF5]),
+    ("Lawn Green", [0x7C, 0xFC, 0x00]),
+    ("Lemon Chiffon", [0xFF, 0xFA, 0xCD]),
+    ("Light Blue", [0xAD, 0xD8, 0xE6]),
+    ("Light Coral", [0xF0, 0x80, 0x80]),
+    ("Light Cyan", [0xE0, 0xFF, 0xFF]),
+    ("Light Goldenrod Yellow", [0xFA, 0xFA, 0xD2]),
+    ("Light Gray", [0xD3, 0xD3, 0xD3]),
+    ("Light Green", [0x90, 0xEE, 0x90]),
+    ("Light Pink", [0xFF, 0xB6, 0xC1]),
+    ("Light Salmon", [0xFF, 0xA0, 0x7A]),
+    ("Light Sea Green", [0x20, 0xB2, 0xAA]),
+    ("Light Sky Blue", [0x87, 0xCE, 0xFA]),
+    ("Light Slate Gray", [0x77, 0x88, 0x99]),
+    ("Light Steel Blue", [0xB0, 0xC4, 0xDE]),
+    ("Light Yellow", [0xFF, 0xFF, 0xE0]),
+    ("Lime", [0x00, 0xFF, 0x00]),
+    ("Lime Green", [0x32, 0xCD, 0x32]),
+    ("Linen", [0xFA, 0xF0, 0xE6]),
+    ("Magenta", [0xFF, 0x00, 0xFF]),
+    ("Maroon", [0x80, 0x00, 0x00]),
+    ("Medium Aquamarine", [0x66, 0xCD, 0xAA]),
+    ("Medium Blue", [0x00, 0x00, 0xCD]),
+    ("Medium Orchid", [0xBA, 0x55, 0xD3]),
+    ("Medium Purple", [0x93, 0x70, 0xDB]),
+    ("Medium Sea Green", [0x3C, 0xB3, 0x71]),
+    ("Medium Slate Blue", [0x7B, 0x68, 0xEE]),
+    ("Medium Spring Green", [0x00, 0xFA, 0x9A]),
+    ("Medium Turquoise", [0x48, 0xD1, 0xCC]),
+    ("Medium Violet Red", [0xC7, 0x15, 0x85]),
+    ("Midnight Blue", [0x19, 0x19, 0x70]),
+    ("Mint Cream", [0xF5, 0xFF, 0xFA]),
+    ("Misty Rose", [0xFF, 0xE4, 0xE1]),
+    ("Moccasin", [0xFF, 0xE4, 0xB5]),
+    ("Navajo White", [0xFF, 0xDE, 0xAD]),
+    ("Navy", [0x00, 0x00, 0x80]),
+    ("Old Lace", [0xFD, 0xF5, 0xE6]),
+    ("Olive", [0x80, 0x80, 0x00]),
+    ("Olive Drab", [0x6B, 0x8E, 0x23]),
+    ("Orange", [0xFF, 0xA5, 0x00]),
+    ("Orange Red", [0xFF, 0x45, 0x00]),
+    ("Orchid", [0xDA, 0x70, 0xD6]),
+    ("Pale Goldenrod", [0xEE, 0xE8, 0xAA]),
+    ("Pale Green", [0x98, 0xFB, 0x98]),
+    ("Pale Turquoise", [0xAF, 0xEE, 0xEE]),
+    ("Pale Violet Red", [0xDB, 0x70, 0x93]),
+    ("Papaya Whip", [0xFF, 0xEF, 0xD5]),
+    ("Peach Puff", [0xFF, 0xDA, 0xB9]),
+    ("Peru", [0xCD, 0x85, 0x3F]),
+    ("Pink", [0xFF, 0xC0, 0xCB]),
+    ("Plum", [0xDD, 0xA0, 0xDD]),
+    ("Powder Blue", [0xB0, 0xE0, 0xE6]),
+    ("Purple", [0x80, 0x00, 0x80]),
+    ("Rebecca Purple", [0x66, 0x33, 0x99]),
+    ("Red", [0xFF, 0x00, 0x00]),
+    ("Rosy Brown", [0xBC, 0x8F, 0x8F]),
+    ("Royal Blue", [0x41, 0x69, 0xE1]),
+    ("Saddle Brown", [0x8B, 0x45, 0x13]),
+    ("Salmon", [0xFA, 0x80, 0x72]),
+    ("Sandy Brown", [0xF4, 0xA4, 0x60]),
+    ("Sea Green", [0x2E, 0x8B, 0x57]),
+    ("Seashell", [0xFF, 0xF5, 0xEE]),
+    ("Sienna", [0xA0, 0x52, 0x2D]),
+    ("Silver", [0xC0, 0xC0, 0xC0]),
+    ("Sky Blue", [0x87, 0xCE, 0xEB]),
+    ("Slate Blue", [0x6A, 0x5A, 0xCD]),
+    ("Slate Gray", [0x70, 0x80, 0x90]),
+    ("Snow", [0xFF, 0xFA, 0xFA]),
+    ("Spring Green", [0x00, 0xFF, 0x7F]),
+    ("Steel Blue", [0x46, 0x82, 0xB4]),
+    ("Tan", [0xD2, 0xB4, 0x8C]),
+    ("Teal", [0x00, 0x80, 0x80]),
+    ("Thistle", [0xD8, 0xBF, 0xD8]),
+    ("Tomato", [0xFF, 0x63, 0x47]),
+    ("Turquoise", [0x40, 0xE0, 0xD0]),
+    ("Violet", [0xEE, 0x82, 0xEE]),
+    ("Wheat", [0xF5, 0xDE, 0xB3]),
+    ("White", [0xFF, 0xFF, 0xFF]),
+    ("White Smoke", [0xF5, 0xF5, 0xF5]),
+    ("Yellow", [0xFF, 0xFF, 0x00]),
+    ("Yellow Green", [0x9A, 0xCD, 0x32]),
+];
+
+/// Name of the CSS color closest to `color` by ΔE (CIE76) in Lab space.
+/// Ties go to the entry listed first, so the result is always the same.
+pub fn nearest_color_name(color: [f32; 3]) -> &'static str {
+    let lab = rgb_to_lab(color);
+    let mut best = ("", f32::INFINITY);
+    for &(name, rgb) in NAMED_COLORS {
+        let distance = lab_distance_sq(&lab, &rgb_to_lab(u8_to_rgb(rgb)));
+        if distance < best.1 {
+            best = (name, distance);
+        }
+    }
+    best.0
+}
+
+/// Rearrange a palette so slot `i` gets `colors[new_indices[i]]`.
+///
+/// Generic so per-slot state (like the window's lock toggles) can be moved
+/// with the same order. Fails unless `new_indices` is a permutation of 0–3.
+pub fn reorder_colors<T: Copy>(colors: &[T; 4], new_indices: [usize; 4]) -> Result<[T; 4], String> {
+    let mut seen = [false; 4];
+    for &i in &new_indices {
+        if i >= 4 || std::mem::replace(&mut seen[i], true) {
+            return Err(format!("Invalid color order: {:?}", new_indices));
+        }
+    }
+    Ok(new_indices.map(|i| colors[i]))
+}
+
+/// What [`sort_colors_by`] orders a palette by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Hue,
+    Luminance,
+    Saturation,
+}
+
+impl SortKey {
+    /// Stable identifier, used as the menu action target
+    pub fn id(self) -> &'static str {
+        match self {
+            SortKey::Hue => "hue",
+            SortKey::Luminance => "luminance",
+            SortKey::Saturation => "saturation",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::all().into_iter().find(|k| k.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Hue => "By Hue",
+            SortKey::Luminance => "Dark to Light",
+            SortKey::Saturation => "By Saturation",
+        }
+    }
+
+    pub fn all() -> [Self; 3] {
+        [SortKey::Hue, SortKey::Luminance, SortKey::Saturation]
+    }
+}
+
+/// Sort a palette in ascending order of `key` (ties keep their order).
+///
+/// Also returns the order applied, in the form [`reorder_colors`] takes,
+/// so callers can rearrange per-slot state to match.
+pub fn sort_colors_by(colors: &[[f32; 3]; 4], key: SortKey) -> ([[f32; 3]; 4], [usize; 4]) {
+    let value = |c: [f32; 3]| match key {
+        SortKey::Hue => rgb_to_hsl(c)[0],
+        SortKey::Luminance => relative_luminance(c),
+        SortKey::Saturation => rgb_to_hsl(c)[1],
+    };
+    let mut order = [0, 1, 2, 3];
+    order.sort_by(|&a, &b| value(colors[a]).total_cmp(&value(colors[b])));
+    (order.map(|i| colors[i]), order)
+}
+
+/// The order of `colors` with the least total ΔE (CIE76) between
+/// neighbours, so a gradient through them changes as little as possible
+/// from stop to stop. Every permutation is tried; of a path and its reverse,
+/// and of other ties, the first in lexicographic order wins.
+///
+/// Returned in the form [`reorder_colors`] takes.
+pub fn optimal_gradient_order(colors: &[[f32; 3]; 4]) -> [usize; 4] {
+    let lab = colors.map(rgb_to_lab);
+    let distance = |a: usize, b: usize| lab_distance_sq(&lab[a], &lab[b]).sqrt();
+    let mut best = ([0, 1, 2, 3], f32::INFINITY);
+    for a in 0..4 {
+        for b in (0..4).filter(|&b| b != a) {
+            for c in (0..4).filter(|&c| c != a && c != b) {
+                let d = 6 - a - b - c;
+                let length = distance(a, b) + distance(b, c) + distance(c, d);
+                if length < best.1 {
+                    best = ([a, b, c, d], length);
+                }
+            }
+        }
+    }
+    best.0
+}
+
+/// Relative luminance of an sRGB color as defined by WCAG 2.x
+fn relative_luminance(c: [f32; 3]) -> f32 {
+    let [r, g, b] = c.map(srgb_to_linear);
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// Color vision deficiencies that [`simulate_cvd`] can preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CvdType {
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl CvdType {
+    pub fn label(self) -> &'static str {
+        match self {
+            CvdType::Protanopia => "Protanopia",
+            CvdType::Deuteranopia => "Deuteranopia",
+            CvdType::Tritanopia => "Tritanopia",
+        }
+    }
+
+    pub fn all() -> [Self; 3] {
+        [
+            CvdType::Protanopia,
+            CvdType::Deuteranopia,
+            CvdType::Tritanopia,
+        ]
+    }
+}
+
+/// Linear RGB to LMS cone space (Viénot, Brettel & Mollon 1999).
+const RGB_TO_LMS: [[f32; 3]; 3] = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+
+/// Inverse of [`RGB_TO_LMS`].
+const LMS_TO_RGB: [[f32; 3]; 3] = [
+    [0.080_944_45, -0.130_504_4, 0.116_721_1],
+    [-0.010_248_53, 0.054_019_33, -0.113_614_7],
+    [-0.000_365_297, -0.004_121_615, 0.693_511_4],
+];
+
+fn mat3_mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Simulate how `colors` (sRGB, 0–1) look with a color vision deficiency.
+///
+/// Colors are linearized, converted to LMS, and the missing cone's response
+/// is rebuilt from the other two (dichromat projection), then converted back
+/// to sRGB and clamped.
+pub fn simulate_cvd(colors: &[[f32; 3]], kind: CvdType) -> Vec<[f32; 3]> {
+    colors
+        .iter()
+        .map(|c| {
+            let [l, m, s] = mat3_mul(&RGB_TO_LMS, c.map(srgb_to_linear));
+            let lms = match kind {
+                CvdType::Protanopia => [2.02344 * m - 2.52581 * s, m, s],
+                CvdType::Deuteranopia => [l, 0.494207 * l + 1.24827 * s, s],
+                CvdType::Tritanopia => [l, m, -0.395913 * l + 0.801109 * m],
+            };
+            mat3_mul(&LMS_TO_RGB, lms).map(|v| linear_to_srgb(v.clamp(0.0, 1.0)))
+        })
+        .collect()
+}
+
+/// Convert a linear-light channel (0–1) to sRGB encoding.
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Linear sRGB to OkLab's cone responses, and their inverse
+const OKLAB_RGB_TO_LMS: [[f32; 3]; 3] = [
+    [0.412_221_46, 0.536_332_55, 0.051_445_995],
+    [0.211_903_5, 0.680_699_5, 0.107_396_96],
+    [0.088_302_46, 0.281_718_85, 0.629_978_7],
+];
+const OKLAB_LMS_TO_RGB: [[f32; 3]; 3] = [
+    [4.076_741_7, -3.307_711_6, 0.230_969_94],
+    [-1.268_438, 2.609_757_4, -0.341_319_38],
+    [-0.004_196_086_3, -0.703_418_6, 1.707_614_7],
+];
+
+/// Cube-rooted cone responses to L, a, b, and back
+const OKLAB_LMS_TO_LAB: [[f32; 3]; 3] = [
+    [0.210_454_26, 0.793_617_8, -0.004_072_047],
+    [1.977_998_5, -2.428_592_2, 0.450_593_7],
+    [0.025_904_037, 0.782_771_77, -0.808_675_77],
+];
+const OKLAB_LAB_TO_LMS: [[f32; 3]; 3] = [
+    [1.0, 0.396_337_78, 0.215_803_76],
+    [1.0, -0.105_561_346, -0.063_854_17],
+    [1.0, -0.089_484_18, -1.291_485_5],
+];
+
+/// Convert an sRGB color (0–1) to OkLab.
+fn rgb_to_oklab(rgb: [f32; 3]) -> [f32; 3] {
+    let linear = rgb.map(|c| srgb_to_linear(c.clamp(0.0, 1.0)));
+    mat3_mul(
+        &OKLAB_LMS_TO_LAB,
+        mat3_mul(&OKLAB_RGB_TO_LMS, linear).map(f32::cbrt),
+    )
+}
+
+/// Convert an OkLab color to sRGB (0–1). Colors outside the sRGB gamut are
+/// clamped per channel.
+fn oklab_to_rgb(lab: [f32; 3]) -> [f32; 3] {
+    let lms = mat3_mul(&OKLAB_LAB_TO_LMS, lab).map(|v| v * v * v);
+    mat3_mul(&OKLAB_LMS_TO_RGB, lms).map(|v| linear_to_srgb(v.clamp(0.0, 1.0)))
+}
+
+/// D65 reference white in XYZ.
+const D65_WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+/// Convert an sRGB color (0–1) to CIE Lab (D65).
+fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let r = srgb_to_linear(rgb[0]);
+    let g = srgb_to_linear(rgb[1]);
+    let b = srgb_to_linear(rgb[2]);
+
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / D65_WHITE[0];
+    let y = (0.2126729 * r + 0.7151522 * g + 0.0721750 * b) / D65_WHITE[1];
+    let z = (0.0193339 * r + 0.119_192 * g + 0.9503041 * b) / D65_WHITE[2];
+
+    let f = |t: f32| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Convert a CIE Lab (D65) color back to sRGB (0–1, clamped).
+fn lab_to_rgb(lab: [f32; 3]) -> [f32; 3] {
+    let fy = (lab[0] + 16.0) / 116.0;
+    let fx = fy + lab[1] / 500.0;
+    let fz = fy - lab[2] / 200.0;
+
+    let f_inv = |t: f32| {
+        let t3 = t * t * t;
+        if t3 > 0.008856 {
+            t3
+        } else {
+            (t - 16.0 / 116.0) / 7.787
+        }
+    };
+    let x = f_inv(fx) * D65_WHITE[0];
+    let y = f_inv(fy) * D65_WHITE[1];
+    let z = f_inv(fz) * D65_WHITE[2];
+
+    let r = 3.2404542 * x - 1.5371385 * y - 0.4985314 * z;
+    let g = -0.969_266 * x + 1.8760108 * y + 0.0415560 * z;
+    let b = 0.0556434 * x - 0.2040259 * y + 1.0572252 * z;
+
+    [
+        linear_to_srgb(r.clamp(0.0, 1.0)),
+        linear_to_srgb(g.clamp(0.0, 1.0)),
+        linear_to_srgb(b.clamp(0.0, 1.0)),
+    ]
+}
+
+/// List all palette images organized by category.
+///
+/// Categories are subfolders inside the palette root directories.
+/// Images directly in the root (not in a subfolder) go into an "Uncategorized" category.
+/// Bundled and user-saved palettes are scanned and merged.
+/// Categories are sorted alphabetically; images within each are sorted by filename.
+pub fn list_palette_categories() -> PaletteCategories {
+    let mut categories: PaletteCategories = BTreeMap::new();
+
+    if let Some(dir) = bundled_palettes_dir() {
+        collect_categorized_images(&dir, &mut categories);
+    }
+
+    // Scan user-saved palettes from the sandbox data directory
+    let user_dir = user_palettes_dir();
+    collect_categorized_images(&user_dir, &mut categories);
+
+    // Sort images within each category by filename
+    for images in categories.values_mut() {
+        images.sort_by(|a, b| {
+            a.file_name()
+                .unwrap_or_default()
+                .cmp(b.file_name().unwrap_or_default())
+        });
+    }
+
+    // Favorites keep the order they were starred in
+    let favorites = load_path_list(&favorites_file());
+    if !favorites.is_empty() {
+        categories.insert(FAVORITES_CATEGORY.to_string(), favorites);
+    }
+    let recent = load_path_list(&recent_file());
+    if !recent.is_empty() {
+        categories.insert(RECENT_CATEGORY.to_string(), recent);
+    }
+
+    categories
+}
+
+/// [`list_palette_categories`] on a worker thread, so checking every file in a
+/// large collection doesn't block the UI. `on_done` is called with the result
+/// on the main context.
+pub fn list_palette_categories_async(on_done: impl FnOnce(PaletteCategories) + 'static) {
+    let scan = gio::spawn_blocking(list_palette_categories);
+    glib::spawn_future_local(async move {
+        match scan.await {
+            Ok(categories) => on_done(categories),
+            Err(_) => eprintln!("Palette scan failed: the worker thread panicked"),
+        }
+    });
+}
+
+/// Category names in display order: Recent, then Favorites, then alphabetical.
+pub fn category_names(categories: &PaletteCategories) -> Vec<String> {
+    let mut names: Vec<String> = [RECENT_CATEGORY, FAVORITES_CATEGORY]
+        .into_iter()
+        .filter(|name| categories.contains_key(*name))
+        .map(String::from)
+        .collect();
+    names.extend(
+        categories
+            .keys()
+            .filter(|name| *name != RECENT_CATEGORY && *name != FAVORITES_CATEGORY)
+            .cloned(),
+    );
+    names
+}
+
+/// Star or unstar a palette (bundled or user). Returns whether it's now a
+/// favorite. Favorites are stored as paths, so the file itself isn't moved.
+pub fn toggle_favorite(path: &Path) -> Result<bool, String> {
+    toggle_favorite_in(&favorites_file(), path)
+}
+
+pub fn is_favorite(path: &Path) -> bool {
+    read_path_list(&favorites_file()).iter().any(|p| p == path)
+}
+
+/// Put a palette that was just selected or set as the wallpaper at the front
+/// of the Recent category. Returns the updated list, newest first.
+pub fn record_recent(path: &Path) -> Result<Vec<PathBuf>, String> {
+    record_recent_in(&recent_file(), path)
+}
+
+/// `~/.config/wallrus/favorites.json`: a JSON array of palette paths
+fn favorites_file() -> PathBuf {
+    glib::user_config_dir()
+        .join("wallrus")
+        .join("favorites.json")
+}
+
+/// `~/.config/wallrus/recent.json`: palette paths like favorites, newest first
+fn recent_file() -> PathBuf {
+    glib::user_config_dir().join("wallrus").join("recent.json")
+}
+
+/// The paths in a list file whose files still exist, rewriting the file if
+/// any were pruned.
+fn load_path_list(file: &Path) -> Vec<PathBuf> {
+    let stored = read_path_list(file);
+    let existing: Vec<PathBuf> = stored.iter().filter(|p| p.is_file()).cloned().collect();
+    if existing.len() != stored.len() {
+        if let Err(e) = write_path_list(file, &existing) {
+            eprintln!("Failed to prune {}: {}", file.display(), e);
+        }
+    }
+    existing
+}
+
+fn record_recent_in(file: &Path, path: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut recent = read_path_list(file);
+    recent.retain(|p| p != path);
+    recent.insert(0, path.to_path_buf());
+    recent.truncate(RECENT_LIMIT);
+    write_path_list(file, &recent)?;
+    Ok(recent)
+}
+
+fn toggle_favorite_in(file: &Path, path: &Path) -> Result<bool, String> {
+    let mut favorites = read_path_list(file);
+    let starred = match favorites.iter().position(|p| p == path) {
+        Some(idx) => {
+            favorites.remove(idx);
+            false
+        }
+        None => {
+            favorites.push(path.to_path_buf());
+            true
+        }
+    };
+    write_path_list(file, &favorites)?;
+    Ok(starred)
+}
+
+/// A missing or malformed file reads as an empty list.
+fn read_path_list(file: &Path) -> Vec<PathBuf> {
+    std::fs::read_to_string(file)
+        .ok()
+        .and_then(|contents| parse_json_strings(&contents))
+        .map(|paths| {
+            paths
+                .into_iter()
+                .map(|p| relocate_user_palette(Path::new(&p)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn write_path_list(file: &Path, paths: &[PathBuf]) -> Result<(), String> {
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let entries: Vec<String> = paths
+        .iter()
+        .map(|p| format!("  {}", json::quote(&p.to_string_lossy())))
+        .collect();
+    let contents = format!("[\n{}\n]\n", entries.join(",\n"));
+    std::fs::write(file, contents).map_err(|e| format!("Failed to save {}: {}", file.display(), e))
+}
+
+/// Parse a JSON array of strings, e.g. `["a", "b\"c"]`. Returns `None` for
+/// anything else.
+fn parse_json_strings(input: &str) -> Option<Vec<String>> {
+    Json::parse(input)?.as_strings()
+}
+
+/// Extensions of text palette definitions, listed alongside palette images
+const DEFINITION_EXTENSIONS: &[&str] = &["json", "toml"];
+
+/// Whether `path` is a text palette definition rather than an image.
+pub fn is_palette_definition(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| DEFINITION_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Read the colors of a text palette definition: a JSON object or a TOML
+/// file with a `colors` list of hex strings and an optional `name`, e.g.
+/// `{ "name": "Dusk", "colors": ["#1d2b53", "#7e2553", "#ff004d", "#ffa300"] }`.
+/// The name is informational; palettes are listed and searched by file
+/// name like image palettes. Fields of other types are skipped; TOML tables
+/// are not supported.
+pub fn read_palette_definition(path: &Path) -> Result<Vec<[f32; 3]>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("toml"));
+    let fields = if is_toml {
+        parse_toml_strings(&text)
+    } else {
+        parse_json_object_strings(&text)
+    };
+    let hexes = fields
+        .and_then(|fields| fields.into_iter().find(|(key, _)| key == "colors"))
+        .map(|(_, values)| values)
+        .filter(|values| !values.is_empty())
+        .ok_or_else(|| format!("{} has no list of colors", path.display()))?;
+    hexes
+        .iter()
+        .map(|hex| hex_to_rgb(hex))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Invalid color in {}: {}", path.display(), e))
+}
+
+/// The string and string-list fields of a JSON object, in file order.
+/// Fields of other types, including lists holding anything but strings,
+/// are skipped. Returns `None` for anything that isn't an object.
+fn parse_json_object_strings(input: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let Json::Object(fields) = Json::parse(input)? else {
+        return None;
+    };
+    Some(
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                Json::String(s) => Some((key, vec![s])),
+                value => Some((key, value.as_strings()?)),
+            })
+            .collect(),
+    )
+}
+
+/// The string and string-list `key = value` pairs of a TOML file, in file
+/// order. Other values (numbers, dates, inline tables, lists holding
+/// anything but strings) are skipped. Returns `None` for `[table]` headers
+/// and anything that doesn't parse.
+fn parse_toml_strings(input: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let mut scanner = json::Scanner::new(input);
+    let mut fields = Vec::new();
+    loop {
+        skip_toml_space(&mut scanner);
+        let key = match scanner.peek() {
+            None => return Some(fields),
+            Some('"' | '\'') => toml_string(&mut scanner)?,
+            Some(_) => {
+                let mut key = String::new();
+                while let Some(c) =
+                    scanner.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+                {
+                    key.push(c);
+                }
+                key
+            }
+        };
+        if key.is_empty() {
+            return None;
+        }
+        while scanner.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+        if !scanner.eat('=') {
+            return None;
+        }
+        while scanner.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+        if let Some(values) = toml_value(&mut scanner)? {
+            fields.push((key, values));
+        }
+    }
+}
+
+/// Skip whitespace and `#` comments
+fn skip_toml_space(scanner: &mut json::Scanner) {
+    loop {
+        scanner.skip_space();
+        if !scanner.eat('#') {
+            return;
+        }
+        while scanner.next_if(|&c| c != '\n').is_some() {}
+    }
+}
+
+/// A `"basic"` string with escapes or a `'literal'` one
+fn toml_string(scanner: &mut json::Scanner) -> Option<String> {
+    if !scanner.eat('\'') {
+        return scanner.string();
+    }
+    let mut s = String::new();
+    while let Some(c) = scanner.next_if(|&c| c != '\'') {
+        s.push(c);
+    }
+    scanner.eat('\'').then_some(s)
+}
+
+/// A TOML value: `Some(strings)` for a string (as a one-element list) or a
+/// list of strings, `None` for a value of another type, which is skipped.
+/// Lists may span lines, with comments and a trailing comma.
+fn toml_value(scanner: &mut json::Scanner) -> Option<Option<Vec<String>>> {
+    match scanner.peek()? {
+        '"' | '\'' => toml_string(scanner).map(|s| Some(vec![s])),
+        '[' => {
+            scanner.eat('[');
+            let mut strings = Some(Vec::new());
+            loop {
+                skip_toml_space(scanner);
+                if scanner.eat(']') {
+                    return Some(strings);
+                }
+                if matches!(scanner.peek(), Some('"' | '\'')) {
+                    let s = toml_string(scanner)?;
+                    if let Some(strings) = &mut strings {
+                        strings.push(s);
+                    }
+                } else {
+                    toml_value(scanner)?;
+                    strings = None;
+                }
+                skip_toml_space(scanner);
+                if scanner.eat(']') {
+                    return Some(strings);
+                }
+                if !scanner.eat(',') {
+                    return None;
+                }
+            }
+        }
+        '{' => {
+            let mut depth = 0;
+            loop {
+                match scanner.peek()? {
+                    '"' | '\'' => {
+                        toml_string(scanner)?;
+                        continue;
+                    }
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                scanner.next_if(|_| true);
+                if depth == 0 {
+                    return Some(None);
+                }
+            }
+        }
+        _ => {
+            // Numbers, booleans and dates run to the end of the line
+            while scanner
+                .next_if(|&c| !matches!(c, '\n' | '#' | ',' | ']'))
+                .is_some()
+            {}
+            Some(None)
+        }
+    }
+}
+
+/// Keep only the palettes matching `query`, preserving the category grouping.
+///
+/// Matching is a case-insensitive substring search on palette file names
+/// (without extension); a category whose name matches keeps all of its
+/// palettes. Categories left empty are dropped. An empty query matches
+/// everything.
+pub fn filter_palettes(categories: &PaletteCategories, query: &str) -> PaletteCategories {
+    let query = query.trim().to_lowercase();
+
+    categories
+        .iter()
+        .filter_map(|(name, images)| {
+            if name.to_lowercase().contains(&query) {
+                return Some((name.clone(), images.clone()));
+            }
+            let matching: Vec<PathBuf> = images
+                .iter()
+                .filter(|path| {
+                    path.file_stem()
+                        .and_then(|s| s.to_str())
+                        .is_some_and(|s| s.to_lowercase().contains(&query))
+                })
+                .cloned()
+                .collect();
+            (!matching.is_empty()).then(|| (name.clone(), matching))
+        })
+        .collect()
+}
+
+/// Save colors as a 1xN px palette PNG (one pixel per color, top to
+/// bottom) in the user data directory.
+///
+/// The image is saved under the "Custom" subfolder with a timestamp-based name.
+/// Returns the path of the saved file.
+///
+/// If the Custom category already holds an identical four-color palette,
+/// nothing is written and the existing file's path is returned instead.
+pub fn save_palette_image(colors: &[[f32; 3]]) -> Result<PathBuf, String> {
+    if colors.is_empty() {
+        return Err("Palette has no colors".to_string());
+    }
+    // Duplicates are only looked for among four-color palettes
+    if let Some(existing) = <&[[f32; 3]; 4]>::try_from(colors)
+        .ok()
+        .and_then(find_duplicate_palette)
+    {
+        return Ok(existing);
+    }
+
+    let custom_dir = custom_palettes_dir();
+    if !custom_dir.exists() {
+        std::fs::create_dir_all(&custom_dir)
+            .map_err(|e| format!("Failed to create custom palettes dir: {}", e))?;
+    }
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let filename = format!("palette_{}.png", timestamp);
+    let path = custom_dir.join(&filename);
+
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(1, colors.len() as u32);
+    for (i, color) in colors.iter().enumerate() {
+        img.put_pixel(0, i as u32, Rgb(rgb_to_u8(*color)));
+    }
+
+    img.save(&path)
+        .map_err(|e| format!("Failed to save palette image: {}", e))?;
+
+    Ok(path)
+}
+
+/// File name ending of the source thumbnail kept beside a palette image,
+/// e.g. `palette_<ts>.png.src.jpg` for `palette_<ts>.png`. The palette's
+/// whole file name is kept, so `foo.png` and `foo.jpg` don't share one.
+const SOURCE_THUMBNAIL_SUFFIX: &str = "src.jpg";
+
+/// Longest side of a source thumbnail, in pixels
+const SOURCE_THUMBNAIL_SIZE: u32 = 256;
+
+/// Where the source thumbnail of `palette` is kept
+fn source_thumbnail_path(palette: &Path) -> PathBuf {
+    let mut name = palette.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(SOURCE_THUMBNAIL_SUFFIX);
+    palette.with_file_name(name)
+}
+
+fn is_source_thumbnail(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.ends_with(&format!(".{}", SOURCE_THUMBNAIL_SUFFIX)))
+}
+
+/// Keep a small JPEG of the image a palette was extracted from beside it,
+/// so the palette list can show where it came from. A thumbnail that's
+/// already there is left alone, since [`save_palette_image`] returns an
+/// existing palette for duplicates.
+pub fn save_source_thumbnail(palette: &Path, source: &Path) -> Result<(), String> {
+    let out = source_thumbnail_path(palette);
+    if out.exists() {
+        return Ok(());
+    }
+    open_oriented(source)?
+        .thumbnail(SOURCE_THUMBNAIL_SIZE, SOURCE_THUMBNAIL_SIZE)
+        .to_rgb8()
+        .save_with_format(&out, ImageFormat::Jpeg)
+        .map_err(|e| format!("Failed to save source thumbnail: {}", e))
+}
+
+/// The source thumbnail saved with `palette`, if it has one
+pub fn source_thumbnail(palette: &Path) -> Option<PathBuf> {
+    Some(source_thumbnail_path(palette)).filter(|path| path.is_file())
+}
+
+/// Move or rename a palette's source thumbnail along with the palette.
+/// Palettes without one are fine; failing to move it only loses the
+/// thumbnail, so that's logged rather than returned.
+fn move_source_thumbnail(from: &Path, to: &Path) {
+    let Some(thumbnail) = source_thumbnail(from) else {
+        return;
+    };
+    if let Err(e) = std::fs::rename(&thumbnail, source_thumbnail_path(to)) {
+        eprintln!("Failed to move source thumbnail: {}", e);
+    }
+}
+
+/// Largest per-channel difference (in 0–255 steps) at which two palettes
+/// count as identical, absorbing rounding from the 8-bit palette images.
+const DUPLICATE_TOLERANCE: u8 = 1;
+
+/// Find a saved Custom palette with the same four colors, if any.
+///
+/// Only the Custom directory is scanned; bundled palettes are read-only
+/// and never written to, so they can't accumulate duplicates.
+pub fn find_duplicate_palette(colors: &[[f32; 3]; 4]) -> Option<PathBuf> {
+    find_duplicate_in(&custom_palettes_dir(), colors)
+}
+
+fn find_duplicate_in(dir: &Path, colors: &[[f32; 3]; 4]) -> Option<PathBuf> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_image_file(path))
+        .collect();
+    paths.sort();
+
+    paths.into_iter().find(|path| {
+        cached_palette_colors(path).is_ok_and(|existing| palettes_match(&existing, colors))
+    })
+}
+
+fn palettes_match(a: &[[f32; 3]; 4], b: &[[f32; 3]; 4]) -> bool {
+    a.iter().zip(b).all(|(ca, cb)| {
+        rgb_to_u8(*ca)
+            .iter()
+            .zip(rgb_to_u8(*cb))
+            .all(|(x, y)| x.abs_diff(y) <= DUPLICATE_TOLERANCE)
+    })
+}
+
+/// Text formats a palette can be exported to.
+#[derive(Debug, Clone, Copy)]
+pub enum PaletteExportFormat {
+    /// One `#RRGGBB` per line
+    Hex,
+    /// Array of `{ "hex", "r", "g", "b" }` objects
+    Json,
+    /// `:root { --color-N: #RRGGBB; }` custom properties
+    Css,
+}
+
+impl PaletteExportFormat {
+    /// Infer format from a file extension string.
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_lowercase().as_str() {
+            "json" => PaletteExportFormat::Json,
+            "css" => PaletteExportFormat::Css,
+            _ => PaletteExportFormat::Hex,
+        }
+    }
+}
+
+/// Export palette colors to a text file for theming terminals, websites, etc.
+///
+/// Channels are rounded to 8 bits the same way as `save_palette_image`.
+pub fn export_palette(
+    colors: &[[f32; 3]],
+    format: PaletteExportFormat,
+    out: &Path,
+) -> Result<(), String> {
+    std::fs::write(out, format_palette(colors, format))
+        .map_err(|e| format!("Failed to export palette: {}", e))
+}
+
+/// Render palette colors in the given text format.
+fn format_palette(colors: &[[f32; 3]], format: PaletteExportFormat) -> String {
+    let mut out = String::new();
+    match format {
+        PaletteExportFormat::Hex => {
+            for color in colors {
+                out.push_str(&rgb_to_hex(*color));
+                out.push('\n');
+            }
+        }
+        PaletteExportFormat::Json => {
+            out.push_str("[\n");
+            for (i, color) in colors.iter().enumerate() {
+                let [r, g, b] = rgb_to_u8(*color);
+                let separator = if i + 1 < colors.len() { "," } else { "" };
+                out.push_str(&format!(
+                    "  {{ \"hex\": \"{}\", \"r\": {}, \"g\": {}, \"b\": {} }}{}\n",
+                    rgb_to_hex(*color),
+                    r,
+                    g,
+                    b,
+                    separator
+                ));
+            }
+            out.push_str("]\n");
+        }
+        PaletteExportFormat::Css => {
+            out.push_str(":root {\n");
+            for (i, color) in colors.iter().enumerate() {
+                out.push_str(&format!("  --color-{}: {};\n", i, rgb_to_hex(*color)));
+            }
+            out.push_str("}\n");
+        }
+    }
+    out
+}
+
+/// Convert a normalized color to 8-bit channels (rounded).
+pub fn rgb_to_u8(color: [f32; 3]) -> [u8; 3] {
+    [
+        (color[0].clamp(0.0, 1.0) * 255.0).round() as u8,
+        (color[1].clamp(0.0, 1.0) * 255.0).round() as u8,
+        (color[2].clamp(0.0, 1.0) * 255.0).round() as u8,
+    ]
+}
+
+/// Format a normalized color as `#RRGGBB`.
+pub fn rgb_to_hex(color: [f32; 3]) -> String {
+    let [r, g, b] = rgb_to_u8(color);
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+/// Parse a hex color into RGB 0–1. Accepts `#RGB`, `#RRGGBB` and
+/// `#RRGGBBAA`, with or without the `#`; the alpha of the last form is
+/// ignored since palette colors are opaque.
+pub fn hex_to_rgb(hex: &str) -> Result<[f32; 3], String> {
+    let text = hex.trim();
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    let invalid = || format!("\"{}\" isn't a hex color like #RGB or #RRGGBB", text);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).map_err(|_| invalid());
+    let rgb = match digits.len() {
+        // Each shorthand digit is doubled: #F80 is #FF8800
+        3 => [0, 1, 2].map(|i| channel(&digits[i..i + 1]).map(|v| v * 17)),
+        6 | 8 => [0, 2, 4].map(|i| channel(&digits[i..i + 2])),
+        _ => return Err(invalid()),
+    };
+    let [r, g, b] = rgb;
+    Ok(u8_to_rgb([r?, g?, b?]))
+}
+
+/// Import a GIMP palette (`.gpl`) file and save it as a custom palette.
+///
+/// Reads every `R G B [name]` row, skipping the header, `Name:`/`Columns:`
+/// lines and comments. The saved palette uses the first 4 colors, repeating
+/// the last one if the file has fewer. Returns all colors read from the file.
+pub fn import_gpl(path: &Path) -> Result<Vec<[f32; 3]>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read palette file: {}", e))?;
+    let colors = parse_gpl(&contents)?;
+
+    save_palette_image(&to_four_colors(&colors))?;
+    Ok(colors)
+}
+
+/// Parse the contents of a GIMP palette file into normalized colors.
+fn parse_gpl(contents: &str) -> Result<Vec<[f32; 3]>, String> {
+    let mut lines = contents.lines().enumerate();
+
+    match lines.next() {
+        Some((_, header)) if header.trim() == "GIMP Palette" => {}
+        _ => return Err("Not a GIMP palette (missing \"GIMP Palette\" header)".to_string()),
+    }
+
+    let mut colors = Vec::new();
+    for (idx, line) in lines {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+
+        let mut channels = [0.0f32; 3];
+        let mut fields = line.split_whitespace();
+        for channel in channels.iter_mut() {
+            let value = fields
+                .next()
+                .and_then(|f| f.parse::<u8>().ok())
+                .ok_or_else(|| format!("Invalid color on line {}: \"{}\"", idx + 1, line))?;
+            *channel = value as f32 / 255.0;
+        }
+        colors.push(channels);
+    }
+
+    if colors.is_empty() {
+        return Err("GIMP palette contains no colors".to_string());
+    }
+
+    Ok(colors)
+}
+
+/// Read the 16 terminal colors from a pywal `colors.json`
+/// (usually `~/.cache/wal/colors.json`).
+///
+/// Returns `color0`..`color15` in order. Nothing is saved, so the caller can
+/// pick which four to keep (see [`distinct_color_indices`]) before calling
+/// [`save_palette_image`].
+pub fn import_pywal(path: &Path) -> Result<Vec<[f32; 3]>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read pywal colors: {}", e))?;
+    parse_pywal(&contents)
+}
+
+/// Number of terminal colors in a pywal scheme.
+const PYWAL_COLOR_COUNT: usize = 16;
+
+/// Pull `color0`..`color15` out of the `"colors"` object of pywal's JSON.
+fn parse_pywal(contents: &str) -> Result<Vec<[f32; 3]>, String> {
+    let colors = Json::parse(contents)
+        .and_then(|json| json.get("colors").cloned())
+        .filter(|colors| matches!(colors, Json::Object(_)))
+        .ok_or("Not a pywal colors file (missing \"colors\" object)")?;
+
+    (0..PYWAL_COLOR_COUNT)
+        .map(|i| {
+            let key = format!("color{}", i);
+            match colors.get(&key) {
+                None => Err(format!("pywal colors file is missing {}", key)),
+                Some(value) => value
+                    .as_str()
+                    .and_then(|hex| hex_to_rgb(hex).ok())
+                    .ok_or_else(|| format!("Invalid color for {}: {:?}", key, value)),
+            }
+        })
+        .collect()
+}
+
+/// Indices of `n` colors that are as far apart as possible (CIE Lab),
+/// in their original order.
+///
+/// Greedy farthest-point selection starting from the first color, which
+/// for pywal is the background. Useful as the default pick when trimming
+/// a larger scheme down to a 4-color palette.
+pub fn distinct_color_indices(colors: &[[f32; 3]], n: usize) -> Vec<usize> {
+    let labs: Vec<[f32; 3]> = colors.iter().map(|c| rgb_to_lab(*c)).collect();
+    let mut chosen: Vec<usize> = Vec::with_capacity(n);
+    if !labs.is_empty() && n > 0 {
+        chosen.push(0);
+    }
+
+    while chosen.len() < n.min(labs.len()) {
+        let next = (0..labs.len())
+            .filter(|i| !chosen.contains(i))
+            .max_by(|&a, &b| {
+                let nearest = |i: usize| {
+                    chosen
+                        .iter()
+                        .map(|&c| lab_distance_sq(&labs[i], &labs[c]))
+                        .fold(f32::INFINITY, f32::min)
+                };
+                // Ties go to the lower index, keeping the result deterministic
+                nearest(a).total_cmp(&nearest(b)).then(b.cmp(&a))
+            });
+        match next {
+            Some(i) => chosen.push(i),
+            None => break,
+        }
+    }
+
+    chosen.sort_unstable();
+    chosen
+}
+
+/// Delete a user-saved palette image.
+///
+/// Only allows deletion of files inside the user palettes directory (safety check).
+pub fn delete_palette_image(path: &Path) -> Result<(), String> {
+    if !is_user_palette(path) {
+        return Err("Cannot delete bundled palettes".to_string());
+    }
+
+    std::fs::remove_file(path).map_err(|e| format!("Failed to delete palette: {}", e))?;
+    // The palette is gone either way; a leftover thumbnail is only clutter
+    if let Some(thumbnail) = source_thumbnail(path) {
+        if let Err(e) = std::fs::remove_file(thumbnail) {
+            eprintln!("Failed to delete source thumbnail: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Delete several user-saved palette images, each checked like
+/// [`delete_palette_image`]. A failure doesn't stop the rest; every path is
+/// returned with its own result, in order.
+pub fn delete_palettes(paths: &[PathBuf]) -> Vec<(PathBuf, Result<(), String>)> {
+    paths
+        .iter()
+        .map(|path| (path.clone(), delete_palette_image(path)))
+        .collect()
+}
+
+/// Rename a user-saved palette image.
+///
+/// The new name is sanitized and the file keeps its extension. If another
+/// palette already uses the name, a numeric suffix is appended.
+/// Returns the new path.
+pub fn rename_palette(path: &Path, new_name: &str) -> Result<PathBuf, String> {
+    if !is_user_palette(path) {
+        return Err("Cannot rename bundled palettes".to_string());
+    }
+
+    let stem = sanitize_name(new_name)?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| "Palette has no parent directory".to_string())?;
+
+    if path.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str()) {
+        return Ok(path.to_path_buf());
+    }
+
+    let target = unique_palette_path(dir, &stem, palette_extension(path));
+    std::fs::rename(path, &target).map_err(|e| format!("Failed to rename palette: {}", e))?;
+    move_source_thumbnail(path, &target);
+    Ok(target)
+}
+
+/// Move a user-saved palette image into another category folder.
+///
+/// The category folder is created inside the user palettes directory if it
+/// doesn't exist yet. Name collisions get a numeric suffix. Returns the new path.
+pub fn move_palette_to_category(path: &Path, category: &str) -> Result<PathBuf, String> {
+    if !is_user_palette(path) {
+        return Err("Cannot move bundled palettes".to_string());
+    }
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| "Palette has an invalid file name".to_string())?;
+
+    let dir = user_category_dir(category)?;
+    if path.parent() == Some(dir.as_path()) {
+        return Ok(path.to_path_buf());
+    }
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create category: {}", e))?;
+
+    let target = unique_palette_path(&dir, stem, palette_extension(path));
+    std::fs::rename(path, &target).map_err(|e| format!("Failed to move palette: {}", e))?;
+    move_source_thumbnail(path, &target);
+    Ok(target)
+}
+
+/// Whether `path` is inside the user palettes directory, so it can be
+/// renamed or deleted
+pub fn is_user_palette(path: &Path) -> bool {
+    path.starts_with(user_palettes_dir())
+}
+
+/// Folder inside the user palettes directory for a category display name.
+///
+/// Reuses an existing folder that matches case-insensitively, otherwise
+/// uses the lowercased name like the bundled category folders.
+fn user_category_dir(category: &str) -> Result<PathBuf, String> {
+    category_dir_in(&user_palettes_dir(), category)
+}
+
+fn category_dir_in(root: &Path, category: &str) -> Result<PathBuf, String> {
+    let name = sanitize_name(category)?;
+    Ok(find_category_dir(root, &name).unwrap_or_else(|| root.join(name.to_lowercase())))
+}
+
+/// An existing category folder directly in `root` matching `name` case-insensitively.
+fn find_category_dir(root: &Path, name: &str) -> Option<PathBuf> {
+    std::fs::read_dir(root)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name.trim()))
+        })
+}
+
+/// Create an empty user category. Returns its name as listed by
+/// [`list_palette_categories`].
+pub fn create_category(name: &str) -> Result<String, String> {
+    if is_bundled_category(name) {
+        return Err(format!("Category \"{}\" already exists", name.trim()));
+    }
+    create_category_in(&user_palettes_dir(), name)
+}
+
+fn create_category_in(root: &Path, name: &str) -> Result<String, String> {
+    let dir = category_dir_in(root, name)?;
+    if is_reserved_category(&category_name(&dir)) {
+        return Err(format!(
+            "\"{}\" is a built-in category",
+            category_name(&dir)
+        ));
+    }
+    if dir.exists() {
+        return Err(format!(
+            "Category \"{}\" already exists",
+            category_name(&dir)
+        ));
+    }
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create category: {}", e))?;
+    Ok(category_name(&dir))
+}
+
+/// Rename a user category folder, moving its palettes with it. Returns the
+/// new listed name.
+///
+/// Only the user's folder is renamed: bundled categories are read-only, and
+/// the Custom category is where saved palettes go, so both are refused.
+pub fn rename_category(old: &str, new: &str) -> Result<String, String> {
+    if is_bundled_category(new) {
+        return Err(format!("Category \"{}\" already exists", new.trim()));
+    }
+    rename_category_in(&user_palettes_dir(), old, new)
+}
+
+fn rename_category_in(root: &Path, old: &str, new: &str) -> Result<String, String> {
+    let dir = editable_category_dir(root, old)?;
+    let target = root.join(sanitize_name(new)?.to_lowercase());
+    if is_reserved_category(&category_name(&target)) {
+        return Err(format!(
+            "\"{}\" is a built-in category",
+            category_name(&target)
+        ));
+    }
+    // Only a case change may reuse the existing folder name
+    let same_folder = target
+        .file_name()
+        .zip(dir.file_name())
+        .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b));
+    if !same_folder && find_category_dir(root, &sanitize_name(new)?).is_some() {
+        return Err(format!("Category \"{}\" already exists", new.trim()));
+    }
+    std::fs::rename(&dir, &target).map_err(|e| format!("Failed to rename category: {}", e))?;
+    Ok(category_name(&target))
+}
+
+/// Delete a user category folder. A category that still holds files is
+/// only removed (with its palettes) when `force` is set.
+pub fn delete_category(name: &str, force: bool) -> Result<(), String> {
+    delete_category_in(&user_palettes_dir(), name, force)
+}
+
+fn delete_category_in(root: &Path, name: &str, force: bool) -> Result<(), String> {
+    let dir = editable_category_dir(root, name)?;
+    let empty = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read category: {}", e))?
+        .next()
+        .is_none();
+    if !empty && !force {
+        return Err(format!("Category \"{}\" is not empty", category_name(&dir)));
+    }
+    std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete category: {}", e))
+}
+
+/// The user folder for a category that may be renamed or deleted.
+fn editable_category_dir(root: &Path, name: &str) -> Result<PathBuf, String> {
+    if is_custom_category(&capitalize_first(name.trim())) {
+        return Err(format!(
+            "The {} category can't be renamed or deleted",
+            CUSTOM_CATEGORY
+        ));
+    }
+    find_category_dir(root, name).ok_or_else(|| {
+        if is_bundled_category(name) {
+            "Cannot modify bundled categories".to_string()
+        } else {
+            format!("Category \"{}\" not found", name.trim())
+        }
+    })
+}
+
+/// Names taken by the synthetic categories; a folder with one of them would
+/// be hidden behind the synthetic list
+fn is_reserved_category(name: &str) -> bool {
+    [FAVORITES_CATEGORY, RECENT_CATEGORY]
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(name.trim()))
+}
+
+fn is_bundled_category(name: &str) -> bool {
+    bundled_palettes_dir().is_some_and(|dir| find_category_dir(&dir, name).is_some())
+}
+
+/// Display name of a category folder, as used in [`PaletteCategories`].
+fn category_name(dir: &Path) -> String {
+    dir.file_name()
+        .and_then(|n| n.to_str())
+        .map(capitalize_first)
+        .unwrap_or_else(|| "Uncategorized".to_string())
+}
+
+/// Clean up a user-supplied palette or category name for use on disk.
+///
+/// Path separators and control characters are removed, a trailing `.png`
+/// is dropped, and names that end up empty (or are just dots) are rejected.
+fn sanitize_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    let name = name
+        .strip_suffix(".png")
+        .or_else(|| name.strip_suffix(".PNG"))
+        .unwrap_or(name);
+
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\') && !c.is_control())
+        .collect();
+    let cleaned = cleaned.trim().to_string();
+
+    if cleaned.is_empty() || cleaned.chars().all(|c| c == '.') {
+        return Err("Name must not be empty".to_string());
+    }
+
+    Ok(cleaned)
+}
+
+/// Extension of a palette file, for keeping it across renames and moves
+fn palette_extension(path: &Path) -> &str {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("png")
+}
+
+/// Find a free `<stem>.<extension>` path in `dir`, appending `_2`, `_3`, …
+/// on collision.
+fn unique_palette_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let candidate = dir.join(format!("{}.{}", stem, extension));
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    (2..)
+        .map(|n| dir.join(format!("{}_{}.{}", stem, n, extension)))
+        .find(|p| !p.exists())
+        .expect("unbounded suffix search always finds a free name")
+}
+
+/// Whether the given category name is the user-saved custom category.
+pub fn is_custom_category(name: &str) -> bool {
+    name == CUSTOM_CATEGORY
+}
+
+/// Folder that `save_palette_image` writes to.
+fn custom_palettes_dir() -> PathBuf {
+    user_palettes_dir().join(CUSTOM_CATEGORY.to_lowercase())
+}
+
+/// Get the user palettes directory inside the sandbox data dir.
+///
+/// In Flatpak this is `~/.var/app/io.github.megakode.Wallrus/data/wallrus/palettes/`.
+/// Outside Flatpak this is `~/.local/share/wallrus/palettes/` (via `g_get_user_data_dir()`).
+/// Creates the directory if it doesn't exist, moving palettes saved by
+/// earlier versions from `palettes/` directly under the data dir into it.
+pub fn user_palettes_dir() -> PathBuf {
+    let dir = glib::user_data_dir().join("wallrus").join("palettes");
+    if !dir.exists() {
+        let legacy = glib::user_data_dir().join("palettes");
+        let _ = std::fs::create_dir_all(glib::user_data_dir().join("wallrus"));
+        if !legacy.is_dir() || std::fs::rename(&legacy, &dir).is_err() {
+            let _ = std::fs::create_dir_all(&dir);
+        }
+    }
+    dir
+}
+
+/// Where a palette path recorded before user palettes moved under
+/// `wallrus/` is now; other paths are returned unchanged.
+pub fn relocate_user_palette(path: &Path) -> PathBuf {
+    match path.strip_prefix(glib::user_data_dir().join("palettes")) {
+        Ok(rest) => user_palettes_dir().join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Get the bundled palettes directory.
+///
+/// Looks for palettes relative to the executable, then falls back to
+/// common installation paths. During development this is `data/palettes/`
+/// relative to the project root.
+pub fn bundled_palettes_dir() -> Option<PathBuf> {
+    // During development: look relative to the executable
+    if let Ok(exe) = std::env::current_exe() {
+        // target/debug/wallrus -> project_root/data/palettes
+        if let Some(project_root) = exe
+            .parent()
+            .and_then(|p| p.parent())
+            .and_then(|p| p.parent())
+        {
+            let dev_path = project_root.join("data").join("palettes");
+            if dev_path.is_dir() {
+                return Some(dev_path);
+            }
+        }
+    }
+
+    // Installed (prefix-relative): <prefix>/bin/wallrus -> <prefix>/share/wallrus/palettes
+    if let Ok(exe) = std::env::current_exe() {
+        if let Some(prefix) = exe.parent().and_then(|p| p.parent()) {
+            let prefix_path = prefix.join("share").join("wallrus").join("palettes");
+            if prefix_path.is_dir() {
+                return Some(prefix_path);
+            }
+        }
+    }
+
+    // Installed: /usr/share/wallrus/palettes
+    let system_path = PathBuf::from("/usr/share/wallrus/palettes");
+    if system_path.is_dir() {
+        return Some(system_path);
+    }
+
+    // Flatpak or local: /app/share/wallrus/palettes
+    let flatpak_path = PathBuf::from("/app/share/wallrus/palettes");
+    if flatpak_path.is_dir() {
+        return Some(flatpak_path);
+    }
+
+    None
+}
+
+/// Scan a palette root directory for categorized images.
+///
+/// - Subfolders become categories (folder name with first letter capitalized).
+/// - Image files directly in the root go into "Uncategorized".
+/// - JSON and TOML palette definitions are listed like images.
+fn collect_categorized_images(root: &Path, categories: &mut PaletteCategories) {
+    let entries = match std::fs::read_dir(root) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            // Subfolder = category; empty ones are listed so new categories show up
+            let images = categories.entry(category_name(&path)).or_default();
+
+            let sub_entries = match std::fs::read_dir(&path) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+
+            for sub_entry in sub_entries.flatten() {
+                let sub_path = sub_entry.path();
+                if sub_path.is_file() && is_palette_file(&sub_path) {
+                    images.push(sub_path);
+                }
+            }
+        } else if path.is_file() && is_palette_file(&path) {
+            // Files directly in root go to "Uncategorized"
+            categories
+                .entry("Uncategorized".to_string())
+                .or_default()
+                .push(path);
+        }
+    }
+}
+
+/// Palette images, and text definitions that parse; invalid definitions
+/// are left out of the list rather than failing the scan.
+fn is_palette_file(path: &Path) -> bool {
+    is_image_file(path) || (is_palette_definition(path) && read_palette_definition(path).is_ok())
+}
+
+/// Extensions listed as palette images. AVIF decoding needs libdav1d, so
+/// it's only listed when built with the `avif` feature.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "bmp", "tif", "tiff"];
+
+/// Formats recognized from a file's first bytes when its extension isn't
+/// one of [`IMAGE_EXTENSIONS`]
+const SNIFFED_FORMATS: &[ImageFormat] = &[ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP];
+
+/// Whether `path` is a palette image. The extension is checked first; files
+/// without a known one are listed if they start like a PNG, JPEG or WebP.
+/// Source thumbnails kept beside palettes are not palettes themselves.
+fn is_image_file(path: &Path) -> bool {
+    if is_source_thumbnail(path) {
+        return false;
+    }
+    let known_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|ext| {
+            let ext = ext.to_lowercase();
+            IMAGE_EXTENSIONS.contains(&ext.as_str()) || (cfg!(feature = "avif") && ext == "avif")
+        })
+        .unwrap_or(false);
+    known_extension || (!is_palette_definition(path) && has_image_signature(path))
+}
+
+fn has_image_signature(path: &Path) -> bool {
+    use std::io::Read;
+
+    // Enough for WebP's "RIFF....WEBP", the longest of the signatures
+    let mut header = Vec::with_capacity(16);
+    let read = std::fs::File::open(path).and_then(|file| file.take(16).read_to_end(&mut header));
+    read.is_ok()
+        && image::guess_format(&header).is_ok_and(|format| SNIFFED_FORMATS.contains(&format))
+}
+
+fn capitalize_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn hsl_of_primaries() {
+        assert_close(rgb_to_hsl([1.0, 0.0, 0.0]), [0.0, 1.0, 0.5]);
+        assert_close(rgb_to_hsl([0.0, 1.0, 0.0]), [120.0, 1.0, 0.5]);
+        assert_close(rgb_to_hsl([0.0, 0.0, 1.0]), [240.0, 1.0, 0.5]);
+        assert_close(rgb_to_hsl([0.5, 0.5, 0.5]), [0.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn hsl_round_trip() {
+        for rgb in [
+            [0.8, 0.33, 0.0],
+            [0.1, 0.6, 0.9],
+            [0.93, 0.53, 0.07],
+            [0.2, 0.2, 0.2],
+            [1.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0],
+        ] {
+            assert_close(hsl_to_rgb(rgb_to_hsl(rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn hsl_hue_wraps() {
+        assert_close(hsl_to_rgb([360.0, 1.0, 0.5]), [1.0, 0.0, 0.0]);
+        assert_close(hsl_to_rgb([-120.0, 1.0, 0.5]), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn harmony_keeps_base_color() {
+        let base = [0.8, 0.33, 0.0];
+        for scheme in HarmonyScheme::all() {
+            assert!(
+                generate_harmony(base, scheme).contains(&base),
+                "{:?}",
+                scheme
+            );
+        }
+    }
+
+    #[test]
+    fn complementary_rotates_hue_180() {
+        let palette = generate_harmony([1.0, 0.0, 0.0], HarmonyScheme::Complementary);
+        assert_close(palette[2], [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn monochromatic_keeps_hue() {
+        let base = [0.1, 0.6, 0.9];
+        let hue = rgb_to_hsl(base)[0];
+        for color in generate_harmony(base, HarmonyScheme::Monochromatic) {
+            assert!((rgb_to_hsl(color)[0] - hue).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn hsl_adjust_zero_is_identity() {
+        let colors = [[0.8, 0.33, 0.0], [0.1, 0.6, 0.9]];
+        let adjusted = adjust_palette_hsl(&colors, 0.0, 0.0, 0.0);
+        for (a, b) in adjusted.iter().zip(&colors) {
+            assert_close(*a, *b);
+        }
+    }
+
+    #[test]
+    fn hsl_adjust_rotates_and_wraps_hue() {
+        let adjusted = adjust_palette_hsl(&[[1.0, 0.0, 0.0]], 480.0, 0.0, 0.0);
+        assert_close(adjusted[0], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn hsl_adjust_clamps_to_unit_range() {
+        let colors = [[1.0, 1.0, 1.0], [0.0, 0.0, 0.0], [0.9, 0.1, 0.5]];
+        for (ds, dl) in [(2.0, 2.0), (-2.0, -2.0)] {
+            for c in adjust_palette_hsl(&colors, 0.0, ds, dl) {
+                assert!(c.iter().all(|v| (0.0..=1.0).contains(v)), "{:?}", c);
+            }
+        }
+        assert_close(
+            adjust_palette_hsl(&colors, 0.0, 0.0, 1.0)[2],
+            [1.0, 1.0, 1.0],
+        );
+        assert_close(
+            adjust_palette_hsl(&colors, 0.0, -1.0, 0.0)[0],
+            [1.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn linear_conversion_matches_srgb_curve() {
+        assert_close(
+            ColorSpace::Linear.decode([0.0, 0.5, 1.0]),
+            [0.0, 0.21404, 1.0],
+        );
+        // Below the knee the curve is the linear segment
+        assert!((ColorSpace::Linear.decode([0.02; 3])[0] - 0.02 / 12.92).abs() < 1e-6);
+        assert_close(
+            ColorSpace::Linear.encode([0.21404, 0.0, 1.0]),
+            [0.5, 0.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn color_space_round_trip() {
+        for space in ColorSpace::ALL {
+            for i in 0..=20 {
+                let v = i as f32 / 20.0;
+                let rgb = [v, 1.0 - v, v * 0.5];
+                assert_close(space.encode(space.decode(rgb)), rgb);
+            }
+        }
+        assert_close(ColorSpace::Srgb.decode([0.3, 0.6, 0.9]), [0.3, 0.6, 0.9]);
+        assert_eq!(ColorSpace::parse("Linear"), Ok(ColorSpace::Linear));
+        assert_eq!(ColorSpace::parse("OkLab"), Ok(ColorSpace::Oklab));
+        assert!(ColorSpace::parse("rgb").is_err());
+    }
+
+    #[test]
+    fn oklab_matches_reference_values() {
+        assert_close(rgb_to_oklab([1.0, 1.0, 1.0]), [1.0, 0.0, 0.0]);
+        assert_close(rgb_to_oklab([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]);
+        // Reference values from Ottosson's OkLab post
+        let red = rgb_to_oklab([1.0, 0.0, 0.0]);
+        assert!((red[0] - 0.62796).abs() < 1e-3, "{:?}", red);
+        assert!((red[1] - 0.22486).abs() < 1e-3, "{:?}", red);
+        assert!((red[2] - 0.12585).abs() < 1e-3, "{:?}", red);
+        // Out-of-gamut Lab values clamp instead of leaving 0–1
+        for c in oklab_to_rgb([0.5, 0.4, -0.4]) {
+            assert!((0.0..=1.0).contains(&c));
+        }
+    }
+
+    #[test]
+    fn oklab_midpoint_is_perceptually_halfway() {
+        let (blue, yellow) = ([0.0, 0.0, 1.0], [1.0, 1.0, 0.0]);
+        let mix = |space: ColorSpace| {
+            let (a, b) = (space.decode(blue), space.decode(yellow));
+            space.encode([0, 1, 2].map(|i| (a[i] + b[i]) / 2.0))
+        };
+        // sRGB mixing of complementary colors gives flat mid gray
+        assert_close(mix(ColorSpace::Srgb), [0.5, 0.5, 0.5]);
+        let lightness = |c: [f32; 3]| rgb_to_oklab(c)[0];
+        let halfway = (lightness(blue) + lightness(yellow)) / 2.0;
+        let mid = mix(ColorSpace::Oklab);
+        assert!((lightness(mid) - halfway).abs() < 0.01, "{:?}", mid);
+        assert!((lightness(mix(ColorSpace::Srgb)) - halfway).abs() > 0.05);
+    }
+
+    #[test]
+    fn palette_diff_flags_changed_slots() {
+        let a = [[0.8, 0.33, 0.0], [0.1, 0.6, 0.9], [0.5, 0.5, 0.5]];
+        let b = [
+            [0.8, 0.33, 0.0],
+            [0.1, 0.6, 0.902],
+            [0.5, 0.5, 0.9],
+            [1.0, 1.0, 1.0],
+        ];
+        let diff = diff_palettes(&a, &b);
+
+        assert_eq!(diff.len(), 3);
+        assert_eq!(diff[0].delta_e, 0.0);
+        assert!(diff[0].identical && diff[1].identical);
+        assert!(!diff[2].identical && diff[2].delta_e > 10.0);
+        assert_close(diff[2].rgb, [0.0, 0.0, 0.4]);
+    }
+
+    #[test]
+    fn warns_about_similar_neighbours_and_flat_palettes() {
+        let distinct = [
+            [0.1, 0.1, 0.4],
+            [0.9, 0.3, 0.1],
+            [0.2, 0.8, 0.3],
+            [1.0, 0.95, 0.8],
+        ];
+        assert!(palette_warnings(&distinct).is_empty());
+
+        let mut doubled = distinct;
+        doubled[2] = [0.9, 0.31, 0.12];
+        let warnings = palette_warnings(&doubled);
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            warnings[0],
+            PaletteWarning::SimilarAdjacent { index: 1, .. }
+        ));
+        // Non-adjacent duplicates don't wash out a band
+        let mut apart = distinct;
+        apart[3] = apart[1];
+        assert!(palette_warnings(&apart).is_empty());
+
+        let flat = [
+            [0.5, 0.5, 0.5],
+            [0.52, 0.5, 0.5],
+            [0.5, 0.53, 0.5],
+            [0.55, 0.55, 0.55],
+        ];
+        let warnings = palette_warnings(&flat);
+        assert!(matches!(warnings[..], [PaletteWarning::LowVariety { .. }]));
+        assert!(palette_warnings(&flat[..1]).is_empty());
+    }
+
+    #[test]
+    fn nearest_color_names_match_exact_and_close_colors() {
+        assert_eq!(nearest_color_name([0.0, 0.0, 0.0]), "Black");
+        assert_eq!(
+            nearest_color_name(u8_to_rgb([0x6A, 0x5A, 0xCD])),
+            "Slate Blue"
+        );
+        assert_eq!(nearest_color_name(u8_to_rgb([0xFE, 0x01, 0x02])), "Red");
+        assert_eq!(nearest_color_name([0.99, 0.99, 0.99]), "White");
+    }
+
+    #[test]
+    fn temperature_zero_is_identity() {
+        let colors = [[0.8, 0.33, 0.0], [0.1, 0.6, 0.9], [0.5, 0.5, 0.5]];
+        for (out, c) in adjust_temperature(&colors, 0.0).into_iter().zip(colors) {
+            assert_close(out, c);
+        }
+    }
+
+    #[test]
+    fn temperature_warms_and_cools() {
+        let gray = [[0.5, 0.5, 0.5]];
+        let [r, _, b] = adjust_temperature(&gray, 2000.0)[0];
+        assert!(r > 0.5 && b < 0.5, "warm: {:?}", [r, b]);
+        let [r, _, b] = adjust_temperature(&gray, -2000.0)[0];
+        assert!(r < 0.5 && b > 0.5, "cool: {:?}", [r, b]);
+        for c in adjust_temperature(&[[1.0, 1.0, 1.0], [0.0, 0.0, 0.0]], 5000.0) {
+            assert!(c.iter().all(|v| (0.0..=1.0).contains(v)), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn brightness_and_contrast() {
+        let colors = [[0.8, 0.33, 0.0], [0.1, 0.6, 0.9], [0.5, 0.5, 0.5]];
+        assert_eq!(apply_brightness_contrast(&colors, 0.0, 1.0), colors);
+        // Out-of-range input is left alone at identity, not clamped
+        assert_eq!(
+            apply_brightness_contrast(&[[1.2, -0.1, 0.5]], 0.0, 1.0),
+            [[1.2, -0.1, 0.5]]
+        );
+
+        assert_close(
+            apply_brightness_contrast(&colors, 0.1, 1.0)[0],
+            [0.9, 0.43, 0.1],
+        );
+        // Contrast pivots on mid-gray
+        assert_close(
+            apply_brightness_contrast(&colors, 0.0, 2.0)[0],
+            [1.0, 0.16, 0.0],
+        );
+        assert_close(
+            apply_brightness_contrast(&colors, 0.0, 0.5)[1],
+            [0.3, 0.55, 0.7],
+        );
+        assert_close(
+            apply_brightness_contrast(&colors, 0.0, 3.0)[2],
+            [0.5, 0.5, 0.5],
+        );
+        assert_close(
+            apply_brightness_contrast(&colors, 0.0, 0.0)[0],
+            [0.5, 0.5, 0.5],
+        );
+        assert_close(
+            apply_brightness_contrast(&colors, -1.0, 1.0)[1],
+            [0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn blending_palettes() {
+        let a = [
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.2, 0.4, 0.6],
+            [0.0, 0.0, 0.0],
+        ];
+        let b = [[0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [0.2, 0.4, 0.6]];
+        let b_oklab = b.map(|c| ColorSpace::Oklab.decode(c));
+        let blend = |a: &[[f32; 3]], b: &[[f32; 3]], t| blend_palettes(a, b, t, ColorSpace::Oklab);
+        let blended = blend(&a, &b_oklab, 0.0);
+        assert_eq!(blended.len(), 3);
+        for (out, c) in blended.into_iter().zip(a) {
+            assert_close(out, c);
+        }
+        for (out, c) in blend(&a, &b_oklab, 1.0).into_iter().zip(b) {
+            assert_close(out, c);
+        }
+        // Out-of-range factors clamp to the ends
+        assert_close(blend(&a, &b_oklab, 2.0)[1], b[1]);
+
+        // Halfway is halfway in perceptual lightness, and equal slots stay put
+        let mid = blend(&a, &b_oklab, 0.5);
+        let halfway = (rgb_to_oklab(a[1])[0] + rgb_to_oklab(b[1])[0]) / 2.0;
+        assert!(
+            (rgb_to_oklab(mid[1])[0] - halfway).abs() < 0.01,
+            "{:?}",
+            mid[1]
+        );
+        assert_close(mid[2], a[2]);
+        assert!(blend(&a, &[], 0.5).is_empty());
+
+        // In linear light, halfway between black and white is 50% light
+        let white = ColorSpace::Linear.decode([1.0; 3]);
+        let gray = blend_palettes(&[[0.0; 3]], &[white], 0.5, ColorSpace::Linear);
+        assert_close(gray[0], [0.73536; 3]);
+    }
+
+    #[test]
+    fn invert_and_grayscale_transforms() {
+        let colors = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.2, 0.4, 0.6],
+            [0.5, 0.5, 0.5],
+        ];
+        let inverted = invert_palette(&colors);
+        assert_close(inverted[0], [0.0, 1.0, 1.0]);
+        assert_close(inverted[2], [0.8, 0.6, 0.4]);
+        for (out, c) in invert_palette(&inverted).into_iter().zip(colors) {
+            assert_close(out, c);
+        }
+
+        let gray = grayscale_palette(&colors);
+        for c in gray {
+            assert!(c[0] == c[1] && c[1] == c[2], "{:?}", c);
+        }
+        // Green carries far more luminance than red
+        assert!(gray[1][0] > gray[0][0]);
+        assert_close(gray[3], [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn rgba_extraction_reads_alpha() {
+        let path = std::env::temp_dir().join("wallrus_test_rgba_palette.png");
+        let img = image::RgbaImage::from_fn(1, 4, |_, y| image::Rgba([255, 0, 0, 64 * y as u8]));
+        img.save(&path).unwrap();
+        let colors = extract_colors_rgba(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(colors[0], [1.0, 0.0, 0.0, 0.0]);
+        assert!((colors[3][3] - 192.0 / 255.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rgba_extraction_defaults_alpha_for_rgb() {
+        let path = std::env::temp_dir().join("wallrus_test_rgb_palette.png");
+        image::RgbImage::from_pixel(1, 4, image::Rgb([0, 255, 0]))
+            .save(&path)
+            .unwrap();
+        let colors = extract_colors_rgba(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(colors.iter().all(|c| c[3] == 1.0));
+    }
+
+    #[test]
+    fn strips_are_sampled_not_clustered() {
+        let path = std::env::temp_dir().join("wallrus_test_strip_palette.png");
+        let rows = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        image::RgbImage::from_fn(1, 4, |_, y| image::Rgb(rows[y as usize]))
+            .save(&path)
+            .unwrap();
+        let (colors, kind) = extract_palette_from_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(kind, PaletteImageKind::Strip);
+
+        // k-means would reorder by population; band sampling keeps row order
+        assert_eq!(colors[0], [1.0, 0.0, 0.0]);
+        assert_eq!(colors[3], [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn streamed_png_samples_match_full_decode() {
+        let path = std::env::temp_dir().join("wallrus_test_streamed_strip.png");
+        let bands = [
+            [200u16, 10, 30],
+            [7, 180, 90],
+            [60, 60, 250],
+            [255, 254, 128],
+        ];
+        let band = move |y: u32| bands[(y / 10) as usize];
+        let images = [
+            image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(30, 40, |x, y| {
+                image::Rgb(band(y).map(|v| (v as u32 + x) as u8))
+            })),
+            image::DynamicImage::ImageRgba16(image::ImageBuffer::from_fn(30, 40, |x, y| {
+                let [r, g, b] = band(y).map(|v| v * 250 + x as u16 * 31);
+                image::Rgba([r, g, b, 40_000])
+            })),
+            image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(30, 40, |_, y| {
+                image::Luma([band(y)[0] as u8])
+            })),
+        ];
+        for img in images {
+            img.save(&path).unwrap();
+            let points: Vec<(u32, u32)> = band_sample_points(30, 40, 4).collect();
+            let streamed = sample_png_rows(&path, &points).unwrap().unwrap();
+            let decoded = image::open(&path).unwrap();
+            let expected: Vec<[u8; 4]> = points
+                .iter()
+                .map(|&(x, y)| decoded.get_pixel(x, y).0)
+                .collect();
+            assert_eq!(streamed, expected, "{:?}", img.color());
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Write an indexed PNG whose rows are `rows` (palette indices, one per
+    /// pixel) at `depth` bits per index
+    fn write_indexed_png(path: &Path, palette: &[[u8; 3]], rows: &[Vec<u8>], depth: png::BitDepth) {
+        let width = rows[0].len() as u32;
+        let file = std::fs::File::create(path).unwrap();
+        let height = rows.len() as u32;
+        let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(depth);
+        encoder.set_palette(palette.concat());
+        let mut writer = encoder.write_header().unwrap();
+        let bits = depth as usize;
+        let data: Vec<u8> = rows
+            .iter()
+            .flat_map(|row| {
+                // Pack the indices MSB first, each row padded to whole bytes
+                let mut packed = vec![0u8; (row.len() * bits).div_ceil(8)];
+                for (i, &index) in row.iter().enumerate() {
+                    let bit = i * bits;
+                    packed[bit / 8] |= index << (8 - bits - bit % 8);
+                }
+                packed
+            })
+            .collect();
+        writer.write_image_data(&data).unwrap();
+    }
+
+    #[test]
+    fn indexed_pngs_give_their_palette_colors() {
+        let path = std::env::temp_dir().join("wallrus_test_indexed_strip.png");
+        // Palette order differs from row order, and has an unused entry
+        let palette = [
+            [9, 9, 9],
+            [0, 0, 255],
+            [255, 0, 0],
+            [0, 255, 0],
+            [51, 102, 153],
+        ];
+        let unit = |c: [u8; 3]| c.map(|v| v as f32 / 255.0);
+        let expected = [palette[2], palette[3], palette[1], palette[4]].map(unit);
+        for depth in [png::BitDepth::Four, png::BitDepth::Eight] {
+            let strip: Vec<Vec<u8>> = [2, 3, 1, 4].iter().map(|&i| vec![i]).collect();
+            write_indexed_png(&path, &palette, &strip, depth);
+            let colors = extract_four_colors(&path).unwrap();
+            assert_eq!(colors, expected);
+
+            // Odd heights split proportionally: 7 rows of 3 pixels, bands
+            // centered on rows 0, 2, 4 and 6
+            let rows: Vec<Vec<u8>> = [2, 2, 3, 3, 1, 4, 4].iter().map(|&i| vec![i; 3]).collect();
+            write_indexed_png(&path, &palette, &rows, depth);
+            let colors = extract_four_colors(&path).unwrap();
+            assert_eq!(colors, expected);
+
+            // The streaming reader expands indices the same way
+            let points: Vec<(u32, u32)> = band_sample_points(3, 7, 4).collect();
+            let streamed = sample_png_rows(&path, &points).unwrap().unwrap();
+            let decoded = image::open(&path).unwrap();
+            let pixels: Vec<[u8; 4]> = points
+                .iter()
+                .map(|&(x, y)| decoded.get_pixel(x, y).0)
+                .collect();
+            assert_eq!(streamed, pixels);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Save `img` to a temporary PNG and extract its four colors
+    fn four_colors_of(img: image::DynamicImage, name: &str) -> [[f32; 3]; 4] {
+        let path = std::env::temp_dir().join(format!("wallrus_test_{}.png", name));
+        img.save(&path).unwrap();
+        let colors = extract_four_colors(&path);
+        let _ = std::fs::remove_file(&path);
+        colors.unwrap()
+    }
+
+    #[test]
+    fn single_pixel_grayscale_fills_all_slots() {
+        let img = image::GrayImage::from_pixel(1, 1, image::Luma([51]));
+        let colors = four_colors_of(img.into(), "gray_1x1");
+        assert_eq!(colors, [[0.2, 0.2, 0.2]; 4]);
+    }
+
+    #[test]
+    fn short_strips_repeat_their_last_color() {
+        let rows = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        for height in [2u32, 3] {
+            let img = image::RgbImage::from_fn(1, height, |_, y| image::Rgb(rows[y as usize]));
+            let colors = four_colors_of(img.into(), &format!("strip_1x{}", height));
+            let last = u8_to_rgb(rows[height as usize - 1]);
+            assert_eq!(colors[0], [1.0, 0.0, 0.0]);
+            assert_eq!(colors[1], [0.0, 1.0, 0.0]);
+            assert!(
+                colors[height as usize..].iter().all(|c| *c == last),
+                "{:?}",
+                colors
+            );
+        }
+    }
+
+    #[test]
+    fn band_sampling_covers_strips_and_squares() {
+        let rows = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let strip = image::RgbImage::from_fn(1, 4, |_, y| image::Rgb(rows[y as usize]));
+        let expected = rows.map(u8_to_rgb);
+        assert_eq!(four_colors_of(strip.into(), "strip_1x4"), expected);
+
+        let square = image::RgbImage::from_fn(16, 16, |_, y| image::Rgb(rows[y as usize / 4]));
+        assert_eq!(four_colors_of(square.into(), "square_16x16"), expected);
+
+        let gray = image::GrayImage::from_fn(16, 16, |_, y| image::Luma([y as u8 * 16]));
+        let colors = four_colors_of(gray.into(), "gray_16x16");
+        for (c, row) in colors.iter().zip([2u8, 6, 10, 14]) {
+            assert_eq!(*c, [row as f32 * 16.0 / 255.0; 3]);
+        }
+    }
+
+    #[test]
+    fn in_memory_strips_sample_one_pixel_per_band() {
+        let rows = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [51, 102, 153]];
+        let strip = RgbImage::from_fn(1, 4, |_, y| Rgb(rows[y as usize]));
+        let colors = extract_colors_from_rgb(&strip, 4);
+        assert_eq!(colors.len(), 4);
+        assert_eq!(colors[0], [1.0, 0.0, 0.0]);
+        assert_eq!(colors[3], [0.2, 0.4, 0.6]);
+        // Fewer colors than rows take the band centers
+        assert_eq!(
+            extract_colors_from_rgb(&strip, 2),
+            vec![[0.0, 1.0, 0.0], [0.2, 0.4, 0.6]]
+        );
+
+        // A wide image split into uneven bands: rows 0–9, 10–19, ... of 30
+        let wide = RgbImage::from_fn(40, 30, |x, y| Rgb([(y / 10) as u8 * 100, x as u8, 0]));
+        let colors = extract_colors_from_rgb(&wide, 3);
+        let expect = |band: u8| [band as f32 * 100.0 / 255.0, 20.0 / 255.0, 0.0];
+        assert_eq!(colors, vec![expect(0), expect(1), expect(2)]);
+    }
+
+    #[test]
+    fn degenerate_in_memory_images_give_fewer_colors() {
+        let strip = RgbImage::from_pixel(1, 2, Rgb([10, 20, 30]));
+        assert_eq!(extract_colors_from_rgb(&strip, 4).len(), 2);
+        assert!(extract_colors_from_rgb(&strip, 0).is_empty());
+        assert!(extract_colors_from_rgb(&RgbImage::new(0, 0), 4).is_empty());
+        assert!(extract_colors_from_rgb(&RgbImage::new(0, 4), 4).is_empty());
+        assert!(extract_colors_from_rgb(&RgbImage::new(4, 0), 4).is_empty());
+    }
+
+    #[test]
+    fn band_sample_points_stay_inside_the_image() {
+        for height in 0..=9 {
+            for n in 0..=6 {
+                let points: Vec<_> = band_sample_points(3, height, n).collect();
+                assert_eq!(points.len(), n.min(height as usize));
+                assert!(points.iter().all(|&(x, y)| x == 1 && y < height));
+            }
+        }
+    }
+
+    #[test]
+    fn grid_cells_are_sampled_row_by_row() {
+        // 2x2 grid of 3x2 px cells, one pixel short in each direction
+        let img = RgbImage::from_fn(5, 3, |x, y| match (x < 3, y < 2) {
+            (true, true) => image::Rgb([255, 0, 0]),
+            (false, true) => image::Rgb([0, 255, 0]),
+            (true, false) => image::Rgb([0, 0, 255]),
+            (false, false) => image::Rgb([255, 255, 255]),
+        });
+        let path = std::env::temp_dir().join("wallrus_test_grid_palette.png");
+        img.save(&path).unwrap();
+        let colors = extract_colors_grid(&path, 2, 2);
+        let clamped = extract_colors_grid(&path, 8, 1).map(|c| c.len());
+        let _ = std::fs::remove_file(&path);
+
+        let expected = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ];
+        assert_eq!(colors.unwrap(), expected);
+        assert_eq!(clamped, Ok(5));
+        for (width, height) in [(0, 4), (4, 0), (7, 3)] {
+            assert!(grid_sample_points(width, height, 4, 4).all(|(x, y)| x < width && y < height));
+        }
+    }
+
+    #[test]
+    fn images_without_extension_are_sniffed() {
+        let strip = image::RgbImage::from_fn(1, 4, |_, y| image::Rgb([0, 0, y as u8 * 60]));
+        let dir = std::env::temp_dir().join("wallrus_test_sniffed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let png = dir.join("strip.png");
+        strip.save(&png).unwrap();
+        let bare = dir.join("strip");
+        let misnamed = dir.join("strip.dat");
+        let text = dir.join("notes");
+        std::fs::copy(&png, &bare).unwrap();
+        std::fs::copy(&png, &misnamed).unwrap();
+        std::fs::write(&text, b"not an image").unwrap();
+
+        let listed = [&bare, &misnamed, &text].map(|path| is_image_file(path));
+        let colors = extract_four_colors(&misnamed);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(listed, [true, true, false]);
+        assert_eq!(colors.unwrap()[3], [0.0, 0.0, 180.0 / 255.0]);
+    }
+
+    #[test]
+    fn exif_orientation_turns_photos_upright() {
+        use image::ImageEncoder;
+
+        // Stored 16x8, red on the left and blue on the right, tagged
+        // orientation 6 (rotate 90° clockwise to view) as phones do
+        let stored = image::RgbImage::from_fn(16, 8, |x, _| match x < 8 {
+            true => image::Rgb([255, 0, 0]),
+            false => image::Rgb([0, 0, 255]),
+        });
+        #[rustfmt::skip]
+        let exif = vec![
+            b'I', b'I', 0x2a, 0x00, 0x08, 0x00, 0x00, 0x00, // little-endian TIFF, IFD at 8
+            0x01, 0x00, // one entry
+            0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, // Orientation = 6
+            0x00, 0x00, 0x00, 0x00, // no next IFD
+        ];
+        let path = std::env::temp_dir().join("wallrus_test_exif_orientation.jpg");
+        let file = std::fs::File::create(&path).unwrap();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, 95);
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(stored.as_raw(), 16, 8, image::ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let upright = open_oriented(&path).map(|img| img.to_rgb8());
+        let _ = std::fs::remove_file(&path);
+
+        // The left edge ends up on top
+        let upright = upright.unwrap();
+        assert_eq!(upright.dimensions(), (8, 16));
+        let [r, _, b] = upright.get_pixel(4, 2).0;
+        assert!(r > 200 && b < 60, "top is {:?}", (r, b));
+        let [r, _, b] = upright.get_pixel(4, 13).0;
+        assert!(b > 200 && r < 60, "bottom is {:?}", (r, b));
+    }
+
+    #[test]
+    fn source_thumbnails_sit_beside_palettes_but_are_not_listed() {
+        let dir = std::env::temp_dir().join("wallrus_test_source_thumbnail");
+        std::fs::create_dir_all(&dir).unwrap();
+        let palette = dir.join("palette_1.png");
+        let photo = dir.join("photo.png");
+        image::RgbImage::from_pixel(1, 4, image::Rgb([10, 20, 30]))
+            .save(&palette)
+            .unwrap();
+        image::RgbImage::from_pixel(640, 480, image::Rgb([200, 100, 50]))
+            .save(&photo)
+            .unwrap();
+
+        let saved = save_source_thumbnail(&palette, &photo);
+        let thumbnail = source_thumbnail(&palette);
+        let size = thumbnail
+            .as_ref()
+            .and_then(|t| image::image_dimensions(t).ok());
+        let listed = thumbnail.as_ref().map(|t| is_image_file(t));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(saved.is_ok());
+        assert_eq!(thumbnail, Some(dir.join("palette_1.png.src.jpg")));
+        assert_eq!(size, Some((SOURCE_THUMBNAIL_SIZE, 192)));
+        assert_eq!(listed, Some(false));
+        // Palettes that differ only in extension keep separate thumbnails
+        assert_ne!(
+            source_thumbnail_path(&dir.join("foo.png")),
+            source_thumbnail_path(&dir.join("foo.jpg"))
+        );
+    }
+
+    #[test]
+    fn bmp_and_tiff_strips_are_extracted() {
+        let rows = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        let strip = image::RgbImage::from_fn(1, 4, |_, y| image::Rgb(rows[y as usize]));
+        for ext in ["bmp", "tiff"] {
+            let path = std::env::temp_dir().join(format!("wallrus_test_strip_palette.{}", ext));
+            strip.save(&path).unwrap();
+            assert!(is_image_file(&path));
+            let colors = extract_four_colors(&path);
+            let _ = std::fs::remove_file(&path);
+
+            let colors = colors.unwrap();
+            assert_eq!(colors[0], [1.0, 0.0, 0.0]);
+            assert_eq!(colors[2], [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn unreadable_images_are_listed_but_fail_to_extract() {
+        let root = std::env::temp_dir().join("wallrus_test_unreadable_images");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Warm")).unwrap();
+        std::fs::write(root.join("Warm").join("broken.tif"), b"not a tiff").unwrap();
+        std::fs::write(root.join("Warm").join("notes.txt"), b"").unwrap();
+
+        let mut categories = PaletteCategories::new();
+        collect_categorized_images(&root, &mut categories);
+        let listed = categories["Warm"].clone();
+        let result = extract_four_colors(&listed[0]);
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(listed.len(), 1);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn json_and_toml_definitions_parse() {
+        let json = r##"{ "name": "Dusk", "version": 2, "tags": {"a": [1, "]"]},
+            "colors": ["#1d2b53", "#7E2553", "ff004d", "#fa3"] }"##;
+        let fields = parse_json_object_strings(json).unwrap();
+        assert_eq!(fields[0], ("name".to_string(), vec!["Dusk".to_string()]));
+        assert_eq!(fields[1].1.len(), 4);
+        assert_eq!(parse_json_object_strings(r##"["#123456"]"##), None);
+        assert_eq!(
+            parse_json_object_strings(r##"{"colors": ["#123456",]}"##),
+            None
+        );
+        let json = r##"{ "weights": [0.1, 0.2], "colors": ["#123456"] }"##;
+        let fields = parse_json_object_strings(json).unwrap();
+        assert_eq!(
+            fields,
+            vec![("colors".to_string(), vec!["#123456".to_string()])]
+        );
+
+        let toml =
+            "# Evening\nname = 'Dusk'\ncolors = [\n  \"#1d2b53\", # first\n  \"#7e2553\",\n]\n";
+        let fields = parse_toml_strings(toml).unwrap();
+        assert_eq!(fields[0], ("name".to_string(), vec!["Dusk".to_string()]));
+        assert_eq!(
+            fields[1].1,
+            vec!["#1d2b53".to_string(), "#7e2553".to_string()]
+        );
+        assert_eq!(parse_toml_strings("[palette]\ncolors = []"), None);
+        let toml = "version = 1\nweights = [0.1, 0.2] # mix\nmeta = { a = \"}\" }\n\
+                    colors = ['#123456']\n";
+        let fields = parse_toml_strings(toml).unwrap();
+        assert_eq!(
+            fields,
+            vec![("colors".to_string(), vec!["#123456".to_string()])]
+        );
+        assert_eq!(
+            parse_toml_strings("colors = [\"#123456\" \"#654321\"]"),
+            None
+        );
+    }
+
+    #[test]
+    fn definitions_are_listed_and_invalid_ones_skipped() {
+        let root = std::env::temp_dir().join("wallrus_test_palette_definitions");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("Text")).unwrap();
+        let dir = root.join("Text");
+        std::fs::write(
+            dir.join("dusk.json"),
+            r##"{"colors": ["#ff0000", "#0000ff"]}"##,
+        )
+        .unwrap();
+        std::fs::write(dir.join("sea.toml"), "colors = [\"#00ff00\"]\n").unwrap();
+        std::fs::write(dir.join("broken.json"), r##"{"colors": ["#nope"]}"##).unwrap();
+        std::fs::write(dir.join("empty.toml"), "name = \"Empty\"\n").unwrap();
+
+        let mut categories = PaletteCategories::new();
+        collect_categorized_images(&root, &mut categories);
+        let mut listed = categories["Text"].clone();
+        listed.sort();
+        let colors: Vec<_> = listed.iter().map(|p| extract_four_colors(p)).collect();
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(listed.len(), 2);
+        let reds = colors[0].as_ref().unwrap();
+        assert_eq!(reds[0], [1.0, 0.0, 0.0]);
+        assert_eq!(reds[3], [0.0, 0.0, 1.0]);
+        assert_eq!(colors[1].as_ref().unwrap()[0], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn classifies_strips_gradients_and_photos() {
+        let strip = image::RgbImage::from_fn(2, 40, |_, y| image::Rgb([(y / 10 * 60) as u8; 3]));
+        assert_eq!(classify_rgb(&strip), PaletteImageKind::Strip);
+
+        let gradient = image::RgbImage::from_fn(1, 256, |_, y| image::Rgb([y as u8; 3]));
+        assert_eq!(classify_rgb(&gradient), PaletteImageKind::IrregularStrip);
+
+        let photo = image::RgbImage::from_pixel(64, 48, image::Rgb([10, 20, 30]));
+        assert_eq!(classify_rgb(&photo), PaletteImageKind::Photo);
+
+        // Single-pixel images are still valid (if short) strips
+        let dot = image::RgbImage::from_pixel(1, 1, image::Rgb([255, 0, 0]));
+        assert_eq!(classify_rgb(&dot), PaletteImageKind::Strip);
+    }
+
+    #[test]
+    fn color_cache_invalidates_on_mtime_change() {
+        let dir = std::env::temp_dir().join("wallrus_test_color_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("palette.png");
+        let cache_file = dir.join("palette-colors");
+
+        image::RgbImage::from_pixel(1, 4, image::Rgb([255, 0, 0]))
+            .save(&image)
+            .unwrap();
+        let mut cache = ColorCache::load(cache_file.clone());
+        assert_eq!(cache.colors(&image).unwrap()[0], [1.0, 0.0, 0.0]);
+
+        // A reloaded cache serves the entry from disk
+        let mut reloaded = ColorCache::load(cache_file.clone());
+        assert!(reloaded.entries.contains_key(&image));
+
+        image::RgbImage::from_pixel(1, 4, image::Rgb([0, 0, 255]))
+            .save(&image)
+            .unwrap();
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&image)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(reloaded.colors(&image).unwrap()[0], [0.0, 0.0, 1.0]);
+
+        // The stale line is dropped on the next load
+        let compacted = ColorCache::load(cache_file.clone());
+        assert_eq!(compacted.entries[&image].1[0], [0.0, 0.0, 1.0]);
+        assert_eq!(
+            std::fs::read_to_string(&cache_file)
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn hex_colors_parse_all_forms() {
+        assert_eq!(hex_to_rgb("#FF8800"), Ok([1.0, 136.0 / 255.0, 0.0]));
+        assert_eq!(hex_to_rgb("f80"), hex_to_rgb("#ff8800"));
+        assert_eq!(hex_to_rgb(" #FF880080 "), hex_to_rgb("#FF8800"));
+        for bad in ["", "#", "#FF88", "#FF880", "#GG8800", "#FF8800800", "#ÿÿÿ"] {
+            assert!(hex_to_rgb(bad).is_err(), "{:?}", bad);
+        }
+        let color = [0.8, 0.2, 0.0];
+        assert_eq!(rgb_to_hex(color), "#CC3300");
+        assert_close(hex_to_rgb(&rgb_to_hex(color)).unwrap(), color);
+    }
+
+    #[test]
+    fn pywal_colors_parse_in_order() {
+        let mut colors = String::new();
+        // Reverse order, to check keys are matched by number, not position
+        for i in (0..16).rev() {
+            colors.push_str(&format!("\"color{}\": \"#{:02x}0000\",\n", i, i));
+        }
+        let json = format!(
+            "{{\"wallpaper\": \"/tmp/colors\", \"special\": {{\"background\": \"#1d1f21\"}},\n\
+             \"colors\": {{\n{}\"ignored\": \"x\"}}}}",
+            colors
+        );
+        let parsed = parse_pywal(&json).unwrap();
+        assert_eq!(parsed.len(), 16);
+        assert_eq!(parsed[0], [0.0, 0.0, 0.0]);
+        assert_eq!(parsed[15], [15.0 / 255.0, 0.0, 0.0]);
+
+        assert!(parse_pywal("{\"colors\": {\"color0\": \"#000000\"}}").is_err());
+        assert!(parse_pywal("{}").is_err());
+    }
+
+    #[test]
+    fn distinct_indices_skip_repeated_colors() {
+        // pywal's bright colors 8-15 often repeat 0-7
+        let base = [
+            [0.1, 0.1, 0.1],
+            [0.8, 0.2, 0.2],
+            [0.2, 0.8, 0.2],
+            [0.9, 0.9, 0.9],
+        ];
+        let colors: Vec<[f32; 3]> = base.iter().chain(base.iter()).copied().collect();
+        assert_eq!(distinct_color_indices(&colors, 4), vec![0, 1, 2, 3]);
+        assert_eq!(distinct_color_indices(&colors[..2], 4), vec![0, 1]);
+        assert!(distinct_color_indices(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn cvd_keeps_grays_and_merges_red_green() {
+        let grays = [[0.0; 3], [0.5; 3], [1.0; 3]];
+        for kind in CvdType::all() {
+            for (sim, gray) in simulate_cvd(&grays, kind).into_iter().zip(grays) {
+                for c in 0..3 {
+                    assert!(
+                        (sim[c] - gray[c]).abs() < 0.02,
+                        "{:?}: {:?} -> {:?}",
+                        kind,
+                        gray,
+                        sim
+                    );
+                }
+            }
+        }
+
+        // Red and green become near-indistinguishable yellows for red-green deficiencies
+        for kind in [CvdType::Protanopia, CvdType::Deuteranopia] {
+            let sim = simulate_cvd(&[[1.0, 0.0, 0.0], [0.0, 0.5, 0.0]], kind);
+            assert!(
+                sim.iter().all(|c| c[2] < 0.2 && (c[0] - c[1]).abs() < 0.15),
+                "{:?}: {:?}",
+                kind,
+                sim
+            );
+        }
+
+        // Reference value: pure red is a dark olive to a protanope
+        let red = simulate_cvd(&[[1.0, 0.0, 0.0]], CvdType::Protanopia)[0];
+        assert_close(red, [0.3693, 0.3693, 0.0508]);
+    }
+
+    #[test]
+    fn reorder_requires_a_permutation() {
+        let colors = [[0.0; 3], [0.25; 3], [0.5; 3], [1.0; 3]];
+        assert_eq!(reorder_colors(&colors, [3, 2, 1, 0]).unwrap()[0], [1.0; 3]);
+        assert!(reorder_colors(&colors, [0, 0, 1, 2]).is_err());
+        assert!(reorder_colors(&colors, [0, 1, 2, 4]).is_err());
+    }
+
+    #[test]
+    fn sort_by_luminance_and_hue() {
+        let colors = [
+            [1.0, 1.0, 1.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ];
+        let (sorted, order) = sort_colors_by(&colors, SortKey::Luminance);
+        assert_eq!(order, [2, 1, 3, 0]);
+        assert_eq!(sorted, reorder_colors(&colors, order).unwrap());
+
+        // Achromatic colors have hue 0 and keep their relative order
+        let (_, order) = sort_colors_by(&colors, SortKey::Hue);
+        assert_eq!(order, [0, 2, 3, 1]);
+    }
+
+    #[test]
+    fn gradient_order_puts_similar_colors_side_by_side() {
+        let colors = [[0.0; 3], [1.0; 3], [0.1; 3], [0.9; 3]];
+        let order = optimal_gradient_order(&colors);
+        assert_eq!(order, [0, 2, 3, 1]);
+        assert!(reorder_colors(&colors, order).is_ok());
+
+        // An already smooth ramp is left alone
+        let ramp = [[0.0; 3], [0.3; 3], [0.6; 3], [1.0; 3]];
+        assert_eq!(optimal_gradient_order(&ramp), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn light_variant_is_lighter_and_dark_is_unchanged() {
+        let colors = [
+            [0.1, 0.2, 0.5],
+            [0.6, 0.2, 0.2],
+            [0.3, 0.3, 0.3],
+            [0.9, 0.8, 0.1],
+        ];
+        assert_eq!(Variant::Dark.apply(&colors), colors);
+
+        let light = Variant::Light.apply(&colors);
+        assert_eq!(light, Variant::Light.apply(&colors));
+        for (l, c) in light.iter().zip(&colors) {
+            assert!(rgb_to_lab(*l)[0] > rgb_to_lab(*c)[0]);
+        }
+    }
+
+    #[test]
+    fn extract_order_sorts_by_luminance() {
+        let white = [1.0, 1.0, 1.0];
+        let black = [0.0, 0.0, 0.0];
+        let red = [1.0, 0.0, 0.0];
+        let colors = vec![white, red, black, white];
+
+        let mut as_is = colors.clone();
+        ExtractOrder::AsIs.apply(&mut as_is);
+        assert_eq!(as_is, colors);
+
+        let mut dark_first = colors.clone();
+        ExtractOrder::DarkToLight.apply(&mut dark_first);
+        assert_eq!(dark_first, [black, red, white, white]);
+
+        let mut light_first = colors;
+        ExtractOrder::LightToDark.apply(&mut light_first);
+        assert_eq!(light_first, [white, white, red, black]);
+
+        assert_eq!(
+            ExtractOrder::parse("Dark-To-Light"),
+            Ok(ExtractOrder::DarkToLight)
+        );
+        assert!(ExtractOrder::parse("sideways").is_err());
+    }
+
+    #[test]
+    fn random_palette_is_reproducible_and_constrained() {
+        let constraint = PaletteConstraint {
+            seed: 42,
+            min_contrast: 2.0,
+            lightness: None,
+            base_hue: None,
+        };
+        let palette = random_palette(constraint);
+        assert_eq!(palette, random_palette(constraint));
+        assert_ne!(
+            palette,
+            random_palette(PaletteConstraint {
+                seed: 43,
+                ..constraint
+            })
+        );
+        for pair in palette.windows(2) {
+            assert!(contrast_ratio(pair[0], pair[1]) >= 2.0, "{:?}", palette);
+        }
+
+        let dark = random_palette(PaletteConstraint {
+            lightness: Some(ThemeLightness::Dark),
+            ..constraint
+        });
+        assert!(
+            dark.iter().all(|c| rgb_to_hsl(*c)[2] <= 0.45 + 1e-4),
+            "{:?}",
+            dark
+        );
+
+        let hued = random_palette(PaletteConstraint {
+            base_hue: Some(200.0),
+            min_contrast: 1.0,
+            ..constraint
+        });
+        assert!((rgb_to_hsl(hued[0])[0] - 200.0).abs() < 0.5, "{:?}", hued);
+    }
+
+    #[test]
+    fn contrast_matches_wcag_examples() {
+        let white = [1.0; 3];
+        assert!((contrast_ratio([0.0; 3], white) - 21.0).abs() < 1e-3);
+        // #777777 on white just misses AA; #767676 is the usual passing gray
+        let gray = |v: u8| u8_to_rgb([v; 3]);
+        assert!((contrast_ratio(gray(0x77), white) - 4.48).abs() < 0.01);
+        assert!(contrast_ratio(gray(0x76), white) >= WCAG_AA_CONTRAST);
+        assert!((contrast_ratio(gray(0x59), white) - 7.0).abs() < 0.01);
+
+        let colors = [[0.0; 3], gray(0x77), [1.0, 0.0, 0.0], white];
+        let matrix = palette_contrast_matrix(&colors);
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row[i], 1.0);
+            assert!(row
+                .iter()
+                .zip(&matrix)
+                .all(|(ratio, other)| *ratio == other[i]));
+        }
+        assert!((matrix[2][3] - 4.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn category_folders_are_managed_in_root() {
+        let root = std::env::temp_dir().join("wallrus_test_categories");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert_eq!(create_category_in(&root, " Ocean/ ").unwrap(), "Ocean");
+        assert!(create_category_in(&root, "OCEAN").is_err());
+        let mut categories = PaletteCategories::new();
+        collect_categorized_images(&root, &mut categories);
+        assert_eq!(categories.get("Ocean"), Some(&Vec::new()));
+
+        std::fs::write(root.join("ocean").join("blue.png"), b"").unwrap();
+        assert_eq!(rename_category_in(&root, "ocean", "Sea").unwrap(), "Sea");
+        assert!(root.join("sea").join("blue.png").exists());
+        assert!(rename_category_in(&root, "missing", "Other").is_err());
+        assert!(rename_category_in(&root, "Custom", "Other").is_err());
+
+        create_category_in(&root, "Lake").unwrap();
+        assert!(rename_category_in(&root, "Lake", "sea").is_err());
+        assert!(create_category_in(&root, "favorites").is_err());
+        assert!(rename_category_in(&root, "Lake", "FAVORITES").is_err());
+        assert!(create_category_in(&root, " Recent ").is_err());
+        assert!(rename_category_in(&root, "Lake", "recent").is_err());
+        delete_category_in(&root, "Lake", false).unwrap();
+
+        assert!(delete_category_in(&root, "Sea", false).is_err());
+        delete_category_in(&root, "Sea", true).unwrap();
+        assert!(!root.join("sea").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn favorites_toggle_and_round_trip() {
+        let file = std::env::temp_dir().join("wallrus_test_favorites.json");
+        let _ = std::fs::remove_file(&file);
+        let a = PathBuf::from("/palettes/warm/sun \"set\".png");
+        let b = PathBuf::from("/palettes/cold/ice\\berg.png");
+
+        assert!(toggle_favorite_in(&file, &a).unwrap());
+        assert!(toggle_favorite_in(&file, &b).unwrap());
+        assert_eq!(read_path_list(&file), vec![a.clone(), b.clone()]);
+        assert!(!toggle_favorite_in(&file, &a).unwrap());
+        assert_eq!(read_path_list(&file), vec![b]);
+        let _ = std::fs::remove_file(&file);
+
+        assert_eq!(parse_json_strings("[]"), Some(vec![]));
+        assert_eq!(
+            parse_json_strings(r#"["\u00e9"]"#),
+            Some(vec!["é".to_string()])
+        );
+        assert_eq!(parse_json_strings(r#"["a" "b"]"#), None);
+        assert_eq!(parse_json_strings("{}"), None);
+    }
+
+    #[test]
+    fn favorites_are_listed_first() {
+        let mut categories = PaletteCategories::new();
+        categories.insert("Cold".to_string(), vec![]);
+        categories.insert(FAVORITES_CATEGORY.to_string(), vec![]);
+        categories.insert("Warm".to_string(), vec![]);
+        assert_eq!(category_names(&categories), ["Favorites", "Cold", "Warm"]);
+        categories.insert(RECENT_CATEGORY.to_string(), vec![]);
+        assert_eq!(
+            category_names(&categories),
+            ["Recent", "Favorites", "Cold", "Warm"]
+        );
+    }
+
+    #[test]
+    fn recent_palettes_move_to_front_and_are_capped() {
+        let file = std::env::temp_dir().join("wallrus_test_recent.json");
+        let _ = std::fs::remove_file(&file);
+        let paths: Vec<PathBuf> = (0..RECENT_LIMIT + 2)
+            .map(|i| PathBuf::from(format!("/palettes/p{}.png", i)))
+            .collect();
+        for path in &paths {
+            record_recent_in(&file, path).unwrap();
+        }
+        let recent = read_path_list(&file);
+        assert_eq!(recent.len(), RECENT_LIMIT);
+        assert_eq!(recent[0], paths[RECENT_LIMIT + 1]);
+
+        let again = record_recent_in(&file, &paths[5]).unwrap();
+        assert_eq!(again[0], paths[5]);
+        assert_eq!(again.iter().filter(|p| **p == paths[5]).count(), 1);
+        assert_eq!(again.len(), RECENT_LIMIT);
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn scheme_ids_round_trip() {
+        for scheme in HarmonyScheme::all() {
+            assert_eq!(HarmonyScheme::from_id(scheme.id()), Some(scheme));
+        }
+    }
+
+    #[test]
+    fn duplicate_palette_found_within_tolerance() {
+        let dir = std::env::temp_dir().join("wallrus_test_duplicates");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let colors = [
+            [0.8, 0.33, 0.0],
+            [0.93, 0.53, 0.07],
+            [1.0, 0.75, 0.15],
+            [1.0, 0.92, 0.35],
+        ];
+        let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(1, 4);
+        for (i, color) in colors.iter().enumerate() {
+            img.put_pixel(0, i as u32, Rgb(rgb_to_u8(*color)));
+        }
+        let saved = dir.join("palette_1.png");
+        img.save(&saved).unwrap();
+
+        let nudged = colors.map(|c| c.map(|v| (v - 0.5 / 255.0).max(0.0)));
+        let mut different = colors;
+        different[2] = [0.0, 0.0, 1.0];
+        let found = find_duplicate_in(&dir, &nudged);
+        let missing = find_duplicate_in(&dir, &different);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(found, Some(saved));
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    fn filter_matches_names_and_categories() {
+        let mut categories = PaletteCategories::new();
+        categories.insert(
+            "Cold".to_string(),
+            vec![
+                PathBuf::from("/p/cold/Glacier.png"),
+                PathBuf::from("/p/cold/ice.png"),
+            ],
+        );
+        categories.insert("Warm".to_string(), vec![PathBuf::from("/p/warm/ember.png")]);
+
+        let by_name = filter_palettes(&categories, "GLAC");
+        assert_eq!(by_name.len(), 1);
+        assert_eq!(by_name["Cold"], vec![PathBuf::from("/p/cold/Glacier.png")]);
+
+        let by_category = filter_palettes(&categories, "warm");
+        assert_eq!(by_category.keys().collect::<Vec<_>>(), ["Warm"]);
+
+        assert!(filter_palettes(&categories, "nothing").is_empty());
+        assert_eq!(filter_palettes(&categories, ""), categories);
+    }
+
+    #[test]
+    fn bulk_delete_refuses_palettes_outside_user_dir() {
+        let outside = std::env::temp_dir().join("wallrus_test_bundled.png");
+        std::fs::write(&outside, b"").unwrap();
+        let missing = PathBuf::from("/nonexistent/palette.png");
+
+        let results = delete_palettes(&[outside.clone(), missing.clone()]);
+        let exists = outside.exists();
+        let _ = std::fs::remove_file(&outside);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, outside);
+        assert_eq!(results[1].0, missing);
+        assert!(results.iter().all(|(_, result)| result.is_err()));
+        assert!(exists);
     }
 }
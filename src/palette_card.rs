@@ -0,0 +1,208 @@
+/// Labeled palette cards: each color as a block with its hex code printed
+/// on it, for documentation and sharing. Unlike `palette::save_palette_image`
+/// (the compact 1x4 source image) these are meant to be looked at.
+use std::path::Path;
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+use crate::palette;
+
+/// Which way the color blocks run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CardLayout {
+    /// Blocks side by side, left to right
+    #[default]
+    Horizontal,
+    /// Blocks stacked, top to bottom
+    Vertical,
+}
+
+impl CardLayout {
+    pub fn id(self) -> &'static str {
+        match self {
+            CardLayout::Horizontal => "horizontal",
+            CardLayout::Vertical => "vertical",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::all().into_iter().find(|layout| layout.id() == id)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CardLayout::Horizontal => "Horizontal",
+            CardLayout::Vertical => "Vertical",
+        }
+    }
+
+    pub fn all() -> [Self; 2] {
+        [CardLayout::Horizontal, CardLayout::Vertical]
+    }
+
+    /// Card size used when none is chosen: square blocks of 400 px for a
+    /// four-color palette
+    pub fn default_size(self) -> (u32, u32) {
+        match self {
+            CardLayout::Horizontal => (1600, 400),
+            CardLayout::Vertical => (400, 1600),
+        }
+    }
+}
+
+/// How a palette card is laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteCardOptions {
+    pub layout: CardLayout,
+    /// Size of the whole card in pixels, split evenly between the colors
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PaletteCardOptions {
+    pub fn new(layout: CardLayout) -> Self {
+        let (width, height) = layout.default_size();
+        Self {
+            layout,
+            width,
+            height,
+        }
+    }
+}
+
+/// Glyph size of the embedded bitmap font, in font pixels
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+/// Font pixels between glyphs
+const GLYPH_SPACING: u32 = 1;
+
+/// A 5x7 bitmap font with just the characters hex codes need. Each row is
+/// the glyph's 5 columns in the low bits, most significant bit leftmost.
+fn glyph(c: char) -> Option<[u8; 7]> {
+    Some(match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        _ => return None,
+    })
+}
+
+/// Width of `text` in font pixels; characters without a glyph still take
+/// up their space
+fn text_width(text: &str) -> u32 {
+    let chars = text.chars().count() as u32;
+    (chars * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING)
+}
+
+/// Draw `text` with its top-left corner at (`x`, `y`), each font pixel
+/// `scale` image pixels square. Pixels outside the image are skipped.
+fn draw_text(img: &mut RgbImage, text: &str, x: u32, y: u32, scale: u32, color: Rgb<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let Some(rows) = glyph(c) else {
+            continue;
+        };
+        let glyph_x = x + i as u32 * (GLYPH_WIDTH + GLYPH_SPACING) * scale;
+        for (row, bits) in rows.iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                let px = glyph_x + col * scale;
+                let py = y + row as u32 * scale;
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        if px + dx < img.width() && py + dy < img.height() {
+                            img.put_pixel(px + dx, py + dy, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Build the card image: one block per color, its hex code centered near the
+/// bottom in black or white, whichever contrasts more.
+pub fn render_palette_card(
+    colors: &[[f32; 3]],
+    opts: &PaletteCardOptions,
+) -> Result<RgbImage, String> {
+    if colors.is_empty() {
+        return Err("Palette has no colors".to_string());
+    }
+    let count = colors.len() as u32;
+    let (along, across) = match opts.layout {
+        CardLayout::Horizontal => (opts.width, opts.height),
+        CardLayout::Vertical => (opts.height, opts.width),
+    };
+    if along < count || across == 0 {
+        return Err(format!(
+            "Palette card of {}x{} is too small for {} colors",
+            opts.width, opts.height, count
+        ));
+    }
+
+    let mut img: RgbImage = ImageBuffer::new(opts.width, opts.height);
+    for (i, &color) in colors.iter().enumerate() {
+        // Block edges rounded so the blocks exactly fill the card
+        let start = along * i as u32 / count;
+        let end = along * (i as u32 + 1) / count;
+        let (x0, y0, block_w, block_h) = match opts.layout {
+            CardLayout::Horizontal => (start, 0, end - start, across),
+            CardLayout::Vertical => (0, start, across, end - start),
+        };
+        let fill = Rgb(palette::rgb_to_u8(color));
+        for y in y0..y0 + block_h {
+            for x in x0..x0 + block_w {
+                img.put_pixel(x, y, fill);
+            }
+        }
+
+        let hex = palette::rgb_to_hex(color);
+        // The label spans at most 60% of the block's width and 15% of its height
+        let scale = (block_w * 3 / 5 / text_width(&hex))
+            .min(block_h * 3 / 20 / GLYPH_HEIGHT)
+            .max(1);
+        let width = text_width(&hex) * scale;
+        let height = GLYPH_HEIGHT * scale;
+        let margin = (block_h / 10).max(scale);
+        let text_x = x0 + block_w.saturating_sub(width) / 2;
+        let text_y = y0 + block_h.saturating_sub(height + margin);
+        let text_color = if palette::contrast_ratio(color, [0.0; 3])
+            >= palette::contrast_ratio(color, [1.0; 3])
+        {
+            Rgb([0, 0, 0])
+        } else {
+            Rgb([255, 255, 255])
+        };
+        draw_text(&mut img, &hex, text_x, text_y, scale, text_color);
+    }
+    Ok(img)
+}
+
+/// Render a palette card and save it to `out`; the format follows the
+/// extension (PNG, JPEG, ...).
+pub fn export_palette_card(
+    colors: &[[f32; 3]],
+    out: &Path,
+    opts: &PaletteCardOptions,
+) -> Result<(), String> {
+    render_palette_card(colors, opts)?
+        .save(out)
+        .map_err(|e| format!("Failed to save palette card: {}", e))
+}
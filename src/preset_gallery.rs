@@ -0,0 +1,122 @@
+/// Small renders of every preset with the current palette, for the preset
+/// gallery. Thumbnails are cached as PNGs under the user cache directory
+/// (`~/.cache/wallrus/preset-thumbnails`), keyed by a hash of the preset's
+/// shader source and the palette, so reopening Wallrus with the same palette
+/// doesn't render them again.
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use gtk4::glib;
+
+use crate::export::{self, ExportFormat};
+use crate::gl_renderer::{self, OffscreenContext, RendererState};
+use crate::palette;
+use crate::shader_presets::{self, PresetEntry};
+
+/// Thumbnail size in pixels, 16:9 like most monitors
+pub const THUMBNAIL_SIZE: (u32, u32) = (128, 72);
+
+/// Thumbnails kept in the cache; the least recently written are removed
+/// past this, so browsing many palettes doesn't grow it without bound
+const CACHE_LIMIT: usize = 300;
+
+/// A thumbnail for each entry, in order: the cached PNG's path, or why the
+/// preset couldn't be rendered. The offscreen renderer is only created if
+/// some thumbnail isn't cached yet.
+pub fn thumbnails(entries: &[PresetEntry], colors: &[[f32; 3]; 4]) -> Vec<Result<PathBuf, String>> {
+    let mut renderer: Option<(RendererState, OffscreenContext)> = None;
+    let thumbnails = entries
+        .iter()
+        .map(|entry| {
+            let source = entry.fragment_source()?;
+            let path = cache_path(entry.name(), &source, colors);
+            if path.exists() {
+                return Ok(path);
+            }
+            if renderer.is_none() {
+                renderer = Some(gl_renderer::create_offscreen_renderer()?);
+            }
+            let (renderer, _) = renderer.as_mut().expect("renderer was just created");
+            render_thumbnail(renderer, entry, colors, &path)?;
+            Ok(path)
+        })
+        .collect();
+    if renderer.is_some() {
+        prune_cache();
+    }
+    thumbnails
+}
+
+/// Render `entry` with its default controls at [`THUMBNAIL_SIZE`] into `path`.
+fn render_thumbnail(
+    renderer: &mut RendererState,
+    entry: &PresetEntry,
+    colors: &[[f32; 3]; 4],
+    path: &Path,
+) -> Result<(), String> {
+    renderer.load_entry(entry)?;
+    let controls = shader_presets::controls_for(entry.name());
+    renderer.scale = controls.scale_range.3 as f32;
+    renderer.speed = controls.speed_range.3 as f32;
+    // A fixed time, so animated presets give the same frame every time
+    renderer.time_override = Some(0.0);
+    [
+        renderer.color1,
+        renderer.color2,
+        renderer.color3,
+        renderer.color4,
+    ] = *colors;
+
+    let (width, height) = THUMBNAIL_SIZE;
+    let pixels = renderer.render_to_pixels(width as i32, height as i32)?;
+    std::fs::create_dir_all(cache_dir())
+        .map_err(|e| format!("Failed to create {}: {}", cache_dir().display(), e))?;
+    export::save_pixels(
+        &pixels,
+        width,
+        height,
+        path,
+        ExportFormat::Png,
+        export::DEFAULT_EXPORT_QUALITY,
+        None,
+    )
+}
+
+fn cache_dir() -> PathBuf {
+    glib::user_cache_dir()
+        .join("wallrus")
+        .join("preset-thumbnails")
+}
+
+/// Cache file for a preset with a palette. The name is part of the key since
+/// it picks the default controls; colors are hashed as hex so float noise
+/// below 8 bits doesn't miss the cache.
+fn cache_path(name: &str, fragment_src: &str, colors: &[[f32; 3]; 4]) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+    fragment_src.hash(&mut hasher);
+    for color in colors {
+        palette::rgb_to_hex(*color).hash(&mut hasher);
+    }
+    THUMBNAIL_SIZE.hash(&mut hasher);
+    cache_dir().join(format!("{:016x}.png", hasher.finish()))
+}
+
+/// Remove the oldest thumbnails beyond [`CACHE_LIMIT`].
+fn prune_cache() {
+    let Ok(entries) = std::fs::read_dir(cache_dir()) else {
+        return;
+    };
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    if files.len() <= CACHE_LIMIT {
+        return;
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - CACHE_LIMIT] {
+        let _ = std::fs::remove_file(path);
+    }
+}
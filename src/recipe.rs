@@ -0,0 +1,221 @@
+/// Wallpaper recipes: everything needed to render a wallpaper again, saved
+/// as a small JSON file (`<name>.wallrus.json`) for sharing:
+///
+/// ```json
+/// {
+///   "format": "wallrus-recipe",
+///   "version": 1,
+///   "app_version": "0.5.0",
+///   "preset": "Plasma",
+///   "colors": ["#1d2b53", "#7e2553", "#ff004d", "#ffa300"],
+///   "color_count": 4,
+///   "color_space": "oklab",
+///   "seed": 1234,
+///   "resolution": [3840, 2160],
+///   "controls": { "uAngle": 0.785, "uScale": 1.5 },
+///   "params": { "uContrast": 1.2 }
+/// }
+/// ```
+///
+/// `controls` holds the shared sliders (angle, scale, time, center, blend,
+/// grain) and the effects (distortion, kaleidoscope, lighting, dither and
+/// Icon Contrast shade), and `params` the preset's own parameters, all by
+/// uniform name. The two halves of `uShadeCenter` are stored as
+/// `uShadeCenter.x` and `uShadeCenter.y`.
+/// Unknown fields are skipped on import, so recipes from newer versions
+/// still load.
+use std::path::Path;
+
+use crate::export::WallpaperMeta;
+use crate::gl_renderer::{RenderSeed, RendererState, MAX_COLOR_COUNT, MIN_COLOR_COUNT};
+use crate::json::{self, Json};
+use crate::palette::{self, ColorSpace};
+
+/// File name suffix of recipes
+pub const RECIPE_EXTENSION: &str = "wallrus.json";
+
+/// Value of the `format` field, telling recipes apart from other JSON
+const RECIPE_FORMAT: &str = "wallrus-recipe";
+
+const RECIPE_VERSION: u32 = 1;
+
+/// A wallpaper's palette, preset and settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recipe {
+    /// Colors, preset, parameters, controls, seed and blend settings, as
+    /// embedded in exported PNGs
+    pub meta: WallpaperMeta,
+    /// Size the wallpaper was rendered at
+    pub resolution: (u32, u32),
+}
+
+impl Recipe {
+    /// The recipe for what `renderer` currently draws, at `resolution`
+    pub fn from_renderer(renderer: &RendererState, resolution: (u32, u32)) -> Self {
+        Self {
+            meta: renderer.wallpaper_meta(),
+            resolution,
+        }
+    }
+}
+
+/// Write `recipe` as JSON to `out`.
+pub fn export_recipe(recipe: &Recipe, out: &Path) -> Result<(), String> {
+    if let Some(dir) = out.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(out, format_recipe(recipe)).map_err(|e| format!("Failed to save recipe: {}", e))
+}
+
+/// Read a recipe written by [`export_recipe`].
+pub fn import_recipe(path: &Path) -> Result<Recipe, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_recipe(&text).map_err(|e| format!("{} is not a valid recipe: {}", path.display(), e))
+}
+
+fn format_recipe(recipe: &Recipe) -> String {
+    let meta = &recipe.meta;
+    let values = |values: &[(String, f32)]| {
+        let fields: Vec<String> = values
+            .iter()
+            .map(|(name, value)| format!("{}: {}", json::quote(name), value))
+            .collect();
+        format!("{{ {} }}", fields.join(", "))
+    };
+    let colors: Vec<String> = meta
+        .colors
+        .iter()
+        .map(|c| json::quote(&palette::rgb_to_hex(*c)))
+        .collect();
+    let fields = [
+        ("format", json::quote(RECIPE_FORMAT)),
+        ("version", RECIPE_VERSION.to_string()),
+        ("app_version", json::quote(&meta.app_version)),
+        ("preset", json::quote(&meta.preset)),
+        ("colors", format!("[{}]", colors.join(", "))),
+        ("color_count", meta.color_count.to_string()),
+        ("color_space", json::quote(meta.color_space.name())),
+        ("seed", meta.seed.to_string()),
+        (
+            "resolution",
+            format!("[{}, {}]", recipe.resolution.0, recipe.resolution.1),
+        ),
+        ("controls", values(&meta.controls)),
+        ("params", values(&meta.params)),
+    ];
+    let lines: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("  {}: {}", json::quote(key), value))
+        .collect();
+    format!("{{\n{}\n}}\n", lines.join(",\n"))
+}
+
+fn parse_recipe(text: &str) -> Result<Recipe, String> {
+    let root = Json::parse(text).ok_or("malformed JSON")?;
+    if !matches!(root, Json::Object(_)) {
+        return Err("expected a JSON object".to_string());
+    }
+    let field = |key: &str| root.get(key);
+
+    if !matches!(field("format"), Some(Json::String(f)) if f == RECIPE_FORMAT) {
+        return Err(format!("missing \"format\": \"{}\"", RECIPE_FORMAT));
+    }
+    let Some(Json::String(preset)) = field("preset") else {
+        return Err("missing preset".to_string());
+    };
+    let colors: Vec<[f32; 3]> = match field("colors") {
+        Some(Json::Array(items)) => items
+            .iter()
+            .map(|item| match item {
+                Json::String(hex) => palette::hex_to_rgb(hex),
+                _ => Err("colors must be hex strings".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err("missing colors".to_string()),
+    };
+    if colors.is_empty() {
+        return Err("missing colors".to_string());
+    }
+
+    let mut meta = WallpaperMeta::new(palette::to_four_colors(&colors), preset);
+    if let Some(Json::String(version)) = field("app_version") {
+        meta.app_version = version.clone();
+    }
+    if let Some(count) = field("color_count").and_then(Json::as_number) {
+        meta.color_count = (count as u32).clamp(MIN_COLOR_COUNT, MAX_COLOR_COUNT);
+    }
+    if let Some(Json::String(space)) = field("color_space") {
+        // A space from a newer version falls back to the default
+        meta.color_space = ColorSpace::parse(space).unwrap_or_default();
+    }
+    if let Some(seed) = field("seed").and_then(Json::as_number) {
+        meta.seed = RenderSeed::parse(&seed.to_string())?;
+    }
+    meta.params = field("params").map(Json::numbers).unwrap_or_default();
+    meta.controls = field("controls").map(Json::numbers).unwrap_or_default();
+
+    let resolution = match field("resolution") {
+        Some(Json::Array(items)) => match items.as_slice() {
+            [w, h] => match (w.as_number(), h.as_number()) {
+                (Some(w), Some(h)) if w >= 1.0 && h >= 1.0 => (w as u32, h as u32),
+                _ => return Err("resolution must be two positive numbers".to_string()),
+            },
+            _ => return Err("resolution must be [width, height]".to_string()),
+        },
+        _ => return Err("missing resolution".to_string()),
+    };
+
+    Ok(Recipe { meta, resolution })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipes_round_trip() {
+        let colors = ["#1d2b53", "#7e2553", "#ff004d", "#ffa300"]
+            .map(|hex| palette::hex_to_rgb(hex).unwrap());
+        let mut meta = WallpaperMeta::new(colors, "Plasma");
+        meta.params = vec![("uContrast".to_string(), 1.2)];
+        meta.seed = RenderSeed(1234);
+        meta.color_space = ColorSpace::Oklab;
+        meta.color_count = 3;
+        meta.controls = vec![
+            ("uAngle".to_string(), 0.785),
+            ("uShadeCenter.x".to_string(), -0.5),
+            ("uSymmetry".to_string(), 6.0),
+        ];
+        let recipe = Recipe {
+            meta,
+            resolution: (3840, 2160),
+        };
+        assert_eq!(parse_recipe(&format_recipe(&recipe)), Ok(recipe));
+    }
+
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let recipe = parse_recipe(
+            r##"{
+                "format": "wallrus-recipe",
+                "version": 2,
+                "preset": "Waves",
+                "colors": ["#000000", "#ffffff"],
+                "color_space": "hsluv",
+                "resolution": [1920, 1080],
+                "layers": [{ "blend": "screen" }],
+                "controls": { "uScale": 2, "uTint": "warm" }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(recipe.meta.preset, "Waves");
+        assert_eq!(recipe.meta.colors[3], [1.0, 1.0, 1.0]);
+        assert_eq!(recipe.meta.color_space, ColorSpace::default());
+        assert_eq!(recipe.meta.controls, vec![("uScale".to_string(), 2.0)]);
+        assert_eq!(recipe.resolution, (1920, 1080));
+
+        assert!(parse_recipe(r#"{ "format": "wallrus-look" }"#).is_err());
+    }
+}
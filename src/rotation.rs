@@ -0,0 +1,245 @@
+/// Wallpaper rotation: on a timer, the next palette from a category is
+/// rendered with a chosen preset and set as the wallpaper. The timer runs
+/// on the application's main loop, so rotation stops when Wallrus closes.
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use gtk4::glib;
+use rand::seq::SliceRandom;
+
+use crate::export::{self, ExportFormat};
+use crate::gl_renderer;
+use crate::palette;
+use crate::wallpaper::{self, WallpaperMode};
+
+/// Shortest and longest allowed rotation intervals, in minutes
+pub const MIN_INTERVAL_MINUTES: u32 = 1;
+pub const MAX_INTERVAL_MINUTES: u32 = 24 * 60;
+
+/// Size used when no monitor is reported
+const FALLBACK_SIZE: (u32, u32) = (1920, 1080);
+
+/// The order palettes are picked from the category
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RotationOrder {
+    /// In the category's display order, wrapping around
+    #[default]
+    Sequential,
+    /// Every palette once in random order, then reshuffled
+    Shuffled,
+}
+
+impl RotationOrder {
+    pub const ALL: [RotationOrder; 2] = [RotationOrder::Sequential, RotationOrder::Shuffled];
+
+    /// Name stored in the saved settings
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationOrder::Sequential => "sequential",
+            RotationOrder::Shuffled => "shuffled",
+        }
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            RotationOrder::Sequential => "Sequential",
+            RotationOrder::Shuffled => "Shuffled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|order| order.name() == s)
+    }
+}
+
+/// What to rotate through and how often
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotationSettings {
+    pub enabled: bool,
+    pub category: String,
+    pub preset: String,
+    pub interval_minutes: u32,
+    pub order: RotationOrder,
+}
+
+impl Default for RotationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            category: String::new(),
+            preset: String::from("Bars"),
+            interval_minutes: 30,
+            order: RotationOrder::default(),
+        }
+    }
+}
+
+/// Runs the rotation timer and remembers which palettes are still due.
+pub struct Rotation {
+    settings: RefCell<RotationSettings>,
+    mode: Rc<Cell<WallpaperMode>>,
+    on_set: Box<dyn Fn(&Path)>,
+    on_error: Box<dyn Fn(&str)>,
+    /// Palettes left before the category starts over
+    queue: RefCell<Vec<PathBuf>>,
+    /// The palette set most recently, where sequential order continues from
+    last: RefCell<Option<PathBuf>>,
+    timer: RefCell<Option<glib::SourceId>>,
+}
+
+impl Rotation {
+    /// Start rotating if `settings` has it enabled. `mode` is the fit mode
+    /// the wallpaper is set with; `on_set` is given each rendered wallpaper
+    /// once it's set, and `on_error` reports failed rotations.
+    pub fn new(
+        settings: RotationSettings,
+        mode: Rc<Cell<WallpaperMode>>,
+        on_set: impl Fn(&Path) + 'static,
+        on_error: impl Fn(&str) + 'static,
+    ) -> Rc<Self> {
+        let rotation = Rc::new(Self {
+            settings: RefCell::new(settings),
+            mode,
+            on_set: Box::new(on_set),
+            on_error: Box::new(on_error),
+            queue: RefCell::new(Vec::new()),
+            last: RefCell::new(None),
+            timer: RefCell::new(None),
+        });
+        rotation.restart_timer();
+        rotation
+    }
+
+    pub fn settings(&self) -> RotationSettings {
+        self.settings.borrow().clone()
+    }
+
+    /// Apply new settings. The timer restarts from the new interval, and a
+    /// changed category or order starts the rotation over.
+    pub fn set_settings(self: &Rc<Self>, settings: RotationSettings) {
+        {
+            let current = self.settings.borrow();
+            if current.category != settings.category || current.order != settings.order {
+                self.queue.borrow_mut().clear();
+            }
+        }
+        *self.settings.borrow_mut() = settings;
+        self.restart_timer();
+    }
+
+    /// Set the next palette as the wallpaper now. The render happens
+    /// synchronously; setting the wallpaper finishes on the main loop.
+    pub fn advance(self: &Rc<Self>) {
+        let result = self.next_palette().and_then(|palette| {
+            let settings = self.settings.borrow();
+            render_wallpaper(&palette, &settings.preset)
+        });
+        let path = match result {
+            Ok(path) => path,
+            Err(e) => {
+                (self.on_error)(&format!("Wallpaper rotation failed: {}", e));
+                return;
+            }
+        };
+
+        let rotation = self.clone();
+        let mode = self.mode.get();
+        glib::MainContext::default().spawn_local(async move {
+            match wallpaper::set_wallpaper(&path, mode).await {
+                Ok(()) => (rotation.on_set)(&path),
+                Err(e) => (rotation.on_error)(&format!("Wallpaper rotation failed: {}", e)),
+            }
+        });
+    }
+
+    fn restart_timer(self: &Rc<Self>) {
+        if let Some(id) = self.timer.borrow_mut().take() {
+            id.remove();
+        }
+        let settings = self.settings.borrow();
+        if !settings.enabled {
+            return;
+        }
+
+        let minutes = settings
+            .interval_minutes
+            .clamp(MIN_INTERVAL_MINUTES, MAX_INTERVAL_MINUTES);
+        let weak = Rc::downgrade(self);
+        let id = glib::timeout_add_seconds_local(minutes * 60, move || match weak.upgrade() {
+            Some(rotation) => {
+                rotation.advance();
+                glib::ControlFlow::Continue
+            }
+            None => glib::ControlFlow::Break,
+        });
+        *self.timer.borrow_mut() = Some(id);
+    }
+
+    /// Take the next palette from the queue, refilling it from the category
+    /// (re-read every time, so added and removed palettes are picked up).
+    fn next_palette(&self) -> Result<PathBuf, String> {
+        let settings = self.settings.borrow();
+        let palettes = palette::list_palette_categories()
+            .remove(&settings.category)
+            .filter(|images| !images.is_empty())
+            .ok_or_else(|| format!("No palettes in category \"{}\"", settings.category))?;
+
+        let mut queue = self.queue.borrow_mut();
+        let mut last = self.last.borrow_mut();
+        queue.retain(|p| palettes.contains(p));
+        if queue.is_empty() {
+            *queue = palettes;
+            match settings.order {
+                RotationOrder::Sequential => {
+                    // Continue after the palette set last, wrapping around
+                    if let Some(i) = last
+                        .as_ref()
+                        .and_then(|l| queue.iter().position(|p| p == l))
+                    {
+                        queue.rotate_left(i + 1);
+                    }
+                }
+                RotationOrder::Shuffled => {
+                    queue.shuffle(&mut rand::thread_rng());
+                    // Don't repeat the previous palette across a reshuffle
+                    if queue.len() > 1 && queue.first() == last.as_ref() {
+                        queue.swap(0, 1);
+                    }
+                }
+            }
+        }
+
+        let next = queue.remove(0);
+        *last = Some(next.clone());
+        Ok(next)
+    }
+}
+
+/// Render `preset` with the palette's colors at the largest monitor's size,
+/// into a file in the wallpaper directory.
+fn render_wallpaper(palette_path: &Path, preset: &str) -> Result<PathBuf, String> {
+    let (width, height) = wallpaper::largest_monitor_size().unwrap_or(FALLBACK_SIZE);
+
+    let (mut renderer, _context) = gl_renderer::create_preset_renderer(preset)?;
+    [
+        renderer.color1,
+        renderer.color2,
+        renderer.color3,
+        renderer.color4,
+    ] = palette::cached_palette_colors(palette_path)?;
+    let pixels = renderer.render_to_pixels(width as i32, height as i32)?;
+
+    let path = wallpaper::new_wallpaper_path("rotation");
+    export::save_pixels(
+        &pixels,
+        width,
+        height,
+        &path,
+        ExportFormat::Png,
+        export::DEFAULT_EXPORT_QUALITY,
+        Some(&renderer.wallpaper_meta()),
+    )?;
+    Ok(path)
+}
@@ -0,0 +1,38 @@
+/// Screen captures through the XDG Desktop Portal's Screenshot interface,
+/// the only way to read the screen on Wayland. The portal lets the user pick
+/// a region (or window) and saves the capture to a file, whose path is
+/// handed back for palette extraction; `window.rs` deletes it once the
+/// palette is saved.
+///
+/// The portal is called through ashpd rather than a hand-built
+/// `gio::DBusProxy`: ashpd already tracks the request object and its
+/// `Response` signal, which a raw proxy would have to match up by hand.
+use std::path::PathBuf;
+
+use ashpd::desktop::screenshot::Screenshot;
+use ashpd::desktop::ResponseError;
+
+/// Ask the portal for an interactive screenshot. Gives `Ok(None)` when the
+/// user cancelled it.
+pub async fn capture_region() -> Result<Option<PathBuf>, String> {
+    let request = Screenshot::request()
+        .interactive(true)
+        .modal(true)
+        .send()
+        .await
+        .map_err(|e| format!("Screenshot portal error: {}", e))?;
+
+    let screenshot = match request.response() {
+        Ok(screenshot) => screenshot,
+        Err(ashpd::Error::Response(ResponseError::Cancelled)) => return Ok(None),
+        Err(ashpd::Error::Response(ResponseError::Other)) => {
+            return Err("Screenshot was denied".to_string())
+        }
+        Err(e) => return Err(format!("Screenshot portal response error: {}", e)),
+    };
+    screenshot
+        .uri()
+        .to_file_path()
+        .map(Some)
+        .map_err(|_| format!("Screenshot is not a local file: {}", screenshot.uri()))
+}
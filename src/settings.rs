@@ -0,0 +1,161 @@
+/// Session state remembered across launches: the selected palette and
+/// preset, the window size, the preview frame rate cap, the export
+/// filename template, the wallpaper fit mode, target and backend, the
+/// wallpaper rotation, the GPU preference, whether the preset is kept
+/// when switching palettes and the blend color space, stored as a GLib key file under
+/// the user config directory. The command line reads it too, for the fit
+/// mode and backend.
+use std::path::{Path, PathBuf};
+
+use gtk4::glib;
+
+use crate::export::DEFAULT_FILENAME_TEMPLATE;
+use crate::gl_renderer::{GpuPreference, DEFAULT_PREVIEW_FPS};
+use crate::palette::{self, ColorSpace};
+use crate::rotation::{RotationOrder, RotationSettings};
+use crate::wallpaper::{WallpaperBackend, WallpaperMode, WallpaperTarget};
+
+const GROUP: &str = "Session";
+
+/// Default window size, used until a size has been saved
+pub const DEFAULT_WINDOW_SIZE: (i32, i32) = (1300, 900);
+
+/// What `window.rs` restores on startup and saves on close
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedState {
+    pub palette: Option<PathBuf>,
+    pub preset: Option<String>,
+    pub window_size: (i32, i32),
+    pub preview_fps: u32,
+    pub filename_template: String,
+    pub wallpaper_mode: WallpaperMode,
+    pub wallpaper_target: WallpaperTarget,
+    /// Backend picked in the Set With menu; `None` detects it each time
+    pub wallpaper_backend: Option<WallpaperBackend>,
+    pub rotation: RotationSettings,
+    pub gpu_preference: GpuPreference,
+    /// Keep the current preset when another palette is selected, rather
+    /// than going back to the first one
+    pub pin_preset: bool,
+    /// Blend Space picked in the Effects group
+    pub color_space: ColorSpace,
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {
+            palette: None,
+            preset: None,
+            window_size: DEFAULT_WINDOW_SIZE,
+            preview_fps: DEFAULT_PREVIEW_FPS,
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_string(),
+            wallpaper_mode: WallpaperMode::default(),
+            wallpaper_target: WallpaperTarget::default(),
+            wallpaper_backend: None,
+            rotation: RotationSettings::default(),
+            gpu_preference: GpuPreference::default(),
+            pin_preset: true,
+            color_space: ColorSpace::default(),
+        }
+    }
+}
+
+/// `~/.config/wallrus/state.ini` (or the Flatpak config dir)
+fn state_file() -> PathBuf {
+    glib::user_config_dir().join("wallrus").join("state.ini")
+}
+
+/// Load the saved state. A missing or unreadable file gives the defaults,
+/// and each missing key falls back individually.
+pub fn load() -> SavedState {
+    let key_file = glib::KeyFile::new();
+    if key_file
+        .load_from_file(state_file(), glib::KeyFileFlags::NONE)
+        .is_err()
+    {
+        return SavedState::default();
+    }
+
+    let string = |key: &str| key_file.string(GROUP, key).ok().map(|s| s.to_string());
+    let integer = |key: &str| key_file.integer(GROUP, key).ok().filter(|v| *v > 0);
+    let (default_width, default_height) = DEFAULT_WINDOW_SIZE;
+    let default_rotation = RotationSettings::default();
+    SavedState {
+        palette: string("palette").map(|p| palette::relocate_user_palette(Path::new(&p))),
+        preset: string("preset"),
+        window_size: (
+            integer("width").unwrap_or(default_width),
+            integer("height").unwrap_or(default_height),
+        ),
+        preview_fps: integer("preview-fps").map_or(DEFAULT_PREVIEW_FPS, |fps| fps as u32),
+        filename_template: string("filename-template")
+            .filter(|t| !t.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_FILENAME_TEMPLATE.to_string()),
+        wallpaper_mode: string("wallpaper-mode")
+            .and_then(|m| WallpaperMode::parse(&m).ok())
+            .unwrap_or_default(),
+        wallpaper_target: string("wallpaper-target")
+            .and_then(|t| WallpaperTarget::parse(&t))
+            .unwrap_or_default(),
+        wallpaper_backend: string("wallpaper-backend").and_then(|b| WallpaperBackend::parse(&b)),
+        rotation: RotationSettings {
+            enabled: key_file.boolean(GROUP, "rotation-enabled").unwrap_or(false),
+            category: string("rotation-category").unwrap_or_default(),
+            preset: string("rotation-preset").unwrap_or(default_rotation.preset),
+            interval_minutes: integer("rotation-interval")
+                .map_or(default_rotation.interval_minutes, |m| m as u32),
+            order: string("rotation-order")
+                .and_then(|o| RotationOrder::parse(&o))
+                .unwrap_or_default(),
+        },
+        gpu_preference: string("gpu")
+            .and_then(|g| GpuPreference::parse(&g))
+            .unwrap_or_default(),
+        pin_preset: key_file.boolean(GROUP, "pin-preset").unwrap_or(true),
+        color_space: string("color-space")
+            .and_then(|c| ColorSpace::parse(&c).ok())
+            .unwrap_or_default(),
+    }
+}
+
+/// Write the state, creating the config directory if needed.
+pub fn save(state: &SavedState) -> Result<(), String> {
+    let path = state_file();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+
+    let key_file = glib::KeyFile::new();
+    if let Some(palette) = state.palette.as_ref().and_then(|p| p.to_str()) {
+        key_file.set_string(GROUP, "palette", palette);
+    }
+    if let Some(ref preset) = state.preset {
+        key_file.set_string(GROUP, "preset", preset);
+    }
+    key_file.set_integer(GROUP, "width", state.window_size.0);
+    key_file.set_integer(GROUP, "height", state.window_size.1);
+    key_file.set_integer(GROUP, "preview-fps", state.preview_fps as i32);
+    key_file.set_string(GROUP, "filename-template", &state.filename_template);
+    key_file.set_string(GROUP, "wallpaper-mode", state.wallpaper_mode.name());
+    key_file.set_string(GROUP, "wallpaper-target", state.wallpaper_target.name());
+    if let Some(backend) = state.wallpaper_backend {
+        key_file.set_string(GROUP, "wallpaper-backend", backend.name());
+    }
+    key_file.set_boolean(GROUP, "rotation-enabled", state.rotation.enabled);
+    key_file.set_string(GROUP, "rotation-category", &state.rotation.category);
+    key_file.set_string(GROUP, "rotation-preset", &state.rotation.preset);
+    key_file.set_integer(
+        GROUP,
+        "rotation-interval",
+        state.rotation.interval_minutes as i32,
+    );
+    key_file.set_string(GROUP, "rotation-order", state.rotation.order.name());
+    key_file.set_string(GROUP, "gpu", state.gpu_preference.name());
+    key_file.set_boolean(GROUP, "pin-preset", state.pin_preset);
+    key_file.set_string(GROUP, "color-space", state.color_space.name());
+
+    key_file
+        .save_to_file(&path)
+        .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
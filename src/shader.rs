@@ -1,6 +1,17 @@
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
 use glow::HasContext;
+use gtk4::glib;
 
-/// A compiled shader program
+/// A compiled shader program.
+///
+/// Besides the palette and effect uniforms, every frame the renderer feeds
+/// these standard animation uniforms, which any preset may declare:
+///
+/// - `uniform float iTime;` — monotonic seconds since the renderer started
+/// - `uniform vec3 iResolution;` — output size in pixels (z is always 1.0)
+/// - `uniform vec2 iMouse;` — pointer position in pixels, origin bottom-left
 pub struct ShaderProgram {
     pub id: glow::Program,
 }
@@ -8,6 +19,86 @@ pub struct ShaderProgram {
 impl ShaderProgram {
     /// Create a new shader program from vertex and fragment shader sources
     pub fn new(gl: &glow::Context, vertex_src: &str, fragment_src: &str) -> Result<Self, String> {
+        Self::compile_and_link(gl, vertex_src, fragment_src, false)
+    }
+
+    /// Like `new`, but reuses a linked program binary from the on-disk cache
+    /// when the driver supports program binaries. A cached binary that fails
+    /// to load (driver update, format mismatch) falls back to compiling from
+    /// source, and the fresh binary replaces the stale cache entry.
+    pub fn new_cached(
+        gl: &glow::Context,
+        vertex_src: &str,
+        fragment_src: &str,
+    ) -> Result<Self, String> {
+        let supported = unsafe { gl.get_parameter_i32(glow::NUM_PROGRAM_BINARY_FORMATS) > 0 };
+        if !supported {
+            return Self::new(gl, vertex_src, fragment_src);
+        }
+
+        let cache_path = binary_cache_path(gl, vertex_src, fragment_src);
+        if let Some(path) = &cache_path {
+            if let Some(program) = Self::load_binary(gl, path) {
+                return Ok(program);
+            }
+        }
+
+        let program = Self::compile_and_link(gl, vertex_src, fragment_src, true)?;
+        if let Some(path) = &cache_path {
+            if let Err(e) = program.save_binary(gl, path) {
+                eprintln!("{}", e);
+            }
+        }
+        Ok(program)
+    }
+
+    /// Load a program from a cached binary. Returns None if the file is
+    /// missing or the driver rejects the binary.
+    fn load_binary(gl: &glow::Context, path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        if data.len() <= 4 {
+            return None;
+        }
+        let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let binary = glow::ProgramBinary {
+            buffer: data[4..].to_vec(),
+            format,
+        };
+
+        unsafe {
+            let program = gl.create_program().ok()?;
+            gl.program_binary(program, &binary);
+            if !gl.get_program_link_status(program) {
+                gl.delete_program(program);
+                return None;
+            }
+            Some(Self { id: program })
+        }
+    }
+
+    /// Write the linked program binary to the cache.
+    /// The file starts with the binary format as a little-endian u32.
+    fn save_binary(&self, gl: &glow::Context, path: &Path) -> Result<(), String> {
+        let binary = unsafe { gl.get_program_binary(self.id) }
+            .ok_or_else(|| "Failed to retrieve program binary".to_string())?;
+
+        let mut data = Vec::with_capacity(binary.buffer.len() + 4);
+        data.extend_from_slice(&binary.format.to_le_bytes());
+        data.extend_from_slice(&binary.buffer);
+
+        // Write to a temporary file first so a crash never leaves a truncated cache entry
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, &data)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to write shader cache: {}", e))
+    }
+
+    fn compile_and_link(
+        gl: &glow::Context,
+        vertex_src: &str,
+        fragment_src: &str,
+        retrievable: bool,
+    ) -> Result<Self, String> {
         unsafe {
             let vertex_shader = Self::compile_shader(gl, glow::VERTEX_SHADER, vertex_src)?;
             let fragment_shader = Self::compile_shader(gl, glow::FRAGMENT_SHADER, fragment_src)?;
@@ -15,6 +106,9 @@ impl ShaderProgram {
             let program = gl.create_program().map_err(|e| e.to_string())?;
             gl.attach_shader(program, vertex_shader);
             gl.attach_shader(program, fragment_shader);
+            if retrievable {
+                gl.program_binary_retrievable_hint(program, true);
+            }
             gl.link_program(program);
 
             if !gl.get_program_link_status(program) {
@@ -57,9 +151,177 @@ impl ShaderProgram {
         Ok(shader)
     }
 
+    /// Set a float uniform by name. Uniforms the program doesn't declare
+    /// are ignored, so presets only need to declare the ones they use.
+    pub fn set_uniform_f32(&self, gl: &glow::Context, name: &str, value: f32) {
+        unsafe {
+            if let Some(loc) = gl.get_uniform_location(self.id, name) {
+                gl.uniform_1_f32(Some(&loc), value);
+            }
+        }
+    }
+
+    /// Set an int uniform by name (ignored if undeclared)
+    pub fn set_uniform_i32(&self, gl: &glow::Context, name: &str, value: i32) {
+        unsafe {
+            if let Some(loc) = gl.get_uniform_location(self.id, name) {
+                gl.uniform_1_i32(Some(&loc), value);
+            }
+        }
+    }
+
+    /// Set a vec2 uniform by name (ignored if undeclared)
+    pub fn set_uniform_vec2(&self, gl: &glow::Context, name: &str, value: [f32; 2]) {
+        unsafe {
+            if let Some(loc) = gl.get_uniform_location(self.id, name) {
+                gl.uniform_2_f32(Some(&loc), value[0], value[1]);
+            }
+        }
+    }
+
+    /// Set a vec3 uniform by name (ignored if undeclared)
+    pub fn set_uniform_vec3(&self, gl: &glow::Context, name: &str, value: [f32; 3]) {
+        unsafe {
+            if let Some(loc) = gl.get_uniform_location(self.id, name) {
+                gl.uniform_3_f32(Some(&loc), value[0], value[1], value[2]);
+            }
+        }
+    }
+
+    /// Set a vec4 uniform by name (ignored if undeclared)
+    pub fn set_uniform_vec4(&self, gl: &glow::Context, name: &str, value: [f32; 4]) {
+        unsafe {
+            if let Some(loc) = gl.get_uniform_location(self.id, name) {
+                gl.uniform_4_f32(Some(&loc), value[0], value[1], value[2], value[3]);
+            }
+        }
+    }
+
     pub fn delete(self, gl: &glow::Context) {
         unsafe {
             gl.delete_program(self.id);
         }
     }
 }
+
+/// The first error the GLSL compiler reported in a [`ShaderProgram`]
+/// error, without the "fragment shader compilation failed" prefix, for
+/// places with room for one line. Messages that aren't compiler logs are
+/// returned whole.
+pub fn first_error_line(error: &str) -> &str {
+    let log = error
+        .split_once(" compilation failed: ")
+        .or_else(|| error.split_once(" linking failed: "))
+        .map_or(error, |(_, log)| log);
+    let mut lines = log.lines().map(str::trim).filter(|line| !line.is_empty());
+    let first = lines.clone().next().unwrap_or(error);
+    lines
+        .find(|line| line.to_ascii_lowercase().contains("error"))
+        .unwrap_or(first)
+}
+
+/// Directory holding cached program binaries (`~/.cache/wallrus/shaders`).
+/// Everything in it can be deleted at any time; programs are recompiled
+/// from source as needed.
+fn binary_cache_dir() -> Option<PathBuf> {
+    let dir = glib::user_cache_dir().join("wallrus").join("shaders");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// FNV-1a (64-bit) of `parts`, each prefixed with its length so that
+/// moving text between parts changes the hash. Unlike `DefaultHasher`
+/// it's fixed, so cache entries survive toolchain updates.
+fn cache_hash(parts: &[&str]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    parts
+        .iter()
+        .flat_map(|part| {
+            (part.len() as u64)
+                .to_le_bytes()
+                .into_iter()
+                .chain(part.bytes())
+        })
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+/// Every cache file name starts with the driver's hash, so entries made by
+/// another driver (or by older versions, named differently) can be told
+/// apart and removed.
+fn is_stale_cache_entry(file_name: &str, driver: u64) -> bool {
+    !file_name.starts_with(&format!("{:016x}-", driver))
+}
+
+/// Remove cache files that another driver produced and this one can't load
+/// (once per run, on first use).
+fn prune_binary_cache(dir: &Path, driver: u64) {
+    static PRUNED: Once = Once::new();
+    PRUNED.call_once(|| {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            if is_stale_cache_entry(&name.to_string_lossy(), driver) {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    });
+}
+
+/// Cache file for a program, `<driver hash>-<program hash>.bin`: binaries
+/// are only valid for the GL driver that produced them, so the driver
+/// identity is hashed separately from both shader sources.
+fn binary_cache_path(gl: &glow::Context, vertex_src: &str, fragment_src: &str) -> Option<PathBuf> {
+    let (renderer, version) = unsafe {
+        (
+            gl.get_parameter_string(glow::RENDERER),
+            gl.get_parameter_string(glow::VERSION),
+        )
+    };
+    let driver = cache_hash(&[&renderer, &version]);
+    let dir = binary_cache_dir()?;
+    prune_binary_cache(&dir, driver);
+    let program = cache_hash(&[vertex_src, fragment_src]);
+    Some(dir.join(format!("{:016x}-{:016x}.bin", driver, program)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_hash_is_fixed_fnv() {
+        // Nothing hashed gives the offset basis; "a" hashes its 8 length
+        // bytes, then the letter
+        assert_eq!(cache_hash(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(cache_hash(&["a"]), 0x529a_4ddc_8ff5_6bbf);
+        assert_ne!(cache_hash(&["ab", "c"]), cache_hash(&["a", "bc"]));
+        assert_ne!(cache_hash(&["a", "b"]), cache_hash(&["b", "a"]));
+
+        let driver = cache_hash(&["Mesa Intel(R) UHD Graphics", "OpenGL ES 3.2 Mesa 24.1.2"]);
+        let current = format!("{:016x}-{:016x}.bin", driver, cache_hash(&["v", "f"]));
+        assert!(!is_stale_cache_entry(&current, driver));
+        assert!(is_stale_cache_entry(&current, driver ^ 1));
+        assert!(is_stale_cache_entry("9f86d081884c7d65.bin", driver));
+    }
+
+    #[test]
+    fn first_error_line_skips_warnings() {
+        let error = "fragment shader compilation failed: 0:3(1): warning: unused\n\
+                     0:12(5): error: `colr' undeclared\n\
+                     0:14(2): error: type mismatch\n";
+        assert_eq!(first_error_line(error), "0:12(5): error: `colr' undeclared");
+        assert_eq!(
+            first_error_line("fragment shader compilation failed: ERROR: 0:7: syntax error"),
+            "ERROR: 0:7: syntax error"
+        );
+        assert_eq!(
+            first_error_line("Failed to read a.frag"),
+            "Failed to read a.frag"
+        );
+    }
+}
@@ -1,6 +1,9 @@
 /// Shader preset definitions.
 /// Each preset has a name, a fragment shader source, and a description of
 /// which UI controls it needs.
+use std::path::{Path, PathBuf};
+
+use gtk4::glib;
 
 /// The shared common GLSL code (uniforms, utility functions) included in all
 /// fragment shaders at the `// common.glsl inserted here` marker.
@@ -8,7 +11,10 @@ const COMMON_GLSL: &str = include_str!("../data/shaders/common.glsl");
 
 /// Names of all available presets, in display order
 pub fn preset_names() -> &'static [&'static str] {
-    &["Bars", "Circle", "Plasma", "Waves", "Terrain"]
+    &[
+        "Bars", "Stripes", "Gradient", "Grain", "Circle", "Radial", "Conic", "Plasma", "Waves",
+        "Terrain", "Noise", "Simplex", "Clouds", "Solid",
+    ]
 }
 
 /// Returns the shared vertex shader source (fullscreen quad passthrough)
@@ -16,24 +22,162 @@ pub fn vertex_shader_source() -> String {
     include_str!("../data/shaders/vertex.glsl").to_string()
 }
 
+const COMMON_MARKER: &str = "// common.glsl inserted here";
+
 /// Assemble a fragment shader by inserting the common code at the marker.
+/// A `#line` directive after the common code keeps compiler error line
+/// numbers matching the shader file.
 fn assemble(shader_src: &str) -> String {
-    shader_src.replacen("// common.glsl inserted here", COMMON_GLSL, 1)
+    let Some(offset) = shader_src.find(COMMON_MARKER) else {
+        return shader_src.to_string();
+    };
+    let marker_line = shader_src[..offset].matches('\n').count() + 1;
+    shader_src.replacen(
+        COMMON_MARKER,
+        &format!("{}\n#line {}\n", COMMON_GLSL, marker_line),
+        1,
+    )
 }
 
 /// Returns the fragment shader source for a given preset name
 pub fn fragment_source_for(name: &str) -> Option<String> {
     let src = match name {
         "Bars" => include_str!("../data/shaders/bars.glsl"),
+        "Stripes" => include_str!("../data/shaders/stripes.glsl"),
+        "Gradient" => include_str!("../data/shaders/gradient.glsl"),
+        "Grain" => include_str!("../data/shaders/grain.glsl"),
         "Plasma" => include_str!("../data/shaders/plasma.glsl"),
         "Waves" => include_str!("../data/shaders/waves.glsl"),
         "Terrain" => include_str!("../data/shaders/terrain.glsl"),
         "Circle" => include_str!("../data/shaders/circle.glsl"),
+        "Radial" => include_str!("../data/shaders/radial.glsl"),
+        "Conic" => include_str!("../data/shaders/conic.glsl"),
+        "Noise" => include_str!("../data/shaders/noise.glsl"),
+        "Simplex" => include_str!("../data/shaders/simplex.glsl"),
+        "Clouds" => include_str!("../data/shaders/clouds.glsl"),
+        "Solid" => include_str!("../data/shaders/solid.glsl"),
         _ => return None,
     };
     Some(assemble(src))
 }
 
+/// File extension of user-provided fragment shaders.
+pub const USER_SHADER_EXTENSION: &str = "frag";
+
+/// Get the user shaders directory inside the sandbox data dir.
+///
+/// User shaders are GLSL ES 3.0 fragment shaders (`*.frag`). They may contain
+/// the `// common.glsl inserted here` marker to use the shared palette,
+/// distortion and lighting helpers. This is `wallrus/shaders/` in the user
+/// data dir; it's created if it doesn't exist, taking over the `shaders/`
+/// folder earlier versions used directly under the data dir.
+pub fn user_shaders_dir() -> PathBuf {
+    let dir = glib::user_data_dir().join("wallrus").join("shaders");
+    if !dir.exists() {
+        let legacy = glib::user_data_dir().join("shaders");
+        let _ = std::fs::create_dir_all(glib::user_data_dir().join("wallrus"));
+        if !legacy.is_dir() || std::fs::rename(&legacy, &dir).is_err() {
+            let _ = std::fs::create_dir_all(&dir);
+        }
+    }
+    dir
+}
+
+/// A selectable shader: either a bundled preset or a user `.frag` file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetEntry {
+    Builtin(&'static str),
+    User { name: String, path: PathBuf },
+}
+
+impl PresetEntry {
+    /// Create an entry for a user shader file, named after the file stem.
+    pub fn user(path: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("shader")
+            .to_string();
+        PresetEntry::User {
+            name,
+            path: path.to_path_buf(),
+        }
+    }
+
+    /// Display name shown in the preset dropdown.
+    pub fn name(&self) -> &str {
+        match self {
+            PresetEntry::Builtin(name) => name,
+            PresetEntry::User { name, .. } => name,
+        }
+    }
+
+    /// Fragment shader source with the common code inserted.
+    pub fn fragment_source(&self) -> Result<String, String> {
+        match self {
+            PresetEntry::Builtin(name) => {
+                fragment_source_for(name).ok_or_else(|| format!("Unknown preset: {}", name))
+            }
+            PresetEntry::User { path, .. } => load_user_shader(path),
+        }
+    }
+}
+
+/// All selectable presets: bundled presets first (in display order), then
+/// user shaders sorted by filename.
+pub fn list_presets() -> Vec<PresetEntry> {
+    let mut entries: Vec<PresetEntry> = preset_names()
+        .iter()
+        .map(|name| PresetEntry::Builtin(name))
+        .collect();
+    entries.extend(list_user_shaders().iter().map(|p| PresetEntry::user(p)));
+    entries
+}
+
+/// List user `.frag` shader files, sorted by filename.
+pub fn list_user_shaders() -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(user_shaders_dir()) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut shaders: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file() && p.extension().and_then(|e| e.to_str()) == Some(USER_SHADER_EXTENSION)
+        })
+        .collect();
+    shaders.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    shaders
+}
+
+/// Read a user fragment shader from disk and insert the common code.
+pub fn load_user_shader(path: &Path) -> Result<String, String> {
+    let src = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read shader '{}': {}", path.display(), e))?;
+    Ok(assemble(&src))
+}
+
+/// How long an animated shader takes to repeat, in seconds of `iTime`,
+/// when it doesn't say: one turn of `sin(iTime)`.
+pub const DEFAULT_LOOP_PERIOD: f32 = std::f32::consts::TAU;
+
+/// The loop period a shader declares with `#define LOOP_PERIOD <seconds>`,
+/// which video export snaps the clip length to. Not a positive number, or
+/// no such line, gives `None`.
+pub fn loop_period(fragment_src: &str) -> Option<f32> {
+    fragment_src.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("#define")?.trim_start();
+        let value = value.strip_prefix("LOOP_PERIOD")?;
+        value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|p| p.is_finite() && *p > 0.0)
+    })
+}
+
 /// Which UI controls a preset uses
 pub struct PresetControls {
     pub has_angle: bool,
@@ -46,11 +190,13 @@ pub struct PresetControls {
     pub speed_range: (f64, f64, f64, f64),
     /// Range for the scale slider: (min, max, step, default)
     pub scale_range: (f64, f64, f64, f64),
+    /// Whether the shader honors `uTile` and renders a seamless tile
+    pub tileable: bool,
 }
 
 pub fn controls_for(name: &str) -> PresetControls {
     match name {
-        "Bars" => PresetControls {
+        "Bars" | "Stripes" | "Gradient" | "Grain" => PresetControls {
             has_angle: true,
             has_scale: false,
             has_speed: false,
@@ -58,6 +204,7 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Speed",
             speed_range: (0.0, 3.0, 0.1, 1.0),
             scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: false,
         },
         "Plasma" => PresetControls {
             has_angle: false,
@@ -67,6 +214,7 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Time",
             speed_range: (0.0, 20.0, 0.1, 0.0),
             scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: false,
         },
         "Waves" => PresetControls {
             has_angle: true,
@@ -76,6 +224,7 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Time",
             speed_range: (0.0, 20.0, 0.1, 0.0),
             scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: false,
         },
         "Terrain" => PresetControls {
             has_angle: false,
@@ -85,6 +234,7 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Time",
             speed_range: (0.0, 20.0, 0.1, 0.0),
             scale_range: (0.1, 2.0, 0.01, 0.5),
+            tileable: false,
         },
         "Circle" => PresetControls {
             has_angle: false,
@@ -94,6 +244,54 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Time",
             speed_range: (0.0, 20.0, 0.1, 0.0),
             scale_range: (0.5, 3.0, 0.1, 1.0),
+            tileable: false,
+        },
+        // Scale is how many times the colors repeat out to the furthest corner
+        "Radial" => PresetControls {
+            has_angle: false,
+            has_scale: true,
+            has_speed: false,
+            has_center: true,
+            speed_label: "Time",
+            speed_range: (0.0, 20.0, 0.1, 0.0),
+            scale_range: (0.5, 8.0, 0.1, 1.0),
+            tileable: false,
+        },
+        // Angle is where the sweep starts
+        "Conic" => PresetControls {
+            has_angle: true,
+            has_scale: false,
+            has_speed: false,
+            has_center: true,
+            speed_label: "Time",
+            speed_range: (0.0, 20.0, 0.1, 0.0),
+            scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: false,
+        },
+        // Procedural noise family: scale is the noise frequency, time scrubs
+        // through the (deterministic) noise field. Value noise wraps its
+        // lattice to tile; simplex's skewed grid and the rotated cloud
+        // octaves can't.
+        "Noise" | "Simplex" | "Clouds" => PresetControls {
+            has_angle: false,
+            has_scale: true,
+            has_speed: true,
+            has_center: false,
+            speed_label: "Time",
+            speed_range: (0.0, 20.0, 0.1, 0.0),
+            scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: name == "Noise",
+        },
+        // A single palette color, picked with the Color parameter
+        "Solid" => PresetControls {
+            has_angle: false,
+            has_scale: false,
+            has_speed: false,
+            has_center: false,
+            speed_label: "Speed",
+            speed_range: (0.0, 3.0, 0.1, 1.0),
+            scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: true,
         },
         _ => PresetControls {
             has_angle: true,
@@ -103,6 +301,128 @@ pub fn controls_for(name: &str) -> PresetControls {
             speed_label: "Speed",
             speed_range: (0.0, 3.0, 0.1, 1.0),
             scale_range: (0.1, 5.0, 0.1, 1.0),
+            tileable: false,
         },
     }
 }
+
+/// How a preset parameter is edited and passed to its uniform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    /// Continuous slider, passed as a `float` uniform
+    Float,
+    /// Whole-number slider, passed as an `int` uniform
+    Int,
+    /// On/off switch, passed as an `int` uniform of 0 or 1
+    Bool,
+}
+
+/// A preset-specific parameter, shown as a slider or switch and bound to the uniform
+/// called `name`. Changing it only updates the uniform; no recompile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PresetParam {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub kind: ParamKind,
+}
+
+const REPEAT_PARAM: PresetParam = PresetParam {
+    name: "uRepeat",
+    label: "Stripe Repeat",
+    min: 1.0,
+    max: 8.0,
+    default: 1.0,
+    kind: ParamKind::Int,
+};
+
+const STRIPE_COUNT_PARAM: PresetParam = PresetParam {
+    name: "uStripeCount",
+    label: "Stripes",
+    min: 2.0,
+    max: 64.0,
+    default: 8.0,
+    kind: ParamKind::Int,
+};
+
+/// 1 to smooth stripe edges over a pixel, 0 for hard pixel edges
+const ANTIALIAS_PARAM: PresetParam = PresetParam {
+    name: "uAntialias",
+    label: "Smooth Edges",
+    min: 0.0,
+    max: 1.0,
+    default: 1.0,
+    kind: ParamKind::Bool,
+};
+
+/// Vertical counterpart of the shared center control, -1 (bottom) to 1 (top)
+const CENTER_Y_PARAM: PresetParam = PresetParam {
+    name: "uCenterY",
+    label: "Vertical Center",
+    min: -1.0,
+    max: 1.0,
+    default: 0.0,
+    kind: ParamKind::Float,
+};
+
+const CONTRAST_PARAM: PresetParam = PresetParam {
+    name: "uContrast",
+    label: "Contrast",
+    min: 0.5,
+    max: 3.0,
+    default: 1.0,
+    kind: ParamKind::Float,
+};
+
+const OCTAVES_PARAM: PresetParam = PresetParam {
+    name: "uOctaves",
+    label: "Detail",
+    min: 1.0,
+    max: 8.0,
+    default: 6.0,
+    kind: ParamKind::Int,
+};
+
+const WARP_PARAM: PresetParam = PresetParam {
+    name: "uWarp",
+    label: "Warp",
+    min: 0.0,
+    max: 2.0,
+    default: 1.0,
+    kind: ParamKind::Float,
+};
+
+const GRAIN_PARAM: PresetParam = PresetParam {
+    name: "uGrain",
+    label: "Grain",
+    min: 0.0,
+    max: 1.0,
+    default: 0.25,
+    kind: ParamKind::Float,
+};
+
+const COLOR_INDEX_PARAM: PresetParam = PresetParam {
+    name: "uColorIndex",
+    label: "Color",
+    min: 1.0,
+    max: 4.0,
+    default: 1.0,
+    kind: ParamKind::Int,
+};
+
+/// Extra parameters a preset exposes beyond the shared controls
+/// (angle, scale, time, center). User shaders have none.
+pub fn params_for(name: &str) -> &'static [PresetParam] {
+    match name {
+        "Bars" => &[REPEAT_PARAM],
+        "Stripes" => &[STRIPE_COUNT_PARAM, ANTIALIAS_PARAM],
+        "Radial" | "Conic" => &[CENTER_Y_PARAM],
+        "Noise" | "Simplex" => &[CONTRAST_PARAM],
+        "Clouds" => &[OCTAVES_PARAM, WARP_PARAM],
+        "Grain" => &[GRAIN_PARAM],
+        "Solid" => &[COLOR_INDEX_PARAM],
+        _ => &[],
+    }
+}
@@ -1,19 +1,354 @@
 use std::fs::File;
 use std::os::fd::AsFd;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use ashpd::desktop::wallpaper::{SetOn, WallpaperRequest};
+use gtk4::prelude::*;
+use gtk4::{gdk, gio, glib};
+
+use crate::wallpaper_history;
+
+/// A mechanism for setting the desktop wallpaper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WallpaperBackend {
+    /// `org.gnome.desktop.background` via `gsettings`
+    Gnome,
+    /// Plasma scripting over D-Bus
+    Kde,
+    /// The `swww` daemon (wlroots compositors)
+    Swww,
+    /// `hyprpaper` via `hyprctl` IPC (Hyprland)
+    Hyprpaper,
+    /// `xfce4-desktop` properties via `xfconf-query`
+    Xfce,
+    /// `feh` setting the root window pixmap (bare X11 window managers)
+    X11,
+    /// XDG Desktop Portal (Flatpak, or when nothing else is detected)
+    Portal,
+}
+
+impl WallpaperBackend {
+    pub const ALL: [WallpaperBackend; 7] = [
+        WallpaperBackend::Gnome,
+        WallpaperBackend::Kde,
+        WallpaperBackend::Swww,
+        WallpaperBackend::Hyprpaper,
+        WallpaperBackend::Xfce,
+        WallpaperBackend::X11,
+        WallpaperBackend::Portal,
+    ];
+
+    /// Name stored in the saved settings
+    pub fn name(self) -> &'static str {
+        match self {
+            WallpaperBackend::Gnome => "gnome",
+            WallpaperBackend::Kde => "kde",
+            WallpaperBackend::Swww => "swww",
+            WallpaperBackend::Hyprpaper => "hyprpaper",
+            WallpaperBackend::Xfce => "xfce",
+            WallpaperBackend::X11 => "feh",
+            WallpaperBackend::Portal => "portal",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|backend| backend.name() == s)
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            WallpaperBackend::Gnome => "GNOME",
+            WallpaperBackend::Kde => "KDE",
+            WallpaperBackend::Swww => "swww",
+            WallpaperBackend::Hyprpaper => "hyprpaper",
+            WallpaperBackend::Xfce => "XFCE",
+            WallpaperBackend::X11 => "feh",
+            WallpaperBackend::Portal => "Desktop Portal",
+        }
+    }
+}
+
+/// How the image is fitted to the screen, named after GNOME's
+/// `picture-options` values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WallpaperMode {
+    /// Scale to cover the screen, cropping the overflow
+    #[default]
+    Zoom,
+    /// Scale to fit inside the screen, keeping the aspect ratio
+    Scaled,
+    /// Center at the original size
+    Centered,
+    /// Stretch one image across all monitors
+    Spanned,
+    /// Repeat the image at its original size
+    Wallpaper,
+}
+
+impl WallpaperMode {
+    pub const ALL: [WallpaperMode; 5] = [
+        WallpaperMode::Zoom,
+        WallpaperMode::Scaled,
+        WallpaperMode::Centered,
+        WallpaperMode::Spanned,
+        WallpaperMode::Wallpaper,
+    ];
+
+    /// The `picture-options` value, also used on the command line and in
+    /// saved settings
+    pub fn name(self) -> &'static str {
+        match self {
+            WallpaperMode::Zoom => "zoom",
+            WallpaperMode::Scaled => "scaled",
+            WallpaperMode::Centered => "centered",
+            WallpaperMode::Spanned => "spanned",
+            WallpaperMode::Wallpaper => "wallpaper",
+        }
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            WallpaperMode::Zoom => "Zoom",
+            WallpaperMode::Scaled => "Scaled",
+            WallpaperMode::Centered => "Centered",
+            WallpaperMode::Spanned => "Spanned",
+            WallpaperMode::Wallpaper => "Tiled",
+        }
+    }
+
+    /// Parse a mode from its `name()`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                format!(
+                    "Invalid fit mode '{}', expected zoom, scaled, centered, spanned or wallpaper",
+                    s
+                )
+            })
+    }
+
+    /// The matching `feh` placement options
+    fn feh_args(self) -> &'static [&'static str] {
+        match self {
+            WallpaperMode::Zoom => &["--bg-fill"],
+            WallpaperMode::Scaled => &["--bg-max"],
+            WallpaperMode::Centered => &["--bg-center"],
+            WallpaperMode::Spanned => &["--no-xinerama", "--bg-fill"],
+            WallpaperMode::Wallpaper => &["--bg-tile"],
+        }
+    }
+}
+
+/// Which wallpaper "Set as Wallpaper" changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WallpaperTarget {
+    #[default]
+    Desktop,
+    LockScreen,
+    Both,
+}
+
+impl WallpaperTarget {
+    pub const ALL: [WallpaperTarget; 3] = [
+        WallpaperTarget::Desktop,
+        WallpaperTarget::LockScreen,
+        WallpaperTarget::Both,
+    ];
+
+    /// Name stored in the saved settings
+    pub fn name(self) -> &'static str {
+        match self {
+            WallpaperTarget::Desktop => "desktop",
+            WallpaperTarget::LockScreen => "lock-screen",
+            WallpaperTarget::Both => "both",
+        }
+    }
+
+    /// Human-readable name for the UI
+    pub fn label(self) -> &'static str {
+        match self {
+            WallpaperTarget::Desktop => "Desktop",
+            WallpaperTarget::LockScreen => "Lock Screen",
+            WallpaperTarget::Both => "Desktop and Lock Screen",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|target| target.name() == s)
+    }
+}
+
+/// Backend picked in the settings, used instead of [`detect_backend`]
+static PREFERRED_BACKEND: Mutex<Option<WallpaperBackend>> = Mutex::new(None);
+
+/// Set wallpapers with `backend` from now on, or with the detected backend
+/// for `None`.
+pub fn set_preferred_backend(backend: Option<WallpaperBackend>) {
+    *PREFERRED_BACKEND.lock().unwrap_or_else(|e| e.into_inner()) = backend;
+}
+
+/// The backend wallpapers are set with: the one picked in the settings, or
+/// else the one detected for the session.
+pub fn backend() -> WallpaperBackend {
+    let preferred = *PREFERRED_BACKEND.lock().unwrap_or_else(|e| e.into_inner());
+    preferred.unwrap_or_else(detect_backend)
+}
+
+/// Pick the wallpaper backend for the running session from
+/// `XDG_CURRENT_DESKTOP`, `XDG_SESSION_TYPE` and the helper binaries on `PATH`.
+///
+/// Inside Flatpak host tools aren't reachable, so the portal is always used.
+/// When the desktop can't be identified, GNOME is assumed if `gsettings` is
+/// available.
+pub fn detect_backend() -> WallpaperBackend {
+    if Path::new("/.flatpak-info").exists() {
+        return WallpaperBackend::Portal;
+    }
+
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let desktop_is = |wanted: &[&str]| {
+        desktop
+            .split(':')
+            .any(|name| wanted.iter().any(|w| name.eq_ignore_ascii_case(w)))
+    };
+    // GNOME needs the background schema, not just the gsettings tool
+    let has_gsettings =
+        || find_executable("gsettings").is_some() && gsettings_has_schema(BACKGROUND_SCHEMA);
+
+    if desktop_is(&["KDE"]) {
+        return WallpaperBackend::Kde;
+    }
+    // Picked even without xfconf-query, so the error says what's missing
+    if desktop_is(&["XFCE"]) {
+        return WallpaperBackend::Xfce;
+    }
+    if desktop_is(&["GNOME", "Unity", "Budgie"]) && has_gsettings() {
+        return WallpaperBackend::Gnome;
+    }
+
+    let is_wayland = std::env::var("XDG_SESSION_TYPE")
+        .map(|t| t.eq_ignore_ascii_case("wayland"))
+        .unwrap_or(false);
+    if is_wayland {
+        if swww_daemon().is_some() {
+            return WallpaperBackend::Swww;
+        }
+        if desktop_is(&["Hyprland"]) && find_executable("hyprctl").is_some() {
+            return WallpaperBackend::Hyprpaper;
+        }
+    }
+
+    let is_x11 = std::env::var("XDG_SESSION_TYPE")
+        .map(|t| t.eq_ignore_ascii_case("x11"))
+        .unwrap_or(false);
+    if is_x11 && find_executable("feh").is_some() {
+        return WallpaperBackend::X11;
+    }
+
+    if has_gsettings() {
+        WallpaperBackend::Gnome
+    } else {
+        WallpaperBackend::Portal
+    }
+}
+
+/// Set the desktop wallpaper using [`backend`].
+/// `mode` is applied on GNOME and with feh; the other backends keep their
+/// own placement setting. On success the image is added to the wallpaper
+/// history and images from earlier calls left in `wallpaper_dir()` are
+/// removed.
+pub async fn set_wallpaper(path: &Path, mode: WallpaperMode) -> Result<(), String> {
+    match backend() {
+        WallpaperBackend::Gnome => {
+            let path = path.to_path_buf();
+            run_blocking(move || set_gnome_wallpaper(&path, mode)).await
+        }
+        WallpaperBackend::Kde => set_kde_wallpaper(path).await,
+        WallpaperBackend::Swww | WallpaperBackend::Hyprpaper => {
+            let path = path.to_path_buf();
+            run_blocking(move || set_wlroots_wallpaper(&path)).await
+        }
+        WallpaperBackend::Xfce => set_xfce_wallpaper(path),
+        WallpaperBackend::X11 => set_x11_wallpaper(path, mode),
+        WallpaperBackend::Portal => set_portal_wallpaper(path).await,
+    }?;
+    remove_stale_wallpapers(&[path]);
+    record_history(path);
+    Ok(())
+}
+
+/// Add a wallpaper that was just set to the history. A failure here
+/// doesn't undo setting it, so it's only logged.
+fn record_history(path: &Path) {
+    if let Err(e) = wallpaper_history::record_wallpaper(path) {
+        eprintln!("{}", e);
+    }
+}
+
+/// Set the desktop wallpaper, the lock screen wallpaper or both.
+/// The lock screen can be set on GNOME and through the portal; other
+/// backends fail without changing anything.
+pub async fn set_wallpaper_on(
+    path: &Path,
+    mode: WallpaperMode,
+    target: WallpaperTarget,
+) -> Result<(), String> {
+    if target == WallpaperTarget::Desktop {
+        return set_wallpaper(path, mode).await;
+    }
+    match backend() {
+        WallpaperBackend::Gnome => {
+            let path = path.to_path_buf();
+            run_blocking(move || {
+                if target == WallpaperTarget::Both {
+                    set_gnome_wallpaper(&path, mode)?;
+                    remove_stale_wallpapers(&[&path]);
+                    record_history(&path);
+                }
+                set_gnome_lockscreen(&path)
+            })
+            .await
+        }
+        WallpaperBackend::Portal => {
+            let set_on = match target {
+                WallpaperTarget::Both => SetOn::Both,
+                _ => SetOn::Lockscreen,
+            };
+            set_portal_wallpaper_on(path, set_on).await?;
+            if target == WallpaperTarget::Both {
+                record_history(path);
+            }
+            Ok(())
+        }
+        backend => Err(format!(
+            "Setting the lock screen wallpaper isn't supported on {}",
+            backend.label()
+        )),
+    }
+}
 
 /// Set the desktop wallpaper using the XDG Desktop Portal.
 ///
 /// The image at `path` is opened and passed as a file descriptor to the portal.
 /// The portal may show a preview dialog to the user before applying.
-pub async fn set_wallpaper(path: &Path) -> Result<(), String> {
+pub async fn set_portal_wallpaper(path: &Path) -> Result<(), String> {
+    set_portal_wallpaper_on(path, SetOn::Both).await
+}
+
+/// Set the wallpaper through the portal on the desktop, the lock screen or both.
+async fn set_portal_wallpaper_on(path: &Path, set_on: SetOn) -> Result<(), String> {
     let file = File::open(path)
         .map_err(|e| format!("Failed to open wallpaper file: {}", e))?;
 
     let request = WallpaperRequest::default()
-        .set_on(SetOn::Both)
+        .set_on(set_on)
         .show_preview(true)
         .build_file(&file.as_fd())
         .await
@@ -23,3 +358,815 @@ pub async fn set_wallpaper(path: &Path) -> Result<(), String> {
         .response()
         .map_err(|e| format!("Wallpaper portal response error: {}", e))
 }
+
+/// Run wallpaper changes that wait on helper tools on a worker thread:
+/// `gsettings` calls wait between retries and `hyprctl` on hyprpaper, which
+/// would freeze the window on the main thread.
+pub async fn run_blocking<T: Send + 'static>(
+    change: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    gio::spawn_blocking(change)
+        .await
+        .map_err(|_| "Setting the wallpaper failed: the worker thread panicked".to_string())?
+}
+
+/// Set the GNOME wallpaper for both light and dark styles via `gsettings`.
+pub fn set_gnome_wallpaper(image_path: &Path, mode: WallpaperMode) -> Result<(), String> {
+    set_gnome_wallpaper_dual(image_path, image_path, mode)
+}
+
+/// Set separate GNOME wallpapers for the light and dark styles via `gsettings`,
+/// with `mode` as the `picture-options` value.
+/// Both paths are validated before either key is written, so a bad path never
+/// leaves only one of them changed.
+pub fn set_gnome_wallpaper_dual(
+    light: &Path,
+    dark: &Path,
+    mode: WallpaperMode,
+) -> Result<(), String> {
+    let light_uri = file_uri(&canonical_wallpaper_path(light)?);
+    let dark_uri = file_uri(&canonical_wallpaper_path(dark)?);
+    stash_gnome_wallpaper()?;
+    apply_gsettings(
+        BACKGROUND_SCHEMA,
+        &[
+            ("picture-options", mode.name()),
+            ("picture-uri", &light_uri),
+            ("picture-uri-dark", &dark_uri),
+        ],
+    )
+}
+
+/// Set the GNOME lock screen wallpaper (`org.gnome.desktop.screensaver`).
+/// The lock screen gets its own copy of the image, so removing old desktop
+/// wallpapers never takes away the one the lock screen shows.
+pub fn set_gnome_lockscreen(image_path: &Path) -> Result<(), String> {
+    let image_path = canonical_wallpaper_path(image_path)?;
+    let extension = image_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+    let copy = wallpaper_dir().join(format!(
+        "{}{}.{}",
+        LOCKSCREEN_FILE_PREFIX,
+        unix_millis(),
+        extension
+    ));
+    std::fs::copy(&image_path, &copy)
+        .map_err(|e| format!("Failed to copy {}: {}", image_path.display(), e))?;
+
+    let uri = file_uri(&canonical_wallpaper_path(&copy)?);
+    if let Err(e) = apply_gsettings(SCREENSAVER_SCHEMA, &[("picture-uri", &uri)]) {
+        let _ = std::fs::remove_file(&copy);
+        return Err(e);
+    }
+    remove_stale_files(LOCKSCREEN_FILE_PREFIX, &[&copy]);
+    Ok(())
+}
+
+/// A monitor's position and size on the desktop, in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitorGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Size in physical pixels of the largest connected monitor, or None when
+/// no monitor is reported.
+pub fn largest_monitor_size() -> Option<(u32, u32)> {
+    monitor_layout()
+        .into_iter()
+        .map(|(_, g)| (g.width, g.height))
+        .max_by_key(|(w, h)| w * h)
+}
+
+/// Connected monitors as (connector name, geometry in physical pixels),
+/// in GDK's order.
+pub fn monitor_layout() -> Vec<(String, MonitorGeometry)> {
+    let Some(display) = gdk::Display::default() else {
+        return Vec::new();
+    };
+    let monitors = display.monitors();
+    (0..monitors.n_items())
+        .filter_map(|i| monitors.item(i).and_downcast::<gdk::Monitor>())
+        .enumerate()
+        .map(|(i, monitor)| {
+            let connector = monitor
+                .connector()
+                .map(|c| c.to_string())
+                .unwrap_or_else(|| format!("monitor-{}", i + 1));
+            let rect = monitor.geometry();
+            let scale = monitor.scale_factor();
+            let geometry = MonitorGeometry {
+                x: rect.x() * scale,
+                y: rect.y() * scale,
+                width: (rect.width() * scale).max(1) as u32,
+                height: (rect.height() * scale).max(1) as u32,
+            };
+            (connector, geometry)
+        })
+        .collect()
+}
+
+/// Set a different image on each monitor (GNOME only).
+///
+/// GNOME has a single wallpaper for the whole desktop, so the per-monitor
+/// images are composited into one image covering every monitor's geometry,
+/// which is set with the "spanned" picture option. Images that don't match
+/// their monitor's size are scaled to fit. With a single monitor the image
+/// is set as a regular wallpaper.
+pub fn set_gnome_wallpaper_spanned(monitors: &[(MonitorGeometry, &Path)]) -> Result<(), String> {
+    match monitors {
+        [] => return Err("No monitors to set a wallpaper on".to_string()),
+        [(_, path)] => {
+            // Copied to a fresh name: GNOME ignores a URI that didn't change
+            let copy = new_wallpaper_path("monitor");
+            std::fs::copy(path, &copy)
+                .map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+            set_gnome_wallpaper(&copy, WallpaperMode::Zoom)?;
+            remove_stale_wallpapers(&[&copy]);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let min_x = monitors.iter().map(|(g, _)| g.x).min().unwrap_or(0);
+    let min_y = monitors.iter().map(|(g, _)| g.y).min().unwrap_or(0);
+    let max_x = monitors
+        .iter()
+        .map(|(g, _)| g.x + g.width as i32)
+        .max()
+        .unwrap_or(0);
+    let max_y = monitors
+        .iter()
+        .map(|(g, _)| g.y + g.height as i32)
+        .max()
+        .unwrap_or(0);
+
+    let mut canvas = image::RgbaImage::new((max_x - min_x) as u32, (max_y - min_y) as u32);
+    for (geometry, path) in monitors {
+        let mut img = image::open(path)
+            .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?
+            .to_rgba8();
+        if img.dimensions() != (geometry.width, geometry.height) {
+            img = image::imageops::resize(
+                &img,
+                geometry.width,
+                geometry.height,
+                image::imageops::FilterType::Lanczos3,
+            );
+        }
+        image::imageops::replace(
+            &mut canvas,
+            &img,
+            (geometry.x - min_x) as i64,
+            (geometry.y - min_y) as i64,
+        );
+    }
+
+    let combined = new_wallpaper_path("spanned");
+    canvas
+        .save(&combined)
+        .map_err(|e| format!("Failed to save spanned wallpaper: {}", e))?;
+
+    let uri = file_uri(&canonical_wallpaper_path(&combined)?);
+    stash_gnome_wallpaper()?;
+    apply_gsettings(
+        BACKGROUND_SCHEMA,
+        &[
+            ("picture-options", WallpaperMode::Spanned.name()),
+            ("picture-uri", &uri),
+            ("picture-uri-dark", &uri),
+        ],
+    )?;
+    remove_stale_wallpapers(&[&combined]);
+    Ok(())
+}
+
+/// Where the image for one monitor of a spanned wallpaper is kept, so each
+/// monitor keeps its palette while the others are changed. `connector` is
+/// the monitor's output name (e.g. "DP-1").
+pub fn monitor_wallpaper_path(connector: &str) -> PathBuf {
+    let dir = wallpaper_dir().join("monitors");
+    if !dir.exists() {
+        let _ = std::fs::create_dir_all(&dir);
+    }
+    let name: String = connector
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("{}.png", name))
+}
+
+/// GNOME background keys saved for undo, in the order they are stashed
+const GNOME_WALLPAPER_KEYS: [&str; 3] = ["picture-uri", "picture-uri-dark", "picture-options"];
+
+/// File storing the wallpaper that was active before Wallrus replaced it,
+/// one `key=uri` line per GNOME background key.
+fn previous_wallpaper_file() -> PathBuf {
+    let file = wallpaper_dir().join("previous-wallpaper");
+    // Stashed by a version that kept it in `wallpapers/` under the data dir
+    let legacy = glib::user_data_dir()
+        .join("wallpapers")
+        .join("previous-wallpaper");
+    if !file.exists() && legacy.exists() {
+        let _ = std::fs::rename(&legacy, &file);
+    }
+    file
+}
+
+/// Save the current GNOME wallpaper so it can be restored later.
+/// If the current wallpaper is already one Wallrus generated, the earlier
+/// stash is kept, so undo always returns to the user's own wallpaper.
+fn stash_gnome_wallpaper() -> Result<(), String> {
+    // Set URIs are canonical, so compare against the canonical directories;
+    // earlier versions kept their images in `wallpapers/` directly under
+    // the data dir
+    let our_dirs = [wallpaper_dir(), glib::user_data_dir().join("wallpapers")]
+        .map(|dir| dir.canonicalize().unwrap_or(dir));
+    let mut lines = Vec::new();
+    for key in GNOME_WALLPAPER_KEYS {
+        let uri = get_gsettings(BACKGROUND_SCHEMA, key)?;
+        let is_ours = gio::File::for_uri(&uri)
+            .path()
+            .is_some_and(|p| our_dirs.iter().any(|dir| p.starts_with(dir)));
+        if is_ours {
+            return Ok(());
+        }
+        lines.push(format!("{}={}", key, uri));
+    }
+
+    std::fs::write(previous_wallpaper_file(), lines.join("\n") + "\n")
+        .map_err(|e| format!("Failed to save previous wallpaper: {}", e))
+}
+
+/// Restore the GNOME wallpaper that was active before Wallrus last set one.
+/// Survives app restarts; fails without touching any settings if the saved
+/// wallpaper image no longer exists.
+pub fn restore_previous_wallpaper() -> Result<(), String> {
+    let contents = std::fs::read_to_string(previous_wallpaper_file())
+        .map_err(|_| "No previous wallpaper to restore".to_string())?;
+
+    let mut saved = Vec::new();
+    for line in contents.lines() {
+        let Some((key, uri)) = line.split_once('=') else {
+            continue;
+        };
+        if !GNOME_WALLPAPER_KEYS.contains(&key) {
+            continue;
+        }
+        // Backgrounds can be non-file URIs in theory; only local files are checked
+        if let Some(path) = gio::File::for_uri(uri).path() {
+            if !path.exists() {
+                return Err(format!(
+                    "The previous wallpaper {} no longer exists",
+                    path.display()
+                ));
+            }
+        }
+        saved.push((key, uri));
+    }
+    if saved.is_empty() {
+        return Err("No previous wallpaper to restore".to_string());
+    }
+
+    apply_gsettings(BACKGROUND_SCHEMA, &saved)?;
+    let _ = std::fs::remove_file(previous_wallpaper_file());
+    Ok(())
+}
+
+/// GSettings schemas of the GNOME desktop background and the lock screen
+const BACKGROUND_SCHEMA: &str = "org.gnome.desktop.background";
+const SCREENSAVER_SCHEMA: &str = "org.gnome.desktop.screensaver";
+
+/// Pauses before each retry of a gsettings call that failed transiently,
+/// e.g. right after login while dconf is still starting
+const GSETTINGS_RETRY_DELAYS: [Duration; 2] =
+    [Duration::from_millis(250), Duration::from_millis(750)];
+
+/// Parts of gsettings errors about the session bus or dconf not being
+/// reachable (yet)
+const TRANSIENT_GSETTINGS_ERRORS: [&str; 5] = [
+    "dconf",
+    "DBus",
+    "D-Bus",
+    "Timeout was reached",
+    "Could not connect",
+];
+
+/// Parts of gsettings errors that retrying can't fix. Checked first, since
+/// a schema or key name may itself contain "dconf".
+const PERMANENT_GSETTINGS_ERRORS: [&str; 3] = ["No such schema", "No such key", "relocatable"];
+
+/// Whether a gsettings error is worth retrying: the session bus or dconf
+/// wasn't reachable, rather than an unknown schema, key or value.
+fn is_transient(error: &str) -> bool {
+    !PERMANENT_GSETTINGS_ERRORS.iter().any(|p| error.contains(p))
+        && TRANSIENT_GSETTINGS_ERRORS.iter().any(|t| error.contains(t))
+}
+
+/// Run `gsettings` with `args`. Transient failures are retried after each of
+/// [`GSETTINGS_RETRY_DELAYS`]; others, and the last retry's, are returned
+/// as they are. Sleeps between retries, so the window calls the GNOME
+/// functions through [`run_blocking`].
+fn run_gsettings(args: &[&str]) -> Result<String, String> {
+    let mut delays = GSETTINGS_RETRY_DELAYS.iter();
+    loop {
+        match run_command(Command::new("gsettings").args(args), "gsettings") {
+            Err(e) if is_transient(&e) => match delays.next() {
+                Some(delay) => std::thread::sleep(*delay),
+                None => return Err(e),
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Set a key in a GSettings schema. Errors name the schema and key, since
+/// several are usually written together.
+fn set_gsettings(schema: &str, key: &str, value: &str) -> Result<(), String> {
+    run_gsettings(&["set", schema, key, value])
+        .map(|_| ())
+        .map_err(|e| gsettings_error(schema, format!("Failed to set {} {}: {}", schema, key, e)))
+}
+
+/// Set several keys of `schema` as one change.
+/// The current values are read first; if any write fails, the keys already
+/// written are put back so the desktop isn't left with a mix of old and new
+/// values, and the original error is returned.
+fn apply_gsettings(schema: &str, values: &[(&str, &str)]) -> Result<(), String> {
+    let snapshot = values
+        .iter()
+        .map(|(key, _)| get_gsettings(schema, key).map(|old| (*key, old)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    for (applied, (key, value)) in values.iter().enumerate() {
+        if let Err(e) = set_gsettings(schema, key, value) {
+            for (key, old) in snapshot[..applied].iter().rev() {
+                if let Err(rollback) = set_gsettings(schema, key, old) {
+                    eprintln!("Failed to roll back {}: {}", key, rollback);
+                }
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Read a string key from a GSettings schema.
+/// `gsettings get` prints GVariant text (a quoted string), which is parsed here.
+fn get_gsettings(schema: &str, key: &str) -> Result<String, String> {
+    let output = run_gsettings(&["get", schema, key]).map_err(|e| {
+        gsettings_error(schema, format!("Failed to read {} {}: {}", schema, key, e))
+    })?;
+    glib::Variant::parse(Some(glib::VariantTy::STRING), &output)
+        .ok()
+        .and_then(|v| v.get::<String>())
+        .ok_or_else(|| format!("Unexpected gsettings value for {}: {}", key, output))
+}
+
+/// Whether `gsettings` is installed and knows `schema`. Minimal systems can
+/// have the tool without the GNOME desktop schemas.
+fn gsettings_has_schema(schema: &str) -> bool {
+    run_command(Command::new("gsettings").arg("list-schemas"), "gsettings")
+        .is_ok_and(|schemas| schemas.lines().any(|line| line.trim() == schema))
+}
+
+/// Replace a failed gsettings call's raw error with a clearer one when the
+/// schema isn't installed at all.
+fn gsettings_error(schema: &str, error: String) -> String {
+    if gsettings_has_schema(schema) {
+        error
+    } else {
+        format!(
+            "GNOME settings not available ({} is not installed); try a different backend",
+            schema
+        )
+    }
+}
+
+/// Directory holding the image handed to the wallpaper backend. Most
+/// backends reference the file by path rather than copying it, so unlike the
+/// temp dir it must survive reboots.
+/// Outside Flatpak this is `~/.local/share/wallrus/wallpapers/`.
+fn wallpaper_dir() -> PathBuf {
+    let dir = glib::user_data_dir().join("wallrus").join("wallpapers");
+    if !dir.exists() {
+        let _ = std::fs::create_dir_all(&dir);
+    }
+    dir
+}
+
+/// Prefix of the image files written to `wallpaper_dir()`
+const WALLPAPER_FILE_PREFIX: &str = "wallrus_wallpaper_";
+
+/// Prefix of the lock screen copies in `wallpaper_dir()`
+const LOCKSCREEN_FILE_PREFIX: &str = "wallrus_lockscreen_";
+
+fn unix_millis() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// A fresh path in `wallpaper_dir()` for the next wallpaper image.
+/// Every call gets a new name: desktops ignore a wallpaper setting whose URI
+/// didn't change, even if the file behind it was rewritten.
+/// `variant` distinguishes images written together (e.g. "light" and "dark").
+pub fn new_wallpaper_path(variant: &str) -> PathBuf {
+    wallpaper_dir().join(format!(
+        "{}{}_{}.png",
+        WALLPAPER_FILE_PREFIX,
+        unix_millis(),
+        variant
+    ))
+}
+
+/// Remove wallpaper images written by earlier runs, except those in `keep`.
+pub fn remove_stale_wallpapers(keep: &[&Path]) {
+    remove_stale_files(WALLPAPER_FILE_PREFIX, keep);
+}
+
+/// Remove the files in `wallpaper_dir()` starting with `prefix`, except
+/// those in `keep`.
+fn remove_stale_files(prefix: &str, keep: &[&Path]) {
+    let Ok(entries) = std::fs::read_dir(wallpaper_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_ours = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(prefix));
+        if is_ours && !keep.contains(&path.as_path()) {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Canonicalize a wallpaper path, failing early if the image doesn't exist
+/// so no desktop settings are touched for a bad path.
+fn canonical_wallpaper_path(image_path: &Path) -> Result<PathBuf, String> {
+    image_path.canonicalize().map_err(|e| {
+        format!(
+            "Failed to resolve wallpaper path {}: {}",
+            image_path.display(),
+            e
+        )
+    })
+}
+
+/// `file://` URI for a canonical path
+fn file_uri(path: &Path) -> String {
+    gio::File::for_path(path).uri().to_string()
+}
+
+/// Find an executable in `PATH`.
+fn find_executable(name: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Set the wallpaper on every KDE Plasma desktop.
+///
+/// Uses Plasma's scripting interface (`org.kde.PlasmaShell.evaluateScript`
+/// on the session bus), falling back to `plasma-apply-wallpaperimage`.
+pub async fn set_kde_wallpaper(image_path: &Path) -> Result<(), String> {
+    let image_path = canonical_wallpaper_path(image_path)?;
+
+    let dbus_error = match set_kde_wallpaper_dbus(&file_uri(&image_path)).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let Some(apply_tool) = find_executable("plasma-apply-wallpaperimage") else {
+        return Err(format!(
+            "Plasma scripting is unavailable ({}) and plasma-apply-wallpaperimage was \
+             not found; setting the wallpaper requires Plasma 5.24 or newer",
+            dbus_error
+        ));
+    };
+
+    run_command(
+        Command::new(apply_tool).arg(&image_path),
+        "plasma-apply-wallpaperimage",
+    )
+    .map(|_| ())
+}
+
+/// Apply the image to all desktops through a Plasma desktop script.
+async fn set_kde_wallpaper_dbus(uri: &str) -> Result<(), String> {
+    // URIs are percent-encoded, but escape anyway so the JS string literal can't break
+    let escaped = uri.replace('\\', "\\\\").replace('"', "\\\"");
+    let script = format!(
+        "desktops().forEach(function (d) {{\n\
+             d.wallpaperPlugin = \"org.kde.image\";\n\
+             d.currentConfigGroup = [\"Wallpaper\", \"org.kde.image\", \"General\"];\n\
+             d.writeConfig(\"Image\", \"{}\");\n\
+         }});",
+        escaped
+    );
+
+    let connection = gio::bus_get_future(gio::BusType::Session)
+        .await
+        .map_err(|e| format!("Failed to connect to the session bus: {}", e))?;
+    connection
+        .call_future(
+            Some("org.kde.plasmashell"),
+            "/PlasmaShell",
+            "org.kde.PlasmaShell",
+            "evaluateScript",
+            Some(&(script,).to_variant()),
+            None,
+            gio::DBusCallFlags::NONE,
+            -1,
+        )
+        .await
+        .map_err(|e| format!("Plasma scripting call failed: {}", e))?;
+    Ok(())
+}
+
+/// xfconf channel holding the XFCE desktop settings
+const XFCE_DESKTOP_CHANNEL: &str = "xfce4-desktop";
+
+/// Set the wallpaper on every XFCE monitor and workspace.
+///
+/// XFCE keeps one `/backdrop/screen<N>/monitor<name>/workspace<N>/last-image`
+/// property per monitor and workspace, so the existing ones are listed with
+/// `xfconf-query` and each is pointed at the image.
+pub fn set_xfce_wallpaper(image_path: &Path) -> Result<(), String> {
+    let image_path = canonical_wallpaper_path(image_path)?;
+    let xfconf = find_executable("xfconf-query")
+        .ok_or("xfconf-query was not found; install xfconf to set the XFCE wallpaper")?;
+
+    let listing = run_command(
+        Command::new(&xfconf).args(["--channel", XFCE_DESKTOP_CHANNEL, "--list"]),
+        "xfconf-query",
+    )?;
+    let keys: Vec<&str> = listing
+        .lines()
+        .map(str::trim)
+        .filter(|key| key.starts_with("/backdrop/") && key.ends_with("/last-image"))
+        .collect();
+    if keys.is_empty() {
+        return Err(
+            "No XFCE backdrop settings found; open Desktop Settings once to \
+                    create them"
+                .to_string(),
+        );
+    }
+
+    let image_arg = image_path.to_string_lossy();
+    for key in keys {
+        run_command(
+            Command::new(&xfconf).args([
+                "--channel",
+                XFCE_DESKTOP_CHANNEL,
+                "--property",
+                key,
+                "--set",
+                &image_arg,
+            ]),
+            "xfconf-query",
+        )?;
+    }
+    Ok(())
+}
+
+/// Set the wallpaper on wlroots-based compositors (Hyprland, Sway, …).
+///
+/// Uses `swww` if its daemon is running, otherwise `hyprpaper` through
+/// `hyprctl` IPC. The image is applied to all outputs.
+pub fn set_wlroots_wallpaper(image_path: &Path) -> Result<(), String> {
+    let image_path = canonical_wallpaper_path(image_path)?;
+    let image_arg = image_path.to_string_lossy();
+
+    if let Some(swww) = swww_daemon() {
+        return run_command(Command::new(&swww).args(["img", &image_arg]), "swww").map(|_| ());
+    }
+
+    if let Some(hyprctl) = find_executable("hyprctl") {
+        run_hyprpaper(&hyprctl, &["preload", &image_arg])?;
+        // An empty monitor name applies the wallpaper to every output
+        run_hyprpaper(&hyprctl, &["wallpaper", &format!(",{}", image_arg)])?;
+        // Free previously preloaded images; failure here is harmless
+        let _ = run_hyprpaper(&hyprctl, &["unload", "unused"]);
+        // The wallpaper is set either way, so a config that can't be
+        // written is only logged
+        if let Err(e) = save_hyprpaper_config(&image_arg) {
+            eprintln!("{}", e);
+        }
+        return Ok(());
+    }
+
+    Err(
+        "No wlroots wallpaper tool found (searched for a running swww daemon, \
+         and hyprpaper via hyprctl)"
+            .to_string(),
+    )
+}
+
+/// hyprpaper's config, read when it starts
+fn hyprpaper_config_file() -> PathBuf {
+    glib::user_config_dir().join("hypr").join("hyprpaper.conf")
+}
+
+/// Point hyprpaper's config at `image`, so it comes back after hyprpaper
+/// restarts; IPC changes only last until then.
+fn save_hyprpaper_config(image: &str) -> Result<(), String> {
+    let file = hyprpaper_config_file();
+    let config = std::fs::read_to_string(&file).unwrap_or_default();
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(&file, hyprpaper_config(&config, image))
+        .map_err(|e| format!("Failed to save {}: {}", file.display(), e))
+}
+
+/// `config` with its `preload` and `wallpaper` lines replaced by ones that
+/// show `image` on every output; other settings and comments are kept.
+fn hyprpaper_config(config: &str, image: &str) -> String {
+    let mut lines: Vec<&str> = config
+        .lines()
+        .filter(|line| {
+            let key = line.split('=').next().unwrap_or_default().trim();
+            key != "preload" && key != "wallpaper"
+        })
+        .collect();
+    let preload = format!("preload = {}", image);
+    let wallpaper = format!("wallpaper = ,{}", image);
+    lines.push(&preload);
+    lines.push(&wallpaper);
+    lines.join("\n") + "\n"
+}
+
+/// Set a different image on each wlroots output with `swww`, given as
+/// (output name, image) pairs. Outputs not listed keep their wallpaper.
+///
+/// Every name is checked against the outputs `swww query` reports before
+/// any image is set, so a typo doesn't leave the desktop half changed.
+pub fn set_wlroots_wallpaper_per_output(assignments: &[(String, PathBuf)]) -> Result<(), String> {
+    if assignments.is_empty() {
+        return Err("No outputs to set a wallpaper on".to_string());
+    }
+    let swww = find_executable("swww")
+        .ok_or("swww was not found; per-output wallpapers on wlroots need swww")?;
+    let outputs = swww_outputs(&swww)?;
+    if let Some((name, _)) = assignments.iter().find(|(name, _)| !outputs.contains(name)) {
+        return Err(format!(
+            "Unknown output '{}', swww reports: {}",
+            name,
+            outputs.join(", ")
+        ));
+    }
+
+    for (name, image_path) in assignments {
+        let image_path = canonical_wallpaper_path(image_path)?;
+        run_command(
+            Command::new(&swww)
+                .args(["img", "--outputs", name.as_str()])
+                .arg(&image_path),
+            "swww",
+        )?;
+    }
+    Ok(())
+}
+
+/// Output names known to the `swww` daemon. `swww query` prints one line
+/// per output, like `DP-1: 2560x1440, scale: 1, currently displaying: …`
+/// (older releases start each line with `: `).
+fn swww_outputs(swww: &Path) -> Result<Vec<String>, String> {
+    let reply = run_command(Command::new(swww).arg("query"), "swww")?;
+    Ok(reply
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start().trim_start_matches(':').trim_start();
+            let (name, _) = line.split_once(':')?;
+            Some(name.trim().to_string()).filter(|name| !name.is_empty())
+        })
+        .collect())
+}
+
+/// Set the root window wallpaper on X11 with `feh`, for window managers
+/// without a desktop of their own. `feh` also writes `~/.fehbg`, which
+/// can be run at login to restore the wallpaper.
+pub fn set_x11_wallpaper(image_path: &Path, mode: WallpaperMode) -> Result<(), String> {
+    let image_path = canonical_wallpaper_path(image_path)?;
+    let feh =
+        find_executable("feh").ok_or("feh was not found; install feh to set the X11 wallpaper")?;
+    run_command(
+        Command::new(&feh).args(mode.feh_args()).arg(&image_path),
+        "feh",
+    )
+    .map(|_| ())
+}
+
+/// Path to `swww` if it is installed and its daemon is running
+/// (`swww query` only succeeds when the daemon is up).
+fn swww_daemon() -> Option<PathBuf> {
+    let swww = find_executable("swww")?;
+    Command::new(&swww)
+        .arg("query")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|_| swww)
+}
+
+/// Run a helper command, turning a non-zero exit into an error with its output.
+fn run_command(command: &mut Command, tool: &str) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", tool, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = match stderr.trim() {
+            "" => output.status.to_string(),
+            message => message.to_string(),
+        };
+        return Err(format!("{} failed: {}", tool, message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Send a hyprpaper IPC command. `hyprctl` reports failures on stdout with a
+/// zero exit status, so anything other than "ok" is an error.
+fn run_hyprpaper(hyprctl: &Path, args: &[&str]) -> Result<(), String> {
+    let reply = run_command(
+        Command::new(hyprctl).arg("hyprpaper").args(args),
+        "hyprpaper",
+    )?;
+    if !reply.is_empty() && reply != "ok" {
+        return Err(format!("hyprpaper failed: {}", reply));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hyprpaper_config_points_at_the_new_wallpaper() {
+        let config = "# set by hand\n\
+                      splash = false\n\
+                      preload = /home/me/old.png\n\
+                      wallpaper = DP-1,/home/me/old.png\n\
+                      ipc = on\n";
+        assert_eq!(
+            hyprpaper_config(config, "/tmp/new.png"),
+            "# set by hand\n\
+             splash = false\n\
+             ipc = on\n\
+             preload = /tmp/new.png\n\
+             wallpaper = ,/tmp/new.png\n"
+        );
+        assert_eq!(
+            hyprpaper_config("", "/tmp/new.png"),
+            "preload = /tmp/new.png\nwallpaper = ,/tmp/new.png\n"
+        );
+    }
+
+    #[test]
+    fn only_bus_and_dconf_errors_are_retried() {
+        let transient = [
+            "gsettings failed: (process:4711): dconf-WARNING **: 10:12:13.456: \
+             failed to commit changes to dconf: Could not connect: No such file or directory",
+            "gsettings failed: GDBus.Error:org.freedesktop.DBus.Error.ServiceUnknown: \
+             The name ca.desrt.dconf was not provided by any .service files",
+            "gsettings failed: (process:4711): dconf-WARNING **: 10:12:13.456: \
+             failed to commit changes to dconf: Error spawning command line \
+             “dbus-launch --autolaunch=0123 --binary-syntax --close-stderr”: \
+             Child process exited with code 1",
+            "gsettings failed: Cannot autolaunch D-Bus without X11 $DISPLAY",
+            "gsettings failed: Timeout was reached",
+        ];
+        for error in transient {
+            assert!(is_transient(error), "{}", error);
+        }
+        let permanent = [
+            "gsettings failed: No such schema “org.gnome.desktop.background”",
+            "gsettings failed: No such key “picture-uri-dark”",
+            "gsettings failed: No such schema “ca.desrt.dconf-editor.Settings”",
+            "gsettings failed: Schema “org.gnome.Terminal.Legacy.Profile” is relocatable \
+             (path must be specified)",
+            "gsettings failed: 0:expected value",
+        ];
+        for error in permanent {
+            assert!(!is_transient(error), "{}", error);
+        }
+    }
+}
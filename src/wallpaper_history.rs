@@ -0,0 +1,218 @@
+/// The wallpapers Wallrus has set, newest first, so earlier ones can be put
+/// back. Each successful `wallpaper::set_wallpaper` copies the image into
+/// the user data directory (`~/.local/share/wallrus/wallpaper-history/`) with a
+/// small key file beside it recording the palette and preset it was
+/// rendered with. Only the latest [`HISTORY_LIMIT`] are kept.
+use std::path::{Path, PathBuf};
+
+use gtk4::glib;
+
+use crate::export;
+use crate::palette;
+
+/// Wallpapers kept in the history; the oldest are removed past this
+pub const HISTORY_LIMIT: usize = 20;
+
+const GROUP: &str = "Wallpaper";
+
+/// A wallpaper in the history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// The copy of the image in the history directory
+    pub image: PathBuf,
+    /// Preset the image was rendered with, if it carried Wallrus metadata
+    pub preset: Option<String>,
+    /// Palette colors in use; empty without Wallrus metadata
+    pub colors: Vec<[f32; 3]>,
+    /// When it was last set, as milliseconds since the Unix epoch
+    pub set_at: u64,
+}
+
+fn history_dir() -> PathBuf {
+    glib::user_data_dir()
+        .join("wallrus")
+        .join("wallpaper-history")
+}
+
+/// The key file recording an image's palette and preset
+fn sidecar_path(image: &Path) -> PathBuf {
+    image.with_extension("ini")
+}
+
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Add a wallpaper that was just set to the history. Setting an image that
+/// is already in the history moves it to the front instead of copying it
+/// again. The oldest entries beyond [`HISTORY_LIMIT`] are removed.
+pub fn record_wallpaper(path: &Path) -> Result<(), String> {
+    record_wallpaper_in(&history_dir(), path)
+}
+
+fn record_wallpaper_in(dir: &Path, path: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let set_at = unix_millis();
+    let in_history = match (path.parent().map(Path::canonicalize), dir.canonicalize()) {
+        (Some(Ok(parent)), Ok(dir)) => parent == dir,
+        _ => false,
+    };
+    let image = if in_history {
+        path.to_path_buf()
+    } else {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+        let image = dir.join(format!("wallpaper_{}.{}", set_at, extension));
+        std::fs::copy(path, &image)
+            .map_err(|e| format!("Failed to copy wallpaper to history: {}", e))?;
+        image
+    };
+
+    let key_file = glib::KeyFile::new();
+    if in_history {
+        // Keep what was recorded when the image was first set
+        let _ = key_file.load_from_file(sidecar_path(&image), glib::KeyFileFlags::NONE);
+    } else if let Some(meta) = export::read_embedded_metadata(&image) {
+        let colors: Vec<String> = meta
+            .colors
+            .iter()
+            .take(meta.color_count as usize)
+            .map(|c| palette::rgb_to_hex(*c))
+            .collect();
+        key_file.set_string(GROUP, "preset", &meta.preset);
+        key_file.set_string(GROUP, "colors", &colors.join(","));
+    }
+    key_file.set_uint64(GROUP, "set-at", set_at);
+    let sidecar = sidecar_path(&image);
+    key_file
+        .save_to_file(&sidecar)
+        .map_err(|e| format!("Failed to save {}: {}", sidecar.display(), e))?;
+
+    for stale in list_wallpaper_history_in(dir).iter().skip(HISTORY_LIMIT) {
+        let _ = std::fs::remove_file(&stale.image);
+        let _ = std::fs::remove_file(sidecar_path(&stale.image));
+    }
+    Ok(())
+}
+
+/// The wallpapers in the history, most recently set first. Entries whose
+/// image or key file is missing are skipped.
+pub fn list_wallpaper_history() -> Vec<HistoryEntry> {
+    list_wallpaper_history_in(&history_dir())
+}
+
+fn list_wallpaper_history_in(dir: &Path) -> Vec<HistoryEntry> {
+    let Ok(files) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<HistoryEntry> = files
+        .flatten()
+        .map(|file| file.path())
+        .filter(|path| path.extension().is_some_and(|e| e != "ini"))
+        .filter_map(|image| {
+            let key_file = glib::KeyFile::new();
+            key_file
+                .load_from_file(sidecar_path(&image), glib::KeyFileFlags::NONE)
+                .ok()?;
+            let colors = key_file
+                .string(GROUP, "colors")
+                .map(|s| {
+                    s.split(',')
+                        .filter_map(|hex| palette::hex_to_rgb(hex).ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(HistoryEntry {
+                preset: key_file.string(GROUP, "preset").ok().map(|s| s.to_string()),
+                colors,
+                set_at: key_file.uint64(GROUP, "set-at").unwrap_or(0),
+                image,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.set_at));
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty history directory under the temp dir, and a wallpaper to
+    /// record outside it
+    fn fixture(name: &str) -> (PathBuf, PathBuf) {
+        let root = std::env::temp_dir().join(format!("wallrus_test_history_{}", name));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let wallpaper = root.join("wallpaper.png");
+        std::fs::write(&wallpaper, b"not really a png").unwrap();
+        (root.join("history"), wallpaper)
+    }
+
+    /// Let the clock move on, since entries are ordered by millisecond
+    fn tick() {
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    #[test]
+    fn recorded_wallpapers_are_listed_newest_first() {
+        let (dir, wallpaper) = fixture("order");
+        record_wallpaper_in(&dir, &wallpaper).unwrap();
+        tick();
+        record_wallpaper_in(&dir, &wallpaper).unwrap();
+
+        let entries = list_wallpaper_history_in(&dir);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].set_at > entries[1].set_at);
+        for entry in &entries {
+            assert_eq!(entry.image.parent(), Some(dir.as_path()));
+            assert_eq!(std::fs::read(&entry.image).unwrap(), b"not really a png");
+            // No Wallrus metadata to record
+            assert_eq!(entry.preset, None);
+            assert!(entry.colors.is_empty());
+        }
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn setting_a_history_entry_moves_it_to_the_front() {
+        let (dir, wallpaper) = fixture("move");
+        record_wallpaper_in(&dir, &wallpaper).unwrap();
+        tick();
+        record_wallpaper_in(&dir, &wallpaper).unwrap();
+        let oldest = list_wallpaper_history_in(&dir)[1].clone();
+
+        tick();
+        record_wallpaper_in(&dir, &oldest.image).unwrap();
+        let entries = list_wallpaper_history_in(&dir);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].image, oldest.image);
+        assert!(entries[0].set_at > oldest.set_at);
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn history_is_pruned_to_the_limit() {
+        let (dir, wallpaper) = fixture("prune");
+        for _ in 0..HISTORY_LIMIT + 3 {
+            record_wallpaper_in(&dir, &wallpaper).unwrap();
+            tick();
+        }
+        assert_eq!(list_wallpaper_history_in(&dir).len(), HISTORY_LIMIT);
+        // The oldest images went with their key files
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, HISTORY_LIMIT * 2);
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_history_is_empty() {
+        let (dir, _) = fixture("missing");
+        assert!(list_wallpaper_history_in(&dir).is_empty());
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+    }
+}
@@ -1,32 +1,87 @@
 use gtk4::gdk;
-use gtk4::gdk_pixbuf;
 use gtk4::gio;
 use gtk4::glib;
 use gtk4::prelude::*;
 use libadwaita as adw;
 use libadwaita::prelude::*;
-use std::cell::RefCell;
-use std::path::PathBuf;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use crate::export::{self, ExportFormat, ExportResolution};
+use crate::application::{self, EXPORT_NOTIFICATION, WALLPAPER_NOTIFICATION};
+use crate::diagnostics;
+use crate::export::{self, Dither, ExportFormat, ExportResolution, SampleQuality, VideoFormat};
 use crate::gl_renderer;
-use crate::palette;
+use crate::looks;
+use crate::palette::{self, ColorSpace};
+use crate::palette_card::{self, CardLayout, PaletteCardOptions};
+use crate::preset_gallery;
+use crate::recipe;
+use crate::rotation;
+use crate::screenshot;
+use crate::settings;
+use crate::shader;
 use crate::shader_presets;
 use crate::wallpaper;
+use crate::wallpaper_history;
+
+/// Rows for the current preset's parameters, by uniform name, with the
+/// adjustment holding each one's value (a slider's, or 0/1 for a switch)
+type ParamSliders = Rc<RefCell<Vec<(&'static str, adw::ActionRow, gtk4::Adjustment)>>>;
+
+/// Delay before recompiling a user shader after the last change event.
+const SHADER_RELOAD_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How long palette edits must settle before they become one undo step.
+const PALETTE_HISTORY_SETTLE: std::time::Duration = std::time::Duration::from_millis(400);
+
+/// Delay before rescanning the palette directories, so a burst of refreshes is scanned once.
+const PALETTE_SCAN_SETTLE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// How long the extraction slider must rest before the photo is re-clustered.
+const EXTRACT_PREVIEW_SETTLE: std::time::Duration = std::time::Duration::from_millis(150);
+
+/// Undo steps kept for palette edits; older ones are dropped.
+const PALETTE_HISTORY_DEPTH: usize = 100;
+
+/// Tooltip of the swatch hex entries while their text is valid.
+const HEX_ENTRY_TOOLTIP: &str = "Hex color: #RGB, #RRGGBB or #RRGGBBAA";
+
+/// Supersampling of the "Render Final Quality" pass, above the fast preview.
+const FINAL_RENDER_QUALITY: SampleQuality = SampleQuality::X4;
 
 pub struct WallrusWindow;
 
 impl WallrusWindow {
     pub fn new(app: &adw::Application) -> adw::ApplicationWindow {
         let state = gl_renderer::new_shared_state();
+        let saved = settings::load();
 
         // --- Header bar ---
         let header = adw::HeaderBar::new();
         header.set_title_widget(Some(&gtk4::Label::new(Some("Wallrus"))));
 
-        // Hamburger menu with About item
+        // Hamburger menu with shortcuts and About items
         let menu = gio::Menu::new();
+        let fps_menu = gio::Menu::new();
+        for fps in gl_renderer::PREVIEW_FPS_CHOICES {
+            fps_menu.append(
+                Some(&format!("{} fps", fps)),
+                Some(&format!("win.preview-fps::{}", fps)),
+            );
+        }
+        menu.append_submenu(Some("Preview Frame Rate"), &fps_menu);
+        menu.append(Some("Transparency Backdrop"), Some("win.preview-backdrop"));
+        let gpu_menu = gio::Menu::new();
+        for pref in gl_renderer::GpuPreference::ALL {
+            gpu_menu.append(
+                Some(pref.label()),
+                Some(&format!("win.gpu-preference::{}", pref.name())),
+            );
+        }
+        menu.append_submenu(Some("GPU"), &gpu_menu);
+        menu.append(Some("Render Diagnostics"), Some("win.render-diagnostics"));
+        menu.append(Some("Keyboard Shortcuts"), Some("win.show-help-overlay"));
         menu.append(Some("About Wallrus"), Some("win.show-about"));
         let menu_button = gtk4::MenuButton::new();
         menu_button.set_icon_name("open-menu-symbolic");
@@ -35,27 +90,119 @@ impl WallrusWindow {
 
         // --- GL preview area ---
         let gl_area = gl_renderer::create_gl_area(state.clone());
+        let frame_limiter =
+            gl_renderer::FrameLimiter::new(&gl_area, state.clone(), saved.preview_fps);
         gl_area.set_size_request(320, 180);
 
+        // A checkerboard behind the preview, seen through shaders that
+        // output transparency while the backdrop is on
+        let preview_backdrop = gtk4::DrawingArea::new();
+        preview_backdrop.set_draw_func(draw_checkerboard);
+        preview_backdrop.set_visible(false);
+        let preview_overlay = gtk4::Overlay::new();
+        preview_overlay.set_child(Some(&preview_backdrop));
+        preview_overlay.add_overlay(&gl_area);
+        preview_overlay.set_measure_overlay(&gl_area, true);
+
         // Wrap in an AspectFrame so the preview keeps 16:9
         let aspect_frame = gtk4::AspectFrame::new(0.5, 0.5, 16.0 / 9.0, false);
-        aspect_frame.set_child(Some(&gl_area));
+        aspect_frame.set_child(Some(&preview_overlay));
         aspect_frame.set_vexpand(true);
 
+        // --- Transparency backdrop, for shaders that read uColorAlpha ---
+        let action_preview_backdrop =
+            gio::SimpleAction::new_stateful("preview-backdrop", None, &false.to_variant());
+        let update_preview_backdrop: Rc<dyn Fn()> = {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let preview_backdrop = preview_backdrop.clone();
+            let action = action_preview_backdrop.clone();
+            Rc::new(move || {
+                let uses_alpha = state.borrow().as_ref().is_some_and(|r| r.uses_alpha());
+                action.set_enabled(uses_alpha);
+                let show = uses_alpha && action.state().and_then(|s| s.get::<bool>()) == Some(true);
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.preview_alpha = show;
+                }
+                preview_backdrop.set_visible(show);
+                gl_area.queue_render();
+            })
+        };
+        {
+            let update = update_preview_backdrop.clone();
+            action_preview_backdrop.connect_activate(move |action, _| {
+                let on = action.state().and_then(|s| s.get::<bool>()) == Some(true);
+                action.set_state(&(!on).to_variant());
+                update();
+            });
+        }
+
+        // Compile errors of the loaded shader, with the compiler's line
+        // numbers; hidden again once it compiles (see update_video_export)
+        let shader_error_label = gtk4::Label::new(None);
+        shader_error_label.set_xalign(0.0);
+        shader_error_label.set_wrap(true);
+        shader_error_label.set_wrap_mode(gtk4::pango::WrapMode::WordChar);
+        shader_error_label.set_selectable(true);
+        shader_error_label.add_css_class("monospace");
+        shader_error_label.add_css_class("error");
+        let shader_error_scroll = gtk4::ScrolledWindow::new();
+        shader_error_scroll.set_child(Some(&shader_error_label));
+        shader_error_scroll.set_hscrollbar_policy(gtk4::PolicyType::Never);
+        shader_error_scroll.set_propagate_natural_height(true);
+        shader_error_scroll.set_max_content_height(160);
+        shader_error_scroll.set_margin_top(8);
+        let shader_error_revealer = gtk4::Revealer::new();
+        shader_error_revealer.set_child(Some(&shader_error_scroll));
+
         // Preview group — matches PreferencesGroup styling used on the left column
         let preview_group = adw::PreferencesGroup::new();
         preview_group.set_title("Preview");
         preview_group.add(&aspect_frame);
+        preview_group.add(&shader_error_revealer);
         preview_group.set_vexpand(true);
 
         // --- Shader preset dropdown ---
-        let preset_names = shader_presets::preset_names();
-        let preset_list = gtk4::StringList::new(preset_names);
+        // Bundled presets followed by user shaders from the shaders directory
+        let preset_entries: Rc<RefCell<Vec<shader_presets::PresetEntry>>> =
+            Rc::new(RefCell::new(shader_presets::list_presets()));
+        let preset_list = gtk4::StringList::new(
+            &preset_entries
+                .borrow()
+                .iter()
+                .map(|e| e.name())
+                .collect::<Vec<_>>(),
+        );
         let preset_row = adw::ComboRow::new();
         preset_row.set_title("Type");
         preset_row.set_model(Some(&preset_list));
         preset_row.set_selected(0);
 
+        // Gallery of preset thumbnails with the current palette, filled by
+        // refresh_gallery below
+        let gallery_flowbox = gtk4::FlowBox::new();
+        gallery_flowbox.set_selection_mode(gtk4::SelectionMode::None);
+        gallery_flowbox.set_homogeneous(true);
+        gallery_flowbox.set_min_children_per_line(3);
+        gallery_flowbox.set_max_children_per_line(3);
+        gallery_flowbox.set_row_spacing(8);
+        gallery_flowbox.set_column_spacing(8);
+        let gallery_scroll = gtk4::ScrolledWindow::new();
+        gallery_scroll.set_hscrollbar_policy(gtk4::PolicyType::Never);
+        gallery_scroll.set_propagate_natural_width(true);
+        gallery_scroll.set_propagate_natural_height(true);
+        gallery_scroll.set_max_content_height(480);
+        gallery_scroll.set_child(Some(&gallery_flowbox));
+        let gallery_popover = gtk4::Popover::new();
+        gallery_popover.set_child(Some(&gallery_scroll));
+        let gallery_button = gtk4::MenuButton::new();
+        gallery_button.set_icon_name("view-grid-symbolic");
+        gallery_button.add_css_class("flat");
+        gallery_button.set_valign(gtk4::Align::Center);
+        gallery_button.set_tooltip_text(Some("Browse presets"));
+        gallery_button.set_popover(Some(&gallery_popover));
+        preset_row.add_suffix(&gallery_button);
+
         // =====================================================================
         // Palette section — category dropdown + FlowBox thumbnail browser
         // =====================================================================
@@ -64,7 +211,7 @@ impl WallrusWindow {
         let all_categories: Rc<RefCell<palette::PaletteCategories>> =
             Rc::new(RefCell::new(palette::list_palette_categories()));
         let category_names: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(
-            all_categories.borrow().keys().cloned().collect(),
+            palette::category_names(&all_categories.borrow()),
         ));
 
         // Category dropdown
@@ -82,7 +229,8 @@ impl WallrusWindow {
 
         // FlowBox for palette thumbnails
         let palette_flowbox = gtk4::FlowBox::new();
-        palette_flowbox.set_selection_mode(gtk4::SelectionMode::Single);
+        // Ctrl- and Shift-click select several palettes, to delete together
+        palette_flowbox.set_selection_mode(gtk4::SelectionMode::Multiple);
         palette_flowbox.set_homogeneous(true);
         palette_flowbox.set_min_children_per_line(3);
         palette_flowbox.set_max_children_per_line(10);
@@ -132,18 +280,17 @@ impl WallrusWindow {
                     return;
                 }
 
+                // Thumbnails are drawn from cached colors rather than decoding each image
                 for path in images {
-                    match gdk_pixbuf::Pixbuf::from_file_at_scale(
-                        path.to_str().unwrap_or_default(),
-                        80,
-                        80,
-                        false,
-                    ) {
-                        Ok(pixbuf) => {
-                            let texture = gdk::Texture::for_pixbuf(&pixbuf);
+                    match palette::cached_palette_colors(path) {
+                        Ok(colors) => {
+                            let texture = swatch_texture(&colors, 80);
                             let image = gtk4::Picture::for_paintable(&texture);
                             image.set_size_request(80, 80);
                             image.set_content_fit(gtk4::ContentFit::Cover);
+                            if let Some(source) = palette::source_thumbnail(path) {
+                                set_source_tooltip(&image, source);
+                            }
 
                             if is_custom {
                                 // Wrap in overlay with delete button
@@ -225,8 +372,30 @@ impl WallrusWindow {
         // Palette group
         let palette_group = adw::PreferencesGroup::new();
         palette_group.set_title("Palette");
+        let palette_search = gtk4::SearchEntry::new();
+        palette_search.set_placeholder_text(Some("Search palettes"));
+        palette_search.set_valign(gtk4::Align::Center);
+        palette_group.set_header_suffix(Some(&palette_search));
         palette_group.add(&category_row);
 
+        // Right-click the category row to manage user category folders
+        {
+            let category_menu = gio::Menu::new();
+            category_menu.append(Some("New Category…"), Some("win.new-category"));
+            category_menu.append(Some("Rename Category…"), Some("win.rename-category"));
+            category_menu.append(Some("Delete Category"), Some("win.delete-category"));
+            let popover = gtk4::PopoverMenu::from_model(Some(&category_menu));
+            popover.set_parent(&category_row);
+            popover.set_has_arrow(false);
+            let click = gtk4::GestureClick::new();
+            click.set_button(gdk::BUTTON_SECONDARY);
+            click.connect_pressed(move |_, _, x, y| {
+                popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                popover.popup();
+            });
+            category_row.add_controller(click);
+        }
+
         // Wrap the scrollable FlowBox in a ListBoxRow so it sits inside the
         // PreferencesGroup's rounded rectangle together with the category dropdown
         let palette_listbox_row = gtk4::ListBoxRow::new();
@@ -252,16 +421,116 @@ impl WallrusWindow {
                 let rgba = gdk::RGBA::new(c[0], c[1], c[2], 1.0);
                 let btn = gtk4::ColorDialogButton::new(Some(color_dialog.clone()));
                 btn.set_rgba(&rgba);
+                // The tooltip names the closest CSS color, e.g. "Slate Blue"
+                btn.set_tooltip_text(Some(palette::nearest_color_name(*c)));
+                btn.connect_rgba_notify(|btn| {
+                    let name = palette::nearest_color_name(rgba_color(&btn.rgba()));
+                    btn.set_tooltip_text(Some(name));
+                });
                 btn
             })
             .collect();
 
+        // Lock toggles under each swatch keep that color when the palette changes
+        let lock_buttons: Vec<gtk4::ToggleButton> = (0..color_buttons.len())
+            .map(|_| {
+                let lock = gtk4::ToggleButton::new();
+                lock.set_icon_name("changes-allow-symbolic");
+                lock.add_css_class("flat");
+                lock.set_tooltip_text(Some("Lock color"));
+                lock.connect_toggled(|lock| {
+                    if lock.is_active() {
+                        lock.set_icon_name("changes-prevent-symbolic");
+                        lock.set_tooltip_text(Some("Unlock color"));
+                    } else {
+                        lock.set_icon_name("changes-allow-symbolic");
+                        lock.set_tooltip_text(Some("Lock color"));
+                    }
+                });
+                lock
+            })
+            .collect();
+
+        // Hex entry under each swatch for typing an exact color
+        let hex_entries: Vec<gtk4::Entry> = color_buttons
+            .iter()
+            .map(|btn| {
+                let entry = gtk4::Entry::new();
+                entry.set_text(&palette::rgb_to_hex(rgba_color(&btn.rgba())));
+                entry.set_width_chars(7);
+                entry.set_max_width_chars(7);
+                entry.set_max_length(9);
+                EditableExt::set_alignment(&entry, 0.5);
+                entry.add_css_class("monospace");
+                entry.set_tooltip_text(Some(HEX_ENTRY_TOOLTIP));
+                entry
+            })
+            .collect();
+
         let color_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
         color_box.set_halign(gtk4::Align::Center);
         color_box.set_margin_top(8);
         color_box.set_margin_bottom(8);
-        for btn in &color_buttons {
-            color_box.append(btn);
+        for (slot, (btn, lock)) in color_buttons.iter().zip(&lock_buttons).enumerate() {
+            let swatch_box = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+            swatch_box.append(btn);
+            swatch_box.append(lock);
+            // The entry sits outside the drag source so text can be selected
+            let swatch_column = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+            swatch_column.append(&swatch_box);
+            swatch_column.append(&hex_entries[slot]);
+            color_box.append(&swatch_column);
+
+            // Right-click or Ctrl+C on a focused swatch copies its hex code
+            let copy_menu = gio::Menu::new();
+            copy_menu.append_item(&gio::MenuItem::new(
+                Some("Copy Hex"),
+                Some(&gio::Action::print_detailed_name(
+                    "win.copy-hex",
+                    Some(&(slot as u32).to_variant()),
+                )),
+            ));
+            let copy_popover = gtk4::PopoverMenu::from_model(Some(&copy_menu));
+            copy_popover.set_parent(btn);
+            copy_popover.set_has_arrow(false);
+            let click = gtk4::GestureClick::new();
+            click.set_button(gdk::BUTTON_SECONDARY);
+            click.connect_pressed(move |_, _, x, y| {
+                copy_popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                copy_popover.popup();
+            });
+            btn.add_controller(click);
+            let shortcuts = gtk4::ShortcutController::new();
+            let copy_shortcut = gtk4::Shortcut::new(
+                gtk4::ShortcutTrigger::parse_string("<Control>c"),
+                Some(gtk4::NamedAction::new("win.copy-hex")),
+            );
+            copy_shortcut.set_arguments(Some(&(slot as u32).to_variant()));
+            shortcuts.add_shortcut(copy_shortcut);
+            btn.add_controller(shortcuts);
+
+            // Drag a swatch onto another to move it there; its lock moves with it
+            let drag_source = gtk4::DragSource::new();
+            drag_source.set_actions(gdk::DragAction::MOVE);
+            drag_source.connect_prepare(move |_, _, _| {
+                Some(gdk::ContentProvider::for_value(&(slot as u32).to_value()))
+            });
+            swatch_box.add_controller(drag_source);
+
+            let drop_target = gtk4::DropTarget::new(u32::static_type(), gdk::DragAction::MOVE);
+            let buttons = color_buttons.clone();
+            let locks = lock_buttons.clone();
+            drop_target.connect_drop(move |_, value, _, _| {
+                let Some(from) = value.get::<u32>().ok().filter(|&from| from < 4) else {
+                    return false;
+                };
+                let mut order = vec![0, 1, 2, 3];
+                let moved = order.remove(from as usize);
+                order.insert(slot, moved);
+                reorder_slots(&buttons, &locks, [order[0], order[1], order[2], order[3]]);
+                true
+            });
+            swatch_box.add_controller(drop_target);
         }
 
         let save_palette_button = gtk4::Button::from_icon_name("document-save-symbolic");
@@ -270,12 +539,190 @@ impl WallrusWindow {
         save_palette_button.set_tooltip_text(Some("Save as custom palette"));
         color_box.append(&save_palette_button);
 
+        // Palette tools menu (import and transforms)
+        let palette_menu = gio::Menu::new();
+        palette_menu.append(Some("Import from Photo…"), Some("win.import-photo"));
+        palette_menu.append(
+            Some("Import from Screenshot…"),
+            Some("win.import-screenshot"),
+        );
+        palette_menu.append(Some("Import GIMP Palette…"), Some("win.import-gpl"));
+        palette_menu.append(Some("Import pywal Colors…"), Some("win.import-pywal"));
+        palette_menu.append(Some("Import Palette Grid…"), Some("win.import-grid"));
+        palette_menu.append(
+            Some("Recreate from Wallpaper…"),
+            Some("win.import-wallpaper"),
+        );
+        palette_menu.append(Some("Open Recipe…"), Some("win.open-recipe"));
+        palette_menu.append(Some("Export Palette…"), Some("win.export-palette"));
+        let card_menu = gio::Menu::new();
+        for layout in CardLayout::all() {
+            card_menu.append(
+                Some(&format!("{}…", layout.label())),
+                Some(&format!("win.export-palette-card::{}", layout.id())),
+            );
+        }
+        palette_menu.append_submenu(Some("Export Palette Card"), &card_menu);
+        palette_menu.append(Some("Contrast Report"), Some("win.contrast-report"));
+        palette_menu.append(
+            Some("Compare with Selected Palette"),
+            Some("win.compare-palette"),
+        );
+        palette_menu.append(Some("Export Wallpaper Pack…"), Some("win.export-category"));
+        let harmony_menu = gio::Menu::new();
+        for scheme in palette::HarmonyScheme::all() {
+            harmony_menu.append(
+                Some(scheme.label()),
+                Some(&format!("win.generate-harmony::{}", scheme.id())),
+            );
+        }
+        palette_menu.append_submenu(Some("Harmony from First Color"), &harmony_menu);
+        let sort_menu = gio::Menu::new();
+        for key in palette::SortKey::all() {
+            sort_menu.append(
+                Some(key.label()),
+                Some(&format!("win.sort-colors::{}", key.id())),
+            );
+        }
+        sort_menu.append(Some("Smoothest Gradient"), Some("win.optimize-order"));
+        palette_menu.append_submenu(Some("Sort Colors"), &sort_menu);
+        palette_menu.append(Some("Invert Colors"), Some("win.invert-palette"));
+        palette_menu.append(Some("Grayscale"), Some("win.grayscale-palette"));
+        palette_menu.append(Some("Reset to Original Colors"), Some("win.reset-palette"));
+        let manage_section = gio::Menu::new();
+        manage_section.append(Some("Favorite"), Some("win.toggle-favorite"));
+        manage_section.append(Some("Keep Preset When Switching"), Some("win.pin-preset"));
+        manage_section.append(Some("Rename Palette…"), Some("win.rename-palette"));
+        manage_section.append(Some("Move to Category…"), Some("win.move-palette"));
+        palette_menu.append_section(None, &manage_section);
+        let palette_menu_button = gtk4::MenuButton::new();
+        palette_menu_button.set_icon_name("view-more-symbolic");
+        palette_menu_button.add_css_class("flat");
+        palette_menu_button.add_css_class("circular");
+        palette_menu_button.set_tooltip_text(Some("Palette tools"));
+        palette_menu_button.set_menu_model(Some(&palette_menu));
+        color_box.append(&palette_menu_button);
+
         let color_picker_row = gtk4::ListBoxRow::new();
         color_picker_row.set_child(Some(&color_box));
         color_picker_row.set_activatable(false);
         color_picker_row.set_selectable(false);
         palette_group.add(&color_picker_row);
 
+        // Non-blocking hint when colors are too close to give distinct bands
+        let palette_warning_label = gtk4::Label::new(None);
+        palette_warning_label.add_css_class("warning");
+        palette_warning_label.add_css_class("caption");
+        palette_warning_label.set_wrap(true);
+        palette_warning_label.set_xalign(0.0);
+        palette_warning_label.set_margin_start(12);
+        palette_warning_label.set_margin_end(12);
+        palette_warning_label.set_margin_top(6);
+        palette_warning_label.set_margin_bottom(6);
+        let palette_warning_row = gtk4::ListBoxRow::new();
+        palette_warning_row.set_child(Some(&palette_warning_label));
+        palette_warning_row.set_activatable(false);
+        palette_warning_row.set_selectable(false);
+        palette_warning_row.set_visible(false);
+        palette_group.add(&palette_warning_row);
+
+        // --- Whole-palette HSL, temperature, brightness and contrast sliders ---
+        let make_adjust_row = |title: &str, min: f64, max: f64| {
+            let scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, min, max, 1.0);
+            scale.set_value(0.0);
+            scale.set_hexpand(true);
+            scale.set_draw_value(true);
+            scale.set_value_pos(gtk4::PositionType::Right);
+            scale.add_mark(0.0, gtk4::PositionType::Bottom, None);
+            let row = adw::ActionRow::builder().title(title).build();
+            row.add_suffix(&scale);
+            (row, scale)
+        };
+        let (hue_shift_row, hue_shift_scale) = make_adjust_row("Hue", -180.0, 180.0);
+        let (sat_shift_row, sat_shift_scale) = make_adjust_row("Saturation", -100.0, 100.0);
+        let (light_shift_row, light_shift_scale) = make_adjust_row("Lightness", -100.0, 100.0);
+        let (temperature_row, temperature_scale) = make_adjust_row("Temperature", -3000.0, 3000.0);
+        temperature_row.set_subtitle("Kelvin; positive is warmer");
+        temperature_scale.set_increments(100.0, 500.0);
+        let (brightness_row, brightness_scale) = make_adjust_row("Brightness", -100.0, 100.0);
+        let (contrast_row, contrast_scale) = make_adjust_row("Contrast", -100.0, 100.0);
+        // Blending toward a second palette, chosen from a palette image
+        let blend_palette_button = gtk4::Button::with_label("Choose…");
+        blend_palette_button.set_valign(gtk4::Align::Center);
+        let blend_palette_row = adw::ActionRow::builder()
+            .title("Blend With")
+            .subtitle("No palette chosen")
+            .build();
+        blend_palette_row.add_suffix(&blend_palette_button);
+        let (blend_amount_row, blend_amount_scale) = make_adjust_row("Blend", 0.0, 100.0);
+        blend_amount_row.set_subtitle("Percent of the way to the other palette");
+        blend_amount_scale.set_sensitive(false);
+        let adjust_row = adw::ExpanderRow::builder()
+            .title("Adjust Colors")
+            .subtitle("Shift all unlocked colors at once")
+            .build();
+        adjust_row.add_row(&hue_shift_row);
+        adjust_row.add_row(&sat_shift_row);
+        adjust_row.add_row(&light_shift_row);
+        adjust_row.add_row(&temperature_row);
+        adjust_row.add_row(&brightness_row);
+        adjust_row.add_row(&contrast_row);
+        adjust_row.add_row(&blend_palette_row);
+        adjust_row.add_row(&blend_amount_row);
+        palette_group.add(&adjust_row);
+
+        // --- Random palette generator ---
+        let random_theme_row = adw::ComboRow::new();
+        random_theme_row.set_title("Theme");
+        random_theme_row.set_model(Some(&gtk4::StringList::new(&["Any", "Light", "Dark"])));
+        let random_contrast_row = adw::SpinRow::with_range(1.0, 7.0, 0.5);
+        random_contrast_row.set_title("Minimum Contrast");
+        random_contrast_row.set_subtitle("Between neighbouring colors");
+        random_contrast_row.set_digits(1);
+        random_contrast_row.set_value(1.5);
+        let random_hue_row = adw::SwitchRow::new();
+        random_hue_row.set_title("Keep First Color's Hue");
+        let random_seed_row = adw::EntryRow::new();
+        random_seed_row.set_title("Seed");
+        random_seed_row.set_show_apply_button(true);
+        random_seed_row.set_input_purpose(gtk4::InputPurpose::Digits);
+        let random_button = gtk4::Button::from_icon_name("media-playlist-shuffle-symbolic");
+        random_button.add_css_class("flat");
+        random_button.set_valign(gtk4::Align::Center);
+        random_button.set_tooltip_text(Some("Generate a random palette"));
+        let random_row = adw::ExpanderRow::builder()
+            .title("Random Palette")
+            .subtitle("Enter a seed to reproduce a palette")
+            .build();
+        random_row.add_suffix(&random_button);
+        random_row.add_row(&random_theme_row);
+        random_row.add_row(&random_contrast_row);
+        random_row.add_row(&random_hue_row);
+        random_row.add_row(&random_seed_row);
+        palette_group.add(&random_row);
+
+        // --- Color vision deficiency preview ---
+        let mut cvd_labels = vec!["Normal"];
+        cvd_labels.extend(palette::CvdType::all().map(|kind| kind.label()));
+        let cvd_row = adw::ComboRow::builder()
+            .title("Color Vision")
+            .subtitle("Preview only; exports keep the real colors")
+            .model(&gtk4::StringList::new(&cvd_labels))
+            .build();
+        let cvd_swatch_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 2);
+        cvd_swatch_box.set_valign(gtk4::Align::Center);
+        cvd_swatch_box.set_visible(false);
+        let cvd_swatches: Vec<gtk4::Picture> = (0..color_buttons.len())
+            .map(|_| {
+                let swatch = gtk4::Picture::new();
+                swatch.set_size_request(16, 24);
+                cvd_swatch_box.append(&swatch);
+                swatch
+            })
+            .collect();
+        cvd_row.add_suffix(&cvd_swatch_box);
+        palette_group.add(&cvd_row);
+
         // =====================================================================
         // Shader parameter sliders
         // =====================================================================
@@ -342,6 +789,16 @@ impl WallrusWindow {
         blend_hint_row.set_activatable(false);
         blend_hint_row.set_selectable(false);
 
+        // --- Number of palette colors the preset blends through ---
+        let color_count_row = adw::SpinRow::with_range(
+            gl_renderer::MIN_COLOR_COUNT as f64,
+            gl_renderer::MAX_COLOR_COUNT as f64,
+            1.0,
+        );
+        color_count_row.set_title("Colors");
+        color_count_row.set_subtitle("Later palette colors are left out");
+        color_count_row.set_value(gl_renderer::MAX_COLOR_COUNT as f64);
+
         // --- Center slider ---
         let center_scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, -1.0, 1.0, 0.01);
         center_scale.set_value(0.0);
@@ -380,6 +837,18 @@ impl WallrusWindow {
         center_hint_row.set_activatable(false);
         center_hint_row.set_selectable(false);
 
+        // --- Render seed for presets with randomness ---
+        let seed_row = adw::EntryRow::new();
+        seed_row.set_title("Seed");
+        seed_row.set_text(&gl_renderer::RenderSeed::default().to_string());
+        seed_row.set_show_apply_button(true);
+        seed_row.set_input_purpose(gtk4::InputPurpose::Digits);
+        let seed_button = gtk4::Button::from_icon_name("media-playlist-shuffle-symbolic");
+        seed_button.add_css_class("flat");
+        seed_button.set_valign(gtk4::Align::Center);
+        seed_button.set_tooltip_text(Some("Pick a new seed"));
+        seed_row.add_suffix(&seed_button);
+
         // --- Controls group ---
         let controls_group = adw::PreferencesGroup::new();
         controls_group.set_title("Pattern");
@@ -389,8 +858,10 @@ impl WallrusWindow {
         controls_group.add(&speed_row);
         controls_group.add(&blend_row);
         controls_group.add(&blend_hint_row);
+        controls_group.add(&color_count_row);
         controls_group.add(&center_row);
         controls_group.add(&center_hint_row);
+        controls_group.add(&seed_row);
 
         // =====================================================================
         // Effects section — fullscreen effects applied to all shaders
@@ -478,6 +949,16 @@ impl WallrusWindow {
         ripple_freq_hint_row.set_selectable(false);
         ripple_freq_hint_row.set_visible(false); // hidden unless "Ripple"
 
+        // --- Kaleidoscope folds, mirrored around the center before distortion ---
+        let symmetry_row = adw::SpinRow::with_range(
+            gl_renderer::MIN_SYMMETRY as f64,
+            gl_renderer::MAX_SYMMETRY as f64,
+            1.0,
+        );
+        symmetry_row.set_title("Kaleidoscope");
+        symmetry_row.set_subtitle("Mirrored folds, 1 for none; even counts tile seamlessly");
+        symmetry_row.set_value(gl_renderer::MIN_SYMMETRY as f64);
+
         // --- Noise slider ---
         let noise_scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, -1.0, 1.0, 0.01);
         noise_scale.set_value(0.0);
@@ -525,6 +1006,16 @@ impl WallrusWindow {
         dither_row.add_suffix(&dither_switch);
         dither_row.set_activatable_widget(Some(&dither_switch));
 
+        // --- Blend color space dropdown ---
+        // sRGB by default so existing palettes render as they always have
+        let blend_space_row = adw::ComboRow::builder()
+            .title("Blend Space")
+            .subtitle("Linear light evens out gradients; OkLab keeps midtones vivid")
+            .model(&gtk4::StringList::new(
+                &ColorSpace::ALL.map(|space| space.label()),
+            ))
+            .build();
+
         let distortion_group = adw::PreferencesGroup::new();
         distortion_group.set_title("Distortion");
         distortion_group.add(&distort_row);
@@ -532,12 +1023,14 @@ impl WallrusWindow {
         distortion_group.add(&distort_strength_hint_row);
         distortion_group.add(&ripple_freq_row);
         distortion_group.add(&ripple_freq_hint_row);
+        distortion_group.add(&symmetry_row);
 
         let effects_group = adw::PreferencesGroup::new();
         effects_group.set_title("Effects");
         effects_group.add(&noise_row);
         effects_group.add(&noise_hint_row);
         effects_group.add(&dither_row);
+        effects_group.add(&blend_space_row);
 
         // =====================================================================
         // Lighting section
@@ -641,6 +1134,44 @@ impl WallrusWindow {
         lighting_group.add(&bevel_width_hint_row);
         lighting_group.add(&light_angle_row);
 
+        // =====================================================================
+        // Icon contrast section
+        // =====================================================================
+
+        // --- Shade type dropdown: darkening over the finished render ---
+        let shade_list = gtk4::StringList::new(&["None", "Vignette", "Corner"]);
+        let shade_row = adw::ComboRow::new();
+        shade_row.set_title("Shade");
+        shade_row.set_subtitle("Darken areas so desktop icons stay legible");
+        shade_row.set_model(Some(&shade_list));
+        shade_row.set_selected(0);
+
+        // --- Shade strength slider ---
+        let shade_strength_scale =
+            gtk4::Scale::with_range(gtk4::Orientation::Horizontal, 0.0, 1.0, 0.01);
+        shade_strength_scale.set_value(0.4);
+        shade_strength_scale.set_hexpand(true);
+        shade_strength_scale.set_draw_value(true);
+        shade_strength_scale.set_value_pos(gtk4::PositionType::Right);
+
+        let shade_strength_row = adw::ActionRow::builder().title("Strength").build();
+        shade_strength_row.add_suffix(&shade_strength_scale);
+        shade_strength_row.set_visible(false); // hidden when "None"
+
+        // --- Shade position: the vignette's bright center, or the darkened corner ---
+        let shade_position_list = gtk4::StringList::new(&SHADE_POSITIONS.map(|(label, _)| label));
+        let shade_position_row = adw::ComboRow::new();
+        shade_position_row.set_title("Position");
+        shade_position_row.set_model(Some(&shade_position_list));
+        shade_position_row.set_selected(0);
+        shade_position_row.set_visible(false); // hidden when "None"
+
+        let shade_group = adw::PreferencesGroup::new();
+        shade_group.set_title("Icon Contrast");
+        shade_group.add(&shade_row);
+        shade_group.add(&shade_strength_row);
+        shade_group.add(&shade_position_row);
+
         // =====================================================================
         // Export section
         // =====================================================================
@@ -672,16 +1203,124 @@ impl WallrusWindow {
             "1080p (1920x1080)",
             "1440p (2560x1440)",
             "4K (3840x2160)",
+            "Ultrawide (3440x1440)",
+            "Custom",
         ]);
         let resolution_row = adw::ComboRow::new();
         resolution_row.set_title("Resolution");
         resolution_row.set_model(Some(&resolution_list));
         resolution_row.set_selected(0); // Default to Display
 
-        let export_button = gtk4::Button::with_label("Export");
+        // Custom size rows, only shown when "Custom" is selected.
+        // Exports render offscreen, so any size works regardless of the window.
+        let custom_range = (
+            export::MIN_CUSTOM_DIMENSION as f64,
+            export::MAX_CUSTOM_DIMENSION as f64,
+        );
+        let custom_width_row = adw::SpinRow::with_range(custom_range.0, custom_range.1, 1.0);
+        custom_width_row.set_title("Width");
+        custom_width_row.set_value(display_dims.0 as f64);
+        custom_width_row.set_visible(false);
+        let custom_height_row = adw::SpinRow::with_range(custom_range.0, custom_range.1, 1.0);
+        custom_height_row.set_title("Height");
+        custom_height_row.set_value(display_dims.1 as f64);
+        custom_height_row.set_visible(false);
+        {
+            let custom_width_row = custom_width_row.clone();
+            let custom_height_row = custom_height_row.clone();
+            resolution_row.connect_selected_notify(move |row| {
+                let custom = row.selected() == ExportResolution::CUSTOM_INDEX;
+                custom_width_row.set_visible(custom);
+                custom_height_row.set_visible(custom);
+            });
+        }
+        let selected_resolution = {
+            let resolution_row = resolution_row.clone();
+            let custom_width_row = custom_width_row.clone();
+            let custom_height_row = custom_height_row.clone();
+            move || {
+                ExportResolution::from_index(
+                    resolution_row.selected(),
+                    display_dims,
+                    (
+                        custom_width_row.value() as u32,
+                        custom_height_row.value() as u32,
+                    ),
+                )
+            }
+        };
+
+        let export_menu = gio::Menu::new();
+        export_menu.append(Some("Copy Image"), Some("win.copy-image"));
+        export_menu.append(Some("Export Video…"), Some("win.export-video"));
+        export_menu.append(Some("Export for All Screens…"), Some("win.export-sizes"));
+        export_menu.append(Some("Export Recipe…"), Some("win.export-recipe"));
+        let export_button = adw::SplitButton::new();
+        export_button.set_label("Export");
+        export_button.set_menu_model(Some(&export_menu));
         export_button.set_tooltip_text(Some("Export image (Ctrl+E)"));
 
-        let set_wallpaper_button = gtk4::Button::with_label("Set as Wallpaper");
+        let wallpaper_menu = gio::Menu::new();
+        wallpaper_menu.append(Some("Render Final Quality…"), Some("win.render-final"));
+        wallpaper_menu.append(
+            Some("Generate Light + Dark Pair"),
+            Some("win.set-wallpaper-dual"),
+        );
+        // One entry per monitor; GNOME gets a spanned image with a separate
+        // palette per monitor, swww one image per output
+        // (see win.set-monitor-wallpaper)
+        let monitor_menu = gio::Menu::new();
+        populate_monitor_menu(&monitor_menu);
+        if let Some(display) = gdk::Display::default() {
+            let monitor_menu = monitor_menu.clone();
+            display
+                .monitors()
+                .connect_items_changed(move |_, _, _, _| populate_monitor_menu(&monitor_menu));
+        }
+        wallpaper_menu.append_submenu(Some("Set for Monitor"), &monitor_menu);
+        let fit_menu = gio::Menu::new();
+        for mode in wallpaper::WallpaperMode::ALL {
+            fit_menu.append(
+                Some(mode.label()),
+                Some(&format!("win.wallpaper-mode::{}", mode.name())),
+            );
+        }
+        wallpaper_menu.append_submenu(Some("Fit"), &fit_menu);
+        let target_menu = gio::Menu::new();
+        for target in wallpaper::WallpaperTarget::ALL {
+            target_menu.append(
+                Some(target.label()),
+                Some(&format!("win.wallpaper-target::{}", target.name())),
+            );
+        }
+        wallpaper_menu.append_submenu(Some("Set On"), &target_menu);
+        let backend_menu = gio::Menu::new();
+        backend_menu.append(
+            Some(&format!(
+                "Automatic ({})",
+                wallpaper::detect_backend().label()
+            )),
+            Some("win.wallpaper-backend::auto"),
+        );
+        for backend in wallpaper::WallpaperBackend::ALL {
+            backend_menu.append(
+                Some(backend.label()),
+                Some(&format!("win.wallpaper-backend::{}", backend.name())),
+            );
+        }
+        wallpaper_menu.append_submenu(Some("Set With"), &backend_menu);
+        wallpaper_menu.append(
+            Some("Restore Previous Wallpaper"),
+            Some("win.restore-wallpaper"),
+        );
+        wallpaper_menu.append(Some("Wallpaper History…"), Some("win.wallpaper-history"));
+        let wallpaper_mode = Rc::new(Cell::new(saved.wallpaper_mode));
+        let wallpaper_target = Rc::new(Cell::new(saved.wallpaper_target));
+        let wallpaper_backend = Rc::new(Cell::new(saved.wallpaper_backend));
+        let gpu_preference = Rc::new(Cell::new(saved.gpu_preference));
+        let set_wallpaper_button = adw::SplitButton::new();
+        set_wallpaper_button.set_label("Set as Wallpaper");
+        set_wallpaper_button.set_menu_model(Some(&wallpaper_menu));
         set_wallpaper_button.add_css_class("suggested-action");
         set_wallpaper_button.set_tooltip_text(Some("Set as desktop wallpaper (Ctrl+Shift+W)"));
 
@@ -692,9 +1331,221 @@ impl WallrusWindow {
         button_box.append(&export_button);
         button_box.append(&set_wallpaper_button);
 
+        let backend_label = gtk4::Label::new(Some(&backend_label_text()));
+        backend_label.add_css_class("dim-label");
+        backend_label.add_css_class("caption");
+
+        // Quality only applies to JPEG; PNG and WebP are exported losslessly
+        let quality_row = adw::SpinRow::with_range(0.0, 100.0, 1.0);
+        quality_row.set_title("Quality");
+        quality_row.set_subtitle("JPEG only; PNG and WebP are lossless");
+        quality_row.set_value(export::DEFAULT_EXPORT_QUALITY as f64);
+
+        // What JPEG exports show behind transparency; only shown for shaders
+        // that can output alpha (see update_video_export)
+        let [r, g, b] = export::DEFAULT_JPEG_BACKGROUND;
+        let jpeg_background_button = gtk4::ColorDialogButton::new(Some(gtk4::ColorDialog::new()));
+        jpeg_background_button.set_rgba(&gdk::RGBA::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            1.0,
+        ));
+        jpeg_background_button.set_valign(gtk4::Align::Center);
+        let jpeg_background_row = adw::ActionRow::builder()
+            .title("JPEG Background")
+            .subtitle("Fills transparent areas; PNG and WebP keep them")
+            .build();
+        jpeg_background_row.add_suffix(&jpeg_background_button);
+        jpeg_background_row.set_visible(false);
+
+        // Video rows, only shown for animated presets (see update_video_export)
+        let video_length_row = adw::SpinRow::with_range(1.0, 60.0, 1.0);
+        video_length_row.set_title("Video Length");
+        video_length_row.set_subtitle("Seconds");
+        video_length_row.set_value(export::DEFAULT_VIDEO_SECONDS as f64);
+        video_length_row.set_visible(false);
+        let video_fps_row = adw::SpinRow::with_range(10.0, 60.0, 1.0);
+        video_fps_row.set_title("Frame Rate");
+        video_fps_row.set_value(export::DEFAULT_VIDEO_FPS as f64);
+        video_fps_row.set_visible(false);
+
+        // Supersampling for image export; 1x keeps export as fast as before
+        let sampling_list = gtk4::StringList::new(&["1x", "2x", "4x"]);
+        let sampling_row = adw::ComboRow::new();
+        sampling_row.set_title("Supersampling");
+        sampling_row.set_subtitle("Smoother edges and gradients, slower export");
+        sampling_row.set_model(Some(&sampling_list));
+        sampling_row.set_selected(0);
+
+        let dither_row = adw::SwitchRow::new();
+        dither_row.set_title("Dithering");
+        dither_row.set_subtitle("Reduce color banding in smooth gradients");
+
+        // Seamless tiles for repeating wallpapers; the subtitle follows the preset
+        let tile_row = adw::SwitchRow::new();
+        tile_row.set_title("Seamless Tile");
+
+        // Output filename for exports and wallpaper packs, without extension
+        let filename_row = adw::EntryRow::new();
+        filename_row.set_title("File Name");
+        filename_row.set_text(&saved.filename_template);
+        filename_row.set_tooltip_text(Some(
+            "Tokens: {palette} {preset} {width} {height} {date} {time} {timestamp} {index}",
+        ));
+
         let export_group = adw::PreferencesGroup::new();
         export_group.set_title("Export");
         export_group.add(&resolution_row);
+        export_group.add(&custom_width_row);
+        export_group.add(&custom_height_row);
+        export_group.add(&quality_row);
+        export_group.add(&jpeg_background_row);
+        export_group.add(&sampling_row);
+        export_group.add(&dither_row);
+        export_group.add(&tile_row);
+        export_group.add(&filename_row);
+        export_group.add(&video_length_row);
+        export_group.add(&video_fps_row);
+
+        // --- Wallpaper rotation (see rotation.rs) ---
+        let rotation_next_button = gtk4::Button::from_icon_name("media-skip-forward-symbolic");
+        rotation_next_button.set_tooltip_text(Some("Set the next wallpaper now"));
+        rotation_next_button.set_valign(gtk4::Align::Center);
+        rotation_next_button.add_css_class("flat");
+
+        let rotation_category_list = gtk4::StringList::new(
+            &category_names
+                .borrow()
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>(),
+        );
+        let rotation_category_row = adw::ComboRow::new();
+        rotation_category_row.set_title("Category");
+        rotation_category_row.set_model(Some(&rotation_category_list));
+        if let Some(i) = category_names
+            .borrow()
+            .iter()
+            .position(|name| *name == saved.rotation.category)
+        {
+            rotation_category_row.set_selected(i as u32);
+        }
+
+        let rotation_preset_row = adw::ComboRow::new();
+        rotation_preset_row.set_title("Preset");
+        rotation_preset_row.set_model(Some(&preset_list));
+        if let Some(i) = preset_entries
+            .borrow()
+            .iter()
+            .position(|e| e.name() == saved.rotation.preset)
+        {
+            rotation_preset_row.set_selected(i as u32);
+        }
+
+        let rotation_interval_row = adw::SpinRow::with_range(
+            rotation::MIN_INTERVAL_MINUTES as f64,
+            rotation::MAX_INTERVAL_MINUTES as f64,
+            5.0,
+        );
+        rotation_interval_row.set_title("Interval");
+        rotation_interval_row.set_subtitle("Minutes");
+        rotation_interval_row.set_value(saved.rotation.interval_minutes as f64);
+
+        let rotation_order_row = adw::ComboRow::new();
+        rotation_order_row.set_title("Order");
+        rotation_order_row.set_model(Some(&gtk4::StringList::new(
+            &rotation::RotationOrder::ALL.map(|o| o.label()),
+        )));
+        rotation_order_row.set_selected(
+            rotation::RotationOrder::ALL
+                .iter()
+                .position(|o| *o == saved.rotation.order)
+                .unwrap_or(0) as u32,
+        );
+
+        let rotation_row = adw::ExpanderRow::builder()
+            .title("Rotate Wallpaper")
+            .subtitle("Set the next palette from a category on a timer while Wallrus is open")
+            .show_enable_switch(true)
+            .enable_expansion(saved.rotation.enabled)
+            .build();
+        rotation_row.add_suffix(&rotation_next_button);
+        rotation_row.add_row(&rotation_category_row);
+        rotation_row.add_row(&rotation_preset_row);
+        rotation_row.add_row(&rotation_interval_row);
+        rotation_row.add_row(&rotation_order_row);
+
+        let rotation_group = adw::PreferencesGroup::new();
+        rotation_group.set_title("Rotation");
+        rotation_group.add(&rotation_row);
+
+        // Current rotation settings as shown in the rows
+        let rotation_settings = {
+            let rotation_row = rotation_row.clone();
+            let category_row = rotation_category_row.clone();
+            let preset_row = rotation_preset_row.clone();
+            let interval_row = rotation_interval_row.clone();
+            let order_row = rotation_order_row.clone();
+            move || {
+                let selected_string = |row: &adw::ComboRow| {
+                    row.selected_item()
+                        .and_downcast::<gtk4::StringObject>()
+                        .map(|s| s.string().to_string())
+                        .unwrap_or_default()
+                };
+                rotation::RotationSettings {
+                    enabled: rotation_row.enables_expansion(),
+                    category: selected_string(&category_row),
+                    preset: selected_string(&preset_row),
+                    interval_minutes: interval_row.value() as u32,
+                    order: rotation::RotationOrder::ALL
+                        .get(order_row.selected() as usize)
+                        .copied()
+                        .unwrap_or_default(),
+                }
+            }
+        };
+
+        // Video export is only offered when the loaded preset animates, the
+        // seed only when it has randomness, the tile subtitle says how the
+        // loaded preset tiles, and its compile error (if any) is shown
+        let action_export_video = gio::SimpleAction::new("export-video", None);
+        action_export_video.set_enabled(false);
+        let update_video_export = {
+            let state = state.clone();
+            let action = action_export_video.clone();
+            let video_length_row = video_length_row.clone();
+            let video_fps_row = video_fps_row.clone();
+            let tile_row = tile_row.clone();
+            let seed_row = seed_row.clone();
+            let frame_limiter = frame_limiter.clone();
+            let shader_error_label = shader_error_label.clone();
+            let shader_error_revealer = shader_error_revealer.clone();
+            let update_preview_backdrop = update_preview_backdrop.clone();
+            let jpeg_background_row = jpeg_background_row.clone();
+            move || {
+                // Static presets draw once here; animated ones restart the timer
+                frame_limiter.update();
+                update_preview_backdrop();
+                let uses_alpha = state.borrow().as_ref().is_some_and(|r| r.uses_alpha());
+                jpeg_background_row.set_visible(uses_alpha);
+                let compile_error = state
+                    .borrow()
+                    .as_ref()
+                    .and_then(|r| r.compile_error.clone());
+                shader_error_label.set_text(compile_error.as_deref().unwrap_or_default());
+                shader_error_revealer.set_reveal_child(compile_error.is_some());
+                let animated = state.borrow().as_ref().is_some_and(|r| r.uses_time());
+                action.set_enabled(animated);
+                video_length_row.set_visible(animated);
+                video_fps_row.set_visible(animated);
+
+                seed_row.set_sensitive(state.borrow().as_ref().is_some_and(|r| r.uses_seed()));
+
+                tile_row.set_subtitle(tile_subtitle(state.borrow().as_ref()));
+            }
+        };
 
         // =====================================================================
         // Layout — two columns: controls (left), preview + export (right)
@@ -727,8 +1578,11 @@ impl WallrusWindow {
         right_box.append(&preview_group);
         right_box.append(&effects_group);
         right_box.append(&lighting_group);
+        right_box.append(&shade_group);
         right_box.append(&export_group);
         right_box.append(&button_box);
+        right_box.append(&rotation_group);
+        right_box.append(&backend_label);
 
         // Two-column horizontal layout
         let columns_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
@@ -738,6 +1592,9 @@ impl WallrusWindow {
 
         let toolbar_view = adw::ToolbarView::new();
         toolbar_view.add_top_bar(&header);
+        // Revealed once the GL area realizes on a software rasterizer
+        let software_banner = adw::Banner::new("Rendering in software mode; previews may be slow");
+        toolbar_view.add_top_bar(&software_banner);
         toolbar_view.set_content(Some(&columns_box));
 
         let toast_overlay = adw::ToastOverlay::new();
@@ -746,17 +1603,170 @@ impl WallrusWindow {
         let window = adw::ApplicationWindow::builder()
             .application(app)
             .title("Wallrus")
-            .default_width(1300)
-            .default_height(900)
+            .default_width(saved.window_size.0)
+            .default_height(saved.window_size.1)
             .content(&toast_overlay)
             .build();
 
-        // --- Tick callback for continuous rendering ---
-        let gl_area_tick = gl_area.clone();
-        gl_area.add_tick_callback(move |_widget, _clock| {
-            gl_area_tick.queue_render();
-            glib::ControlFlow::Continue
-        });
+        // --- Wallpaper rotation: runs until the window closes ---
+        let rotation = {
+            let window_weak = window.downgrade();
+            let on_set = {
+                let window_weak = window_weak.clone();
+                move |path: &Path| {
+                    if let Some(window) = window_weak.upgrade() {
+                        notify(
+                            &window,
+                            WALLPAPER_NOTIFICATION,
+                            "Wallpaper rotated",
+                            &path.display().to_string(),
+                        );
+                    }
+                }
+            };
+            rotation::Rotation::new(
+                rotation_settings(),
+                wallpaper_mode.clone(),
+                on_set,
+                // A toast while the window can be seen, otherwise a notification
+                move |e| {
+                    if let Some(window) = window_weak.upgrade() {
+                        if is_window_hidden(&window) {
+                            notify(&window, WALLPAPER_NOTIFICATION, "Wallpaper rotation", e);
+                        } else {
+                            show_toast(&window, e);
+                        }
+                    }
+                },
+            )
+        };
+        {
+            let rotation = rotation.clone();
+            let rotation_settings = rotation_settings.clone();
+            let on_changed = Rc::new(move || rotation.set_settings(rotation_settings()));
+            {
+                let on_changed = on_changed.clone();
+                rotation_row.connect_enable_expansion_notify(move |_| on_changed());
+            }
+            {
+                let on_changed = on_changed.clone();
+                rotation_category_row.connect_selected_notify(move |_| on_changed());
+            }
+            {
+                let on_changed = on_changed.clone();
+                rotation_preset_row.connect_selected_notify(move |_| on_changed());
+            }
+            {
+                let on_changed = on_changed.clone();
+                rotation_interval_row.connect_value_notify(move |_| on_changed());
+            }
+            rotation_order_row.connect_selected_notify(move |_| on_changed());
+        }
+        {
+            let rotation = rotation.clone();
+            rotation_next_button.connect_clicked(move |_| rotation.advance());
+        }
+        // Categories can be added or renamed meanwhile; refresh on expanding
+        {
+            let list = rotation_category_list.clone();
+            let category_row = rotation_category_row.clone();
+            rotation_row.connect_expanded_notify(move |row| {
+                if !row.is_expanded() {
+                    return;
+                }
+                let names = palette::category_names(&palette::list_palette_categories());
+                let current: Vec<String> = (0..list.n_items())
+                    .filter_map(|i| list.string(i).map(|s| s.to_string()))
+                    .collect();
+                if names == current {
+                    return;
+                }
+                let selected = current.get(category_row.selected() as usize).cloned();
+                let refs: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+                list.splice(0, list.n_items(), &refs);
+                let index = selected
+                    .and_then(|name| names.iter().position(|n| *n == name))
+                    .unwrap_or(0);
+                category_row.set_selected(index as u32);
+            });
+        }
+
+        // --- Slow down or pause animation when the window isn't in front ---
+        {
+            let limiter = frame_limiter.clone();
+            window.connect_is_active_notify(move |window| {
+                limiter.set_focused(window.is_active());
+            });
+        }
+        {
+            let limiter = frame_limiter.clone();
+            window.connect_realize(move |window| {
+                let Some(toplevel) = window.surface().and_downcast::<gdk::Toplevel>() else {
+                    return;
+                };
+                let limiter = limiter.clone();
+                toplevel.connect_state_notify(move |toplevel| {
+                    limiter.set_visible(!toplevel.state().contains(gdk::ToplevelState::MINIMIZED));
+                });
+            });
+        }
+
+        // --- Pointer tracking for the iMouse uniform ---
+        {
+            let motion = gtk4::EventControllerMotion::new();
+            let state = state.clone();
+            let gl_area_ref = gl_area.clone();
+            motion.connect_motion(move |_, x, y| {
+                let (w, h) = (gl_area_ref.width() as f64, gl_area_ref.height() as f64);
+                if w <= 0.0 || h <= 0.0 {
+                    return;
+                }
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    // GTK's origin is top-left; GL's is bottom-left
+                    renderer.mouse = [(x / w) as f32, (1.0 - y / h) as f32];
+                }
+                gl_area_ref.queue_render();
+            });
+            gl_area.add_controller(motion);
+        }
+
+        // --- Ctrl+C on the focused preview copies the image, arrows step presets ---
+        {
+            // GLArea doesn't take focus by itself; clicking the preview focuses it
+            gl_area.set_focusable(true);
+            let click = gtk4::GestureClick::new();
+            click.connect_pressed(|gesture, _, _, _| {
+                if let Some(widget) = gesture.widget() {
+                    widget.grab_focus();
+                }
+            });
+            gl_area.add_controller(click);
+            let shortcuts = gtk4::ShortcutController::new();
+            shortcuts.add_shortcut(gtk4::Shortcut::new(
+                gtk4::ShortcutTrigger::parse_string("<Control>c"),
+                Some(gtk4::NamedAction::new("win.copy-image")),
+            ));
+            shortcuts.add_shortcut(gtk4::Shortcut::new(
+                gtk4::ShortcutTrigger::parse_string("Left"),
+                Some(gtk4::NamedAction::new("win.previous-preset")),
+            ));
+            shortcuts.add_shortcut(gtk4::Shortcut::new(
+                gtk4::ShortcutTrigger::parse_string("Right"),
+                Some(gtk4::NamedAction::new("win.next-preset")),
+            ));
+            gl_area.add_controller(shortcuts);
+        }
+
+        // --- Delete on the focused palette browser deletes the selected palette ---
+        // Scoped to the browser so Delete still edits text in entries.
+        {
+            let shortcuts = gtk4::ShortcutController::new();
+            shortcuts.add_shortcut(gtk4::Shortcut::new(
+                gtk4::ShortcutTrigger::parse_string("Delete"),
+                Some(gtk4::NamedAction::new("win.delete-palette")),
+            ));
+            palette_flowbox.add_controller(shortcuts);
+        }
 
         // =====================================================================
         // Signal connections
@@ -766,8 +1776,14 @@ impl WallrusWindow {
         // Also used by save/delete to refresh the current view.
 
         // Shared refresh: reloads categories from disk and repopulates the current view.
+        // The scan runs on a worker thread; refreshes requested within
+        // PALETTE_SCAN_SETTLE of each other, or while a scan is running,
+        // coalesce into one, and only the newest scan's result is shown.
         let refresh_current_category: Rc<RefCell<Option<Rc<dyn Fn()>>>> =
             Rc::new(RefCell::new(None));
+        // Run once the next refresh has been shown, e.g. to select a new palette
+        type AfterRefresh = Box<dyn FnOnce()>;
+        let after_refresh: Rc<RefCell<Vec<AfterRefresh>>> = Rc::new(RefCell::new(Vec::new()));
 
         {
             let all_cats = all_categories.clone();
@@ -775,11 +1791,10 @@ impl WallrusWindow {
             let populate = populate_flowbox.clone();
             let category_row_ref = category_row.clone();
             let refresh_ref = refresh_current_category.clone();
+            let after_refresh = after_refresh.clone();
 
-            let do_refresh: Rc<dyn Fn()> = Rc::new(move || {
-                // Reload from disk
-                let new_cats = palette::list_palette_categories();
-                let new_names: Vec<String> = new_cats.keys().cloned().collect();
+            let apply_categories = move |new_cats: palette::PaletteCategories| {
+                let new_names = palette::category_names(&new_cats);
 
                 // Capture current selection BEFORE replacing the model (set_model resets index to 0)
                 let prev_idx = category_row_ref.selected() as usize;
@@ -809,6 +1824,35 @@ impl WallrusWindow {
                     // Set selected after populating to avoid double-trigger
                     category_row_ref.set_selected(new_idx as u32);
                 }
+
+                let pending: Vec<AfterRefresh> = after_refresh.borrow_mut().drain(..).collect();
+                for then in pending {
+                    then();
+                }
+            };
+            let apply_categories = Rc::new(apply_categories);
+
+            let scan_generation = Rc::new(Cell::new(0u64));
+            let scan_timer: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+            let do_refresh: Rc<dyn Fn()> = Rc::new(move || {
+                if let Some(id) = scan_timer.borrow_mut().take() {
+                    id.remove();
+                }
+                let scan_timer_inner = scan_timer.clone();
+                let scan_generation = scan_generation.clone();
+                let apply_categories = apply_categories.clone();
+                let id = glib::timeout_add_local_once(PALETTE_SCAN_SETTLE, move || {
+                    scan_timer_inner.borrow_mut().take();
+                    // A scan started later makes this one's result stale
+                    let generation = scan_generation.get() + 1;
+                    scan_generation.set(generation);
+                    palette::list_palette_categories_async(move |new_cats| {
+                        if scan_generation.get() == generation {
+                            apply_categories(new_cats);
+                        }
+                    });
+                });
+                *scan_timer.borrow_mut() = Some(id);
             });
 
             *refresh_current_category.borrow_mut() = Some(do_refresh.clone());
@@ -836,45 +1880,108 @@ impl WallrusWindow {
             });
         }
 
+        // Refresh, then act on the reloaded categories
+        let refresh_then: Rc<dyn Fn(AfterRefresh)> = {
+            let refresh = refresh_current_category.clone();
+            Rc::new(move |then| {
+                after_refresh.borrow_mut().push(then);
+                if let Some(ref cb) = *refresh.borrow() {
+                    cb();
+                }
+            })
+        };
+
+        // --- Recent palettes: remember selected and wallpapered palettes ---
+        // The Recent list is updated in place; only a newly appearing category
+        // needs the dropdown rebuilt by a rescan.
+        let remember_recent: Rc<dyn Fn(&std::path::Path)> = {
+            let all_cats = all_categories.clone();
+            let refresh = refresh_current_category.clone();
+            Rc::new(move |path| match palette::record_recent(path) {
+                Ok(recent) => {
+                    let added = all_cats
+                        .borrow_mut()
+                        .insert(palette::RECENT_CATEGORY.to_string(), recent)
+                        .is_none();
+                    if added {
+                        if let Some(ref cb) = *refresh.borrow() {
+                            cb();
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Failed to remember recent palette: {}", e),
+            })
+        };
+
+        // --- Palette search: show matches from every category ---
+        // Clearing the search returns to the selected category.
+        {
+            let all_cats = all_categories.clone();
+            let populate = populate_flowbox.clone();
+            let flowbox = palette_flowbox.clone();
+            let category_row_ref = category_row.clone();
+            palette_search.connect_search_changed(move |entry| {
+                let query = entry.text();
+                if query.trim().is_empty() {
+                    category_row_ref.set_sensitive(true);
+                    // Re-emit so the category handler repopulates the view
+                    category_row_ref.notify("selected");
+                    return;
+                }
+                category_row_ref.set_sensitive(false);
+
+                let matches = palette::filter_palettes(&all_cats.borrow(), &query);
+                // Favorites repeat palettes from other categories; show each once
+                let mut seen = std::collections::HashSet::new();
+                let images: Vec<PathBuf> = matches
+                    .into_values()
+                    .flatten()
+                    .filter(|path| seen.insert(path.clone()))
+                    .collect();
+                populate(&images, false, None);
+                if images.is_empty() {
+                    // Replace the per-category placeholder with a search-specific one
+                    while let Some(child) = flowbox.first_child() {
+                        flowbox.remove(&child);
+                    }
+                    let label = gtk4::Label::new(Some("No matching palettes."));
+                    label.add_css_class("dim-label");
+                    label.set_margin_top(12);
+                    label.set_margin_bottom(12);
+                    flowbox.insert(&label, -1);
+                }
+            });
+        }
+
         // --- Save palette button handler ---
         {
             let color_btns = color_buttons.clone();
             let window_ref = window.clone();
-            let refresh = refresh_current_category.clone();
+            let refresh_then = refresh_then.clone();
             let cat_names_ref = category_names.clone();
             let category_row_ref = category_row.clone();
             save_palette_button.connect_clicked(move |_| {
-                let colors: [[f32; 3]; 4] = [
-                    {
-                        let c = color_btns[0].rgba();
-                        [c.red(), c.green(), c.blue()]
-                    },
-                    {
-                        let c = color_btns[1].rgba();
-                        [c.red(), c.green(), c.blue()]
-                    },
-                    {
-                        let c = color_btns[2].rgba();
-                        [c.red(), c.green(), c.blue()]
-                    },
-                    {
-                        let c = color_btns[3].rgba();
-                        [c.red(), c.green(), c.blue()]
-                    },
-                ];
+                let colors = button_colors(&color_btns);
+                let duplicate = palette::find_duplicate_palette(&colors).is_some();
 
                 match palette::save_palette_image(&colors) {
                     Ok(_) => {
                         // Refresh categories and switch to Custom
-                        if let Some(ref cb) = *refresh.borrow() {
-                            cb();
-                        }
-                        // Switch to Custom category
-                        let names = cat_names_ref.borrow();
-                        if let Some(idx) = names.iter().position(|n| palette::is_custom_category(n)) {
-                            category_row_ref.set_selected(idx as u32);
+                        let cat_names_ref = cat_names_ref.clone();
+                        let category_row_ref = category_row_ref.clone();
+                        refresh_then(Box::new(move || {
+                            let names = cat_names_ref.borrow();
+                            if let Some(idx) =
+                                names.iter().position(|n| palette::is_custom_category(n))
+                            {
+                                category_row_ref.set_selected(idx as u32);
+                            }
+                        }));
+                        if duplicate {
+                            show_toast(&window_ref, "Palette already saved in Custom");
+                        } else {
+                            show_toast(&window_ref, "Palette saved");
                         }
-                        show_toast(&window_ref, "Palette saved");
                     }
                     Err(e) => {
                         show_toast(&window_ref, &format!("Failed to save palette: {}", e));
@@ -884,50 +1991,71 @@ impl WallrusWindow {
         }
 
         // --- Palette selection: extract colors from selected palette image ---
+        let selected_palette: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
+        // Whether selecting a palette keeps the preset or goes back to the first
+        let pin_preset = Rc::new(Cell::new(saved.pin_preset));
+        let action_pin_preset =
+            gio::SimpleAction::new_stateful("pin-preset", None, &pin_preset.get().to_variant());
         {
+            let pin_preset = pin_preset.clone();
+            action_pin_preset.connect_activate(move |action, _| {
+                pin_preset.set(!pin_preset.get());
+                action.set_state(&pin_preset.get().to_variant());
+            });
+        }
+        window.add_action(&action_pin_preset);
+        // Colors of the palette as loaded, before any edits (win.reset-palette)
+        let loaded_colors: Rc<Cell<Option<[[f32; 3]; 4]>>> = Rc::new(Cell::new(None));
+        let action_reset_palette = gio::SimpleAction::new("reset-palette", None);
+        action_reset_palette.set_enabled(false);
+        // Show a palette that was just loaded and remember it for win.reset-palette
+        let show_loaded_palette: Rc<dyn Fn([[f32; 3]; 4])> = {
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            let loaded_colors = loaded_colors.clone();
+            let reset_action = action_reset_palette.clone();
+            Rc::new(move |colors| {
+                // The color buttons' notify handlers update the renderer
+                set_button_colors(&color_btns, &lock_btns, &colors);
+                loaded_colors.set(Some(colors));
+                reset_action.set_enabled(true);
+            })
+        };
+        // Checked in the palette menu when the selected palette is starred
+        let action_toggle_favorite =
+            gio::SimpleAction::new_stateful("toggle-favorite", None, &false.to_variant());
+        {
+            let favorite_action = action_toggle_favorite.clone();
             let paths = palette_paths.clone();
             let state = state.clone();
-            let cb0 = color_buttons[0].clone();
-            let cb1 = color_buttons[1].clone();
-            let cb2 = color_buttons[2].clone();
-            let cb3 = color_buttons[3].clone();
+            let gl_area = gl_area.clone();
+            let selected = selected_palette.clone();
+            let remember_recent = remember_recent.clone();
+            let show_loaded_palette = show_loaded_palette.clone();
+            let pin_preset = pin_preset.clone();
+            let preset_row = preset_row.clone();
             palette_flowbox.connect_child_activated(move |_flowbox, child| {
                 let idx = child.index() as usize;
                 let paths_ref = paths.borrow();
                 if let Some(path) = paths_ref.get(idx) {
-                    match palette::extract_colors_from_image(path) {
-                        Ok(colors) => {
+                    *selected.borrow_mut() = Some(path.clone());
+                    favorite_action.set_state(&palette::is_favorite(path).to_variant());
+                    match palette::extract_colors_rgba(path) {
+                        Ok(rgba) => {
+                            show_loaded_palette(rgba.map(|c| [c[0], c[1], c[2]]));
                             if let Some(ref mut renderer) = *state.borrow_mut() {
-                                renderer.color1 = colors[0];
-                                renderer.color2 = colors[1];
-                                renderer.color3 = colors[2];
-                                renderer.color4 = colors[3];
+                                renderer.color_alpha = rgba.map(|c| c[3]);
+                            }
+                            gl_area.queue_render();
+                            remember_recent(path);
+                            if !pin_preset.get() {
+                                // Reselecting the first preset resets its sliders too
+                                if preset_row.selected() == 0 {
+                                    preset_row.notify("selected");
+                                } else {
+                                    preset_row.set_selected(0);
+                                }
                             }
-                            // Update color picker buttons to reflect extracted colors
-                            cb0.set_rgba(&gdk::RGBA::new(
-                                colors[0][0],
-                                colors[0][1],
-                                colors[0][2],
-                                1.0,
-                            ));
-                            cb1.set_rgba(&gdk::RGBA::new(
-                                colors[1][0],
-                                colors[1][1],
-                                colors[1][2],
-                                1.0,
-                            ));
-                            cb2.set_rgba(&gdk::RGBA::new(
-                                colors[2][0],
-                                colors[2][1],
-                                colors[2][2],
-                                1.0,
-                            ));
-                            cb3.set_rgba(&gdk::RGBA::new(
-                                colors[3][0],
-                                colors[3][1],
-                                colors[3][2],
-                                1.0,
-                            ));
                         }
                         Err(e) => {
                             eprintln!("Failed to extract colors from '{}': {}", path.display(), e);
@@ -937,43 +2065,642 @@ impl WallrusWindow {
             });
         }
 
+        // --- Helper: show a palette's category and activate its thumbnail ---
+        // Returns false if the palette isn't in any category.
+        let select_palette: Rc<dyn Fn(&std::path::Path) -> bool> = {
+            let all_cats = all_categories.clone();
+            let cat_names = category_names.clone();
+            let category_row = category_row.clone();
+            let palette_search = palette_search.clone();
+            let flowbox = palette_flowbox.clone();
+            let paths = palette_paths.clone();
+            Rc::new(move |path| {
+                // Recent holds every palette just used; show its own category instead
+                let category = all_cats
+                    .borrow()
+                    .iter()
+                    .filter(|(name, _)| *name != palette::RECENT_CATEGORY)
+                    .find(|(_, images)| images.iter().any(|p| p == path))
+                    .map(|(name, _)| name.clone());
+                let Some(category) = category else {
+                    return false;
+                };
+                let Some(cat_idx) = cat_names.borrow().iter().position(|n| *n == category) else {
+                    return false;
+                };
+
+                // Leave search mode so the flowbox shows the category again
+                if !palette_search.text().is_empty() {
+                    palette_search.set_text("");
+                }
+                if category_row.selected() == cat_idx as u32 {
+                    category_row.notify("selected");
+                } else {
+                    category_row.set_selected(cat_idx as u32);
+                }
+
+                let idx = paths.borrow().iter().position(|p| p == path);
+                match idx.and_then(|i| flowbox.child_at_index(i as i32)) {
+                    Some(child) => {
+                        flowbox.unselect_all();
+                        flowbox.select_child(&child);
+                        child.emit_activate();
+                        true
+                    }
+                    None => false,
+                }
+            })
+        };
+
+        // --- Random palette: a new seed from the button, or the typed seed ---
+        {
+            let generate: Rc<dyn Fn(u64)> = {
+                let color_btns = color_buttons.clone();
+                let lock_btns = lock_buttons.clone();
+                let theme_row = random_theme_row.clone();
+                let contrast_row = random_contrast_row.clone();
+                let hue_row = random_hue_row.clone();
+                Rc::new(move |seed| {
+                    let lightness = match theme_row.selected() {
+                        1 => Some(palette::ThemeLightness::Light),
+                        2 => Some(palette::ThemeLightness::Dark),
+                        _ => None,
+                    };
+                    let base_hue = hue_row
+                        .is_active()
+                        .then(|| palette::rgb_to_hsl(button_colors(&color_btns)[0])[0]);
+                    let colors = palette::random_palette(palette::PaletteConstraint {
+                        seed,
+                        min_contrast: contrast_row.value() as f32,
+                        lightness,
+                        base_hue,
+                    });
+                    set_button_colors(&color_btns, &lock_btns, &colors);
+                })
+            };
+
+            let seed_row = random_seed_row.clone();
+            let generate_new = generate.clone();
+            random_button.connect_clicked(move |_| {
+                let seed = rand::random::<u32>() as u64;
+                seed_row.set_text(&seed.to_string());
+                generate_new(seed);
+            });
+
+            let window_ref = window.clone();
+            random_seed_row.connect_apply(move |row| match row.text().trim().parse::<u64>() {
+                Ok(seed) => generate(seed),
+                Err(_) => show_toast(&window_ref, "Seed must be a whole number"),
+            });
+        }
+
+        // --- Color vision preview: simulate the swatches and the preview ---
+        {
+            let update_cvd_preview: Rc<dyn Fn()> = {
+                let state = state.clone();
+                let gl_area = gl_area.clone();
+                let cvd_row = cvd_row.clone();
+                let color_btns = color_buttons.clone();
+                Rc::new(move || {
+                    // Index 0 is "Normal"
+                    let kind = (cvd_row.selected() as usize)
+                        .checked_sub(1)
+                        .and_then(|i| palette::CvdType::all().get(i).copied());
+                    if let Some(ref mut renderer) = *state.borrow_mut() {
+                        renderer.cvd_preview = kind;
+                    }
+                    gl_area.queue_render();
+                    cvd_swatch_box.set_visible(kind.is_some());
+                    if let Some(kind) = kind {
+                        let simulated = palette::simulate_cvd(&button_colors(&color_btns), kind);
+                        for (swatch, color) in cvd_swatches.iter().zip(simulated) {
+                            swatch.set_paintable(Some(&swatch_texture(&[color; 4], 24)));
+                        }
+                    }
+                })
+            };
+            let update = update_cvd_preview.clone();
+            cvd_row.connect_selected_notify(move |_| update());
+            for btn in &color_buttons {
+                let update = update_cvd_preview.clone();
+                btn.connect_rgba_notify(move |_| update());
+            }
+        }
+
+        // --- Warn about colors that wash out (only the rendered ones count) ---
+        {
+            let update_palette_warnings = {
+                let color_btns = color_buttons.clone();
+                let color_count_row = color_count_row.clone();
+                Rc::new(move || {
+                    let colors = button_colors(&color_btns);
+                    let count = (color_count_row.value() as usize).clamp(1, colors.len());
+                    let messages: Vec<String> = palette::palette_warnings(&colors[..count])
+                        .into_iter()
+                        .map(|w| w.message())
+                        .collect();
+                    palette_warning_row.set_visible(!messages.is_empty());
+                    palette_warning_label.set_text(&messages.join("\n"));
+                })
+            };
+            update_palette_warnings();
+            let update = update_palette_warnings.clone();
+            color_count_row.connect_value_notify(move |_| update());
+            for btn in &color_buttons {
+                let update = update_palette_warnings.clone();
+                btn.connect_rgba_notify(move |_| update());
+            }
+        }
+
         // --- Color picker manual change handlers ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             color_buttons[0].connect_rgba_notify(move |btn| {
                 let rgba = btn.rgba();
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.color1 = [rgba.red(), rgba.green(), rgba.blue()];
                 }
+                gl_area.queue_render();
             });
         }
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             color_buttons[1].connect_rgba_notify(move |btn| {
                 let rgba = btn.rgba();
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.color2 = [rgba.red(), rgba.green(), rgba.blue()];
                 }
+                gl_area.queue_render();
             });
         }
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             color_buttons[2].connect_rgba_notify(move |btn| {
                 let rgba = btn.rgba();
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.color3 = [rgba.red(), rgba.green(), rgba.blue()];
                 }
+                gl_area.queue_render();
             });
         }
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             color_buttons[3].connect_rgba_notify(move |btn| {
                 let rgba = btn.rgba();
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.color4 = [rgba.red(), rgba.green(), rgba.blue()];
                 }
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Hex entries ---
+        // A typed color applies on Enter or when the entry loses focus.
+        // Invalid text is flagged and kept so it can be corrected.
+        for (btn, entry) in color_buttons.iter().zip(&hex_entries) {
+            {
+                let entry = entry.clone();
+                btn.connect_rgba_notify(move |btn| {
+                    entry.set_text(&palette::rgb_to_hex(rgba_color(&btn.rgba())));
+                    entry.remove_css_class("error");
+                    entry.set_tooltip_text(Some(HEX_ENTRY_TOOLTIP));
+                });
+            }
+            let apply: Rc<dyn Fn(&gtk4::Entry)> = {
+                let btn = btn.clone();
+                Rc::new(move |entry| match palette::hex_to_rgb(&entry.text()) {
+                    Ok(c) => {
+                        entry.remove_css_class("error");
+                        entry.set_tooltip_text(Some(HEX_ENTRY_TOOLTIP));
+                        // Normalize the text even when the color is unchanged
+                        entry.set_text(&palette::rgb_to_hex(c));
+                        btn.set_rgba(&gdk::RGBA::new(c[0], c[1], c[2], 1.0));
+                    }
+                    Err(e) => {
+                        entry.add_css_class("error");
+                        entry.set_tooltip_text(Some(&e));
+                    }
+                })
+            };
+            {
+                let apply = apply.clone();
+                let window = window.clone();
+                entry.connect_activate(move |entry| {
+                    apply(entry);
+                    if entry.has_css_class("error") {
+                        if let Some(hint) = entry.tooltip_text() {
+                            show_toast(&window, &hint);
+                        }
+                    }
+                });
+            }
+            let focus = gtk4::EventControllerFocus::new();
+            {
+                let entry = entry.clone();
+                let btn = btn.clone();
+                focus.connect_leave(move |_| {
+                    // Only edits are applied: tabbing through an untouched
+                    // field leaves the color and the undo history alone
+                    if entry.text() != palette::rgb_to_hex(rgba_color(&btn.rgba())) {
+                        apply(&entry);
+                    }
+                });
+            }
+            entry.add_controller(focus);
+        }
+
+        // --- Whole-palette blend, HSL, temperature, brightness and contrast ---
+        // Slider values are offsets from the palette as it was when adjusting
+        // started; any other color change makes that the new starting point
+        // and resets the sliders.
+        {
+            let hsl_base: Rc<RefCell<Option<[[f32; 3]; 4]>>> = Rc::new(RefCell::new(None));
+            // The palette to blend toward, in the blend space it was loaded in
+            let blend_target: Rc<RefCell<(ColorSpace, Vec<[f32; 3]>)>> =
+                Rc::new(RefCell::new((ColorSpace::default(), Vec::new())));
+            let selected_space = {
+                let blend_space_row = blend_space_row.clone();
+                move || {
+                    ColorSpace::ALL
+                        .get(blend_space_row.selected() as usize)
+                        .copied()
+                        .unwrap_or_default()
+                }
+            };
+            let syncing = Rc::new(Cell::new(false));
+            let scales = [
+                hue_shift_scale.clone(),
+                sat_shift_scale.clone(),
+                light_shift_scale.clone(),
+                temperature_scale.clone(),
+                brightness_scale.clone(),
+                contrast_scale.clone(),
+                blend_amount_scale.clone(),
+            ];
+
+            let apply: Rc<dyn Fn()> = {
+                let hsl_base = hsl_base.clone();
+                let blend_target = blend_target.clone();
+                let selected_space = selected_space.clone();
+                let syncing = syncing.clone();
+                let scales = scales.clone();
+                let color_btns = color_buttons.clone();
+                let lock_btns = lock_buttons.clone();
+                Rc::new(move || {
+                    if syncing.get() {
+                        return;
+                    }
+                    let base = *hsl_base
+                        .borrow_mut()
+                        .get_or_insert_with(|| button_colors(&color_btns));
+                    // Slots past the end of a shorter palette aren't blended
+                    let mut blended = base;
+                    let space = selected_space();
+                    let mut target = blend_target.borrow_mut();
+                    if target.0 != space {
+                        let (loaded_in, ref mut colors) = *target;
+                        for c in colors.iter_mut() {
+                            *c = space.decode(loaded_in.encode(*c));
+                        }
+                        target.0 = space;
+                    }
+                    let mixed = palette::blend_palettes(
+                        &base,
+                        &target.1,
+                        scales[6].value() as f32 / 100.0,
+                        space,
+                    );
+                    drop(target);
+                    blended[..mixed.len()].copy_from_slice(&mixed);
+                    let adjusted = palette::adjust_palette_hsl(
+                        &blended,
+                        scales[0].value() as f32,
+                        scales[1].value() as f32 / 100.0,
+                        scales[2].value() as f32 / 100.0,
+                    );
+                    let adjusted = palette::adjust_temperature(&adjusted, scales[3].value() as f32);
+                    let adjusted = palette::apply_brightness_contrast(
+                        &adjusted,
+                        scales[4].value() as f32 / 100.0,
+                        1.0 + scales[5].value() as f32 / 100.0,
+                    );
+                    syncing.set(true);
+                    set_button_colors(&color_btns, &lock_btns, &palette::to_four_colors(&adjusted));
+                    syncing.set(false);
+                })
+            };
+            for scale in &scales {
+                let apply = apply.clone();
+                scale.connect_value_changed(move |_| apply());
+            }
+
+            // A new blend space mixes the loaded palette in that space
+            {
+                let blend_target = blend_target.clone();
+                let apply = apply.clone();
+                blend_space_row.connect_selected_notify(move |_| {
+                    if !blend_target.borrow().1.is_empty() {
+                        apply();
+                    }
+                });
+            }
+
+            let window_ref = window.clone();
+            blend_palette_button.connect_clicked(move |_| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Blend With Palette");
+                let image_filter = gtk4::FileFilter::new();
+                image_filter.set_name(Some("Images"));
+                image_filter.add_pixbuf_formats();
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&image_filter);
+                dialog.set_filters(Some(&filters));
+                if let Some(dir) = palette::bundled_palettes_dir() {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(dir)));
+                }
+
+                let window_clone = window_ref.clone();
+                let blend_target = blend_target.clone();
+                let selected_space = selected_space.clone();
+                let blend_palette_row = blend_palette_row.clone();
+                let blend_amount_scale = blend_amount_scale.clone();
+                let apply = apply.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let space = selected_space();
+                            match palette::extract_colors_in(
+                                &path,
+                                palette::DEFAULT_COLOR_COUNT,
+                                space,
+                            ) {
+                                Ok(colors) => {
+                                    *blend_target.borrow_mut() = (space, colors);
+                                    let name = path.file_name().unwrap_or_default();
+                                    blend_palette_row.set_subtitle(&name.to_string_lossy());
+                                    blend_amount_scale.set_sensitive(true);
+                                    apply();
+                                }
+                                Err(e) => show_toast(
+                                    &window_clone,
+                                    &format!("Failed to load palette: {}", e),
+                                ),
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Failed to open: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+
+            for btn in &color_buttons {
+                let hsl_base = hsl_base.clone();
+                let syncing = syncing.clone();
+                let scales = scales.clone();
+                btn.connect_rgba_notify(move |_| {
+                    if syncing.get() {
+                        return;
+                    }
+                    hsl_base.borrow_mut().take();
+                    syncing.set(true);
+                    for scale in &scales {
+                        scale.set_value(0.0);
+                    }
+                    syncing.set(false);
+                });
+            }
+        }
+
+        // --- Undo/redo of palette edits ---
+        // Snapshots of the swatches and locks are recorded once edits settle,
+        // so a slider drag or a new palette (one notify per swatch) is a
+        // single step. Snapshots being restored aren't recorded again.
+        let palette_history = Rc::new(RefCell::new(PaletteHistory::default()));
+        let history_pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+        let history_restoring = Rc::new(Cell::new(false));
+        let record_palette_edit: Rc<dyn Fn()> = {
+            let history = palette_history.clone();
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            Rc::new(move || {
+                history
+                    .borrow_mut()
+                    .record(palette_slots(&color_btns, &lock_btns));
+            })
+        };
+        let schedule_palette_edit: Rc<dyn Fn()> = {
+            let pending = history_pending.clone();
+            let restoring = history_restoring.clone();
+            let record = record_palette_edit.clone();
+            Rc::new(move || {
+                if restoring.get() {
+                    return;
+                }
+                if let Some(id) = pending.borrow_mut().take() {
+                    id.remove();
+                }
+                let pending_ref = pending.clone();
+                let record = record.clone();
+                let id = glib::timeout_add_local_once(PALETTE_HISTORY_SETTLE, move || {
+                    pending_ref.borrow_mut().take();
+                    record();
+                });
+                *pending.borrow_mut() = Some(id);
+            })
+        };
+        for btn in &color_buttons {
+            let schedule = schedule_palette_edit.clone();
+            btn.connect_rgba_notify(move |_| schedule());
+        }
+        for lock in &lock_buttons {
+            let schedule = schedule_palette_edit.clone();
+            lock.connect_toggled(move |_| schedule());
+        }
+        // --- Preset gallery ---
+        // Thumbnails are rebuilt when the palette settles after a change and
+        // each time the gallery opens (cheap when cached), so user shaders
+        // added since show up too.
+        let refresh_gallery: Rc<dyn Fn()> = {
+            let flowbox = gallery_flowbox.clone();
+            let entries = preset_entries.clone();
+            let color_btns = color_buttons.clone();
+            Rc::new(move || {
+                let entries = entries.borrow().clone();
+                let thumbnails = preset_gallery::thumbnails(&entries, &button_colors(&color_btns));
+                while let Some(child) = flowbox.first_child() {
+                    flowbox.remove(&child);
+                }
+                let (width, height) = preset_gallery::THUMBNAIL_SIZE;
+                for (entry, thumbnail) in entries.iter().zip(thumbnails) {
+                    let picture = match thumbnail {
+                        Ok(path) => gtk4::Picture::for_filename(&path),
+                        Err(e) => {
+                            eprintln!("Failed to render thumbnail of {}: {}", entry.name(), e);
+                            gtk4::Picture::new()
+                        }
+                    };
+                    picture.set_size_request(width as i32, height as i32);
+                    picture.set_content_fit(gtk4::ContentFit::Cover);
+                    let label = gtk4::Label::new(Some(entry.name()));
+                    label.add_css_class("caption");
+                    label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+                    label.set_max_width_chars(16);
+                    let column = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+                    column.append(&picture);
+                    column.append(&label);
+                    flowbox.insert(&column, -1);
+                }
+            })
+        };
+        {
+            let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+            let schedule_gallery: Rc<dyn Fn()> = {
+                let refresh = refresh_gallery.clone();
+                Rc::new(move || {
+                    if let Some(id) = pending.borrow_mut().take() {
+                        id.remove();
+                    }
+                    let pending_ref = pending.clone();
+                    let refresh = refresh.clone();
+                    let id = glib::timeout_add_local_once(PALETTE_HISTORY_SETTLE, move || {
+                        pending_ref.borrow_mut().take();
+                        refresh();
+                    });
+                    *pending.borrow_mut() = Some(id);
+                })
+            };
+            for btn in &color_buttons {
+                let schedule = schedule_gallery.clone();
+                btn.connect_rgba_notify(move |_| schedule());
+            }
+            // Initial render at startup; restoring the palette reschedules it
+            schedule_gallery();
+        }
+        {
+            let refresh = refresh_gallery.clone();
+            gallery_popover.connect_show(move |_| refresh());
+        }
+        {
+            let preset_row = preset_row.clone();
+            let popover = gallery_popover.clone();
+            gallery_flowbox.connect_child_activated(move |_, child| {
+                // The preset change handler loads the shader
+                preset_row.set_selected(child.index() as u32);
+                popover.popdown();
+            });
+        }
+
+        for (name, undo) in [("undo-palette", true), ("redo-palette", false)] {
+            let action = gio::SimpleAction::new(name, None);
+            let history = palette_history.clone();
+            let pending = history_pending.clone();
+            let restoring = history_restoring.clone();
+            let record = record_palette_edit.clone();
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action.connect_activate(move |_, _| {
+                // An edit still settling is recorded first, so it can be undone
+                if let Some(id) = pending.borrow_mut().take() {
+                    id.remove();
+                    record();
+                }
+                let slots = if undo {
+                    history.borrow_mut().undo()
+                } else {
+                    history.borrow_mut().redo()
+                };
+                let Some(slots) = slots else { return };
+                restoring.set(true);
+                for ((btn, lock), (c, locked)) in color_btns
+                    .iter()
+                    .zip(&lock_btns)
+                    .zip(slots.colors.iter().zip(slots.locked))
+                {
+                    btn.set_rgba(&gdk::RGBA::new(c[0], c[1], c[2], 1.0));
+                    lock.set_active(locked);
+                }
+                restoring.set(false);
+            });
+            window.add_action(&action);
+        }
+
+        // --- Reset: back to the loaded palette's colors, as one undo step ---
+        {
+            let pending = history_pending.clone();
+            let record = record_palette_edit.clone();
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action_reset_palette.connect_activate(move |_, _| {
+                let Some(colors) = loaded_colors.get() else {
+                    return;
+                };
+                // Record an edit still settling so it isn't merged into the reset
+                if let Some(id) = pending.borrow_mut().take() {
+                    id.remove();
+                    record();
+                }
+                set_button_colors(&color_btns, &lock_btns, &colors);
             });
         }
+        window.add_action(&action_reset_palette);
+
+        // --- Preset parameter sliders, rebuilt for each preset ---
+        // Each slider writes its uniform's value straight into the renderer.
+        // A "Look" dropdown above them recalls named parameter sets.
+        let param_scales: ParamSliders = Rc::new(RefCell::new(Vec::new()));
+        let look_row: Rc<RefCell<Option<adw::ComboRow>>> = Rc::new(RefCell::new(None));
+        let rebuild_param_rows = {
+            let window = window.clone();
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let controls_group = controls_group.clone();
+            let param_scales = param_scales.clone();
+            let look_row = look_row.clone();
+            move |name: &str| {
+                for (_, row, _) in param_scales.borrow_mut().drain(..) {
+                    controls_group.remove(&row);
+                }
+                if let Some(row) = look_row.borrow_mut().take() {
+                    controls_group.remove(&row);
+                }
+                if !shader_presets::params_for(name).is_empty() {
+                    let row = build_look_row(&window, name, &param_scales);
+                    controls_group.add(&row);
+                    *look_row.borrow_mut() = Some(row);
+                }
+                for param in shader_presets::params_for(name) {
+                    let (row, adjustment) = match param.kind {
+                        shader_presets::ParamKind::Bool => param_switch_row(param),
+                        shader_presets::ParamKind::Float => param_slider_row(param, 0.01, 2),
+                        shader_presets::ParamKind::Int => param_slider_row(param, 1.0, 0),
+                    };
+                    controls_group.add(&row);
+
+                    let state = state.clone();
+                    let gl_area = gl_area.clone();
+                    let uniform = param.name;
+                    adjustment.connect_value_changed(move |adjustment| {
+                        if let Some(ref mut renderer) = *state.borrow_mut() {
+                            renderer.params.insert(uniform, adjustment.value() as f32);
+                        }
+                        gl_area.queue_render();
+                    });
+                    param_scales
+                        .borrow_mut()
+                        .push((param.name, row, adjustment));
+                }
+            }
+        };
 
         // --- Update visibility of shader controls based on preset ---
         let update_control_visibility = {
@@ -986,6 +2713,7 @@ impl WallrusWindow {
             let center_hint_row = center_hint_row.clone();
             let center_scale = center_scale.clone();
             move |name: &str| {
+                rebuild_param_rows(name);
                 let controls = shader_presets::controls_for(name);
                 angle_row.set_visible(controls.has_angle);
                 scale_row.set_visible(controls.has_scale);
@@ -1014,69 +2742,338 @@ impl WallrusWindow {
         {
             let state = state.clone();
             let gl_area = gl_area.clone();
+            let entries = preset_entries.clone();
+            let window_ref = window.clone();
+            let update_video_export = update_video_export.clone();
             preset_row.connect_selected_notify(move |combo| {
                 let idx = combo.selected();
-                let names = shader_presets::preset_names();
-                if let Some(name) = names.get(idx as usize) {
-                    update_control_visibility(name);
+                let entries = entries.borrow();
+                if let Some(entry) = entries.get(idx as usize) {
+                    update_control_visibility(entry.name());
                     if let Some(ref mut renderer) = *state.borrow_mut() {
                         gl_area.make_current();
-                        if let Err(e) = renderer.load_preset(name) {
-                            eprintln!("Failed to load preset '{}': {}", name, e);
+                        // The error itself is shown below the preview
+                        if renderer.load_entry(entry).is_err() {
+                            show_toast(
+                                &window_ref,
+                                &format!("Failed to load preset '{}'", entry.name()),
+                            );
+                        }
+                    }
+                    update_video_export();
+                }
+            });
+        }
+
+        // --- Skip user shaders that don't compile, flag software rendering ---
+        // Runs after create_gl_area's realize handler has created the renderer.
+        {
+            let state = state.clone();
+            let entries = preset_entries.clone();
+            let preset_list = preset_list.clone();
+            let window_ref = window.clone();
+            let update_video_export = update_video_export.clone();
+            let software_banner = software_banner.clone();
+            gl_area.connect_realize(move |_| {
+                update_video_export();
+                // Collected first: removing a row can change the selection, and
+                // the preset handler borrows the entries and the renderer
+                let broken: Vec<usize> = {
+                    let state_ref = state.borrow();
+                    let Some(renderer) = state_ref.as_ref() else {
+                        return;
+                    };
+                    software_banner.set_revealed(renderer.is_software());
+
+                    let entries = entries.borrow();
+                    (0..entries.len())
+                        .filter(|&idx| {
+                            let entry = &entries[idx];
+                            if !matches!(entry, shader_presets::PresetEntry::User { .. }) {
+                                return false;
+                            }
+                            let Err(e) = renderer.compile_check(entry) else {
+                                return false;
+                            };
+                            eprintln!("Skipping user shader '{}': {}", entry.name(), e);
+                            show_toast(
+                                &window_ref,
+                                &format!(
+                                    "Skipped broken shader '{}': {}",
+                                    entry.name(),
+                                    shader::first_error_line(&e)
+                                ),
+                            );
+                            true
+                        })
+                        .collect()
+                };
+
+                for idx in broken.into_iter().rev() {
+                    entries.borrow_mut().remove(idx);
+                    preset_list.remove(idx as u32);
+                }
+            });
+        }
+
+        // --- Restore the last session's blend space, preset and palette ---
+        // Runs once, after broken user shaders have been removed above. A
+        // palette that no longer exists falls back to the first bundled one.
+        {
+            let restored = Cell::new(false);
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            let all_cats = all_categories.clone();
+            let select_palette = select_palette.clone();
+            let blend_space_row = blend_space_row.clone();
+            let saved = saved.clone();
+            gl_area.connect_realize(move |_| {
+                if restored.replace(true) {
+                    return;
+                }
+                let space = ColorSpace::ALL.iter().position(|&s| s == saved.color_space);
+                blend_space_row.set_selected(space.unwrap_or(0) as u32);
+                // The palette goes first, since selecting one may reset the preset
+                if let Some(ref path) = saved.palette {
+                    if !select_palette(path) {
+                        let first = all_cats
+                            .borrow()
+                            .values()
+                            .find_map(|images| images.first().cloned());
+                        if let Some(first) = first {
+                            select_palette(&first);
                         }
                     }
                 }
+                if let Some(ref name) = saved.preset {
+                    let idx = entries.borrow().iter().position(|e| e.name() == name);
+                    if let Some(idx) = idx {
+                        preset_row.set_selected(idx as u32);
+                    }
+                }
+            });
+        }
+
+        // --- Save the session on close ---
+        {
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            let selected = selected_palette.clone();
+            let frame_limiter = frame_limiter.clone();
+            let filename_row = filename_row.clone();
+            let wallpaper_mode = wallpaper_mode.clone();
+            let wallpaper_target = wallpaper_target.clone();
+            let wallpaper_backend = wallpaper_backend.clone();
+            let rotation = rotation.clone();
+            let gpu_preference = gpu_preference.clone();
+            let pin_preset = pin_preset.clone();
+            let blend_space_row = blend_space_row.clone();
+            window.connect_close_request(move |window| {
+                let (width, height) = window.default_size();
+                let state = settings::SavedState {
+                    palette: selected.borrow().clone(),
+                    preset: entries
+                        .borrow()
+                        .get(preset_row.selected() as usize)
+                        .map(|e| e.name().to_string()),
+                    window_size: (width, height),
+                    preview_fps: frame_limiter.fps(),
+                    filename_template: filename_row.text().to_string(),
+                    wallpaper_mode: wallpaper_mode.get(),
+                    wallpaper_target: wallpaper_target.get(),
+                    wallpaper_backend: wallpaper_backend.get(),
+                    rotation: rotation.settings(),
+                    gpu_preference: gpu_preference.get(),
+                    pin_preset: pin_preset.get(),
+                    color_space: ColorSpace::ALL
+                        .get(blend_space_row.selected() as usize)
+                        .copied()
+                        .unwrap_or_default(),
+                };
+                if let Err(e) = settings::save(&state) {
+                    eprintln!("{}", e);
+                }
+                glib::Propagation::Proceed
             });
         }
 
+        // --- Hot-reload user shaders ---
+        // Watches the user shaders directory; when a .frag file is written,
+        // it is selected (added to the preset list if new) and recompiled.
+        // Rapid successive change events (one editor save can emit several)
+        // are debounced.
+        {
+            let shaders_dir = gio::File::for_path(shader_presets::user_shaders_dir());
+            match shaders_dir.monitor_directory(
+                gio::FileMonitorFlags::WATCH_MOVES,
+                None::<&gio::Cancellable>,
+            ) {
+                Ok(monitor) => {
+                    let state = state.clone();
+                    let gl_area = gl_area.clone();
+                    let window_ref = window.clone();
+                    let entries = preset_entries.clone();
+                    let preset_list = preset_list.clone();
+                    let preset_row = preset_row.clone();
+                    let update_video_export = update_video_export.clone();
+                    let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+                    monitor.connect_changed(move |_, file, other_file, event| {
+                        let changed = match event {
+                            gio::FileMonitorEvent::Changed
+                            | gio::FileMonitorEvent::Created
+                            | gio::FileMonitorEvent::MovedIn => file.path(),
+                            gio::FileMonitorEvent::Renamed => other_file.and_then(|f| f.path()),
+                            _ => None,
+                        };
+                        let Some(path) = changed else { return };
+                        if path.extension().and_then(|e| e.to_str())
+                            != Some(shader_presets::USER_SHADER_EXTENSION)
+                        {
+                            return;
+                        }
+
+                        if let Some(id) = pending.borrow_mut().take() {
+                            id.remove();
+                        }
+
+                        let state = state.clone();
+                        let gl_area = gl_area.clone();
+                        let window_ref = window_ref.clone();
+                        let entries = entries.clone();
+                        let preset_list = preset_list.clone();
+                        let preset_row = preset_row.clone();
+                        let update_video_export = update_video_export.clone();
+                        let pending_ref = pending.clone();
+                        let id = glib::timeout_add_local_once(SHADER_RELOAD_DEBOUNCE, move || {
+                            pending_ref.borrow_mut().take();
+
+                            let entry = shader_presets::PresetEntry::user(&path);
+                            let existing = entries.borrow().iter().position(|e| *e == entry);
+                            let idx = existing.unwrap_or_else(|| {
+                                preset_list.append(entry.name());
+                                let mut entries = entries.borrow_mut();
+                                entries.push(entry.clone());
+                                entries.len() - 1
+                            });
+
+                            if preset_row.selected() as usize != idx {
+                                // The preset change handler loads the shader
+                                preset_row.set_selected(idx as u32);
+                                return;
+                            }
+
+                            if let Some(ref mut renderer) = *state.borrow_mut() {
+                                gl_area.make_current();
+                                match renderer.load_entry(&entry) {
+                                    Ok(()) => show_toast(
+                                        &window_ref,
+                                        &format!("Reloaded {}", entry.name()),
+                                    ),
+                                    Err(_) => show_toast(
+                                        &window_ref,
+                                        &format!("Failed to compile {}", entry.name()),
+                                    ),
+                                }
+                            }
+                            update_video_export();
+                        });
+                        *pending.borrow_mut() = Some(id);
+                    });
+                    // Keep the monitor alive for the lifetime of the window
+                    window.connect_destroy(move |_| {
+                        monitor.cancel();
+                    });
+                }
+                Err(e) => eprintln!("Failed to watch user shaders directory: {}", e),
+            }
+        }
+
+        // --- Render seed: typed in, or shuffled ---
+        let set_seed: Rc<dyn Fn(gl_renderer::RenderSeed)> = {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let seed_row = seed_row.clone();
+            Rc::new(move |seed| {
+                seed_row.set_text(&seed.to_string());
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.seed = seed;
+                }
+                gl_area.queue_render();
+            })
+        };
+        {
+            let set_seed = set_seed.clone();
+            seed_button.connect_clicked(move |_| set_seed(gl_renderer::RenderSeed::random()));
+        }
+        {
+            let set_seed = set_seed.clone();
+            let window_ref = window.clone();
+            seed_row.connect_apply(
+                move |row| match gl_renderer::RenderSeed::parse(&row.text()) {
+                    Ok(seed) => set_seed(seed),
+                    Err(e) => show_toast(&window_ref, &e),
+                },
+            );
+        }
+
         // --- Angle change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             angle_scale.connect_value_changed(move |scale| {
                 let radians = (scale.value() as f32).to_radians();
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.angle = radians;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Scale change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             scale_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.scale = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Speed change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             speed_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.speed = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Blend change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             blend_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.blend = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Center change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             center_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.center = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
@@ -1091,6 +3088,7 @@ impl WallrusWindow {
         // --- Distortion type change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             let distort_strength_row = distort_strength_row.clone();
             let distort_strength_scale = distort_strength_scale.clone();
             let distort_strength_hint_row = distort_strength_hint_row.clone();
@@ -1105,6 +3103,7 @@ impl WallrusWindow {
                         renderer.distort_strength = 0.0;
                     }
                 }
+                gl_area.queue_render();
                 // Visibility
                 distort_strength_row.set_visible(distort_type != 0);
                 if distort_type == 0 {
@@ -1119,10 +3118,12 @@ impl WallrusWindow {
         // --- Distortion strength change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             distort_strength_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.distort_strength = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
@@ -1137,20 +3138,24 @@ impl WallrusWindow {
         // --- Ripple frequency change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             ripple_freq_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.ripple_freq = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Noise change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             noise_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.noise = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
@@ -1165,16 +3170,60 @@ impl WallrusWindow {
         // --- Dither change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             dither_switch.connect_active_notify(move |switch| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.dither = if switch.is_active() { 1.0 } else { 0.0 };
                 }
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Kaleidoscope change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let tile_row = tile_row.clone();
+            symmetry_row.connect_value_notify(move |row| {
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.symmetry = row.value() as u32;
+                }
+                tile_row.set_subtitle(tile_subtitle(state.borrow().as_ref()));
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Color count change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            color_count_row.connect_value_notify(move |row| {
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.color_count = row.value() as u32;
+                }
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Blend color space change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            blend_space_row.connect_selected_notify(move |row| {
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.color_space = ColorSpace::ALL
+                        .get(row.selected() as usize)
+                        .copied()
+                        .unwrap_or_default();
+                }
+                gl_area.queue_render();
             });
         }
 
         // --- Lighting type change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             let light_strength_row = light_strength_row.clone();
             let light_strength_hint_row = light_strength_hint_row.clone();
             let bevel_width_row = bevel_width_row.clone();
@@ -1185,6 +3234,7 @@ impl WallrusWindow {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.lighting_type = idx;
                 }
+                gl_area.queue_render();
                 // Visibility logic per the specification table
                 let show_strength = idx != 0;
                 light_strength_row.set_visible(show_strength);
@@ -1198,26 +3248,31 @@ impl WallrusWindow {
         // --- Light strength change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             light_strength_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.light_strength = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Bevel width change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             bevel_width_scale.connect_value_changed(move |scale| {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.bevel_width = scale.value() as f32;
                 }
+                gl_area.queue_render();
             });
         }
 
         // --- Light angle change ---
         {
             let state = state.clone();
+            let gl_area = gl_area.clone();
             light_angle_scale.connect_value_changed(move |scale| {
                 // Convert degrees to radians, with offset so 0° = light from top
                 let degrees = scale.value() as f32;
@@ -1225,6 +3280,50 @@ impl WallrusWindow {
                 if let Some(ref mut renderer) = *state.borrow_mut() {
                     renderer.light_angle = radians;
                 }
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Shade type change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let shade_strength_row = shade_strength_row.clone();
+            let shade_position_row = shade_position_row.clone();
+            shade_row.connect_selected_notify(move |combo| {
+                let idx = combo.selected() as i32; // 0=None, 1=Vignette, 2=Corner
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.shade_type = idx;
+                }
+                gl_area.queue_render();
+                shade_strength_row.set_visible(idx != 0);
+                shade_position_row.set_visible(idx != 0);
+            });
+        }
+
+        // --- Shade strength change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            shade_strength_scale.connect_value_changed(move |scale| {
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.shade_strength = scale.value() as f32;
+                }
+                gl_area.queue_render();
+            });
+        }
+
+        // --- Shade position change ---
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            shade_position_row.connect_selected_notify(move |combo| {
+                let idx = combo.selected() as usize;
+                let (_, center) = SHADE_POSITIONS[idx.min(SHADE_POSITIONS.len() - 1)];
+                if let Some(ref mut renderer) = *state.borrow_mut() {
+                    renderer.shade_center = center;
+                }
+                gl_area.queue_render();
             });
         }
 
@@ -1234,56 +3333,72 @@ impl WallrusWindow {
 
         let make_export_handler = {
             let state = state.clone();
-            let resolution_row = resolution_row.clone();
+            let selected_resolution = selected_resolution.clone();
+            let quality_row = quality_row.clone();
+            let jpeg_background_button = jpeg_background_button.clone();
+            let sampling_row = sampling_row.clone();
+            let dither_row = dither_row.clone();
+            let tile_row = tile_row.clone();
+            let filename_row = filename_row.clone();
+            let selected_palette = selected_palette.clone();
             let gl_area = gl_area.clone();
             let window_ref = window.clone();
-            move |_button: &gtk4::Button| {
-                let resolution =
-                    ExportResolution::from_index(resolution_row.selected(), display_dims);
-                let (w, h) = resolution.dimensions();
-
-                gl_area.make_current();
-
-                let pixels = {
-                    let state_ref = state.borrow();
-                    match state_ref.as_ref() {
-                        Some(renderer) => renderer.render_to_pixels(w as i32, h as i32),
-                        None => {
-                            show_toast(&window_ref, "Renderer not initialized");
-                            return;
-                        }
-                    }
-                };
-
-                let preset_name = {
-                    let state_ref = state.borrow();
-                    state_ref
-                        .as_ref()
-                        .map(|r| r.current_preset.clone())
-                        .unwrap_or_else(|| "wallpaper".to_string())
+            move |_button: &adw::SplitButton| {
+                let (w, h) = selected_resolution().dimensions();
+                let sampling = SampleQuality::from_index(sampling_row.selected());
+                let dither = selected_dither(&dither_row);
+                let tile = tile_row.is_active();
+
+                let Some(meta) = state.borrow().as_ref().map(|r| r.wallpaper_meta()) else {
+                    show_toast(&window_ref, "Renderer not initialized");
+                    return;
                 };
 
-                let timestamp = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
                 // Default filename uses JPEG; user can switch filter to PNG in the dialog
+                let fields = filename_fields(&selected_palette.borrow(), &meta.preset, w, h);
                 let filename = format!(
-                    "wallrus_{}_{}.jpg",
-                    preset_name.to_lowercase(),
-                    timestamp,
+                    "{}.jpg",
+                    export::expand_filename_template(&filename_row.text(), &fields)
                 );
 
-                let dialog = gtk4::FileDialog::new();
-                dialog.set_initial_name(Some(&filename));
-
-                // Default to the user's Pictures folder (portal-safe hint)
-                if let Some(pictures_dir) = glib::user_special_dir(glib::UserDirectory::Pictures) {
-                    dialog.set_initial_folder(Some(&gio::File::for_path(pictures_dir)));
+                // Rendered before the file is picked, so a supersampled
+                // render can be cancelled without choosing a file first
+                let result = run_export(
+                    &window_ref,
+                    &state,
+                    &gl_area,
+                    "Rendering Wallpaper",
+                    |renderer, control| {
+                        control.next(export::ExportProgress {
+                            done: 0,
+                            total: 1,
+                            current: std::path::Path::new(&filename),
+                            pass: None,
+                        })?;
+                        render_export_pixels(renderer, w, h, sampling, dither, tile, control)
+                    },
+                );
+                let pixels = match result {
+                    Ok(pixels) => pixels,
+                    Err(e) if e == export::EXPORT_CANCELLED => {
+                        show_toast(&window_ref, &e);
+                        return;
+                    }
+                    Err(e) => {
+                        show_toast(&window_ref, &format!("Render failed: {}", e));
+                        return;
+                    }
+                };
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_initial_name(Some(&filename));
+
+                // Default to the user's Pictures folder (portal-safe hint)
+                if let Some(pictures_dir) = glib::user_special_dir(glib::UserDirectory::Pictures) {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(pictures_dir)));
                 }
 
-                // Offer both PNG and JPEG filters; user picks in the dialog
+                // Offer PNG, JPEG and WebP filters; user picks in the dialog
                 let png_filter = gtk4::FileFilter::new();
                 png_filter.set_name(Some("PNG images"));
                 png_filter.add_mime_type("image/png");
@@ -1295,13 +3410,22 @@ impl WallrusWindow {
                 jpeg_filter.add_suffix("jpg");
                 jpeg_filter.add_suffix("jpeg");
 
+                let webp_filter = gtk4::FileFilter::new();
+                webp_filter.set_name(Some("WebP images (lossless)"));
+                webp_filter.add_mime_type("image/webp");
+                webp_filter.add_suffix("webp");
+
                 let filters = gio::ListStore::new::<gtk4::FileFilter>();
                 filters.append(&jpeg_filter);
                 filters.append(&png_filter);
+                filters.append(&webp_filter);
                 dialog.set_filters(Some(&filters));
                 dialog.set_default_filter(Some(&jpeg_filter));
 
+                let quality = quality_row.value() as u8;
+                let background = rgba_color(&jpeg_background_button.rgba());
                 let window_clone = window_ref.clone();
+                let state = state.clone();
                 dialog.save(
                     Some(&window_ref),
                     None::<&gio::Cancellable>,
@@ -1314,18 +3438,52 @@ impl WallrusWindow {
                                         .and_then(|e| e.to_str())
                                         .unwrap_or("jpg"),
                                 );
-                                match export::save_pixels(&pixels, w, h, &path, format) {
+                                // JPEG can't keep transparency, so it's flattened
+                                let flattened = format == ExportFormat::Jpeg
+                                    && export::has_transparency(&pixels);
+                                let flat_pixels;
+                                let pixels = if flattened {
+                                    flat_pixels = export::composite_over(
+                                        &pixels,
+                                        palette::rgb_to_u8(background),
+                                    );
+                                    &flat_pixels
+                                } else {
+                                    &pixels
+                                };
+                                let started = std::time::Instant::now();
+                                let saved = export::save_pixels(
+                                    pixels,
+                                    w,
+                                    h,
+                                    &path,
+                                    format,
+                                    quality,
+                                    Some(&meta),
+                                );
+                                if let Some(renderer) = state.borrow().as_ref() {
+                                    renderer.record_encode(started.elapsed());
+                                }
+                                match saved {
                                     Ok(()) => {
-                                        show_toast(
-                                            &window_clone,
-                                            &format!("Saved to {}", path.display()),
-                                        );
+                                        let message = if flattened {
+                                            format!(
+                                                "Saved to {}; JPEG has no transparency, so it was filled with {}",
+                                                path.display(),
+                                                palette::rgb_to_hex(background),
+                                            )
+                                        } else {
+                                            format!("Saved to {}", path.display())
+                                        };
+                                        show_toast(&window_clone, &message);
+                                        notify_export(&window_clone, Ok(&path));
                                     }
                                     Err(e) => {
                                         show_toast(
                                             &window_clone,
                                             &format!("Export failed: {}", e),
                                         );
+                                        notify_export(&window_clone, Err(&e));
                                     }
                                 }
                             }
@@ -1343,48 +3501,452 @@ impl WallrusWindow {
 
         export_button.connect_clicked(make_export_handler);
 
-        // --- Set as wallpaper handler ---
-        // Shared logic for all wallpaper modes (Both / LightOnly / DarkOnly).
-        // --- Set as wallpaper handler (uses XDG Desktop Portal) ---
+        // --- Export video (animated presets only) ---
+        // Frames are rendered offscreen at the export resolution and piped to ffmpeg.
         {
             let state = state.clone();
-            let resolution_row = resolution_row.clone();
+            let selected_resolution = selected_resolution.clone();
+            let video_length_row = video_length_row.clone();
+            let video_fps_row = video_fps_row.clone();
+            let filename_row = filename_row.clone();
+            let selected_palette = selected_palette.clone();
+            let gl_area = gl_area.clone();
+            let window_ref = window.clone();
+            action_export_video.connect_activate(move |_, _| {
+                let Some(preset_name) = state.borrow().as_ref().map(|r| r.current_preset.clone())
+                else {
+                    show_toast(&window_ref, "Renderer not initialized");
+                    return;
+                };
+
+                let (w, h) = selected_resolution().dimensions();
+                let fields = filename_fields(&selected_palette.borrow(), &preset_name, w, h);
+                let filename = format!(
+                    "{}.mp4",
+                    export::expand_filename_template(&filename_row.text(), &fields)
+                );
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_initial_name(Some(&filename));
+                if let Some(videos_dir) = glib::user_special_dir(glib::UserDirectory::Videos) {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(videos_dir)));
+                }
+
+                let mp4_filter = gtk4::FileFilter::new();
+                mp4_filter.set_name(Some("MP4 videos"));
+                mp4_filter.add_mime_type("video/mp4");
+                mp4_filter.add_suffix("mp4");
+
+                let webm_filter = gtk4::FileFilter::new();
+                webm_filter.set_name(Some("WebM videos"));
+                webm_filter.add_mime_type("video/webm");
+                webm_filter.add_suffix("webm");
+
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&mp4_filter);
+                filters.append(&webm_filter);
+                dialog.set_filters(Some(&filters));
+                dialog.set_default_filter(Some(&mp4_filter));
+
+                let seconds = video_length_row.value() as u32;
+                let fps = video_fps_row.value() as u32;
+                let state = state.clone();
+                let gl_area = gl_area.clone();
+                let window_clone = window_ref.clone();
+                dialog.save(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let format = VideoFormat::from_extension(
+                                path.extension().and_then(|e| e.to_str()).unwrap_or("mp4"),
+                            );
+                            let result = run_export(
+                                &window_clone,
+                                &state,
+                                &gl_area,
+                                "Exporting Video",
+                                |renderer, control| {
+                                    export::export_video(
+                                        renderer,
+                                        (w, h),
+                                        fps,
+                                        seconds,
+                                        &path,
+                                        format,
+                                        control,
+                                    )
+                                },
+                            );
+                            match result {
+                                Ok(()) => {
+                                    show_toast(
+                                        &window_clone,
+                                        &format!("Saved to {}", path.display()),
+                                    );
+                                    notify_export(&window_clone, Ok(&path));
+                                }
+                                Err(e) if e == export::EXPORT_CANCELLED => {
+                                    show_toast(&window_clone, &e);
+                                }
+                                Err(e) => {
+                                    show_toast(
+                                        &window_clone,
+                                        &format!("Video export failed: {}", e),
+                                    );
+                                    notify_export(&window_clone, Err(&e));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_video);
+        window.add_action(&action_preview_backdrop);
+
+        // --- Render timings, for judging supersampling and animation costs ---
+        let action_render_diagnostics = gio::SimpleAction::new("render-diagnostics", None);
+        {
+            let state = state.clone();
+            let window_ref = window.clone();
+            action_render_diagnostics.connect_activate(move |_, _| {
+                let body = match state.borrow().as_ref() {
+                    Some(renderer) => {
+                        let mut lines = vec![renderer.gpu_description(), String::new()];
+                        lines.extend(renderer.timings().lines());
+                        lines.join("\n")
+                    }
+                    None => "Renderer not initialized".to_string(),
+                };
+                let dialog = adw::MessageDialog::new(
+                    Some(&window_ref),
+                    Some("Render Diagnostics"),
+                    Some(&body),
+                );
+                dialog.add_response("close", "Close");
+                dialog.set_close_response("close");
+                dialog.present();
+            });
+        }
+        window.add_action(&action_render_diagnostics);
+
+        // --- Copy the rendered wallpaper to the clipboard ---
+        // Same offscreen render as file export, handed to GDK as a texture.
+        let action_copy_image = gio::SimpleAction::new("copy-image", None);
+        {
+            let state = state.clone();
+            let selected_resolution = selected_resolution.clone();
+            let sampling_row = sampling_row.clone();
+            let dither_row = dither_row.clone();
+            let tile_row = tile_row.clone();
+            let gl_area = gl_area.clone();
+            let window_ref = window.clone();
+            action_copy_image.connect_activate(move |_, _| {
+                let (w, h) = selected_resolution().dimensions();
+                let sampling = SampleQuality::from_index(sampling_row.selected());
+                let dither = selected_dither(&dither_row);
+                let tile = tile_row.is_active();
+
+                gl_area.make_current();
+                // Quick enough for the clipboard not to need a progress dialog
+                let mut control =
+                    export::ExportControl::new(export::CancelToken::default(), |_| {});
+                let pixels = match state.borrow_mut().as_mut() {
+                    Some(renderer) => {
+                        render_export_pixels(renderer, w, h, sampling, dither, tile, &mut control)
+                    }
+                    None => Err("Renderer not initialized".to_string()),
+                };
+                match pixels {
+                    Ok(pixels) => {
+                        let texture = export::rgba_texture(&pixels, w, h);
+                        window_ref.clipboard().set_texture(&texture);
+                        show_toast(&window_ref, "Image copied to clipboard");
+                    }
+                    Err(e) => show_toast(&window_ref, &format!("Copy failed: {}", e)),
+                }
+            });
+        }
+        window.add_action(&action_copy_image);
+
+        // --- Set as wallpaper handler (backend picked by wallpaper::backend) ---
+        {
+            let state = state.clone();
+            let selected_resolution = selected_resolution.clone();
             let gl_area = gl_area.clone();
             let window_ref = window.clone();
+            let wallpaper_mode = wallpaper_mode.clone();
+            let wallpaper_target = wallpaper_target.clone();
+            let selected = selected_palette.clone();
+            let remember_recent = remember_recent.clone();
             set_wallpaper_button.connect_clicked(move |_| {
-                let resolution =
-                    ExportResolution::from_index(resolution_row.selected(), display_dims);
-                let (w, h) = resolution.dimensions();
+                let (w, h) = selected_resolution().dimensions();
 
                 gl_area.make_current();
+                let path = match render_wallpaper_file(&state, w, h, None, "both") {
+                    Ok(path) => path,
+                    Err(e) => {
+                        show_toast(&window_ref, &e);
+                        return;
+                    }
+                };
 
-                let pixels = {
-                    let state_ref = state.borrow();
-                    match state_ref.as_ref() {
-                        Some(renderer) => renderer.render_to_pixels(w as i32, h as i32),
-                        None => {
-                            show_toast(&window_ref, "Renderer not initialized");
-                            return;
-                        }
+                if let Some(palette) = selected.borrow().as_ref() {
+                    remember_recent(palette);
+                }
+                set_wallpaper_file(
+                    &window_ref,
+                    path,
+                    wallpaper_mode.get(),
+                    wallpaper_target.get(),
+                );
+            });
+        }
+
+        // --- Final-quality render before setting the wallpaper ---
+        // Renders at the largest monitor's size with supersampling, then previews
+        // that file, so what gets set is exactly what was shown.
+        let action_render_final = gio::SimpleAction::new("render-final", None);
+        {
+            let state = state.clone();
+            let selected_resolution = selected_resolution.clone();
+            let dither_row = dither_row.clone();
+            let gl_area = gl_area.clone();
+            let window_ref = window.clone();
+            let wallpaper_mode = wallpaper_mode.clone();
+            let wallpaper_target = wallpaper_target.clone();
+            action_render_final.connect_activate(move |_, _| {
+                let (w, h) = wallpaper::largest_monitor_size()
+                    .unwrap_or_else(|| selected_resolution().dimensions());
+                let dither = selected_dither(&dither_row);
+
+                let path = wallpaper::new_wallpaper_path("final");
+                let rendered = run_export(
+                    &window_ref,
+                    &state,
+                    &gl_area,
+                    "Rendering Wallpaper",
+                    |renderer, control| {
+                        control.next(export::ExportProgress {
+                            done: 0,
+                            total: 1,
+                            current: &path,
+                            pass: None,
+                        })?;
+                        export::render_pixels(renderer, w, h, FINAL_RENDER_QUALITY, dither, control)
+                            .map(|pixels| (pixels, renderer.wallpaper_meta()))
+                    },
+                );
+                let result = rendered.and_then(|(pixels, meta)| {
+                    export::save_pixels(
+                        &pixels,
+                        w,
+                        h,
+                        &path,
+                        ExportFormat::Png,
+                        export::DEFAULT_EXPORT_QUALITY,
+                        Some(&meta),
+                    )
+                });
+                match result {
+                    Ok(()) => show_final_render(
+                        &window_ref,
+                        path,
+                        (w, h),
+                        wallpaper_mode.clone(),
+                        wallpaper_target.clone(),
+                    ),
+                    Err(e) if e == export::EXPORT_CANCELLED => show_toast(&window_ref, &e),
+                    Err(e) => show_toast(&window_ref, &format!("Render failed: {}", e)),
+                }
+            });
+        }
+        window.add_action(&action_render_final);
+
+        // --- Set separate light/dark wallpapers (GNOME only) ---
+        // Renders the current preset twice: the palette as is for the dark
+        // style and lightened for the light style (see palette::Variant).
+        let action_set_wallpaper_dual = gio::SimpleAction::new("set-wallpaper-dual", None);
+        {
+            let state = state.clone();
+            let selected_resolution = selected_resolution.clone();
+            let gl_area = gl_area.clone();
+            let window_ref = window.clone();
+            let color_btns = color_buttons.clone();
+            let wallpaper_mode = wallpaper_mode.clone();
+            action_set_wallpaper_dual.connect_activate(move |_, _| {
+                if wallpaper::backend() != wallpaper::WallpaperBackend::Gnome {
+                    show_toast(
+                        &window_ref,
+                        "Separate light and dark wallpapers require GNOME",
+                    );
+                    return;
+                }
+
+                let (w, h) = selected_resolution().dimensions();
+                let colors = button_colors(&color_btns);
+
+                gl_area.make_current();
+                let paths = render_variant(&state, w, h, &colors, palette::Variant::Light)
+                    .and_then(|light_path| {
+                        render_variant(&state, w, h, &colors, palette::Variant::Dark)
+                            .map(|dark_path| (light_path, dark_path))
+                    });
+
+                let (light_path, dark_path) = match paths {
+                    Ok(paths) => paths,
+                    Err(e) => {
+                        show_toast(&window_ref, &format!("Failed: {}", e));
+                        return;
+                    }
+                };
+                let mode = wallpaper_mode.get();
+                let window_ref = window_ref.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    let result = wallpaper::run_blocking(move || {
+                        wallpaper::set_gnome_wallpaper_dual(&light_path, &dark_path, mode)?;
+                        wallpaper::remove_stale_wallpapers(&[&light_path, &dark_path]);
+                        Ok(())
+                    })
+                    .await;
+                    match result {
+                        Ok(()) => show_toast(&window_ref, "Light and dark wallpapers set!"),
+                        Err(e) => show_toast(&window_ref, &format!("Failed: {}", e)),
                     }
+                });
+            });
+        }
+        window.add_action(&action_set_wallpaper_dual);
+
+        // --- Set the current palette on one monitor (GNOME or swww) ---
+        // Each monitor's render is kept, so monitors set earlier keep their
+        // palette; monitors never set use the current one. swww sets each
+        // output on its own, so only the chosen one is rendered there.
+        let action_set_monitor_wallpaper =
+            gio::SimpleAction::new("set-monitor-wallpaper", Some(glib::VariantTy::STRING));
+        {
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let window_ref = window.clone();
+            action_set_monitor_wallpaper.connect_activate(move |_, param| {
+                let Some(connector) = param.and_then(|p| p.get::<String>()) else {
+                    return;
                 };
+                let backend = wallpaper::backend();
+                if !matches!(
+                    backend,
+                    wallpaper::WallpaperBackend::Gnome | wallpaper::WallpaperBackend::Swww
+                ) {
+                    show_toast(&window_ref, "Per-monitor wallpapers require GNOME or swww");
+                    return;
+                }
 
-                // Save to a temporary file and hand it to the portal
-                let tmp_path = std::env::temp_dir().join("wallrus_wallpaper.png");
-                if let Err(e) = export::save_pixels(&pixels, w, h, &tmp_path, ExportFormat::Png) {
-                    show_toast(&window_ref, &format!("Failed to save: {}", e));
+                let layout = wallpaper::monitor_layout();
+                let Some((_, geometry)) = layout.iter().find(|(name, _)| *name == connector) else {
+                    show_toast(
+                        &window_ref,
+                        &format!("Monitor {} is not connected", connector),
+                    );
+                    return;
+                };
+                gl_area.make_current();
+                if backend == wallpaper::WallpaperBackend::Swww {
+                    let path = wallpaper::monitor_wallpaper_path(&connector);
+                    let result =
+                        render_to_file(&state, geometry.width, geometry.height, None, &path)
+                            .and_then(|()| {
+                                wallpaper::set_wlroots_wallpaper_per_output(&[(
+                                    connector.clone(),
+                                    path,
+                                )])
+                            });
+                    match result {
+                        Ok(()) => {
+                            show_toast(&window_ref, &format!("Wallpaper set on {}", connector))
+                        }
+                        Err(e) => show_toast(&window_ref, &format!("Failed: {}", e)),
+                    }
                     return;
                 }
+                let renders = layout
+                    .iter()
+                    .map(|(name, geometry)| {
+                        let path = wallpaper::monitor_wallpaper_path(name);
+                        if *name == connector || !path.exists() {
+                            render_to_file(&state, geometry.width, geometry.height, None, &path)?;
+                        }
+                        Ok((*geometry, path))
+                    })
+                    .collect::<Result<Vec<_>, String>>();
+                let renders = match renders {
+                    Ok(renders) => renders,
+                    Err(e) => {
+                        show_toast(&window_ref, &format!("Failed: {}", e));
+                        return;
+                    }
+                };
+                let window_ref = window_ref.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    let result = wallpaper::run_blocking(move || {
+                        let monitors: Vec<_> =
+                            renders.iter().map(|(g, p)| (*g, p.as_path())).collect();
+                        wallpaper::set_gnome_wallpaper_spanned(&monitors)
+                    })
+                    .await;
+                    match result {
+                        Ok(()) => {
+                            show_toast(&window_ref, &format!("Wallpaper set on {}", connector))
+                        }
+                        Err(e) => show_toast(&window_ref, &format!("Failed: {}", e)),
+                    }
+                });
+            });
+        }
+        window.add_action(&action_set_monitor_wallpaper);
 
-                let window_ref2 = window_ref.clone();
+        // --- Undo: restore the wallpaper from before Wallrus set one (GNOME only) ---
+        let action_restore_wallpaper = gio::SimpleAction::new("restore-wallpaper", None);
+        {
+            let window_ref = window.clone();
+            action_restore_wallpaper.connect_activate(move |_, _| {
+                if wallpaper::backend() != wallpaper::WallpaperBackend::Gnome {
+                    show_toast(
+                        &window_ref,
+                        "Restoring the previous wallpaper requires GNOME",
+                    );
+                    return;
+                }
+                let window_ref = window_ref.clone();
                 glib::MainContext::default().spawn_local(async move {
-                    match wallpaper::set_wallpaper(&tmp_path).await {
-                        Ok(()) => show_toast(&window_ref2, "Wallpaper set!"),
-                        Err(e) => show_toast(&window_ref2, &format!("Failed: {}", e)),
+                    let result =
+                        wallpaper::run_blocking(wallpaper::restore_previous_wallpaper).await;
+                    match result {
+                        Ok(()) => show_toast(&window_ref, "Previous wallpaper restored"),
+                        Err(e) => show_toast(&window_ref, &format!("Failed: {}", e)),
                     }
                 });
             });
         }
+        window.add_action(&action_restore_wallpaper);
+
+        // --- Browse and re-apply wallpapers set earlier ---
+        let action_wallpaper_history = gio::SimpleAction::new("wallpaper-history", None);
+        {
+            let window_ref = window.clone();
+            let wallpaper_mode = wallpaper_mode.clone();
+            let wallpaper_target = wallpaper_target.clone();
+            action_wallpaper_history.connect_activate(move |_, _| {
+                show_wallpaper_history(&window_ref, wallpaper_mode.get(), wallpaper_target.get());
+            });
+        }
+        window.add_action(&action_wallpaper_history);
 
         // =====================================================================
         // Keyboard shortcuts via GActions
@@ -1404,33 +3966,2854 @@ impl WallrusWindow {
         }
         window.add_action(&action_set_wallpaper);
 
+        let action_save_palette = gio::SimpleAction::new("save-palette", None);
+        {
+            let btn = save_palette_button.clone();
+            action_save_palette.connect_activate(move |_, _| btn.emit_clicked());
+        }
+        window.add_action(&action_save_palette);
+
+        // Step through presets, wrapping at either end
+        for (name, step) in [("next-preset", 1i64), ("previous-preset", -1)] {
+            let action = gio::SimpleAction::new(name, None);
+            let preset_row = preset_row.clone();
+            let entries = preset_entries.clone();
+            action.connect_activate(move |_, _| {
+                let count = entries.borrow().len() as i64;
+                if count == 0 {
+                    return;
+                }
+                let idx = (preset_row.selected() as i64 + step).rem_euclid(count);
+                preset_row.set_selected(idx as u32);
+            });
+            window.add_action(&action);
+        }
+
         app.set_accels_for_action("win.export", &["<Control>e"]);
-        app.set_accels_for_action("win.set-wallpaper", &["<Control><Shift>w"]);
+        app.set_accels_for_action(
+            "win.set-wallpaper",
+            &["<Control>Return", "<Control><Shift>w"],
+        );
+        app.set_accels_for_action("win.save-palette", &["<Control>s"]);
+        app.set_accels_for_action("win.undo-palette", &["<Control>z"]);
+        app.set_accels_for_action("win.redo-palette", &["<Control><Shift>z", "<Control>y"]);
+        window.set_help_overlay(Some(&build_shortcuts_window()));
+
+        // --- Apply a palette image and preset (used by the D-Bus service) ---
+        // Parameter is (palette path, preset name); an empty preset keeps the current one.
+        let action_apply_palette =
+            gio::SimpleAction::new("apply-palette", Some(glib::VariantTy::new("(ss)").unwrap()));
+        {
+            let window_ref = window.clone();
+            let show_loaded_palette = show_loaded_palette.clone();
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            action_apply_palette.connect_activate(move |_, param| {
+                let Some((path, preset)) = param.and_then(|p| p.get::<(String, String)>()) else {
+                    return;
+                };
+                let path = std::path::Path::new(&path);
+                match palette::extract_four_colors(path) {
+                    Ok(colors) => {
+                        show_loaded_palette(colors);
+                        let warning = palette::classify_palette_image(path)
+                            .ok()
+                            .and_then(|kind| kind.warning());
+                        if let Some(warning) = warning {
+                            show_toast(&window_ref, warning);
+                        }
+                    }
+                    Err(e) => {
+                        show_toast(&window_ref, &e);
+                        return;
+                    }
+                }
+                if !preset.is_empty() {
+                    let idx = entries
+                        .borrow()
+                        .iter()
+                        .position(|e| e.name().eq_ignore_ascii_case(&preset));
+                    match idx {
+                        Some(idx) => preset_row.set_selected(idx as u32),
+                        None => show_toast(&window_ref, &format!("Unknown preset: {}", preset)),
+                    }
+                }
+            });
+        }
+        window.add_action(&action_apply_palette);
 
-        // --- About dialog action ---
-        let action_about = gio::SimpleAction::new("show-about", None);
+        // --- Generate a harmony palette from the first color ---
+        let action_generate_harmony =
+            gio::SimpleAction::new("generate-harmony", Some(glib::VariantTy::STRING));
+        {
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action_generate_harmony.connect_activate(move |_, param| {
+                let Some(scheme) = param
+                    .and_then(|p| p.str())
+                    .and_then(palette::HarmonyScheme::from_id)
+                else {
+                    return;
+                };
+                let base = button_colors(&color_btns)[0];
+                set_button_colors(
+                    &color_btns,
+                    &lock_btns,
+                    &palette::generate_harmony(base, scheme),
+                );
+            });
+        }
+        window.add_action(&action_generate_harmony);
+
+        // --- One-click invert and desaturate (locked colors are kept) ---
+        type Transform = fn(&[[f32; 3]; 4]) -> [[f32; 3]; 4];
+        let transforms: [(&str, Transform); 2] = [
+            ("invert-palette", palette::invert_palette),
+            ("grayscale-palette", palette::grayscale_palette),
+        ];
+        for (name, transform) in transforms {
+            let action = gio::SimpleAction::new(name, None);
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action.connect_activate(move |_, _| {
+                set_button_colors(
+                    &color_btns,
+                    &lock_btns,
+                    &transform(&button_colors(&color_btns)),
+                );
+            });
+            window.add_action(&action);
+        }
+
+        // --- WCAG contrast of every color pair ---
+        let action_contrast_report = gio::SimpleAction::new("contrast-report", None);
         {
             let window_ref = window.clone();
-            action_about.connect_activate(move |_, _| {
-                let about = adw::AboutWindow::builder()
-                    .application_name("Wallrus")
-                    .application_icon("io.github.megakode.Wallrus")
-                    .developer_name("Peter Boné")
-                    .version(env!("CARGO_PKG_VERSION"))
-                    .website("https://github.com/megakode/wallrus")
-                    .issue_url("https://github.com/megakode/wallrus/issues")
-                    .license_type(gtk4::License::Gpl30)
-                    .copyright("© 2026 Peter Boné")
-                    .developers(vec!["Peter Boné"])
-                    .transient_for(&window_ref)
-                    .modal(true)
-                    .build();
-                about.present();
+            let color_btns = color_buttons.clone();
+            action_contrast_report.connect_activate(move |_, _| {
+                show_contrast_report(&window_ref, &button_colors(&color_btns));
             });
         }
-        window.add_action(&action_about);
+        window.add_action(&action_contrast_report);
 
-        window
+        // --- Sort the palette colors (locks follow their colors) ---
+        let action_sort_colors =
+            gio::SimpleAction::new("sort-colors", Some(glib::VariantTy::STRING));
+        {
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action_sort_colors.connect_activate(move |_, param| {
+                let Some(key) = param
+                    .and_then(|p| p.str())
+                    .and_then(palette::SortKey::from_id)
+                else {
+                    return;
+                };
+                let (_, order) = palette::sort_colors_by(&button_colors(&color_btns), key);
+                reorder_slots(&color_btns, &lock_btns, order);
+            });
+        }
+        window.add_action(&action_sort_colors);
+
+        // --- Reorder the colors for the smoothest gradient (locks follow) ---
+        let action_optimize_order = gio::SimpleAction::new("optimize-order", None);
+        {
+            let color_btns = color_buttons.clone();
+            let lock_btns = lock_buttons.clone();
+            action_optimize_order.connect_activate(move |_, _| {
+                let order = palette::optimal_gradient_order(&button_colors(&color_btns));
+                reorder_slots(&color_btns, &lock_btns, order);
+            });
+        }
+        window.add_action(&action_optimize_order);
+
+        // --- Batch-export a wallpaper for every palette in the current category ---
+        let action_export_category = gio::SimpleAction::new("export-category", None);
+        {
+            let window_ref = window.clone();
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let category_names = category_names.clone();
+            let category_row = category_row.clone();
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            let selected_resolution = selected_resolution.clone();
+            let filename_row = filename_row.clone();
+            action_export_category.connect_activate(move |_, _| {
+                let Some(category) = category_names
+                    .borrow()
+                    .get(category_row.selected() as usize)
+                    .cloned()
+                else {
+                    return;
+                };
+                let Some(preset) = entries
+                    .borrow()
+                    .get(preset_row.selected() as usize)
+                    .cloned()
+                else {
+                    return;
+                };
+                let resolution = selected_resolution().dimensions();
+                let template = filename_row.text().to_string();
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title(&format!("Export “{}” Wallpapers To", category));
+                if let Some(pictures_dir) = glib::user_special_dir(glib::UserDirectory::Pictures) {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(pictures_dir)));
+                }
+
+                let window_clone = window_ref.clone();
+                let state = state.clone();
+                let gl_area = gl_area.clone();
+                dialog.select_folder(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(folder) => {
+                            let Some(out_dir) = folder.path() else { return };
+                            let heading = format!("Exporting “{}” Wallpapers", category);
+                            let result = run_export(
+                                &window_clone,
+                                &state,
+                                &gl_area,
+                                &heading,
+                                |renderer, control| {
+                                    export::batch_export(
+                                        renderer, &category, &preset, resolution, &out_dir,
+                                        &template, control,
+                                    )
+                                },
+                            );
+                            match result {
+                                Ok(summary) => {
+                                    for (path, e) in &summary.errors {
+                                        eprintln!("Failed to export '{}': {}", path.display(), e);
+                                    }
+                                    let message = if summary.errors.is_empty() {
+                                        format!("Exported {} wallpapers", summary.exported.len())
+                                    } else {
+                                        format!(
+                                            "Exported {} wallpapers, {} failed",
+                                            summary.exported.len(),
+                                            summary.errors.len()
+                                        )
+                                    };
+                                    show_toast(&window_clone, &message);
+                                    notify(
+                                        &window_clone,
+                                        EXPORT_NOTIFICATION,
+                                        &message,
+                                        &out_dir.display().to_string(),
+                                    );
+                                }
+                                Err(e) if e == export::EXPORT_CANCELLED => {
+                                    show_toast(&window_clone, &e);
+                                }
+                                Err(e) => {
+                                    show_toast(&window_clone, &format!("Export failed: {}", e));
+                                    notify_export(&window_clone, Err(&e));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_category);
+
+        // --- Export the current scene at phone, laptop, desktop and 4K sizes ---
+        let action_export_sizes = gio::SimpleAction::new("export-sizes", None);
+        {
+            let window_ref = window.clone();
+            let state = state.clone();
+            let gl_area = gl_area.clone();
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            let selected_palette = selected_palette.clone();
+            let filename_row = filename_row.clone();
+            let sampling_row = sampling_row.clone();
+            let dither_row = dither_row.clone();
+            action_export_sizes.connect_activate(move |_, _| {
+                let Some(preset) = entries
+                    .borrow()
+                    .get(preset_row.selected() as usize)
+                    .map(|e| e.name().to_string())
+                else {
+                    return;
+                };
+                // {width} and {height} describe the largest size in the pack
+                let (_, w, h) = export::PACK_SIZES[export::PACK_SIZES.len() - 1];
+                let fields = filename_fields(&selected_palette.borrow(), &preset, w, h);
+                let name = export::expand_filename_template(&filename_row.text(), &fields);
+                let sampling = SampleQuality::from_index(sampling_row.selected());
+                let dither = selected_dither(&dither_row);
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Export Wallpapers for All Screens To");
+                if let Some(pictures_dir) = glib::user_special_dir(glib::UserDirectory::Pictures) {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(pictures_dir)));
+                }
+
+                let window_clone = window_ref.clone();
+                let state = state.clone();
+                let gl_area = gl_area.clone();
+                dialog.select_folder(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(folder) => {
+                            let Some(out_dir) = folder.path() else { return };
+                            let written = run_export(
+                                &window_clone,
+                                &state,
+                                &gl_area,
+                                "Exporting Wallpapers for All Screens",
+                                |renderer, control| {
+                                    export::export_pack(
+                                        renderer, &out_dir, &name, sampling, dither, control,
+                                    )
+                                },
+                            );
+                            match written {
+                                Ok(paths) => {
+                                    notify_export(&window_clone, Ok(&out_dir));
+                                    show_pack_manifest(&window_clone, &out_dir, &paths);
+                                }
+                                Err(e) if e == export::EXPORT_CANCELLED => {
+                                    show_toast(&window_clone, &e);
+                                }
+                                Err(e) => {
+                                    show_toast(&window_clone, &format!("Export failed: {}", e));
+                                    notify_export(&window_clone, Err(&e));
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_sizes);
+
+        // --- Save the current wallpaper as a shareable recipe ---
+        let action_export_recipe = gio::SimpleAction::new("export-recipe", None);
+        {
+            let window_ref = window.clone();
+            let state = state.clone();
+            let selected_resolution = selected_resolution.clone();
+            let selected_palette = selected_palette.clone();
+            let filename_row = filename_row.clone();
+            action_export_recipe.connect_activate(move |_, _| {
+                let resolution = selected_resolution().dimensions();
+                let Some(recipe) = state
+                    .borrow()
+                    .as_ref()
+                    .map(|r| recipe::Recipe::from_renderer(r, resolution))
+                else {
+                    show_toast(&window_ref, "Renderer not initialized");
+                    return;
+                };
+                let fields = filename_fields(
+                    &selected_palette.borrow(),
+                    &recipe.meta.preset,
+                    resolution.0,
+                    resolution.1,
+                );
+                let filename = format!(
+                    "{}.{}",
+                    export::expand_filename_template(&filename_row.text(), &fields),
+                    recipe::RECIPE_EXTENSION
+                );
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Export Recipe");
+                dialog.set_initial_name(Some(&filename));
+
+                let window_clone = window_ref.clone();
+                dialog.save(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            match recipe::export_recipe(&recipe, &path) {
+                                Ok(()) => show_toast(
+                                    &window_clone,
+                                    &format!("Saved to {}", path.display()),
+                                ),
+                                Err(e) => {
+                                    show_toast(&window_clone, &format!("Export failed: {}", e))
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_recipe);
+
+        // --- Restore what a recipe or an exported wallpaper recorded ---
+        // Returns whether the preset was found; the palette and effects are
+        // restored either way
+        let restore_meta: Rc<dyn Fn(&export::WallpaperMeta) -> bool> = {
+            let show_loaded_palette = show_loaded_palette.clone();
+            let entries = preset_entries.clone();
+            let preset_row = preset_row.clone();
+            let param_scales = param_scales.clone();
+            let set_seed = set_seed.clone();
+            let blend_space_row = blend_space_row.clone();
+            let color_count_row = color_count_row.clone();
+            let controls = [
+                ("uAngle", angle_scale.clone()),
+                ("uScale", scale_scale.clone()),
+                ("uSpeed", speed_scale.clone()),
+                ("uCenter", center_scale.clone()),
+                ("uBlend", blend_scale.clone()),
+                ("uNoise", noise_scale.clone()),
+            ];
+            // Types go first, since choosing one resets its sliders
+            let effect_types = [
+                ("uDistortType", distort_row.clone()),
+                ("uLightingType", lighting_row.clone()),
+                ("uShadeType", shade_row.clone()),
+            ];
+            let effects = [
+                ("uDistortStrength", distort_strength_scale.clone()),
+                ("uRippleFreq", ripple_freq_scale.clone()),
+                ("uLightStrength", light_strength_scale.clone()),
+                ("uBevelWidth", bevel_width_scale.clone()),
+                ("uLightAngle", light_angle_scale.clone()),
+                ("uShadeStrength", shade_strength_scale.clone()),
+            ];
+            let symmetry_row = symmetry_row.clone();
+            let dither_switch = dither_switch.clone();
+            let shade_position_row = shade_position_row.clone();
+            Rc::new(move |meta| {
+                show_loaded_palette(meta.colors);
+                set_seed(meta.seed);
+                let space = ColorSpace::ALL.iter().position(|&s| s == meta.color_space);
+                blend_space_row.set_selected(space.unwrap_or(0) as u32);
+                color_count_row.set_value(meta.color_count as f64);
+
+                for (uniform, row) in &effect_types {
+                    if let Some(value) = meta.control(uniform) {
+                        row.set_selected(value.max(0.0) as u32);
+                    }
+                }
+                for (uniform, scale) in &effects {
+                    let Some(value) = meta.control(uniform) else {
+                        continue;
+                    };
+                    let value = if *uniform == "uLightAngle" {
+                        // The slider puts 0° at the top, see its handler
+                        (value.to_degrees() + 90.0).rem_euclid(360.0)
+                    } else {
+                        value
+                    };
+                    scale.set_value(value as f64);
+                }
+                if let Some(folds) = meta.control("uSymmetry") {
+                    symmetry_row.set_value(folds as f64);
+                }
+                if let Some(dither) = meta.control("uDither") {
+                    dither_switch.set_active(dither > 0.5);
+                }
+                let shade_x = meta.control("uShadeCenter.x");
+                let shade_y = meta.control("uShadeCenter.y");
+                if let Some((x, y)) = shade_x.zip(shade_y) {
+                    let nearest = (0..SHADE_POSITIONS.len()).min_by(|&a, &b| {
+                        let distance = |i: usize| {
+                            let [cx, cy] = SHADE_POSITIONS[i].1;
+                            (cx - x).powi(2) + (cy - y).powi(2)
+                        };
+                        distance(a).total_cmp(&distance(b))
+                    });
+                    shade_position_row.set_selected(nearest.unwrap_or(0) as u32);
+                }
+
+                let idx = entries
+                    .borrow()
+                    .iter()
+                    .position(|e| e.name() == meta.preset);
+                let Some(idx) = idx else {
+                    return false;
+                };
+                // Selecting the preset resets its sliders, so restore them after
+                preset_row.set_selected(idx as u32);
+                for (name, _, adjustment) in param_scales.borrow().iter() {
+                    if let Some((_, value)) = meta.params.iter().find(|(n, _)| n == name) {
+                        adjustment.set_value(*value as f64);
+                    }
+                }
+                for (uniform, scale) in &controls {
+                    let Some(value) = meta.control(uniform) else {
+                        continue;
+                    };
+                    let value = if *uniform == "uAngle" {
+                        value.to_degrees().rem_euclid(360.0)
+                    } else {
+                        value
+                    };
+                    scale.set_value(value as f64);
+                }
+                true
+            })
+        };
+
+        // --- Recreate palette and preset from an exported wallpaper's metadata ---
+        let action_import_wallpaper = gio::SimpleAction::new("import-wallpaper", None);
+        {
+            let window_ref = window.clone();
+            let restore_meta = restore_meta.clone();
+            action_import_wallpaper.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Recreate from Wallpaper");
+
+                let png_filter = gtk4::FileFilter::new();
+                png_filter.set_name(Some("PNG images"));
+                png_filter.add_mime_type("image/png");
+                png_filter.add_suffix("png");
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&png_filter);
+                dialog.set_filters(Some(&filters));
+                dialog.set_default_filter(Some(&png_filter));
+
+                let window_clone = window_ref.clone();
+                let restore_meta = restore_meta.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let Some(meta) = export::read_embedded_metadata(&path) else {
+                                show_toast(&window_clone, "No Wallrus palette found in this image");
+                                return;
+                            };
+                            if restore_meta(&meta) {
+                                show_toast(&window_clone, "Palette and preset restored");
+                            } else {
+                                show_toast(
+                                    &window_clone,
+                                    &format!(
+                                        "Palette restored; preset '{}' not found",
+                                        meta.preset
+                                    ),
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_import_wallpaper);
+
+        // --- Restore the full state saved in a recipe ---
+        let action_open_recipe = gio::SimpleAction::new("open-recipe", None);
+        {
+            let window_ref = window.clone();
+            let restore_meta = restore_meta.clone();
+            let resolution_row = resolution_row.clone();
+            let custom_width_row = custom_width_row.clone();
+            let custom_height_row = custom_height_row.clone();
+            action_open_recipe.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Open Recipe");
+
+                let recipe_filter = gtk4::FileFilter::new();
+                recipe_filter.set_name(Some("Wallrus recipes"));
+                recipe_filter.add_suffix(recipe::RECIPE_EXTENSION);
+                recipe_filter.add_suffix("json");
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&recipe_filter);
+                dialog.set_filters(Some(&filters));
+                dialog.set_default_filter(Some(&recipe_filter));
+
+                let window_clone = window_ref.clone();
+                let restore_meta = restore_meta.clone();
+                let resolution_row = resolution_row.clone();
+                let custom_width_row = custom_width_row.clone();
+                let custom_height_row = custom_height_row.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let recipe = match recipe::import_recipe(&path) {
+                                Ok(recipe) => recipe,
+                                Err(e) => {
+                                    show_toast(&window_clone, &format!("Import failed: {}", e));
+                                    return;
+                                }
+                            };
+
+                            // Pick the matching resolution, or enter it as a custom size
+                            let matching = (0..ExportResolution::CUSTOM_INDEX).find(|&i| {
+                                ExportResolution::from_index(i, display_dims, recipe.resolution)
+                                    .dimensions()
+                                    == recipe.resolution
+                            });
+                            match matching {
+                                Some(index) => resolution_row.set_selected(index),
+                                None => {
+                                    custom_width_row.set_value(recipe.resolution.0 as f64);
+                                    custom_height_row.set_value(recipe.resolution.1 as f64);
+                                    resolution_row.set_selected(ExportResolution::CUSTOM_INDEX);
+                                }
+                            }
+
+                            if restore_meta(&recipe.meta) {
+                                show_toast(&window_clone, "Recipe restored");
+                            } else {
+                                show_toast(
+                                    &window_clone,
+                                    &format!(
+                                        "Palette restored; preset '{}' not found",
+                                        recipe.meta.preset
+                                    ),
+                                );
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_open_recipe);
+
+        // --- Import palette from photo (k-means extraction) ---
+        // Shared with screenshots: preview the extraction, then save and
+        // select the palette, reporting `done` in a toast
+        // `temporary` photos (portal screenshots) are deleted once the
+        // palette and its thumbnail have been saved from them
+        let extract_photo: Rc<dyn Fn(PathBuf, &'static str, bool)> = {
+            let window_ref = window.clone();
+            let refresh_then = refresh_then.clone();
+            let select_palette = select_palette.clone();
+            Rc::new(move |path, done, temporary| {
+                let window_inner = window_ref.clone();
+                let refresh_then = refresh_then.clone();
+                let select_palette = select_palette.clone();
+                let original = path.clone();
+                prompt_extract_colors(&window_ref, path, move |colors, source| {
+                    match palette::save_palette_image(&colors) {
+                        Ok(saved) => {
+                            if let Some(source) = source {
+                                if let Err(e) = palette::save_source_thumbnail(&saved, &source) {
+                                    eprintln!("{}", e);
+                                }
+                            }
+                            if temporary {
+                                if let Err(e) = std::fs::remove_file(&original) {
+                                    eprintln!("Failed to remove {}: {}", original.display(), e);
+                                }
+                            }
+                            let select_palette = select_palette.clone();
+                            refresh_then(Box::new(move || {
+                                select_palette(&saved);
+                            }));
+                            show_toast(&window_inner, done);
+                        }
+                        Err(e) => {
+                            show_toast(&window_inner, &format!("Failed to save palette: {}", e))
+                        }
+                    }
+                });
+            })
+        };
+
+        let action_import_photo = gio::SimpleAction::new("import-photo", None);
+        {
+            let window_ref = window.clone();
+            let extract_photo = extract_photo.clone();
+            action_import_photo.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Import Palette from Photo");
+
+                let image_filter = gtk4::FileFilter::new();
+                image_filter.set_name(Some("Images"));
+                image_filter.add_pixbuf_formats();
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&image_filter);
+                dialog.set_filters(Some(&filters));
+
+                let window_clone = window_ref.clone();
+                let extract_photo = extract_photo.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            extract_photo(path, "Palette extracted from photo", false);
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_import_photo);
+
+        // --- Import palette from a screenshot region, through the portal ---
+        let action_import_screenshot = gio::SimpleAction::new("import-screenshot", None);
+        {
+            let window_ref = window.clone();
+            action_import_screenshot.connect_activate(move |_, _| {
+                let window_ref = window_ref.clone();
+                let extract_photo = extract_photo.clone();
+                glib::MainContext::default().spawn_local(async move {
+                    match screenshot::capture_region().await {
+                        Ok(Some(path)) => {
+                            extract_photo(path, "Palette extracted from screenshot", true)
+                        }
+                        Ok(None) => show_toast(&window_ref, "Screenshot cancelled"),
+                        Err(e) => show_toast(&window_ref, &format!("Screenshot failed: {}", e)),
+                    }
+                });
+            });
+        }
+        window.add_action(&action_import_screenshot);
+
+        // --- Drop an image onto the window to create a palette ---
+        {
+            let drop_target =
+                gtk4::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+            let window_ref = window.clone();
+            let refresh_then = refresh_then.clone();
+            let select_palette = select_palette.clone();
+            drop_target.connect_drop(move |_, value, _, _| {
+                let Ok(files) = value.get::<gdk::FileList>() else {
+                    return false;
+                };
+                let Some(path) = files.files().first().and_then(|f| f.path()) else {
+                    return false;
+                };
+                if image::ImageFormat::from_path(&path).is_err() {
+                    show_toast(&window_ref, "Dropped file is not an image");
+                    return false;
+                }
+
+                let saved = palette::extract_palette_from_file(&path).and_then(|(colors, kind)| {
+                    let saved = palette::save_palette_image(&colors)?;
+                    // Photos keep a thumbnail; a strip is its own best preview
+                    if kind == palette::PaletteImageKind::Photo {
+                        if let Err(e) = palette::save_source_thumbnail(&saved, &path) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Ok((saved, kind))
+                });
+                match saved {
+                    Ok((saved_path, kind)) => {
+                        let select_palette = select_palette.clone();
+                        refresh_then(Box::new(move || {
+                            select_palette(&saved_path);
+                        }));
+                        // Photos are expected here, only irregular strips are worth a warning
+                        match kind {
+                            palette::PaletteImageKind::IrregularStrip => {
+                                show_toast(&window_ref, kind.warning().unwrap_or_default())
+                            }
+                            _ => show_toast(&window_ref, "Palette created from image"),
+                        }
+                        true
+                    }
+                    Err(e) => {
+                        show_toast(&window_ref, &format!("Import failed: {}", e));
+                        false
+                    }
+                }
+            });
+            window.add_controller(drop_target);
+        }
+
+        // --- Import GIMP palette (.gpl) as a custom palette ---
+        let action_import_gpl = gio::SimpleAction::new("import-gpl", None);
+        {
+            let window_ref = window.clone();
+            let show_loaded_palette = show_loaded_palette.clone();
+            let refresh_then = refresh_then.clone();
+            let cat_names_ref = category_names.clone();
+            let category_row_ref = category_row.clone();
+            action_import_gpl.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Import GIMP Palette");
+
+                let gpl_filter = gtk4::FileFilter::new();
+                gpl_filter.set_name(Some("GIMP palettes"));
+                gpl_filter.add_suffix("gpl");
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&gpl_filter);
+                dialog.set_filters(Some(&filters));
+
+                let window_clone = window_ref.clone();
+                let show_loaded_palette = show_loaded_palette.clone();
+                let refresh_then = refresh_then.clone();
+                let cat_names_ref = cat_names_ref.clone();
+                let category_row_ref = category_row_ref.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            if let Some(path) = file.path() {
+                                match palette::import_gpl(&path) {
+                                    Ok(colors) => {
+                                        // Refresh categories and switch to Custom
+                                        let cat_names_ref = cat_names_ref.clone();
+                                        let category_row_ref = category_row_ref.clone();
+                                        let show_loaded_palette = show_loaded_palette.clone();
+                                        refresh_then(Box::new(move || {
+                                            let names = cat_names_ref.borrow();
+                                            if let Some(idx) = names
+                                                .iter()
+                                                .position(|n| palette::is_custom_category(n))
+                                            {
+                                                category_row_ref.set_selected(idx as u32);
+                                            }
+                                            show_loaded_palette(palette::to_four_colors(&colors));
+                                        }));
+                                        show_toast(&window_clone, "Palette imported");
+                                    }
+                                    Err(e) => {
+                                        show_toast(&window_clone, &format!("Import failed: {}", e));
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_import_gpl);
+
+        // --- Import pywal colors, picking four of the sixteen ---
+        let action_import_pywal = gio::SimpleAction::new("import-pywal", None);
+        {
+            let window_ref = window.clone();
+            let refresh_then = refresh_then.clone();
+            let select_palette = select_palette.clone();
+            action_import_pywal.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Import pywal Colors");
+
+                let json_filter = gtk4::FileFilter::new();
+                json_filter.set_name(Some("pywal colors (colors.json)"));
+                json_filter.add_suffix("json");
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&json_filter);
+                dialog.set_filters(Some(&filters));
+                let wal_dir = glib::user_cache_dir().join("wal");
+                if wal_dir.is_dir() {
+                    dialog.set_initial_folder(Some(&gio::File::for_path(wal_dir)));
+                }
+
+                let window_clone = window_ref.clone();
+                let refresh_then = refresh_then.clone();
+                let select_palette = select_palette.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let colors = match palette::import_pywal(&path) {
+                                Ok(colors) => colors,
+                                Err(e) => {
+                                    show_toast(&window_clone, &format!("Import failed: {}", e));
+                                    return;
+                                }
+                            };
+                            let preselected = palette::distinct_color_indices(
+                                &colors,
+                                palette::DEFAULT_COLOR_COUNT,
+                            );
+                            let window_inner = window_clone.clone();
+                            let refresh_then = refresh_then.clone();
+                            let select_palette = select_palette.clone();
+                            let scheme = colors.clone();
+                            prompt_pick_colors(
+                                &window_clone,
+                                "Choose Four Colors",
+                                &colors,
+                                &preselected,
+                                move |picked| {
+                                    let picked: Vec<[f32; 3]> =
+                                        picked.iter().map(|&i| scheme[i]).collect();
+                                    match palette::save_palette_image(&palette::to_four_colors(
+                                        &picked,
+                                    )) {
+                                        Ok(saved) => {
+                                            let select_palette = select_palette.clone();
+                                            refresh_then(Box::new(move || {
+                                                select_palette(&saved);
+                                            }));
+                                            show_toast(&window_inner, "Palette imported");
+                                        }
+                                        Err(e) => show_toast(
+                                            &window_inner,
+                                            &format!("Failed to save palette: {}", e),
+                                        ),
+                                    }
+                                },
+                            );
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_import_pywal);
+
+        // --- Import a palette laid out as a grid of swatches ---
+        let action_import_grid = gio::SimpleAction::new("import-grid", None);
+        {
+            let window_ref = window.clone();
+            let refresh_then = refresh_then.clone();
+            let select_palette = select_palette.clone();
+            action_import_grid.connect_activate(move |_, _| {
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Import Palette Grid");
+
+                let image_filter = gtk4::FileFilter::new();
+                image_filter.set_name(Some("Images"));
+                image_filter.add_pixbuf_formats();
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&image_filter);
+                dialog.set_filters(Some(&filters));
+
+                let window_clone = window_ref.clone();
+                let refresh_then = refresh_then.clone();
+                let select_palette = select_palette.clone();
+                dialog.open(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            let Some(path) = file.path() else { return };
+                            let window_inner = window_clone.clone();
+                            let refresh_then = refresh_then.clone();
+                            let select_palette = select_palette.clone();
+                            prompt_grid_size(&window_clone, move |cols, rows| {
+                                let colors = match palette::extract_colors_grid(&path, cols, rows) {
+                                    Ok(colors) => colors,
+                                    Err(e) => {
+                                        show_toast(&window_inner, &format!("Import failed: {}", e));
+                                        return;
+                                    }
+                                };
+                                let save = {
+                                    let window_inner = window_inner.clone();
+                                    let refresh_then = refresh_then.clone();
+                                    let select_palette = select_palette.clone();
+                                    move |picked: &[[f32; 3]]| match palette::save_palette_image(
+                                        &palette::to_four_colors(picked),
+                                    ) {
+                                        Ok(saved) => {
+                                            let select_palette = select_palette.clone();
+                                            refresh_then(Box::new(move || {
+                                                select_palette(&saved);
+                                            }));
+                                            show_toast(&window_inner, "Palette imported");
+                                        }
+                                        Err(e) => show_toast(
+                                            &window_inner,
+                                            &format!("Failed to save palette: {}", e),
+                                        ),
+                                    }
+                                };
+                                // Larger grids have more colors than a palette holds
+                                if colors.len() <= palette::DEFAULT_COLOR_COUNT {
+                                    save(&colors);
+                                    return;
+                                }
+                                let preselected = palette::distinct_color_indices(
+                                    &colors,
+                                    palette::DEFAULT_COLOR_COUNT,
+                                );
+                                let grid = colors.clone();
+                                prompt_pick_colors(
+                                    &window_inner,
+                                    "Choose Four Colors",
+                                    &colors,
+                                    &preselected,
+                                    move |picked| {
+                                        let picked: Vec<[f32; 3]> =
+                                            picked.iter().map(|&i| grid[i]).collect();
+                                        save(&picked);
+                                    },
+                                );
+                            });
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Import failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_import_grid);
+
+        // --- Export current palette as hex / JSON / CSS ---
+        let action_export_palette = gio::SimpleAction::new("export-palette", None);
+        {
+            let window_ref = window.clone();
+            let color_btns = color_buttons.clone();
+            action_export_palette.connect_activate(move |_, _| {
+                let colors = button_colors(&color_btns);
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Export Palette");
+                dialog.set_initial_name(Some("palette.css"));
+
+                let css_filter = gtk4::FileFilter::new();
+                css_filter.set_name(Some("CSS variables"));
+                css_filter.add_suffix("css");
+
+                let json_filter = gtk4::FileFilter::new();
+                json_filter.set_name(Some("JSON"));
+                json_filter.add_suffix("json");
+
+                let hex_filter = gtk4::FileFilter::new();
+                hex_filter.set_name(Some("Hex list"));
+                hex_filter.add_suffix("txt");
+
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&css_filter);
+                filters.append(&json_filter);
+                filters.append(&hex_filter);
+                dialog.set_filters(Some(&filters));
+                dialog.set_default_filter(Some(&css_filter));
+
+                let window_clone = window_ref.clone();
+                dialog.save(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            if let Some(path) = file.path() {
+                                let format = palette::PaletteExportFormat::from_extension(
+                                    path.extension().and_then(|e| e.to_str()).unwrap_or("txt"),
+                                );
+                                match palette::export_palette(&colors, format, &path) {
+                                    Ok(()) => show_toast(
+                                        &window_clone,
+                                        &format!("Saved to {}", path.display()),
+                                    ),
+                                    Err(e) => {
+                                        show_toast(&window_clone, &format!("Export failed: {}", e))
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_palette);
+
+        // --- Copy one swatch's #RRGGBB to the clipboard ---
+        let action_copy_hex = gio::SimpleAction::new("copy-hex", Some(glib::VariantTy::UINT32));
+        {
+            let window_ref = window.clone();
+            let color_btns = color_buttons.clone();
+            action_copy_hex.connect_activate(move |_, param| {
+                let Some(btn) = param
+                    .and_then(|p| p.get::<u32>())
+                    .and_then(|slot| color_btns.get(slot as usize))
+                else {
+                    return;
+                };
+                let hex = palette::rgb_to_hex(rgba_color(&btn.rgba()));
+                window_ref.clipboard().set_text(&hex);
+                show_toast(&window_ref, &format!("Copied {}", hex));
+            });
+        }
+        window.add_action(&action_copy_hex);
+
+        // --- Export a labeled swatch card (hex codes printed on each color) ---
+        let action_export_palette_card =
+            gio::SimpleAction::new("export-palette-card", Some(glib::VariantTy::STRING));
+        {
+            let window_ref = window.clone();
+            let color_btns = color_buttons.clone();
+            action_export_palette_card.connect_activate(move |_, param| {
+                let Some(layout) = param.and_then(|p| p.str()).and_then(CardLayout::from_id) else {
+                    return;
+                };
+                let colors = button_colors(&color_btns);
+
+                let dialog = gtk4::FileDialog::new();
+                dialog.set_title("Export Palette Card");
+                dialog.set_initial_name(Some("palette-card.png"));
+
+                let png_filter = gtk4::FileFilter::new();
+                png_filter.set_name(Some("PNG image"));
+                png_filter.add_suffix("png");
+                let filters = gio::ListStore::new::<gtk4::FileFilter>();
+                filters.append(&png_filter);
+                dialog.set_filters(Some(&filters));
+                dialog.set_default_filter(Some(&png_filter));
+
+                let window_clone = window_ref.clone();
+                dialog.save(
+                    Some(&window_ref),
+                    None::<&gio::Cancellable>,
+                    move |result| match result {
+                        Ok(file) => {
+                            if let Some(path) = file.path() {
+                                let opts = PaletteCardOptions::new(layout);
+                                match palette_card::export_palette_card(&colors, &path, &opts) {
+                                    Ok(()) => show_toast(
+                                        &window_clone,
+                                        &format!("Saved to {}", path.display()),
+                                    ),
+                                    Err(e) => {
+                                        show_toast(&window_clone, &format!("Export failed: {}", e))
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            if !e.matches(gio::IOErrorEnum::Cancelled) {
+                                show_toast(&window_clone, &format!("Export failed: {}", e));
+                            }
+                        }
+                    },
+                );
+            });
+        }
+        window.add_action(&action_export_palette_card);
+
+        // --- Rename / move the selected custom palette ---
+        let selected_palette_path: Rc<dyn Fn() -> Option<PathBuf>> = {
+            let flowbox = palette_flowbox.clone();
+            let paths = palette_paths.clone();
+            Rc::new(move || {
+                let child = flowbox.selected_children().into_iter().next()?;
+                paths.borrow().get(child.index() as usize).cloned()
+            })
+        };
+
+        let action_rename_palette = gio::SimpleAction::new("rename-palette", None);
+        {
+            let window_ref = window.clone();
+            let selected = selected_palette_path.clone();
+            let refresh = refresh_current_category.clone();
+            action_rename_palette.connect_activate(move |_, _| {
+                let Some(path) = selected() else {
+                    show_toast(&window_ref, "Select a palette first");
+                    return;
+                };
+                let current_name = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or_default()
+                    .to_string();
+
+                let window_clone = window_ref.clone();
+                let refresh = refresh.clone();
+                prompt_text(
+                    &window_ref,
+                    "Rename Palette",
+                    &current_name,
+                    "Rename",
+                    move |name| match palette::rename_palette(&path, &name) {
+                        Ok(_) => {
+                            if let Some(ref cb) = *refresh.borrow() {
+                                cb();
+                            }
+                            show_toast(&window_clone, "Palette renamed");
+                        }
+                        Err(e) => show_toast(&window_clone, &format!("Failed to rename: {}", e)),
+                    },
+                );
+            });
+        }
+        window.add_action(&action_rename_palette);
+
+        let action_move_palette = gio::SimpleAction::new("move-palette", None);
+        {
+            let window_ref = window.clone();
+            let selected = selected_palette_path.clone();
+            let refresh = refresh_current_category.clone();
+            let cat_names_ref = category_names.clone();
+            let category_row_ref = category_row.clone();
+            action_move_palette.connect_activate(move |_, _| {
+                let Some(path) = selected() else {
+                    show_toast(&window_ref, "Select a palette first");
+                    return;
+                };
+                let current_category = cat_names_ref
+                    .borrow()
+                    .get(category_row_ref.selected() as usize)
+                    .cloned()
+                    .unwrap_or_default();
+
+                let window_clone = window_ref.clone();
+                let refresh = refresh.clone();
+                prompt_text(
+                    &window_ref,
+                    "Move to Category",
+                    &current_category,
+                    "Move",
+                    move |category| match palette::move_palette_to_category(&path, &category) {
+                        Ok(_) => {
+                            if let Some(ref cb) = *refresh.borrow() {
+                                cb();
+                            }
+                            show_toast(&window_clone, &format!("Moved to {}", category));
+                        }
+                        Err(e) => show_toast(&window_clone, &format!("Failed to move: {}", e)),
+                    },
+                );
+            });
+        }
+        window.add_action(&action_move_palette);
+
+        // --- Compare the swatches with the selected saved palette ---
+        let action_compare_palette = gio::SimpleAction::new("compare-palette", None);
+        {
+            let window_ref = window.clone();
+            let selected = selected_palette_path.clone();
+            let color_btns = color_buttons.clone();
+            action_compare_palette.connect_activate(move |_, _| {
+                let Some(path) = selected() else {
+                    show_toast(&window_ref, "Select a palette first");
+                    return;
+                };
+                match palette::cached_palette_colors(&path) {
+                    Ok(saved) => {
+                        let name = path
+                            .file_stem()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or_default();
+                        let diff = palette::diff_palettes(&saved, &button_colors(&color_btns));
+                        show_palette_diff(&window_ref, name, &diff);
+                    }
+                    Err(e) => show_toast(&window_ref, &format!("Failed to compare: {}", e)),
+                }
+            });
+        }
+        window.add_action(&action_compare_palette);
+
+        {
+            let window_ref = window.clone();
+            let selected = selected_palette_path.clone();
+            let refresh = refresh_current_category.clone();
+            action_toggle_favorite.connect_activate(move |action, _| {
+                let Some(path) = selected() else {
+                    show_toast(&window_ref, "Select a palette first");
+                    return;
+                };
+                match palette::toggle_favorite(&path) {
+                    Ok(starred) => {
+                        action.set_state(&starred.to_variant());
+                        if let Some(ref cb) = *refresh.borrow() {
+                            cb();
+                        }
+                        let message = if starred {
+                            "Added to Favorites"
+                        } else {
+                            "Removed from Favorites"
+                        };
+                        show_toast(&window_ref, message);
+                    }
+                    Err(e) => show_toast(&window_ref, &e),
+                }
+            });
+        }
+        window.add_action(&action_toggle_favorite);
+
+        // delete_palette_image refuses bundled palettes; the action is
+        // disabled while any selected palette is bundled. Several selected
+        // palettes are deleted together after one confirmation.
+        let action_delete_palette = gio::SimpleAction::new("delete-palette", None);
+        {
+            let window_ref = window.clone();
+            let flowbox = palette_flowbox.clone();
+            let paths = palette_paths.clone();
+            let refresh = refresh_current_category.clone();
+            action_delete_palette.connect_activate(move |_, _| {
+                let selected: Vec<PathBuf> = flowbox
+                    .selected_children()
+                    .iter()
+                    .filter_map(|child| paths.borrow().get(child.index() as usize).cloned())
+                    .collect();
+                let refresh_view = {
+                    let refresh = refresh.clone();
+                    move || {
+                        if let Some(ref cb) = *refresh.borrow() {
+                            cb();
+                        }
+                    }
+                };
+                match selected.as_slice() {
+                    [] => show_toast(&window_ref, "Select a palette first"),
+                    [path] => match palette::delete_palette_image(path) {
+                        Ok(()) => {
+                            refresh_view();
+                            show_toast(&window_ref, "Palette deleted");
+                        }
+                        Err(e) => show_toast(&window_ref, &format!("Failed to delete: {}", e)),
+                    },
+                    _ => confirm_delete_palettes(&window_ref, selected, refresh_view),
+                }
+            });
+        }
+        {
+            let action = action_delete_palette.clone();
+            let paths = palette_paths.clone();
+            palette_flowbox.connect_selected_children_changed(move |flowbox| {
+                let all_user = flowbox.selected_children().iter().all(|child| {
+                    paths
+                        .borrow()
+                        .get(child.index() as usize)
+                        .is_some_and(|path| palette::is_user_palette(path))
+                });
+                action.set_enabled(all_user);
+            });
+        }
+        window.add_action(&action_delete_palette);
+
+        // --- Category folder management (category row context menu) ---
+        // After a change, categories are reloaded and the affected one selected.
+        let select_category: Rc<dyn Fn(&str)> = {
+            let refresh_then = refresh_then.clone();
+            let cat_names = category_names.clone();
+            let category_row = category_row.clone();
+            Rc::new(move |name| {
+                let name = name.to_string();
+                let cat_names = cat_names.clone();
+                let category_row = category_row.clone();
+                refresh_then(Box::new(move || {
+                    let idx = cat_names.borrow().iter().position(|n| *n == name);
+                    if let Some(idx) = idx {
+                        category_row.set_selected(idx as u32);
+                    }
+                }));
+            })
+        };
+        let current_category: Rc<dyn Fn() -> Option<String>> = {
+            let cat_names = category_names.clone();
+            let category_row = category_row.clone();
+            Rc::new(move || {
+                cat_names
+                    .borrow()
+                    .get(category_row.selected() as usize)
+                    .cloned()
+            })
+        };
+
+        let action_new_category = gio::SimpleAction::new("new-category", None);
+        {
+            let window_ref = window.clone();
+            let select_category = select_category.clone();
+            action_new_category.connect_activate(move |_, _| {
+                let window_clone = window_ref.clone();
+                let select_category = select_category.clone();
+                prompt_text(&window_ref, "New Category", "", "Create", move |name| {
+                    match palette::create_category(&name) {
+                        Ok(name) => select_category(&name),
+                        Err(e) => show_toast(&window_clone, &format!("Failed to create: {}", e)),
+                    }
+                });
+            });
+        }
+        window.add_action(&action_new_category);
+
+        let action_rename_category = gio::SimpleAction::new("rename-category", None);
+        {
+            let window_ref = window.clone();
+            let select_category = select_category.clone();
+            let current_category = current_category.clone();
+            action_rename_category.connect_activate(move |_, _| {
+                let Some(old) = current_category() else {
+                    return;
+                };
+                let window_clone = window_ref.clone();
+                let select_category = select_category.clone();
+                let old_name = old.clone();
+                prompt_text(&window_ref, "Rename Category", &old, "Rename", move |new| {
+                    match palette::rename_category(&old_name, &new) {
+                        Ok(name) => select_category(&name),
+                        Err(e) => show_toast(&window_clone, &format!("Failed to rename: {}", e)),
+                    }
+                });
+            });
+        }
+        window.add_action(&action_rename_category);
+
+        // Non-empty categories need confirmation before their palettes go too
+        let action_delete_category = gio::SimpleAction::new("delete-category", None);
+        {
+            let window_ref = window.clone();
+            let refresh = refresh_current_category.clone();
+            let current_category = current_category.clone();
+            let all_cats = all_categories.clone();
+            action_delete_category.connect_activate(move |_, _| {
+                let Some(name) = current_category() else {
+                    return;
+                };
+                let count = all_cats
+                    .borrow()
+                    .get(&name)
+                    .map_or(0, |images| images.len());
+                let refresh_view = {
+                    let refresh = refresh.clone();
+                    move || {
+                        if let Some(ref cb) = *refresh.borrow() {
+                            cb();
+                        }
+                    }
+                };
+                if count == 0 {
+                    match palette::delete_category(&name, false) {
+                        Ok(()) => refresh_view(),
+                        Err(e) => show_toast(&window_ref, &format!("Failed to delete: {}", e)),
+                    }
+                    return;
+                }
+
+                let dialog = adw::MessageDialog::new(
+                    Some(&window_ref),
+                    Some(&format!("Delete “{}”?", name)),
+                    Some(&format!("Its {} palette(s) will be deleted too.", count)),
+                );
+                dialog.add_response("cancel", "Cancel");
+                dialog.add_response("delete", "Delete");
+                dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+                dialog.set_close_response("cancel");
+                let window_clone = window_ref.clone();
+                dialog.connect_response(None, move |_, response| {
+                    if response != "delete" {
+                        return;
+                    }
+                    match palette::delete_category(&name, true) {
+                        Ok(()) => refresh_view(),
+                        Err(e) => show_toast(&window_clone, &format!("Failed to delete: {}", e)),
+                    }
+                });
+                dialog.present();
+            });
+        }
+        window.add_action(&action_delete_category);
+
+        // --- Cap the preview's animation frame rate ---
+        let action_preview_fps = gio::SimpleAction::new_stateful(
+            "preview-fps",
+            Some(glib::VariantTy::STRING),
+            &frame_limiter.fps().to_string().to_variant(),
+        );
+        {
+            let limiter = frame_limiter.clone();
+            action_preview_fps.connect_activate(move |action, param| {
+                let Some(fps) = param.and_then(|p| p.str()).and_then(|s| s.parse().ok()) else {
+                    return;
+                };
+                limiter.set_fps(fps);
+                action.set_state(&fps.to_string().to_variant());
+            });
+        }
+        window.add_action(&action_preview_fps);
+
+        // --- How "Set as Wallpaper" fits the image to the screen ---
+        let action_wallpaper_mode = gio::SimpleAction::new_stateful(
+            "wallpaper-mode",
+            Some(glib::VariantTy::STRING),
+            &wallpaper_mode.get().name().to_variant(),
+        );
+        {
+            let wallpaper_mode = wallpaper_mode.clone();
+            action_wallpaper_mode.connect_activate(move |action, param| {
+                let Some(mode) = param
+                    .and_then(|p| p.str())
+                    .and_then(|s| wallpaper::WallpaperMode::parse(s).ok())
+                else {
+                    return;
+                };
+                wallpaper_mode.set(mode);
+                action.set_state(&mode.name().to_variant());
+            });
+        }
+        window.add_action(&action_wallpaper_mode);
+
+        // --- Whether "Set as Wallpaper" sets the desktop, lock screen or both ---
+        let action_wallpaper_target = gio::SimpleAction::new_stateful(
+            "wallpaper-target",
+            Some(glib::VariantTy::STRING),
+            &wallpaper_target.get().name().to_variant(),
+        );
+        {
+            let wallpaper_target = wallpaper_target.clone();
+            action_wallpaper_target.connect_activate(move |action, param| {
+                let Some(target) = param
+                    .and_then(|p| p.str())
+                    .and_then(wallpaper::WallpaperTarget::parse)
+                else {
+                    return;
+                };
+                wallpaper_target.set(target);
+                action.set_state(&target.name().to_variant());
+            });
+        }
+        window.add_action(&action_wallpaper_target);
+
+        // --- Which backend sets the wallpaper; "auto" detects it ---
+        let action_wallpaper_backend = gio::SimpleAction::new_stateful(
+            "wallpaper-backend",
+            Some(glib::VariantTy::STRING),
+            &wallpaper_backend
+                .get()
+                .map_or("auto", |b| b.name())
+                .to_variant(),
+        );
+        {
+            let wallpaper_backend = wallpaper_backend.clone();
+            let backend_label = backend_label.clone();
+            action_wallpaper_backend.connect_activate(move |action, param| {
+                let Some(name) = param.and_then(|p| p.str()) else {
+                    return;
+                };
+                let backend = wallpaper::WallpaperBackend::parse(name);
+                if backend.is_none() && name != "auto" {
+                    return;
+                }
+                wallpaper_backend.set(backend);
+                wallpaper::set_preferred_backend(backend);
+                action.set_state(&name.to_variant());
+                backend_label.set_text(&backend_label_text());
+            });
+        }
+        window.add_action(&action_wallpaper_backend);
+
+        // --- GPU to render on; applied at the next launch ---
+        let action_gpu_preference = gio::SimpleAction::new_stateful(
+            "gpu-preference",
+            Some(glib::VariantTy::STRING),
+            &gpu_preference.get().name().to_variant(),
+        );
+        {
+            let gpu_preference = gpu_preference.clone();
+            let window_ref = window.clone();
+            action_gpu_preference.connect_activate(move |action, param| {
+                let Some(pref) = param
+                    .and_then(|p| p.str())
+                    .and_then(gl_renderer::GpuPreference::parse)
+                else {
+                    return;
+                };
+                if pref != gpu_preference.get() {
+                    gpu_preference.set(pref);
+                    action.set_state(&pref.name().to_variant());
+                    show_toast(&window_ref, "Restart Wallrus to switch GPUs");
+                }
+            });
+        }
+        window.add_action(&action_gpu_preference);
+
+        // --- About dialog action ---
+        // The debug info names the GPU in use, for diagnosing hybrid graphics,
+        // along with the rest of `diagnostics::collect_diagnostics`
+        let action_about = gio::SimpleAction::new("show-about", None);
+        {
+            let window_ref = window.clone();
+            let state = state.clone();
+            let gpu_preference = gpu_preference.clone();
+            action_about.connect_activate(move |_, _| {
+                let diagnostics = diagnostics::collect_diagnostics(state.borrow().as_ref());
+                let debug_info = format!(
+                    "{}GPU preference: {}\n",
+                    diagnostics,
+                    gpu_preference.get().label(),
+                );
+                let about = adw::AboutWindow::builder()
+                    .application_name("Wallrus")
+                    .application_icon("io.github.megakode.Wallrus")
+                    .developer_name("Peter Boné")
+                    .version(env!("CARGO_PKG_VERSION"))
+                    .website("https://github.com/megakode/wallrus")
+                    .issue_url("https://github.com/megakode/wallrus/issues")
+                    .license_type(gtk4::License::Gpl30)
+                    .copyright("© 2026 Peter Boné")
+                    .developers(vec!["Peter Boné"])
+                    .debug_info(debug_info)
+                    .debug_info_filename("wallrus-diagnostics.txt")
+                    .transient_for(&window_ref)
+                    .modal(true)
+                    .build();
+                about.present();
+            });
+        }
+        window.add_action(&action_about);
+
+        window
+    }
+}
+
+/// Show a new palette in the color buttons, leaving locked slots unchanged.
+/// The buttons' notify handlers propagate the new colors to the renderer.
+fn set_button_colors(
+    buttons: &[gtk4::ColorDialogButton],
+    locks: &[gtk4::ToggleButton],
+    colors: &[[f32; 3]; 4],
+) {
+    let mut locked = [false; 4];
+    for (slot, lock) in locked.iter_mut().zip(locks) {
+        *slot = lock.is_active();
+    }
+    let mut slots = palette::PaletteSlots::new(button_colors(buttons), locked);
+    slots.regenerate(colors);
+
+    for (btn, c) in buttons.iter().zip(&slots.colors) {
+        btn.set_rgba(&gdk::RGBA::new(c[0], c[1], c[2], 1.0));
+    }
+}
+
+/// A slider row for a preset parameter, stepping by `step` and showing
+/// `digits` decimals, and the adjustment holding its value
+fn param_slider_row(
+    param: &shader_presets::PresetParam,
+    step: f64,
+    digits: i32,
+) -> (adw::ActionRow, gtk4::Adjustment) {
+    let scale = gtk4::Scale::with_range(gtk4::Orientation::Horizontal, param.min, param.max, step);
+    scale.set_value(param.default);
+    scale.set_digits(digits);
+    scale.set_round_digits(digits);
+    scale.set_hexpand(true);
+    scale.set_draw_value(true);
+    scale.set_value_pos(gtk4::PositionType::Right);
+
+    let row = adw::ActionRow::builder().title(param.label).build();
+    row.add_suffix(&scale);
+    (row, scale.adjustment())
+}
+
+/// A switch row for an on/off preset parameter, with an adjustment kept at
+/// 0 or 1 in step with the switch, so looks and recipes treat it like a
+/// slider
+fn param_switch_row(param: &shader_presets::PresetParam) -> (adw::ActionRow, gtk4::Adjustment) {
+    let adjustment = gtk4::Adjustment::new(param.default, 0.0, 1.0, 1.0, 1.0, 0.0);
+    let row = adw::SwitchRow::builder()
+        .title(param.label)
+        .active(param.default >= 0.5)
+        .build();
+    {
+        let adjustment = adjustment.clone();
+        row.connect_active_notify(move |row| {
+            adjustment.set_value(if row.is_active() { 1.0 } else { 0.0 });
+        });
+    }
+    {
+        let row = row.downgrade();
+        adjustment.connect_value_changed(move |adjustment| {
+            if let Some(row) = row.upgrade() {
+                row.set_active(adjustment.value() >= 0.5);
+            }
+        });
+    }
+    (row.upcast(), adjustment)
+}
+/// The "Look" dropdown for `preset`: "Default" resets the parameter sliders,
+/// every saved look sets them to its values, and the save button stores the
+/// current values as a named look.
+fn build_look_row(
+    window: &adw::ApplicationWindow,
+    preset: &str,
+    param_scales: &ParamSliders,
+) -> adw::ComboRow {
+    let looks = Rc::new(RefCell::new(looks::list_looks(preset)));
+    let model = gtk4::StringList::new(&["Default"]);
+    for look in looks.borrow().iter() {
+        model.append(&look.name);
+    }
+    let row = adw::ComboRow::new();
+    row.set_title("Look");
+    row.set_model(Some(&model));
+
+    {
+        let looks = looks.clone();
+        let param_scales = param_scales.clone();
+        let preset = preset.to_string();
+        row.connect_selected_notify(move |row| {
+            let scales = param_scales.borrow();
+            match row.selected() {
+                0 => {
+                    for param in shader_presets::params_for(&preset) {
+                        if let Some((_, _, adjustment)) =
+                            scales.iter().find(|(n, _, _)| *n == param.name)
+                        {
+                            adjustment.set_value(param.default);
+                        }
+                    }
+                }
+                i => {
+                    let looks = looks.borrow();
+                    let Some(look) = looks.get(i as usize - 1) else {
+                        return;
+                    };
+                    for (uniform, value) in &look.params {
+                        if let Some((_, _, adjustment)) =
+                            scales.iter().find(|(n, _, _)| n == uniform)
+                        {
+                            adjustment.set_value(*value as f64);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    let save_button = gtk4::Button::from_icon_name("document-save-symbolic");
+    save_button.add_css_class("flat");
+    save_button.set_valign(gtk4::Align::Center);
+    save_button.set_tooltip_text(Some("Save as look"));
+    {
+        let window = window.clone();
+        let param_scales = param_scales.clone();
+        let preset = preset.to_string();
+        let row = row.clone();
+        save_button.connect_clicked(move |_| {
+            let params: Vec<(String, f32)> = param_scales
+                .borrow()
+                .iter()
+                .map(|(name, _, adjustment)| (name.to_string(), adjustment.value() as f32))
+                .collect();
+            let window_ref = window.clone();
+            let preset = preset.clone();
+            let looks = looks.clone();
+            let model = model.clone();
+            let row = row.clone();
+            prompt_text(&window, "Save Look", "", "Save", move |name| {
+                if let Err(e) = looks::save_look(&preset, &name, &params) {
+                    show_toast(&window_ref, &e);
+                    return;
+                }
+                *looks.borrow_mut() = looks::list_looks(&preset);
+                let names: Vec<String> = looks.borrow().iter().map(|l| l.name.clone()).collect();
+                let refs: Vec<&str> = names.iter().map(|s| s.as_str()).collect();
+                model.splice(1, model.n_items() - 1, &refs);
+                if let Some(i) = names.iter().position(|n| *n == name.trim()) {
+                    row.set_selected(i as u32 + 1);
+                }
+                show_toast(&window_ref, &format!("Saved look “{}”", name.trim()));
+            });
+        });
+    }
+    row.add_suffix(&save_button);
+    row
+}
+
+/// Show the pairwise contrast ratios of `colors` as a grid with swatch
+/// headers, flagging pairs below WCAG AA for normal text.
+fn show_contrast_report(window: &adw::ApplicationWindow, colors: &[[f32; 3]; 4]) {
+    let matrix = palette::palette_contrast_matrix(colors);
+    let grid = gtk4::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_halign(gtk4::Align::Center);
+
+    let swatch = |color: [f32; 3]| {
+        let picture = gtk4::Picture::for_paintable(&swatch_texture(&[color; 4], 24));
+        picture.set_size_request(24, 24);
+        picture.set_tooltip_text(Some(&palette::rgb_to_hex(color)));
+        picture
+    };
+    for (i, color) in colors.iter().enumerate() {
+        grid.attach(&swatch(*color), i as i32 + 1, 0, 1, 1);
+        grid.attach(&swatch(*color), 0, i as i32 + 1, 1, 1);
+    }
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, ratio) in row.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            let label = gtk4::Label::new(Some(&format!("{:.1}:1", ratio)));
+            label.add_css_class("numeric");
+            if *ratio < palette::WCAG_AA_CONTRAST {
+                label.add_css_class("error");
+            }
+            grid.attach(&label, j as i32 + 1, i as i32 + 1, 1, 1);
+        }
+    }
+
+    let failing = (0..4)
+        .flat_map(|i| (i + 1..4).map(move |j| (i, j)))
+        .filter(|&(i, j)| matrix[i][j] < palette::WCAG_AA_CONTRAST)
+        .count();
+    let body = if failing == 0 {
+        "Every color pair meets the 4.5:1 WCAG AA ratio for text.".to_string()
+    } else {
+        format!(
+            "{} of 6 color pairs are below the 4.5:1 WCAG AA ratio for text.",
+            failing
+        )
+    };
+
+    let dialog = adw::MessageDialog::new(Some(window), Some("Contrast Report"), Some(&body));
+    dialog.set_extra_child(Some(&grid));
+    dialog.add_response("close", "Close");
+    dialog.set_close_response("close");
+    dialog.present();
+}
+
+/// Show a saved palette (A) above the current swatches (B), with the ΔE
+/// of each slot and the changed ones highlighted.
+fn show_palette_diff(window: &adw::ApplicationWindow, name: &str, diff: &[palette::ColorDelta]) {
+    let grid = gtk4::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    grid.set_halign(gtk4::Align::Center);
+
+    let swatch = |color: [f32; 3]| {
+        let picture = gtk4::Picture::for_paintable(&swatch_texture(&[color; 4], 32));
+        picture.set_size_request(32, 32);
+        picture.set_tooltip_text(Some(&palette::rgb_to_hex(color)));
+        picture
+    };
+    for (row, label) in ["A", "B"].into_iter().enumerate() {
+        let label = gtk4::Label::new(Some(label));
+        label.add_css_class("dim-label");
+        grid.attach(&label, 0, row as i32, 1, 1);
+    }
+    for (i, delta) in diff.iter().enumerate() {
+        let col = i as i32 + 1;
+        grid.attach(&swatch(delta.a), col, 0, 1, 1);
+        grid.attach(&swatch(delta.b), col, 1, 1, 1);
+        let label = if delta.identical {
+            let label = gtk4::Label::new(Some("same"));
+            label.add_css_class("dim-label");
+            label
+        } else {
+            let label = gtk4::Label::new(Some(&format!("ΔE {:.1}", delta.delta_e)));
+            label.add_css_class("accent");
+            label.set_tooltip_text(Some(&format!(
+                "RGB {:+.0} {:+.0} {:+.0}",
+                delta.rgb[0] * 255.0,
+                delta.rgb[1] * 255.0,
+                delta.rgb[2] * 255.0
+            )));
+            label
+        };
+        label.add_css_class("numeric");
+        label.add_css_class("caption");
+        grid.attach(&label, col, 2, 1, 1);
+    }
+
+    let changed = diff.iter().filter(|d| !d.identical).count();
+    let body = match changed {
+        0 => format!("The swatches match \"{}\".", name),
+        n => format!(
+            "{} of {} colors differ from \"{}\" (A).",
+            n,
+            diff.len(),
+            name
+        ),
+    };
+
+    let dialog = adw::MessageDialog::new(Some(window), Some("Compare Palettes"), Some(&body));
+    dialog.set_extra_child(Some(&grid));
+    dialog.add_response("close", "Close");
+    dialog.set_close_response("close");
+    dialog.present();
+}
+
+/// List the files of an exported resolution pack, relative to `out_dir`.
+fn show_pack_manifest(
+    window: &adw::ApplicationWindow,
+    out_dir: &std::path::Path,
+    paths: &[PathBuf],
+) {
+    let list = paths
+        .iter()
+        .map(|p| p.strip_prefix(out_dir).unwrap_or(p).display().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body = format!(
+        "Wrote {} files to {}:\n\n{}",
+        paths.len(),
+        out_dir.display(),
+        list
+    );
+
+    let dialog = adw::MessageDialog::new(Some(window), Some("Wallpapers Exported"), Some(&body));
+    dialog.add_response("close", "Close");
+    dialog.set_close_response("close");
+    dialog.present();
+}
+
+/// Rearrange the swatches so slot `i` shows what slot `order[i]` did,
+/// moving lock states along with their colors.
+fn reorder_slots(
+    buttons: &[gtk4::ColorDialogButton],
+    locks: &[gtk4::ToggleButton],
+    order: [usize; 4],
+) {
+    let locked = [0, 1, 2, 3].map(|i| locks[i].is_active());
+    let (Ok(colors), Ok(locked)) = (
+        palette::reorder_colors(&button_colors(buttons), order),
+        palette::reorder_colors(&locked, order),
+    ) else {
+        return;
+    };
+    for ((btn, lock), (c, is_locked)) in buttons.iter().zip(locks).zip(colors.iter().zip(locked)) {
+        btn.set_rgba(&gdk::RGBA::new(c[0], c[1], c[2], 1.0));
+        lock.set_active(is_locked);
+    }
+}
+
+/// The swatch colors and their locks, as recorded in the palette history
+fn palette_slots(
+    buttons: &[gtk4::ColorDialogButton],
+    locks: &[gtk4::ToggleButton],
+) -> palette::PaletteSlots {
+    palette::PaletteSlots::new(
+        button_colors(buttons),
+        [0, 1, 2, 3].map(|i| locks[i].is_active()),
+    )
+}
+
+/// Undo/redo stacks of palette snapshots. The last undo entry is the
+/// current state, so undoing needs at least two.
+#[derive(Default)]
+struct PaletteHistory {
+    undo: Vec<palette::PaletteSlots>,
+    redo: Vec<palette::PaletteSlots>,
+}
+
+impl PaletteHistory {
+    /// Record `slots` as the new current state. Repeats of the current
+    /// state are ignored; anything else clears the redo stack.
+    fn record(&mut self, slots: palette::PaletteSlots) {
+        if self.undo.last() == Some(&slots) {
+            return;
+        }
+        self.undo.push(slots);
+        if self.undo.len() > PALETTE_HISTORY_DEPTH {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Step back, returning the state to restore
+    fn undo(&mut self) -> Option<palette::PaletteSlots> {
+        if self.undo.len() < 2 {
+            return None;
+        }
+        self.redo.extend(self.undo.pop());
+        self.undo.last().copied()
+    }
+
+    /// Step forward again after an undo
+    fn redo(&mut self) -> Option<palette::PaletteSlots> {
+        let slots = self.redo.pop()?;
+        self.undo.push(slots);
+        Some(slots)
+    }
+}
+
+/// Size of the transparency checkerboard's squares, in pixels
+const CHECKERBOARD_CELL: i32 = 8;
+
+/// Draw the two-tone checkerboard shown behind transparent previews.
+fn draw_checkerboard(_: &gtk4::DrawingArea, cr: &gtk4::cairo::Context, width: i32, height: i32) {
+    cr.set_source_rgb(0.8, 0.8, 0.8);
+    let _ = cr.paint();
+    cr.set_source_rgb(0.6, 0.6, 0.6);
+    for row in 0..(height + CHECKERBOARD_CELL - 1) / CHECKERBOARD_CELL {
+        for col in (row % 2..(width + CHECKERBOARD_CELL - 1) / CHECKERBOARD_CELL).step_by(2) {
+            cr.rectangle(
+                (col * CHECKERBOARD_CELL) as f64,
+                (row * CHECKERBOARD_CELL) as f64,
+                CHECKERBOARD_CELL as f64,
+                CHECKERBOARD_CELL as f64,
+            );
+        }
+    }
+    let _ = cr.fill();
+}
+
+/// Read the current palette from the color picker buttons.
+fn button_colors(buttons: &[gtk4::ColorDialogButton]) -> [[f32; 3]; 4] {
+    let mut colors = [[0.0f32; 3]; 4];
+    for (color, btn) in colors.iter_mut().zip(buttons) {
+        *color = rgba_color(&btn.rgba());
+    }
+    colors
+}
+
+/// The RGB part of a GDK color.
+fn rgba_color(rgba: &gdk::RGBA) -> [f32; 3] {
+    [rgba.red(), rgba.green(), rgba.blue()]
+}
+
+/// Ask the user for a single line of text in a modal dialog.
+/// `on_accept` receives the trimmed text when the user confirms a non-empty value.
+fn prompt_text(
+    window: &adw::ApplicationWindow,
+    heading: &str,
+    initial: &str,
+    accept_label: &str,
+    on_accept: impl Fn(String) + 'static,
+) {
+    let entry = gtk4::Entry::new();
+    entry.set_text(initial);
+    entry.set_activates_default(true);
+
+    let dialog = adw::MessageDialog::new(Some(window), Some(heading), None);
+    dialog.set_extra_child(Some(&entry));
+    dialog.add_response("cancel", "Cancel");
+    dialog.add_response("accept", accept_label);
+    dialog.set_response_appearance("accept", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("accept"));
+    dialog.set_close_response("cancel");
+
+    dialog.connect_response(None, move |_, response| {
+        if response == "accept" {
+            let text = entry.text().trim().to_string();
+            if !text.is_empty() {
+                on_accept(text);
+            }
+        }
+    });
+    dialog.present();
+}
+
+/// Ask the user to pick `DEFAULT_COLOR_COUNT` of `colors`, starting from
+/// `preselected`. `on_accept` gets the chosen indices in order.
+fn prompt_pick_colors(
+    window: &adw::ApplicationWindow,
+    heading: &str,
+    colors: &[[f32; 3]],
+    preselected: &[usize],
+    on_accept: impl Fn(Vec<usize>) + 'static,
+) {
+    let grid = gtk4::FlowBox::new();
+    grid.set_selection_mode(gtk4::SelectionMode::None);
+    grid.set_homogeneous(true);
+    grid.set_min_children_per_line(8);
+    grid.set_max_children_per_line(8);
+    grid.set_row_spacing(4);
+    grid.set_column_spacing(4);
+
+    let toggles: Vec<gtk4::ToggleButton> = colors
+        .iter()
+        .enumerate()
+        .map(|(i, color)| {
+            let swatch = gtk4::Picture::for_paintable(&swatch_texture(&[*color; 4], 32));
+            swatch.set_size_request(32, 32);
+            let toggle = gtk4::ToggleButton::new();
+            toggle.set_child(Some(&swatch));
+            toggle.set_tooltip_text(Some(&palette::rgb_to_hex(*color)));
+            toggle.set_active(preselected.contains(&i));
+            grid.insert(&toggle, -1);
+            toggle
+        })
+        .collect();
+
+    let dialog = adw::MessageDialog::new(
+        Some(window),
+        Some(heading),
+        Some("The most distinct colors are selected by default."),
+    );
+    dialog.set_extra_child(Some(&grid));
+    dialog.add_response("cancel", "Cancel");
+    dialog.add_response("accept", "Save Palette");
+    dialog.set_response_appearance("accept", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("accept"));
+    dialog.set_close_response("cancel");
+
+    // Only allow saving with exactly four colors picked
+    let update_accept = {
+        let dialog = dialog.clone();
+        let toggles = toggles.clone();
+        move || {
+            let count = toggles.iter().filter(|t| t.is_active()).count();
+            dialog.set_response_enabled("accept", count == palette::DEFAULT_COLOR_COUNT);
+        }
+    };
+    update_accept();
+    for toggle in &toggles {
+        let update_accept = update_accept.clone();
+        toggle.connect_toggled(move |_| update_accept());
+    }
+
+    dialog.connect_response(None, move |_, response| {
+        if response == "accept" {
+            let picked = (0..toggles.len())
+                .filter(|&i| toggles[i].is_active())
+                .collect();
+            on_accept(picked);
+        }
+    });
+    dialog.present();
+}
+
+/// Columns and rows a grid palette can be imported with
+const GRID_SIZE_RANGE: (f64, f64) = (1.0, 16.0);
+
+/// Ask how many columns and rows of swatches a palette grid has
+fn prompt_grid_size(window: &adw::ApplicationWindow, on_accept: impl Fn(usize, usize) + 'static) {
+    let cols_row = adw::SpinRow::with_range(GRID_SIZE_RANGE.0, GRID_SIZE_RANGE.1, 1.0);
+    cols_row.set_title("Columns");
+    cols_row.set_value(4.0);
+    let rows_row = adw::SpinRow::with_range(GRID_SIZE_RANGE.0, GRID_SIZE_RANGE.1, 1.0);
+    rows_row.set_title("Rows");
+    rows_row.set_value(4.0);
+    let list = gtk4::ListBox::new();
+    list.add_css_class("boxed-list");
+    list.set_selection_mode(gtk4::SelectionMode::None);
+    list.append(&cols_row);
+    list.append(&rows_row);
+
+    let dialog = adw::MessageDialog::new(
+        Some(window),
+        Some("Grid Size"),
+        Some("The center of each swatch is sampled, left to right and top to bottom."),
+    );
+    dialog.set_extra_child(Some(&list));
+    dialog.add_response("cancel", "Cancel");
+    dialog.add_response("accept", "Import");
+    dialog.set_response_appearance("accept", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("accept"));
+    dialog.set_close_response("cancel");
+    dialog.connect_response(None, move |_, response| {
+        if response == "accept" {
+            on_accept(cols_row.value() as usize, rows_row.value() as usize);
+        }
+    });
+    dialog.present();
+}
+
+/// Ask how many colors (2–8) to pull from the photo at `path` with k-means,
+/// previewing the result as the slider moves. Dropping another image onto
+/// the dialog extracts from that one instead. `on_accept` gets the colors
+/// shown when "Save Palette" is pressed, and the image they came from when
+/// its thumbnail should be kept with the palette.
+fn prompt_extract_colors(
+    window: &adw::ApplicationWindow,
+    path: PathBuf,
+    on_accept: impl Fn(Vec<[f32; 3]>, Option<PathBuf>) + 'static,
+) {
+    let count_scale = gtk4::Scale::with_range(
+        gtk4::Orientation::Horizontal,
+        palette::MIN_EXTRACT_COUNT as f64,
+        palette::MAX_EXTRACT_COUNT as f64,
+        1.0,
+    );
+    count_scale.set_value(palette::DEFAULT_COLOR_COUNT as f64);
+    count_scale.set_digits(0);
+    count_scale.set_draw_value(true);
+    count_scale.set_round_digits(0);
+    for count in palette::MIN_EXTRACT_COUNT..=palette::MAX_EXTRACT_COUNT {
+        count_scale.add_mark(count as f64, gtk4::PositionType::Bottom, None);
+    }
+
+    let swatches = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    swatches.set_halign(gtk4::Align::Center);
+    swatches.set_size_request(-1, 48);
+    let status = gtk4::Label::new(None);
+    status.add_css_class("dim-label");
+    status.set_wrap(true);
+    status.set_visible(false);
+    let keep_source = gtk4::CheckButton::with_label("Keep a thumbnail of the photo");
+    keep_source.set_active(true);
+
+    let content = gtk4::Box::new(gtk4::Orientation::Vertical, 12);
+    content.append(&swatches);
+    content.append(&status);
+    content.append(&count_scale);
+    content.append(&keep_source);
+
+    let dialog = adw::MessageDialog::new(
+        Some(window),
+        Some("Extract Palette from Photo"),
+        Some("Choose how many colors to pull from the photo, or drop another image here."),
+    );
+    dialog.set_extra_child(Some(&content));
+    dialog.add_response("cancel", "Cancel");
+    dialog.add_response("accept", "Save Palette");
+    dialog.set_response_appearance("accept", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("accept"));
+    dialog.set_close_response("cancel");
+
+    let source = Rc::new(RefCell::new(path));
+    let extracted: Rc<RefCell<Vec<[f32; 3]>>> = Rc::new(RefCell::new(Vec::new()));
+    // Held by handlers on the dialog's own widgets, so it only keeps weak
+    // references to them; strong ones would keep every dialog alive
+    let extract = {
+        let dialog = dialog.downgrade();
+        let count_scale = count_scale.downgrade();
+        let swatches = swatches.downgrade();
+        let status = status.downgrade();
+        let source = source.clone();
+        let extracted = extracted.clone();
+        move || {
+            let (Some(dialog), Some(count_scale), Some(swatches), Some(status)) = (
+                dialog.upgrade(),
+                count_scale.upgrade(),
+                swatches.upgrade(),
+                status.upgrade(),
+            ) else {
+                return;
+            };
+            let count = count_scale.value().round() as usize;
+            while let Some(child) = swatches.first_child() {
+                swatches.remove(&child);
+            }
+            match palette::extract_palette_kmeans(&source.borrow(), count) {
+                Ok(colors) => {
+                    for color in &colors {
+                        let texture = swatch_texture(&[*color; 4], 48);
+                        let picture = gtk4::Picture::for_paintable(&texture);
+                        picture.set_size_request(48, 48);
+                        picture.set_tooltip_text(Some(&palette::rgb_to_hex(*color)));
+                        swatches.append(&picture);
+                    }
+                    // Photos with few distinct colors give fewer than asked for
+                    status.set_label(&format!("Only {} distinct colors found", colors.len()));
+                    status.set_visible(colors.len() < count);
+                    dialog.set_response_enabled("accept", true);
+                    *extracted.borrow_mut() = colors;
+                }
+                Err(e) => {
+                    status.set_label(&e);
+                    status.set_visible(true);
+                    dialog.set_response_enabled("accept", false);
+                    extracted.borrow_mut().clear();
+                }
+            }
+        }
+    };
+    extract();
+
+    // k-means runs on each settled slider position, not every step
+    {
+        let pending: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+        let extract = extract.clone();
+        count_scale.connect_value_changed(move |_| {
+            if let Some(id) = pending.borrow_mut().take() {
+                id.remove();
+            }
+            let pending_ref = pending.clone();
+            let extract = extract.clone();
+            let id = glib::timeout_add_local_once(EXTRACT_PREVIEW_SETTLE, move || {
+                pending_ref.borrow_mut().take();
+                extract();
+            });
+            *pending.borrow_mut() = Some(id);
+        });
+    }
+
+    let drop_target = gtk4::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+    let dropped_source = source.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        let Some(path) = value
+            .get::<gdk::FileList>()
+            .ok()
+            .and_then(|files| files.files().first().and_then(|f| f.path()))
+        else {
+            return false;
+        };
+        if image::ImageFormat::from_path(&path).is_err() {
+            return false;
+        }
+        *dropped_source.borrow_mut() = path;
+        extract();
+        true
+    });
+    content.add_controller(drop_target);
+
+    dialog.connect_response(None, move |_, response| {
+        let colors = extracted.borrow().clone();
+        if response == "accept" && !colors.is_empty() {
+            let source = keep_source.is_active().then(|| source.borrow().clone());
+            on_accept(colors, source);
+        }
+    });
+    dialog.present();
+}
+
+/// Run a long export behind a modal progress dialog with a Cancel button.
+///
+/// The renderer is taken out of `state` for the duration: pending events
+/// are handled between items so the dialog stays live, and handlers that
+/// run meanwhile find no renderer instead of one that's already borrowed.
+/// So that nothing is started or changed behind the dialog, the window's
+/// actions are disabled and closing it is refused until the export
+/// returns; then the renderer is put back and the preview redrawn.
+fn run_export<R>(
+    window: &adw::ApplicationWindow,
+    state: &gl_renderer::SharedRendererState,
+    gl_area: &gtk4::GLArea,
+    heading: &str,
+    run: impl FnOnce(&mut gl_renderer::RendererState, &mut export::ExportControl) -> Result<R, String>,
+) -> Result<R, String> {
+    let Some(mut renderer) = state.borrow_mut().take() else {
+        return Err("Renderer not initialized".to_string());
+    };
+
+    let disabled: Vec<gio::SimpleAction> = window
+        .list_actions()
+        .iter()
+        .filter_map(|name| window.lookup_action(name))
+        .filter_map(|action| action.downcast::<gio::SimpleAction>().ok())
+        .filter(|action| action.is_enabled())
+        .collect();
+    for action in &disabled {
+        action.set_enabled(false);
+    }
+    let close_guard = window.connect_close_request(|_| glib::Propagation::Stop);
+
+    let bar = gtk4::ProgressBar::new();
+    bar.set_show_text(true);
+    let dialog = adw::MessageDialog::new(Some(window), Some(heading), None);
+    dialog.set_extra_child(Some(&bar));
+    dialog.add_response("cancel", "Cancel");
+    dialog.set_close_response("cancel");
+    let cancel = export::CancelToken::default();
+    {
+        let cancel = cancel.clone();
+        dialog.connect_response(None, move |_, _| cancel.cancel());
+    }
+    dialog.present();
+
+    let mut control = export::ExportControl::new(cancel, |progress| {
+        bar.set_fraction(progress.fraction());
+        bar.set_text(Some(&progress.to_string()));
+        let context = glib::MainContext::default();
+        while context.iteration(false) {}
+        // Drawing the dialog may have made another GL context current
+        gl_area.make_current();
+    });
+    gl_area.make_current();
+    let result = run(&mut renderer, &mut control);
+    drop(control);
+
+    *state.borrow_mut() = Some(renderer);
+    window.disconnect(close_guard);
+    for action in &disabled {
+        action.set_enabled(true);
+    }
+    dialog.close();
+    gl_area.queue_render();
+    result
+}
+
+/// Subtitle of the Seamless Tile switch: how `render_tile` will make the
+/// current preset repeat.
+fn tile_subtitle(renderer: Option<&gl_renderer::RendererState>) -> &'static str {
+    match renderer {
+        Some(r) if r.is_tileable() => "Repeat the pattern without visible edges",
+        Some(r) if r.symmetry_tiles() => "The kaleidoscope mirrors the edges so the image repeats",
+        _ => "Blend the edges so the image repeats",
+    }
+}
+
+/// Set `path` as the wallpaper on `target`, reporting the outcome in a toast.
+fn set_wallpaper_file(
+    window: &adw::ApplicationWindow,
+    path: PathBuf,
+    mode: wallpaper::WallpaperMode,
+    target: wallpaper::WallpaperTarget,
+) {
+    let window = window.clone();
+    glib::MainContext::default().spawn_local(async move {
+        let result = wallpaper::set_wallpaper_on(&path, mode, target).await;
+        let app = window.application();
+        application::notify_wallpaper_result(app.as_ref().map(|a| a.upcast_ref()), &path, &result);
+        match result {
+            Ok(()) => show_toast(
+                &window,
+                match target {
+                    wallpaper::WallpaperTarget::Desktop => "Wallpaper set!",
+                    wallpaper::WallpaperTarget::LockScreen => "Lock screen set!",
+                    wallpaper::WallpaperTarget::Both => "Wallpaper and lock screen set!",
+                },
+            ),
+            Err(e) => show_toast(&window, &format!("Failed: {}", e)),
+        }
+    });
+}
+
+/// Ask before deleting several palettes at once, then delete them and
+/// report how many failed. `on_deleted` runs if any were deleted.
+fn confirm_delete_palettes(
+    window: &adw::ApplicationWindow,
+    paths: Vec<PathBuf>,
+    on_deleted: impl Fn() + 'static,
+) {
+    let heading = format!("Delete {} Palettes?", paths.len());
+    let dialog = adw::MessageDialog::new(
+        Some(window),
+        Some(&heading),
+        Some("The palette images will be removed permanently."),
+    );
+    dialog.add_response("cancel", "Cancel");
+    dialog.add_response("delete", "Delete");
+    dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let window = window.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response != "delete" {
+            return;
+        }
+        let results = palette::delete_palettes(&paths);
+        let failures: Vec<String> = results
+            .iter()
+            .filter_map(|(path, result)| {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                result.as_ref().err().map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+        let deleted = results.len() - failures.len();
+        if deleted > 0 {
+            on_deleted();
+        }
+        let message = if failures.is_empty() {
+            format!("Deleted {} palettes", deleted)
+        } else {
+            format!(
+                "Deleted {} of {} palettes. Failed: {}",
+                deleted,
+                results.len(),
+                failures.join("; ")
+            )
+        };
+        show_toast(&window, &message);
+    });
+    dialog.present();
+}
+
+/// Show the wallpaper history as a grid of thumbnails; clicking one sets it
+/// again with the current fit and target.
+fn show_wallpaper_history(
+    window: &adw::ApplicationWindow,
+    mode: wallpaper::WallpaperMode,
+    target: wallpaper::WallpaperTarget,
+) {
+    let entries = wallpaper_history::list_wallpaper_history();
+    if entries.is_empty() {
+        show_toast(window, "No wallpapers in the history yet");
+        return;
+    }
+
+    let grid = gtk4::FlowBox::new();
+    grid.set_selection_mode(gtk4::SelectionMode::None);
+    grid.set_homogeneous(true);
+    grid.set_min_children_per_line(4);
+    grid.set_max_children_per_line(4);
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(6);
+
+    let dialog = adw::MessageDialog::new(
+        Some(window),
+        Some("Wallpaper History"),
+        Some("Click a wallpaper to set it again."),
+    );
+    for entry in entries {
+        let picture = gtk4::Picture::for_filename(&entry.image);
+        picture.set_content_fit(gtk4::ContentFit::Cover);
+        picture.set_size_request(128, 72);
+        let button = gtk4::Button::new();
+        button.set_child(Some(&picture));
+        let hexes: Vec<String> = entry
+            .colors
+            .iter()
+            .map(|c| palette::rgb_to_hex(*c))
+            .collect();
+        let tooltip = match entry.preset {
+            Some(preset) => format!("{}\n{}", preset, hexes.join(" ")),
+            None => entry.image.display().to_string(),
+        };
+        button.set_tooltip_text(Some(tooltip.trim_end()));
+        {
+            let window = window.clone();
+            let dialog = dialog.clone();
+            button.connect_clicked(move |_| {
+                set_wallpaper_file(&window, entry.image.clone(), mode, target);
+                dialog.close();
+            });
+        }
+        grid.insert(&button, -1);
+    }
+
+    let scroller = gtk4::ScrolledWindow::new();
+    scroller.set_hscrollbar_policy(gtk4::PolicyType::Never);
+    scroller.set_propagate_natural_height(true);
+    scroller.set_max_content_height(360);
+    scroller.set_child(Some(&grid));
+    dialog.set_extra_child(Some(&scroller));
+    dialog.add_response("close", "Close");
+    dialog.set_close_response("close");
+    dialog.present();
+}
+
+/// Preview a final-quality render and offer to set that file as the
+/// wallpaper. The file is deleted if the dialog is dismissed.
+fn show_final_render(
+    window: &adw::ApplicationWindow,
+    path: PathBuf,
+    (width, height): (u32, u32),
+    mode: Rc<Cell<wallpaper::WallpaperMode>>,
+    target: Rc<Cell<wallpaper::WallpaperTarget>>,
+) {
+    let picture = gtk4::Picture::for_filename(&path);
+    picture.set_content_fit(gtk4::ContentFit::Contain);
+    picture.set_size_request(360, 360 * height as i32 / width.max(1) as i32);
+
+    let body = format!(
+        "{}×{}, {}x supersampled",
+        width,
+        height,
+        FINAL_RENDER_QUALITY.factor()
+    );
+    let dialog = adw::MessageDialog::new(Some(window), Some("Final Render"), Some(&body));
+    dialog.set_extra_child(Some(&picture));
+    dialog.add_responses(&[("cancel", "Cancel"), ("set", "Set as Wallpaper")]);
+    dialog.set_response_appearance("set", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("set"));
+    dialog.set_close_response("cancel");
+
+    let window = window.clone();
+    dialog.connect_response(None, move |_, response| {
+        if response == "set" {
+            set_wallpaper_file(&window, path.clone(), mode.get(), target.get());
+        } else {
+            let _ = std::fs::remove_file(&path);
+        }
+    });
+    dialog.present();
+}
+
+/// Render the current preset at `width`x`height` and save it as a PNG for a
+/// wallpaper backend. `colors` temporarily overrides the palette for this
+/// render only.
+fn render_wallpaper_file(
+    state: &gl_renderer::SharedRendererState,
+    width: u32,
+    height: u32,
+    colors: Option<[[f32; 3]; 4]>,
+    variant: &str,
+) -> Result<PathBuf, String> {
+    // Save to a persistent file, since most backends reference it by path
+    let path = wallpaper::new_wallpaper_path(variant);
+    render_to_file(state, width, height, colors, &path)?;
+    Ok(path)
+}
+
+/// Render the wallpaper for one style of a light/dark pair: `colors` put
+/// through [`palette::Variant::apply`], saved like `render_wallpaper_file`.
+fn render_variant(
+    state: &gl_renderer::SharedRendererState,
+    width: u32,
+    height: u32,
+    colors: &[[f32; 3]; 4],
+    variant: palette::Variant,
+) -> Result<PathBuf, String> {
+    render_wallpaper_file(
+        state,
+        width,
+        height,
+        Some(variant.apply(colors)),
+        variant.name(),
+    )
+}
+
+/// Render the current preset (optionally with other colors) to a PNG at `path`.
+fn render_to_file(
+    state: &gl_renderer::SharedRendererState,
+    width: u32,
+    height: u32,
+    colors: Option<[[f32; 3]; 4]>,
+    path: &std::path::Path,
+) -> Result<(), String> {
+    let (pixels, meta) = {
+        let mut state_ref = state.borrow_mut();
+        let renderer = state_ref
+            .as_mut()
+            .ok_or_else(|| "Renderer not initialized".to_string())?;
+
+        let saved = [
+            renderer.color1,
+            renderer.color2,
+            renderer.color3,
+            renderer.color4,
+        ];
+        if let Some(c) = colors {
+            [
+                renderer.color1,
+                renderer.color2,
+                renderer.color3,
+                renderer.color4,
+            ] = c;
+        }
+        let result = renderer.render_to_pixels(width as i32, height as i32);
+        let meta = renderer.wallpaper_meta();
+        [
+            renderer.color1,
+            renderer.color2,
+            renderer.color3,
+            renderer.color4,
+        ] = saved;
+
+        (result.map_err(|e| format!("Render failed: {}", e))?, meta)
+    };
+
+    export::save_pixels(
+        &pixels,
+        width,
+        height,
+        path,
+        ExportFormat::Png,
+        export::DEFAULT_EXPORT_QUALITY,
+        Some(&meta),
+    )
+    .map_err(|e| format!("Failed to save: {}", e))
+}
+
+/// Fill `menu` with one "set for this monitor" item per connected monitor
+fn populate_monitor_menu(menu: &gio::Menu) {
+    menu.remove_all();
+    for (connector, geometry) in wallpaper::monitor_layout() {
+        let label = format!("{} ({}×{})", connector, geometry.width, geometry.height);
+        menu.append(
+            Some(&label),
+            Some(&gio::Action::print_detailed_name(
+                "win.set-monitor-wallpaper",
+                Some(&connector.to_variant()),
+            )),
+        );
+    }
+}
+
+/// Groups of (accelerator, title) rows shown in the shortcuts window
+const SHORTCUT_GROUPS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Wallpaper",
+        &[
+            ("<Control>Return", "Set as wallpaper"),
+            ("<Control>e", "Export image"),
+            ("<Control>c", "Copy image (preview focused)"),
+        ],
+    ),
+    (
+        "Palettes",
+        &[
+            ("<Control>s", "Save palette"),
+            ("<Control>z", "Undo palette edit"),
+            ("<Control><Shift>z", "Redo palette edit"),
+            ("Delete", "Delete selected custom palette (browser focused)"),
+            ("<Control>c", "Copy hex code (swatch focused)"),
+        ],
+    ),
+    (
+        "Presets",
+        &[
+            ("Left", "Previous preset (preview focused)"),
+            ("Right", "Next preset (preview focused)"),
+        ],
+    ),
+    ("General", &[("<Control>question", "Keyboard shortcuts")]),
+];
+
+/// Build the Ctrl+? help overlay from [`SHORTCUT_GROUPS`].
+fn build_shortcuts_window() -> gtk4::ShortcutsWindow {
+    let mut groups = String::new();
+    for (title, shortcuts) in SHORTCUT_GROUPS {
+        groups.push_str(&format!(
+            "<child><object class=\"GtkShortcutsGroup\"><property name=\"title\">{}</property>",
+            title
+        ));
+        for (accel, label) in *shortcuts {
+            groups.push_str(&format!(
+                "<child><object class=\"GtkShortcutsShortcut\">\
+                 <property name=\"accelerator\">{}</property>\
+                 <property name=\"title\">{}</property></object></child>",
+                glib::markup_escape_text(accel),
+                glib::markup_escape_text(label),
+            ));
+        }
+        groups.push_str("</object></child>");
+    }
+    let ui = format!(
+        "<interface><object class=\"GtkShortcutsWindow\" id=\"shortcuts\">\
+         <property name=\"modal\">true</property>\
+         <child><object class=\"GtkShortcutsSection\">\
+         <property name=\"section-name\">shortcuts</property>{}</object></child>\
+         </object></interface>",
+        groups
+    );
+    gtk4::Builder::from_string(&ui)
+        .object("shortcuts")
+        .expect("shortcuts window missing from builder UI")
+}
+
+/// Show the image a palette was extracted from when hovering `widget`. The
+/// thumbnail is only decoded once the tooltip is asked for.
+fn set_source_tooltip(widget: &gtk4::Picture, source: PathBuf) {
+    widget.set_has_tooltip(true);
+    widget.connect_query_tooltip(move |_, _, _, _, tooltip| {
+        let Ok(texture) = gdk::Texture::from_filename(&source) else {
+            return false;
+        };
+        let picture = gtk4::Picture::for_paintable(&texture);
+        picture.set_can_shrink(false);
+        tooltip.set_custom(Some(&picture));
+        true
+    });
+}
+
+/// A `size`×`size` thumbnail of four horizontal color bands, top to bottom
+fn swatch_texture(colors: &[[f32; 3]; 4], size: u32) -> gdk::MemoryTexture {
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        let band = (y as usize * colors.len() / size as usize).min(colors.len() - 1);
+        let [r, g, b] = colors[band].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        for _ in 0..size {
+            pixels.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    export::rgba_texture(&pixels, size, size)
+}
+
+/// Template fields for a single export of the selected palette
+/// ("custom" when none is selected).
+fn filename_fields(
+    palette: &Option<PathBuf>,
+    preset: &str,
+    width: u32,
+    height: u32,
+) -> export::FilenameFields {
+    export::FilenameFields {
+        palette: palette
+            .as_ref()
+            .and_then(|p| p.file_stem())
+            .and_then(|s| s.to_str())
+            .unwrap_or("custom")
+            .to_string(),
+        preset: preset.to_string(),
+        width,
+        height,
+        timestamp: export::unix_timestamp(),
+        index: 1,
+    }
+}
+
+/// Render for image export or the clipboard, as a seamless tile if `tile`.
+fn render_export_pixels(
+    renderer: &mut gl_renderer::RendererState,
+    width: u32,
+    height: u32,
+    quality: SampleQuality,
+    dither: Dither,
+    tile: bool,
+    control: &mut export::ExportControl,
+) -> Result<Vec<u8>, String> {
+    if tile {
+        export::render_tile(renderer, width, height, quality, dither, control)
+    } else {
+        export::render_pixels(renderer, width, height, quality, dither, control)
+    }
+}
+
+/// Icon Contrast positions and their points in shader uv (origin bottom-left)
+const SHADE_POSITIONS: [(&str, [f32; 2]); 5] = [
+    ("Center", [0.5, 0.5]),
+    ("Top Left", [0.0, 1.0]),
+    ("Top Right", [1.0, 1.0]),
+    ("Bottom Left", [0.0, 0.0]),
+    ("Bottom Right", [1.0, 0.0]),
+];
+
+fn selected_dither(row: &adw::SwitchRow) -> Dither {
+    if row.is_active() {
+        Dither::Ordered
+    } else {
+        Dither::Off
+    }
+}
+
+/// The caption under the wallpaper buttons naming the backend in use
+fn backend_label_text() -> String {
+    format!("Setting wallpaper via: {}", wallpaper::backend().label())
+}
+
+/// Send a desktop notification from the window's application.
+fn notify(window: &adw::ApplicationWindow, id: &str, title: &str, body: &str) {
+    let app = window.application();
+    application::notify(app.as_ref().map(|a| a.upcast_ref()), id, title, body);
+}
+
+/// Whether the window is hidden or minimized, so a toast on it would go
+/// unseen
+fn is_window_hidden(window: &adw::ApplicationWindow) -> bool {
+    !window.is_visible()
+        || window
+            .surface()
+            .and_downcast::<gdk::Toplevel>()
+            .is_some_and(|toplevel| toplevel.state().contains(gdk::ToplevelState::MINIMIZED))
+}
+
+/// Notify that an export to `path` finished, or why it failed, so long
+/// exports are noticed while Wallrus is in the background.
+fn notify_export(window: &adw::ApplicationWindow, result: Result<&Path, &str>) {
+    match result {
+        Ok(path) => notify(
+            window,
+            EXPORT_NOTIFICATION,
+            "Export complete",
+            &path.display().to_string(),
+        ),
+        Err(e) => notify(window, EXPORT_NOTIFICATION, "Export failed", e),
     }
 }
 